@@ -0,0 +1,529 @@
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{Client, Url};
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize,
+};
+use sha1::Sha1;
+use thiserror::Error;
+
+use super::{DnsRecord, Provider, RecordOptions};
+use crate::{host::normalize_host, retry::Retryable};
+
+/// Free AliDNS instances don't accept TTLs below 10 minutes.
+const DEFAULT_TTL: u32 = 600;
+const BASE_URL: &str = "https://alidns.aliyuncs.com/";
+const API_VERSION: &str = "2015-01-09";
+/// Maximum page size of `DescribeDomainRecords`.
+const PAGE_SIZE: u32 = 500;
+
+const CNAME_TYPE: &str = "CNAME";
+/// Record name of the zone apex.
+const APEX: &str = "@";
+
+/// Characters escaped by the RPC signature: everything but the RFC 3986 unreserved characters.
+const RPC_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+#[derive(Debug)]
+pub struct AliDnsProvider {
+    dest: String,
+    domain: String,
+
+    base_url: Url,
+    access_key_id: String,
+    access_key_secret: String,
+    client: Client,
+
+    ttl: u32,
+}
+
+impl AliDnsProvider {
+    pub fn new(
+        access_key_id: String,
+        access_key_secret: String,
+        domain: String,
+        dest: String,
+    ) -> Self {
+        Self::with_base_url(
+            access_key_id,
+            access_key_secret,
+            domain,
+            dest,
+            Url::parse(BASE_URL).unwrap(),
+        )
+    }
+
+    pub fn with_base_url(
+        access_key_id: String,
+        access_key_secret: String,
+        domain: String,
+        dest: String,
+        base_url: Url,
+    ) -> Self {
+        Self {
+            dest,
+            domain: normalize_host(&domain),
+            base_url,
+            access_key_id,
+            access_key_secret,
+            client: crate::http::default_client(),
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    /// Returns the API endpoint of `region`, like `cn-hangzhou` or `ap-southeast-1`.
+    pub fn region_url(region: &str) -> Result<Url, url::ParseError> {
+        Url::parse(&format!("https://alidns.{}.aliyuncs.com/", region))
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn ttl(&self) -> &u32 {
+        &self.ttl
+    }
+    pub fn ttl_mut(&mut self) -> &mut u32 {
+        &mut self.ttl
+    }
+
+    /// HTTP client of the API requests, e.g. to send them through a proxy.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+    pub fn client_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    /// Calls the RPC API `action` with `params`, signing the request.
+    async fn call<T: DeserializeOwned>(
+        &self,
+        action: &str,
+        params: &[(&str, &str)],
+    ) -> Result<T, AliDnsError> {
+        let timestamp = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        let nonce = rand::random::<u64>().to_string();
+
+        let mut query: BTreeMap<&str, &str> = params.iter().copied().collect();
+        query.extend([
+            ("Action", action),
+            ("Format", "JSON"),
+            ("Version", API_VERSION),
+            ("AccessKeyId", self.access_key_id.as_str()),
+            ("SignatureMethod", "HMAC-SHA1"),
+            ("SignatureVersion", "1.0"),
+            ("SignatureNonce", nonce.as_str()),
+            ("Timestamp", timestamp.as_str()),
+        ]);
+
+        let canonical = canonical_query(&query);
+        let signature = sign(&self.access_key_secret, &canonical);
+
+        let mut url = self.base_url.clone();
+        url.set_query(Some(&format!(
+            "{}&Signature={}",
+            canonical,
+            percent_encode(&signature)
+        )));
+
+        let res = self.client.get(url).send().await?;
+        let status = res.status();
+        if status.is_success() {
+            return Ok(res.json::<T>().await?);
+        }
+
+        // Failed calls carry the reason in the body, which the status alone doesn't tell
+        match res.json::<AliDnsErrorBody>().await {
+            Ok(body) => Err(AliDnsError::Api {
+                status: status.as_u16(),
+                code: body.code,
+                message: body.message,
+            }),
+            Err(_) => Err(AliDnsError::Api {
+                status: status.as_u16(),
+                code: String::new(),
+                message: status.to_string(),
+            }),
+        }
+    }
+
+    /// Lists every record of the domain.
+    async fn describe_records(&self) -> Result<Vec<AliDnsRecord>, AliDnsError> {
+        let mut records = Vec::new();
+        for page in 1.. {
+            let page = page.to_string();
+            let page_size = PAGE_SIZE.to_string();
+            let res: DescribeDomainRecords = self
+                .call(
+                    "DescribeDomainRecords",
+                    &[
+                        ("DomainName", &self.domain),
+                        ("PageNumber", &page),
+                        ("PageSize", &page_size),
+                    ],
+                )
+                .await?;
+
+            let empty = res.domain_records.record.is_empty();
+            records.extend(res.domain_records.record);
+            if empty || records.len() as u64 >= res.total_count {
+                break;
+            }
+        }
+        Ok(records)
+    }
+
+    /// Converts a fully qualified host into a record name relative to the domain.
+    fn relative_name<'a>(host: &'a str, domain: &str) -> Result<&'a str, AliDnsError> {
+        if host == domain {
+            return Ok(APEX);
+        }
+        host.strip_suffix(domain)
+            .and_then(|s| s.strip_suffix('.'))
+            .ok_or_else(|| AliDnsError::OutsideZone(host.to_owned()))
+    }
+
+    /// Converts a record name relative to the domain into a fully qualified host.
+    fn absolute_name(name: &str, domain: &str) -> String {
+        if name == APEX {
+            normalize_host(domain)
+        } else {
+            normalize_host(&format!("{}.{}", name, domain))
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for AliDnsProvider {
+    type Error = AliDnsError;
+
+    fn destination(&self) -> &str {
+        &self.dest
+    }
+    fn destination_mut(&mut self) -> &mut String {
+        &mut self.dest
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        Ok(self
+            .describe_records()
+            .await?
+            .into_iter()
+            .filter(|r| r.r#type == CNAME_TYPE && r.value == self.dest)
+            .map(|r| DnsRecord {
+                host: Self::absolute_name(&r.rr, &self.domain),
+                id: Some(r.record_id),
+                ttl: Some(r.ttl),
+                content: r.value,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        let ttl = options.ttl.unwrap_or(self.ttl).to_string();
+        let _: IgnoredAny = self
+            .call(
+                "AddDomainRecord",
+                &[
+                    ("DomainName", &self.domain),
+                    ("RR", Self::relative_name(host, &self.domain)?),
+                    ("Type", CNAME_TYPE),
+                    ("Value", &self.dest),
+                    ("TTL", &ttl),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        let id = match &record.id {
+            Some(id) => id.clone(),
+            None => {
+                let name = Self::relative_name(&record.host, &self.domain)?;
+                self.describe_records()
+                    .await?
+                    .into_iter()
+                    .find(|r| r.r#type == CNAME_TYPE && r.rr == name)
+                    .ok_or(AliDnsError::RecordNotFound)?
+                    .record_id
+            }
+        };
+
+        let _: IgnoredAny = self
+            .call("DeleteDomainRecord", &[("RecordId", &id)])
+            .await?;
+        Ok(())
+    }
+}
+
+/// Percent-encodes `s` as required by the RPC signature.
+fn percent_encode(s: &str) -> String {
+    utf8_percent_encode(s, RPC_ESCAPE).to_string()
+}
+
+/// Returns the sorted, encoded query string of `params`.
+fn canonical_query(params: &BTreeMap<&str, &str>) -> String {
+    params
+        .iter()
+        .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Signs the canonical query of a GET request with the access key secret.
+fn sign(secret: &str, canonical_query: &str) -> String {
+    let string_to_sign = format!(
+        "GET&{}&{}",
+        percent_encode("/"),
+        percent_encode(canonical_query)
+    );
+    let mut mac = Hmac::<Sha1>::new_from_slice(format!("{}&", secret).as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(string_to_sign.as_bytes());
+    BASE64.encode(mac.finalize().into_bytes())
+}
+
+#[derive(Debug, Error)]
+pub enum AliDnsError {
+    #[error(transparent)]
+    ReqwestError(#[from] reqwest::Error),
+    #[error("AliDNS API error ({status}) {code}: {message}")]
+    Api {
+        status: u16,
+        code: String,
+        message: String,
+    },
+    #[error("host {0} is outside of the zone")]
+    OutsideZone(String),
+    #[error("record not found")]
+    RecordNotFound,
+}
+
+impl Retryable for AliDnsError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            AliDnsError::ReqwestError(e) => e.is_timeout() || e.is_connect(),
+            AliDnsError::Api { status, code, .. } => {
+                *status == 429 || *status >= 500 || code.starts_with("Throttling")
+            }
+            AliDnsError::OutsideZone(_) | AliDnsError::RecordNotFound => false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AliDnsErrorBody {
+    code: String,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeDomainRecords {
+    total_count: u64,
+    domain_records: AliDnsRecords,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AliDnsRecords {
+    #[serde(default)]
+    record: Vec<AliDnsRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AliDnsRecord {
+    record_id: String,
+    #[serde(rename = "RR")]
+    rr: String,
+    r#type: String,
+    value: String,
+    #[serde(rename = "TTL")]
+    ttl: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    use super::*;
+
+    const RECORDS: &str = r#"
+        {
+            "TotalCount": 4,
+            "PageNumber": 1,
+            "PageSize": 500,
+            "DomainRecords": {
+                "Record": [
+                    {"RecordId": "10", "RR": "test1", "Type": "CNAME", "Value": "dest", "TTL": 600},
+                    {"RecordId": "11", "RR": "@", "Type": "CNAME", "Value": "dest", "TTL": 600},
+                    {"RecordId": "12", "RR": "wrong-type", "Type": "A", "Value": "dest", "TTL": 600},
+                    {"RecordId": "13", "RR": "wrong-dest", "Type": "CNAME", "Value": "wrong.dest.com", "TTL": 600}
+                ]
+            }
+        }
+    "#;
+
+    fn provider(server: &Server) -> AliDnsProvider {
+        let base_url = Url::parse(&server.url_str("/")).unwrap();
+        AliDnsProvider::with_base_url(
+            "id".to_string(),
+            "secret".to_string(),
+            "example.com".to_string(),
+            "dest".to_string(),
+            base_url,
+        )
+    }
+
+    #[test]
+    fn test_sign() {
+        // Example of the AliDNS signature documentation
+        let params = BTreeMap::from([
+            ("AccessKeyId", "testid"),
+            ("Action", "DescribeDomainRecords"),
+            ("DomainName", "example.com"),
+            ("Format", "XML"),
+            ("SignatureMethod", "HMAC-SHA1"),
+            ("SignatureNonce", "f59ed6a9-83fc-473b-9cc6-99c95df3856e"),
+            ("SignatureVersion", "1.0"),
+            ("Timestamp", "2016-03-24T16:41:54Z"),
+            ("Version", "2015-01-09"),
+        ]);
+        let canonical = canonical_query(&params);
+        assert!(canonical.contains("&Timestamp=2016-03-24T16%3A41%3A54Z&"));
+        assert_eq!(
+            sign("testsecret", &canonical),
+            "uRpHwaSEt3J+6KQD//svCh/x+pI="
+        );
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(percent_encode("a-b_c.d~e"), "a-b_c.d~e");
+        assert_eq!(percent_encode("a b*c/d+e"), "a%20b%2Ac%2Fd%2Be");
+    }
+
+    #[tokio::test]
+    async fn test_list_records() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/"),
+                request::query(url_decoded(contains(("Action", "DescribeDomainRecords")))),
+                request::query(url_decoded(contains(("DomainName", "example.com")))),
+                request::query(url_decoded(contains(("AccessKeyId", "id")))),
+                request::query(url_decoded(contains(key("Signature")))),
+            ])
+            .respond_with(status_code(200).body(RECORDS)),
+        );
+
+        let records = provider(&server).list_records().await.unwrap();
+        assert_eq!(
+            records,
+            vec![
+                DnsRecord {
+                    host: "test1.example.com".to_string(),
+                    id: Some("10".to_string()),
+                    ttl: Some(600),
+                    content: "dest".to_string(),
+                },
+                DnsRecord {
+                    host: "example.com".to_string(),
+                    id: Some("11".to_string()),
+                    ttl: Some(600),
+                    content: "dest".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_record() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/"),
+                request::query(url_decoded(contains(("Action", "AddDomainRecord")))),
+                request::query(url_decoded(contains(("RR", "test")))),
+                request::query(url_decoded(contains(("Type", "CNAME")))),
+                request::query(url_decoded(contains(("Value", "dest")))),
+                request::query(url_decoded(contains(("TTL", "1200")))),
+            ])
+            .respond_with(status_code(200).body(r#"{"RequestId": "req", "RecordId": "20"}"#)),
+        );
+
+        let options = RecordOptions {
+            ttl: Some(1200),
+            ..Default::default()
+        };
+        provider(&server)
+            .create_record("test.example.com", &options)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_record_outside_zone() {
+        let server = Server::run();
+
+        let err = provider(&server)
+            .create_record("test.example.org", &RecordOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AliDnsError::OutsideZone(_)));
+    }
+
+    #[tokio::test]
+    async fn test_delete_record() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/"),
+                request::query(url_decoded(contains(("Action", "DeleteDomainRecord")))),
+                request::query(url_decoded(contains(("RecordId", "10")))),
+            ])
+            .respond_with(status_code(200).body(r#"{"RequestId": "req", "RecordId": "10"}"#)),
+        );
+
+        let record = DnsRecord {
+            host: "test1.example.com".to_string(),
+            id: Some("10".to_string()),
+            ttl: Some(600),
+            content: "dest".to_string(),
+        };
+        provider(&server).delete_record(&record).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_api_error() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/")).respond_with(
+                status_code(400).body(
+                    r#"{"RequestId": "req", "Code": "Throttling.User", "Message": "Request was denied due to user flow control."}"#,
+                ),
+            ),
+        );
+
+        let err = provider(&server).list_records().await.unwrap_err();
+        assert!(matches!(
+            &err,
+            AliDnsError::Api { status: 400, code, .. } if code == "Throttling.User"
+        ));
+        assert!(err.is_retryable());
+    }
+}
@@ -0,0 +1,778 @@
+use std::{collections::HashSet, net::IpAddr, sync::Arc};
+
+use hickory_resolver::error::ResolveError;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+
+use super::{
+    ownership::Ownership, resolve::DestinationResolver, DnsRecord, Provider, RecordOptions,
+};
+use crate::{host::normalize_host, retry::Retryable};
+
+const DEFAULT_TTL: u32 = 300;
+const BASE_URL: &str = "https://api.bunny.net/";
+
+/// Bunny DNS record type for A records.
+const A_TYPE: u8 = 0;
+/// Bunny DNS record type for AAAA records.
+const AAAA_TYPE: u8 = 1;
+/// Bunny DNS record type for CNAME records.
+const CNAME_TYPE: u8 = 2;
+/// Bunny DNS record type for TXT records.
+const TXT_TYPE: u8 = 3;
+
+#[derive(Debug)]
+pub struct BunnyProvider {
+    dest: String,
+    zone_id: u64,
+
+    base_url: Url,
+    api_key: String,
+    client: Client,
+
+    /// Domain of the zone, fetched on first use.
+    domain: OnceCell<String>,
+
+    ttl: u32,
+    ownership: Option<Ownership>,
+    resolver: Option<Arc<DestinationResolver>>,
+}
+
+impl BunnyProvider {
+    pub fn new(api_key: String, zone_id: u64, dest: String) -> Self {
+        Self::with_base_url(api_key, zone_id, dest, Url::parse(BASE_URL).unwrap())
+    }
+
+    pub fn with_base_url(api_key: String, zone_id: u64, dest: String, base_url: Url) -> Self {
+        Self {
+            dest,
+            zone_id,
+            base_url,
+            api_key,
+            client: crate::http::default_client(),
+            domain: OnceCell::new(),
+            ttl: DEFAULT_TTL,
+            ownership: None,
+            resolver: None,
+        }
+    }
+
+    pub fn ttl(&self) -> &u32 {
+        &self.ttl
+    }
+    pub fn ttl_mut(&mut self) -> &mut u32 {
+        &mut self.ttl
+    }
+
+    /// HTTP client of the API requests, e.g. to send them through a proxy.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+    pub fn client_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    pub fn ownership(&self) -> &Option<Ownership> {
+        &self.ownership
+    }
+    pub fn ownership_mut(&mut self) -> &mut Option<Ownership> {
+        &mut self.ownership
+    }
+
+    /// Resolver of the destination, to create A/AAAA records instead of CNAMEs.
+    pub fn resolver(&self) -> &Option<Arc<DestinationResolver>> {
+        &self.resolver
+    }
+    pub fn resolver_mut(&mut self) -> &mut Option<Arc<DestinationResolver>> {
+        &mut self.resolver
+    }
+
+    async fn get_zone(&self) -> Result<BunnyZone, BunnyError> {
+        let url = self.base_url.join(&format!("dnszone/{}", self.zone_id))?;
+        let zone = self
+            .client
+            .get(url)
+            .header("AccessKey", &self.api_key)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<BunnyZone>()
+            .await?;
+
+        // Take the opportunity to cache the zone domain
+        let _ = self.domain.set(zone.domain.clone());
+
+        Ok(zone)
+    }
+
+    async fn domain(&self) -> Result<&str, BunnyError> {
+        self.domain
+            .get_or_try_init(|| async { Ok(self.get_zone().await?.domain) })
+            .await
+            .map(String::as_str)
+    }
+
+    async fn put_record(&self, record: &BunnyNewRecord<'_>) -> Result<(), BunnyError> {
+        let url = self
+            .base_url
+            .join(&format!("dnszone/{}/records", self.zone_id))?;
+
+        self.client
+            .put(url)
+            .header("AccessKey", &self.api_key)
+            .json(record)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn update_record(&self, id: u64, record: &BunnyNewRecord<'_>) -> Result<(), BunnyError> {
+        let url = self
+            .base_url
+            .join(&format!("dnszone/{}/records/{}", self.zone_id, id))?;
+
+        self.client
+            .post(url)
+            .header("AccessKey", &self.api_key)
+            .json(record)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    async fn delete_record_id(&self, id: &str) -> Result<(), BunnyError> {
+        let url = self
+            .base_url
+            .join(&format!("dnszone/{}/records/{}", self.zone_id, id))?;
+
+        self.client
+            .delete(url)
+            .header("AccessKey", &self.api_key)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Replaces the A/AAAA records of `host` with the resolved addresses of the destination.
+    ///
+    /// Returns `true` if the host already has an ownership marker.
+    async fn replace_address_records(
+        &self,
+        resolver: &DestinationResolver,
+        host: &str,
+        options: &RecordOptions,
+    ) -> Result<bool, BunnyError> {
+        let zone = self.get_zone().await?;
+        let name = Self::relative_name(host, &zone.domain)?;
+
+        let existing: Vec<_> = zone
+            .records
+            .iter()
+            .filter(|r| r.name == name)
+            .filter_map(|r| Some((r, r.address()?)))
+            .collect();
+
+        for (record, _) in existing.iter().filter(|(_, a)| !resolver.is_current(a)) {
+            self.delete_record_id(&record.id.to_string()).await?;
+        }
+
+        for addr in resolver.addresses() {
+            if existing.iter().any(|(_, a)| *a == addr) {
+                continue;
+            }
+            self.put_record(&BunnyNewRecord {
+                r#type: if addr.is_ipv4() { A_TYPE } else { AAAA_TYPE },
+                ttl: options.ttl.unwrap_or(self.ttl),
+                value: &addr.to_string(),
+                name,
+            })
+            .await?;
+        }
+
+        self.has_marker(&zone, host)
+    }
+
+    /// Points the CNAME record of `host` at the destination, updating it in place if it exists.
+    ///
+    /// Returns `true` if the host already has an ownership marker.
+    async fn replace_cname_record(
+        &self,
+        host: &str,
+        options: &RecordOptions,
+    ) -> Result<bool, BunnyError> {
+        let zone = self.get_zone().await?;
+        let record = BunnyNewRecord {
+            r#type: CNAME_TYPE,
+            ttl: options.ttl.unwrap_or(self.ttl),
+            value: &self.dest,
+            name: Self::relative_name(host, &zone.domain)?,
+        };
+
+        let existing = zone
+            .records
+            .iter()
+            .find(|r| r.r#type == CNAME_TYPE && r.name == record.name);
+        match existing {
+            Some(existing) if existing.value == self.dest => {}
+            Some(existing) => self.update_record(existing.id, &record).await?,
+            None => self.put_record(&record).await?,
+        }
+
+        self.has_marker(&zone, host)
+    }
+
+    /// Returns `true` if `host` has an ownership marker in `zone`.
+    fn has_marker(&self, zone: &BunnyZone, host: &str) -> Result<bool, BunnyError> {
+        let marker = Ownership::record_name(host);
+        let marker = Self::relative_name(&marker, &zone.domain)?;
+        Ok(self.ownership.as_ref().is_some_and(|ownership| {
+            zone.records
+                .iter()
+                .any(|r| r.r#type == TXT_TYPE && r.name == marker && ownership.matches(&r.value))
+        }))
+    }
+
+    /// Converts a fully qualified host into a record name relative to the zone.
+    fn relative_name<'a>(host: &'a str, domain: &str) -> Result<&'a str, BunnyError> {
+        if host == domain {
+            return Ok("");
+        }
+        host.strip_suffix(domain)
+            .and_then(|s| s.strip_suffix('.'))
+            .ok_or_else(|| BunnyError::OutsideZone(host.to_owned()))
+    }
+
+    /// Converts a record name relative to the zone into a fully qualified host.
+    fn absolute_name(name: &str, domain: &str) -> String {
+        if name.is_empty() {
+            normalize_host(domain)
+        } else {
+            normalize_host(&format!("{}.{}", name, domain))
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for BunnyProvider {
+    type Error = BunnyError;
+
+    fn destination(&self) -> &str {
+        &self.dest
+    }
+    fn destination_mut(&mut self) -> &mut String {
+        &mut self.dest
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        let zone = self.get_zone().await?;
+
+        // Hosts with a matching ownership marker
+        let owned: Option<HashSet<_>> = self.ownership.as_ref().map(|ownership| {
+            zone.records
+                .iter()
+                .filter(|r| r.r#type == TXT_TYPE && ownership.matches(&r.value))
+                .map(|r| Self::absolute_name(&r.name, &zone.domain))
+                .filter_map(|name| Ownership::marked_host(&name))
+                .collect()
+        });
+
+        let resolver = self.resolver.as_deref();
+        if let Some(resolver) = resolver {
+            resolver.refresh(&self.dest).await?;
+        }
+
+        Ok(zone
+            .records
+            .into_iter()
+            .filter(|r| match resolver {
+                Some(resolver) => r.address().is_some_and(|a| resolver.is_known(&a)),
+                // Without markers, only records pointing at the destination are known to be ours
+                None => r.r#type == CNAME_TYPE && (owned.is_some() || r.value == self.dest),
+            })
+            .filter(|r| {
+                owned
+                    .as_ref()
+                    .is_none_or(|owned| owned.contains(&Self::absolute_name(&r.name, &zone.domain)))
+            })
+            .map(|r| DnsRecord {
+                host: Self::absolute_name(&r.name, &zone.domain),
+                id: Some(r.id.to_string()),
+                ttl: Some(r.ttl),
+                content: r.value,
+            })
+            .collect())
+    }
+
+    fn is_current(&self, record: &DnsRecord) -> bool {
+        match &self.resolver {
+            Some(resolver) => record
+                .content
+                .parse()
+                .is_ok_and(|addr| resolver.is_current(&addr)),
+            None => record.content == self.dest,
+        }
+    }
+
+    // Existing records are updated in place, and missing ownership markers added
+    async fn ensure_record(
+        &self,
+        host: &str,
+        _existing: &[DnsRecord],
+        options: &RecordOptions,
+    ) -> Result<(), Self::Error> {
+        self.create_record(host, options).await
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        let has_marker = match &self.resolver {
+            Some(resolver) => {
+                self.replace_address_records(resolver, host, options)
+                    .await?
+            }
+            None => self.replace_cname_record(host, options).await?,
+        };
+        // Stale hosts are created again, and may already have a marker
+        if has_marker {
+            return Ok(());
+        }
+
+        let domain = self.domain().await?;
+
+        if let Some(ownership) = &self.ownership {
+            let name = Ownership::record_name(host);
+            self.put_record(&BunnyNewRecord {
+                r#type: TXT_TYPE,
+                ttl: self.ttl,
+                value: &ownership.value(),
+                name: Self::relative_name(&name, domain)?,
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        let id = match &record.id {
+            Some(id) => id.clone(),
+            None => {
+                let zone = self.get_zone().await?;
+                let name = Self::relative_name(&record.host, &zone.domain)?;
+                zone.records
+                    .iter()
+                    .filter(|r| r.name == name)
+                    .find(|r| match self.resolver {
+                        Some(_) => r.address().is_some_and(|a| a.to_string() == record.content),
+                        None => r.r#type == CNAME_TYPE,
+                    })
+                    .ok_or(BunnyError::RecordNotFound)?
+                    .id
+                    .to_string()
+            }
+        };
+
+        self.delete_record_id(&id).await?;
+
+        if let Some(ownership) = &self.ownership {
+            let zone = self.get_zone().await?;
+            let marker = Ownership::record_name(&record.host);
+            let name = Self::relative_name(&marker, &zone.domain)?;
+            let markers = zone
+                .records
+                .iter()
+                .filter(|r| r.r#type == TXT_TYPE && r.name == name && ownership.matches(&r.value));
+            for marker in markers {
+                self.delete_record_id(&marker.id.to_string()).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BunnyError {
+    #[error(transparent)]
+    ReqwestError(#[from] reqwest::Error),
+    #[error(transparent)]
+    UrlParseError(#[from] url::ParseError),
+    #[error("host {0} is outside of the zone")]
+    OutsideZone(String),
+    #[error("record not found")]
+    RecordNotFound,
+    #[error("failed to resolve destination: {0}")]
+    ResolveError(#[from] ResolveError),
+}
+
+impl Retryable for BunnyError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            BunnyError::ReqwestError(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status()
+                        .is_some_and(|s| s.as_u16() == 429 || s.is_server_error())
+            }
+            BunnyError::UrlParseError(_)
+            | BunnyError::OutsideZone(_)
+            | BunnyError::RecordNotFound => false,
+            BunnyError::ResolveError(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BunnyZone {
+    domain: String,
+    records: Vec<BunnyRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BunnyRecord {
+    id: u64,
+    r#type: u8,
+    ttl: u32,
+    value: String,
+    name: String,
+}
+
+impl BunnyRecord {
+    /// Returns the address of an A/AAAA record.
+    fn address(&self) -> Option<IpAddr> {
+        match self.r#type {
+            A_TYPE | AAAA_TYPE => self.value.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct BunnyNewRecord<'a> {
+    r#type: u8,
+    ttl: u32,
+    value: &'a str,
+    name: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    use super::*;
+
+    const ZONE: &str = r#"
+        {
+            "Id": 1,
+            "Domain": "example.com",
+            "Records": [
+                {"Id": 10, "Type": 2, "Ttl": 300, "Value": "dest", "Name": "test1"},
+                {"Id": 11, "Type": 2, "Ttl": 300, "Value": "dest", "Name": ""},
+                {"Id": 12, "Type": 0, "Ttl": 300, "Value": "dest", "Name": "wrong-type"},
+                {"Id": 13, "Type": 2, "Ttl": 300, "Value": "wrong.dest.com", "Name": "wrong-dest"}
+            ]
+        }
+    "#;
+
+    fn provider(server: &Server) -> BunnyProvider {
+        let base_url = Url::parse(&server.url_str("/")).unwrap();
+        BunnyProvider::with_base_url("key".to_string(), 1, "dest".to_string(), base_url)
+    }
+
+    #[tokio::test]
+    async fn test_list_records() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/dnszone/1"),
+                request::headers(contains(("accesskey", "key"))),
+            ])
+            .respond_with(status_code(200).body(ZONE)),
+        );
+
+        let records = provider(&server).list_records().await.unwrap();
+
+        let hosts: Vec<_> = records.iter().map(|r| r.host.as_str()).collect();
+        assert_eq!(hosts, vec!["test1.example.com", "example.com"]);
+        assert_eq!(records[0].id.as_deref(), Some("10"));
+    }
+
+    #[tokio::test]
+    async fn test_list_records_ownership() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/dnszone/1")).respond_with(
+                status_code(200).body(
+                    r#"
+                    {
+                        "Id": 1,
+                        "Domain": "example.com",
+                        "Records": [
+                            {"Id": 10, "Type": 2, "Ttl": 300, "Value": "dest", "Name": "owned"},
+                            {"Id": 11, "Type": 3, "Ttl": 300, "Value": "heritage=traefik-dns-rs,owner=owner1", "Name": "_traefik-dns.owned"},
+                            {"Id": 12, "Type": 2, "Ttl": 300, "Value": "dest", "Name": "unmarked"}
+                        ]
+                    }
+                    "#,
+                ),
+            ),
+        );
+
+        let mut provider = provider(&server);
+        *provider.ownership_mut() = Some(Ownership::new("owner1".to_string()));
+
+        let records = provider.list_records().await.unwrap();
+
+        let hosts: Vec<_> = records.iter().map(|r| r.host.as_str()).collect();
+        assert_eq!(hosts, vec!["owned.example.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_create_record_resolved() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/dnszone/1"))
+                .times(1..)
+                .respond_with(status_code(200).body(
+                    r#"
+                    {
+                        "Id": 1,
+                        "Domain": "example.com",
+                        "Records": [
+                            {"Id": 10, "Type": 0, "Ttl": 300, "Value": "192.0.2.1", "Name": "test"}
+                        ]
+                    }
+                    "#,
+                )),
+        );
+        // The stale address is replaced
+        server.expect(
+            Expectation::matching(request::method_path("DELETE", "/dnszone/1/records/10"))
+                .respond_with(status_code(204)),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("PUT", "/dnszone/1/records"),
+                request::body(json_decoded(eq(serde_json::json!({
+                    "Type": 0,
+                    "Ttl": 300,
+                    "Value": "192.0.2.2",
+                    "Name": "test",
+                })))),
+            ])
+            .respond_with(status_code(201)),
+        );
+
+        let mut provider = provider(&server);
+        *provider.destination_mut() = "192.0.2.1".to_string();
+        *provider.resolver_mut() = Some(Arc::new(crate::dns::resolve::tests::resolver()));
+
+        let records = provider.list_records().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].content, "192.0.2.1");
+        assert!(provider.is_current(&records[0]));
+
+        // The destination moves, so the listed record becomes stale
+        *provider.destination_mut() = "192.0.2.2".to_string();
+        let records = provider.list_records().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(!provider.is_current(&records[0]));
+
+        provider
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_record() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/dnszone/1"))
+                .times(1..)
+                .respond_with(status_code(200).body(ZONE)),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("PUT", "/dnszone/1/records"),
+                request::headers(contains(("accesskey", "key"))),
+                request::body(json_decoded(eq(serde_json::json!({
+                    "Type": 2,
+                    "Ttl": 300,
+                    "Value": "dest",
+                    "Name": "test2",
+                })))),
+            ])
+            .respond_with(status_code(201)),
+        );
+
+        provider(&server)
+            .create_record("test2.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_record_ttl() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/dnszone/1"))
+                .times(1..)
+                .respond_with(status_code(200).body(ZONE)),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("PUT", "/dnszone/1/records"),
+                request::body(json_decoded(eq(serde_json::json!({
+                    "Type": 2,
+                    "Ttl": 60,
+                    "Value": "dest",
+                    "Name": "test2",
+                })))),
+            ])
+            .respond_with(status_code(201)),
+        );
+
+        provider(&server)
+            .create_record(
+                "test2.example.com",
+                &RecordOptions {
+                    ttl: Some(60),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_record_stale() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/dnszone/1"))
+                .times(1..)
+                .respond_with(status_code(200).body(
+                    r#"
+                    {
+                        "Id": 1,
+                        "Domain": "example.com",
+                        "Records": [
+                            {"Id": 10, "Type": 2, "Ttl": 300, "Value": "old.dest", "Name": "test"},
+                            {"Id": 11, "Type": 3, "Ttl": 300, "Value": "heritage=traefik-dns-rs,owner=owner1", "Name": "_traefik-dns.test"}
+                        ]
+                    }
+                    "#,
+                )),
+        );
+        // The existing record is updated in place, and the marker is kept
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/dnszone/1/records/10"),
+                request::body(json_decoded(eq(serde_json::json!({
+                    "Type": 2,
+                    "Ttl": 300,
+                    "Value": "dest",
+                    "Name": "test",
+                })))),
+            ])
+            .respond_with(status_code(204)),
+        );
+
+        let mut provider = provider(&server);
+        *provider.ownership_mut() = Some(Ownership::new("owner1".to_string()));
+
+        // Owned records pointing elsewhere are listed as stale
+        let records = provider.list_records().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].content, "old.dest");
+        assert!(!provider.is_current(&records[0]));
+
+        provider
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+    }
+
+    fn record(host: &str, id: Option<&str>) -> DnsRecord {
+        DnsRecord {
+            host: host.to_string(),
+            id: id.map(str::to_string),
+            ttl: None,
+            content: "dest".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_record() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/dnszone/1"))
+                .respond_with(status_code(200).body(ZONE)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("DELETE", "/dnszone/1/records/10"))
+                .respond_with(status_code(204)),
+        );
+
+        provider(&server)
+            .delete_record(&record("test1.example.com", None))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_record_by_id() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("DELETE", "/dnszone/1/records/10"))
+                .respond_with(status_code(204)),
+        );
+
+        provider(&server)
+            .delete_record(&record("test1.example.com", Some("10")))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_record_missing() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/dnszone/1"))
+                .respond_with(status_code(200).body(ZONE)),
+        );
+
+        let err = provider(&server)
+            .delete_record(&record("missing.example.com", None))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, BunnyError::RecordNotFound));
+    }
+
+    #[test]
+    fn test_relative_name() {
+        assert_eq!(
+            BunnyProvider::relative_name("a.example.com", "example.com").unwrap(),
+            "a"
+        );
+        assert_eq!(
+            BunnyProvider::relative_name("example.com", "example.com").unwrap(),
+            ""
+        );
+        assert!(BunnyProvider::relative_name("a.badexample.com", "example.com").is_err());
+    }
+}
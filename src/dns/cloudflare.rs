@@ -1,49 +1,384 @@
+use std::{collections::HashSet, net::IpAddr, sync::Arc, time::Duration};
+
 use cloudflare::{
-    endpoints::dns::{
-        CreateDnsRecord, CreateDnsRecordParams, DeleteDnsRecord, DnsContent, DnsRecord,
-        ListDnsRecords, ListDnsRecordsParams,
+    endpoints::{
+        dns::{
+            CreateDnsRecordParams, DeleteDnsRecord, DeleteDnsRecordResponse, DnsContent,
+            DnsRecord as CloudflareRecord, ListDnsRecordsParams, UpdateDnsRecord,
+            UpdateDnsRecordParams,
+        },
+        zone::{ListZones, ListZonesParams, Zone},
     },
     framework::{
-        async_api::{ApiClient, Client},
-        auth::Credentials,
-        response::ApiFailure,
-        Environment, HttpApiClientConfig,
+        async_api::ApiClient,
+        auth::{AuthClient, Credentials},
+        endpoint::{Endpoint, Method},
+        response::{ApiErrors, ApiFailure, ApiResponse, ApiResult, ApiSuccess},
+        Environment,
     },
 };
+use hickory_resolver::error::ResolveError;
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::Serialize;
 use thiserror::Error;
+use tokio::{sync::OnceCell, time};
+use tracing::warn;
+use url::Url;
+
+use super::{
+    ownership::Ownership, rate_limit::RateLimit, resolve::DestinationResolver, DnsRecord,
+    RecordOptions, RecordType,
+};
+use crate::{host::normalize_host, http::HttpOptions, retry::Retryable};
 
 const DEFAULT_TTL: u32 = 300;
+/// A TTL of 1 lets Cloudflare choose it.
+pub const AUTO_TTL: u32 = 1;
+const MIN_TTL: u32 = 60;
+const MAX_TTL: u32 = 86400;
 const DEFAULT_PROXIED: bool = false;
+/// Cloudflare's global API rate limit is 1200 requests per 5 minutes.
+pub const REQUEST_LIMIT: u64 = 1200;
+pub const REQUEST_PERIOD: Duration = Duration::from_secs(5 * 60);
+/// Records listed per request.
+const PAGE_SIZE: u32 = 100;
+/// Same as the `cloudflare` crate's client.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximum number of attempts of requests rate limited by Cloudflare, including the first one.
+const MAX_RATE_LIMITED_ATTEMPTS: u32 = 3;
+/// Delay before retrying rate limited requests without a valid `Retry-After` header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+/// Longest `Retry-After` delay waited for.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Client of the Cloudflare API, like the `cloudflare` crate's, that also retries requests rate
+/// limited despite [`RateLimit`] after their `Retry-After` delay. The crate's client discards
+/// response headers, so it can't.
+pub struct HttpClient {
+    http: reqwest::Client,
+    environment: Environment,
+    credentials: Credentials,
+}
+
+impl HttpClient {
+    /// Creates a client whose requests are configured by `options`.
+    pub fn new(
+        credentials: Credentials,
+        environment: Environment,
+        options: &HttpOptions,
+    ) -> Result<Self, reqwest::Error> {
+        let http = options.builder()?.timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Self {
+            http,
+            environment,
+            credentials,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ApiClient for HttpClient {
+    async fn request<ResultType, QueryType, BodyType>(
+        &self,
+        endpoint: &(dyn Endpoint<ResultType, QueryType, BodyType> + Send + Sync),
+    ) -> ApiResponse<ResultType>
+    where
+        ResultType: ApiResult,
+        QueryType: Serialize,
+        BodyType: Serialize,
+    {
+        let mut attempt = 1;
+        loop {
+            let mut request = self
+                .http
+                .request(
+                    http_method(endpoint.method()),
+                    endpoint.url(&self.environment),
+                )
+                .query(&endpoint.query());
+            if let Some(body) = endpoint.body() {
+                request = request
+                    .body(serde_json::to_string(&body).unwrap())
+                    .header(reqwest::header::CONTENT_TYPE, endpoint.content_type());
+            }
+
+            let response = request.auth(&self.credentials).send().await?;
+            if response.status() == StatusCode::TOO_MANY_REQUESTS
+                && attempt < MAX_RATE_LIMITED_ATTEMPTS
+            {
+                let delay = retry_after(response.headers())
+                    .unwrap_or(DEFAULT_RETRY_AFTER)
+                    .min(MAX_RETRY_AFTER);
+                warn!("Rate limited by Cloudflare, retrying in {:?}", delay);
+                time::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+            return map_api_response(response).await;
+        }
+    }
+}
+
+fn http_method(method: Method) -> reqwest::Method {
+    match method {
+        Method::Get => reqwest::Method::GET,
+        Method::Post => reqwest::Method::POST,
+        Method::Put => reqwest::Method::PUT,
+        Method::Delete => reqwest::Method::DELETE,
+        Method::Patch => reqwest::Method::PATCH,
+    }
+}
+
+/// Reads the delay of a `Retry-After` header in seconds, the form Cloudflare sends.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let secs = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs))
+}
+
+/// Parses a response like the `cloudflare` crate's client, with the API errors of failed ones.
+async fn map_api_response<ResultType: ApiResult>(
+    response: reqwest::Response,
+) -> ApiResponse<ResultType> {
+    let status = response.status();
+    if status.is_success() {
+        response
+            .json::<ApiSuccess<ResultType>>()
+            .await
+            .map_err(ApiFailure::Invalid)
+    } else {
+        let errors = response.json::<ApiErrors>().await.unwrap_or_default();
+        Err(ApiFailure::Error(status, errors))
+    }
+}
+
+/// Lists DNS records, optionally only the ones carrying a tag, which the `cloudflare` crate's
+/// endpoint can't filter by.
+struct ListTaggedDnsRecords<'a> {
+    zone_identifier: &'a str,
+    params: ListTaggedDnsRecordsParams,
+}
+
+impl Endpoint<Vec<CloudflareRecord>, ListTaggedDnsRecordsParams> for ListTaggedDnsRecords<'_> {
+    fn method(&self) -> Method {
+        Method::Get
+    }
+    fn path(&self) -> String {
+        format!("zones/{}/dns_records", self.zone_identifier)
+    }
+    fn query(&self) -> Option<ListTaggedDnsRecordsParams> {
+        Some(self.params.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ListTaggedDnsRecordsParams {
+    #[serde(flatten)]
+    params: ListDnsRecordsParams,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+}
+
+/// Creates a DNS record along with its comment & tags, which the `cloudflare` crate's endpoint
+/// doesn't support, so a record is never left without them.
+struct CreateAnnotatedDnsRecord<'a> {
+    zone_identifier: &'a str,
+    params: CreateAnnotatedDnsRecordParams<'a>,
+}
+
+impl<'a> Endpoint<CloudflareRecord, (), CreateAnnotatedDnsRecordParams<'a>>
+    for CreateAnnotatedDnsRecord<'a>
+{
+    fn method(&self) -> Method {
+        Method::Post
+    }
+    fn path(&self) -> String {
+        format!("zones/{}/dns_records", self.zone_identifier)
+    }
+    fn body(&self) -> Option<CreateAnnotatedDnsRecordParams<'a>> {
+        Some(self.params.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CreateAnnotatedDnsRecordParams<'a> {
+    #[serde(flatten)]
+    params: CreateDnsRecordParams<'a>,
+    #[serde(flatten)]
+    annotations: PatchDnsRecordParams,
+}
+
+/// Partially updates a DNS record, for fields the `cloudflare` crate's endpoints don't support.
+struct PatchDnsRecord<'a> {
+    zone_identifier: &'a str,
+    identifier: &'a str,
+    params: PatchDnsRecordParams,
+}
+
+impl Endpoint<CloudflareRecord, (), PatchDnsRecordParams> for PatchDnsRecord<'_> {
+    fn method(&self) -> Method {
+        Method::Patch
+    }
+    fn path(&self) -> String {
+        format!(
+            "zones/{}/dns_records/{}",
+            self.zone_identifier, self.identifier
+        )
+    }
+    fn body(&self) -> Option<PatchDnsRecordParams> {
+        Some(self.params.clone())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PatchDnsRecordParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+/// Cloudflare API calls made by [`CloudflareProvider`], so tests can mock them.
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait CloudflareClient: Send + Sync {
+    /// Lists the records matching `params`, and carrying `tag` if set.
+    async fn list_records(
+        &self,
+        zone_id: &str,
+        params: ListDnsRecordsParams,
+        tag: Option<String>,
+    ) -> ApiResponse<Vec<CloudflareRecord>>;
+    /// Creates a record, with the comment & tags of `annotations` if set.
+    async fn create_record<'a>(
+        &self,
+        zone_id: &str,
+        params: CreateDnsRecordParams<'a>,
+        annotations: PatchDnsRecordParams,
+    ) -> ApiResponse<CloudflareRecord>;
+    async fn update_record<'a>(
+        &self,
+        zone_id: &str,
+        id: &str,
+        params: UpdateDnsRecordParams<'a>,
+    ) -> ApiResponse<CloudflareRecord>;
+    async fn patch_record(
+        &self,
+        zone_id: &str,
+        id: &str,
+        params: PatchDnsRecordParams,
+    ) -> ApiResponse<CloudflareRecord>;
+    async fn delete_record(&self, zone_id: &str, id: &str) -> ApiResponse<DeleteDnsRecordResponse>;
+    async fn list_zones(&self, params: ListZonesParams) -> ApiResponse<Vec<Zone>>;
+}
+
+#[async_trait::async_trait]
+impl CloudflareClient for HttpClient {
+    async fn list_records(
+        &self,
+        zone_id: &str,
+        params: ListDnsRecordsParams,
+        tag: Option<String>,
+    ) -> ApiResponse<Vec<CloudflareRecord>> {
+        let request = ListTaggedDnsRecords {
+            zone_identifier: zone_id,
+            params: ListTaggedDnsRecordsParams { params, tag },
+        };
+        self.request(&request).await
+    }
+
+    async fn create_record<'a>(
+        &self,
+        zone_id: &str,
+        params: CreateDnsRecordParams<'a>,
+        annotations: PatchDnsRecordParams,
+    ) -> ApiResponse<CloudflareRecord> {
+        let request = CreateAnnotatedDnsRecord {
+            zone_identifier: zone_id,
+            params: CreateAnnotatedDnsRecordParams {
+                params,
+                annotations,
+            },
+        };
+        self.request(&request).await
+    }
+
+    async fn update_record<'a>(
+        &self,
+        zone_id: &str,
+        id: &str,
+        params: UpdateDnsRecordParams<'a>,
+    ) -> ApiResponse<CloudflareRecord> {
+        let request = UpdateDnsRecord {
+            zone_identifier: zone_id,
+            identifier: id,
+            params,
+        };
+        self.request(&request).await
+    }
+
+    async fn patch_record(
+        &self,
+        zone_id: &str,
+        id: &str,
+        params: PatchDnsRecordParams,
+    ) -> ApiResponse<CloudflareRecord> {
+        let request = PatchDnsRecord {
+            zone_identifier: zone_id,
+            identifier: id,
+            params,
+        };
+        self.request(&request).await
+    }
+
+    async fn delete_record(&self, zone_id: &str, id: &str) -> ApiResponse<DeleteDnsRecordResponse> {
+        let request = DeleteDnsRecord {
+            zone_identifier: zone_id,
+            identifier: id,
+        };
+        self.request(&request).await
+    }
+
+    async fn list_zones(&self, params: ListZonesParams) -> ApiResponse<Vec<Zone>> {
+        self.request(&ListZones { params }).await
+    }
+}
 
 pub struct CloudflareProvider {
     dest: String,
-    zone_id: String,
+    /// Zone id, looked up by `zone_name` on first use if it wasn't configured.
+    zone_id: OnceCell<String>,
+    zone_name: Option<String>,
 
-    client: Client,
+    client: Arc<dyn CloudflareClient>,
 
     ttl: u32,
     proxied: bool,
+    ownership: Option<Ownership>,
+    resolver: Option<Arc<DestinationResolver>>,
+    record_type: Option<RecordType>,
+    comment_template: Option<String>,
+    tags: Vec<String>,
+    owner_tag: Option<String>,
+    limiter: Arc<RateLimit>,
 }
 
 impl CloudflareProvider {
     pub fn new(creds: Credentials, zone_id: String, dest: String) -> Result<Self, CloudflareError> {
-        let client = Client::new(
-            creds,
-            HttpApiClientConfig::default(),
-            Environment::Production,
-        )
-        .map_err(|e| match e.downcast::<reqwest::Error>() {
-            Ok(e) => CloudflareError::NewClientError(e),
-            Err(e) => panic!("Unexpected error: {}", e),
-        })?;
+        Self::builder()
+            .credentials(creds)
+            .zone_id(zone_id)
+            .destination(dest)
+            .build()
+    }
 
-        Ok(Self {
-            dest,
-            zone_id,
-            client,
-            ttl: DEFAULT_TTL,
-            proxied: DEFAULT_PROXIED,
-        })
+    pub fn builder() -> CloudflareProviderBuilder {
+        CloudflareProviderBuilder::default()
     }
 
     pub fn ttl(&self) -> &u32 {
@@ -60,22 +395,365 @@ impl CloudflareProvider {
         &mut self.proxied
     }
 
-    async fn list_records(&self) -> Result<Vec<DnsRecord>, CloudflareError> {
-        let request = ListDnsRecords {
-            zone_identifier: &self.zone_id,
-            params: ListDnsRecordsParams {
-                record_type: Some(DnsContent::CNAME {
-                    content: self.dest.clone(),
+    pub fn ownership(&self) -> &Option<Ownership> {
+        &self.ownership
+    }
+    pub fn ownership_mut(&mut self) -> &mut Option<Ownership> {
+        &mut self.ownership
+    }
+
+    /// Resolver of the destination, to create A/AAAA records instead of CNAMEs.
+    pub fn resolver(&self) -> &Option<Arc<DestinationResolver>> {
+        &self.resolver
+    }
+    pub fn resolver_mut(&mut self) -> &mut Option<Arc<DestinationResolver>> {
+        &mut self.resolver
+    }
+
+    /// Type of the created records, or `None` for CNAMEs, or A & AAAA records if the
+    /// destination is resolved. A & AAAA require a resolver.
+    pub fn record_type(&self) -> Option<RecordType> {
+        self.record_type
+    }
+    pub fn record_type_mut(&mut self) -> &mut Option<RecordType> {
+        &mut self.record_type
+    }
+
+    /// Template of the comment set on created records, with `{router}` & `{host}` replaced by
+    /// the id of the host's router & the host.
+    pub fn comment_template(&self) -> &Option<String> {
+        &self.comment_template
+    }
+    pub fn comment_template_mut(&mut self) -> &mut Option<String> {
+        &mut self.comment_template
+    }
+
+    /// Tags set on created records.
+    pub fn tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+    pub fn tags_mut(&mut self) -> &mut Vec<String> {
+        &mut self.tags
+    }
+
+    /// Tag marking the records created by this provider, which are then the only ones listed &
+    /// deleted.
+    pub fn owner_tag(&self) -> &Option<String> {
+        &self.owner_tag
+    }
+    pub fn owner_tag_mut(&mut self) -> &mut Option<String> {
+        &mut self.owner_tag
+    }
+
+    /// Returns the tags to set on created records, including the owner tag.
+    fn record_tags(&self) -> Vec<String> {
+        let mut tags = self.tags.clone();
+        if let Some(owner) = &self.owner_tag {
+            if !tags.contains(owner) {
+                tags.push(owner.clone());
+            }
+        }
+        tags
+    }
+
+    /// Returns the comment to set on the records of `host`, if comments are configured.
+    fn comment(&self, host: &str, options: &RecordOptions) -> Option<String> {
+        let template = self.comment_template.as_ref()?;
+        Some(
+            template
+                .replace("{router}", options.router.as_deref().unwrap_or_default())
+                .replace("{host}", host),
+        )
+    }
+
+    /// Returns `true` if the records created by this provider may hold `addr`.
+    fn holds(&self, addr: &IpAddr) -> bool {
+        self.record_type.is_none_or(|t| t.holds(addr))
+    }
+
+    /// Rate limiter of the API requests, shared by providers using the same credentials.
+    pub fn limiter(&self) -> &Arc<RateLimit> {
+        &self.limiter
+    }
+    pub fn limiter_mut(&mut self) -> &mut Arc<RateLimit> {
+        &mut self.limiter
+    }
+
+    /// Returns the id of the zone, looking it up by name the first time if needed.
+    pub async fn zone_id(&self) -> Result<&str, CloudflareError> {
+        self.zone_id
+            .get_or_try_init(|| self.find_zone())
+            .await
+            .map(String::as_str)
+    }
+
+    /// Finds the id of the zone named `zone_name`.
+    async fn find_zone(&self) -> Result<String, CloudflareError> {
+        let name = self
+            .zone_name
+            .as_deref()
+            .ok_or(CloudflareError::MissingField("zone_id"))?;
+        let params = ListZonesParams {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+        let zones = self.client().await.list_zones(params).await?.result;
+
+        let mut ids = zones
+            .into_iter()
+            .filter(|zone| normalize_host(&zone.name) == normalize_host(name))
+            .map(|zone| zone.id);
+        match (ids.next(), ids.count()) {
+            (Some(id), 0) => Ok(id),
+            (Some(_), others) => Err(CloudflareError::AmbiguousZone {
+                name: name.to_string(),
+                count: others + 1,
+            }),
+            (None, _) => Err(CloudflareError::ZoneNotFound(name.to_string())),
+        }
+    }
+
+    /// Returns the client, once the rate limit allows a request.
+    async fn client(&self) -> &dyn CloudflareClient {
+        self.limiter.ready().await;
+        self.client.as_ref()
+    }
+
+    /// Lists the records matching `params`, and carrying `tag` if set, across all pages.
+    async fn list_all(
+        &self,
+        params: ListDnsRecordsParams,
+        tag: Option<&str>,
+    ) -> Result<Vec<CloudflareRecord>, CloudflareError> {
+        let mut records = Vec::new();
+        for page in 1.. {
+            let zone_id = self.zone_id().await?;
+            let params = ListDnsRecordsParams {
+                page: Some(page),
+                per_page: Some(PAGE_SIZE),
+                ..params.clone()
+            };
+            let response = self
+                .client()
+                .await
+                .list_records(zone_id, params, tag.map(str::to_owned))
+                .await?;
+
+            let done = response.result.is_empty()
+                || total_pages(response.result_info.as_ref()).is_none_or(|total| page >= total);
+            records.extend(response.result);
+            if done {
+                break;
+            }
+        }
+        Ok(records)
+    }
+
+    /// Lists CNAME records with their target, optionally only the one of `host`, or only owned
+    /// ones.
+    async fn list_cname_records(
+        &self,
+        host: Option<&str>,
+        owned: bool,
+    ) -> Result<Vec<(CloudflareRecord, String)>, CloudflareError> {
+        Ok(self
+            .list_all(
+                ListDnsRecordsParams {
+                    name: host.map(str::to_owned),
+                    ..Default::default()
+                },
+                self.owner_tag.as_deref().filter(|_| owned),
+            )
+            .await?
+            .into_iter()
+            .filter_map(|r| match &r.content {
+                DnsContent::CNAME { content } => {
+                    let content = content.clone();
+                    Some((r, content))
+                }
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Lists A/AAAA records, optionally only the ones of `host`, or only owned ones.
+    async fn list_address_records(
+        &self,
+        host: Option<&str>,
+        owned: bool,
+    ) -> Result<Vec<(CloudflareRecord, IpAddr)>, CloudflareError> {
+        Ok(self
+            .list_all(
+                ListDnsRecordsParams {
+                    name: host.map(str::to_owned),
+                    ..Default::default()
+                },
+                self.owner_tag.as_deref().filter(|_| owned),
+            )
+            .await?
+            .into_iter()
+            .filter_map(|r| {
+                let addr = match r.content {
+                    DnsContent::A { content } => IpAddr::V4(content),
+                    DnsContent::AAAA { content } => IpAddr::V6(content),
+                    _ => return None,
+                };
+                self.holds(&addr).then_some((r, addr))
+            })
+            .collect())
+    }
+
+    /// Parameters creating a record of `host`, with `options` overriding the provider's settings.
+    fn create_params<'a>(
+        &self,
+        host: &'a str,
+        content: DnsContent,
+        options: &RecordOptions,
+    ) -> CreateDnsRecordParams<'a> {
+        CreateDnsRecordParams {
+            ttl: Some(options.ttl.unwrap_or(self.ttl)),
+            priority: None,
+            proxied: Some(options.proxied.unwrap_or(self.proxied)),
+            name: host,
+            content,
+        }
+    }
+
+    /// Creates a record of `host` with its comment & tags in a single request, so a failure never
+    /// leaves a record behind that isn't recognized as ours.
+    async fn create(
+        &self,
+        host: &str,
+        content: DnsContent,
+        options: &RecordOptions,
+    ) -> Result<(), CloudflareError> {
+        let zone_id = self.zone_id().await?;
+        let params = self.create_params(host, content, options);
+        let annotations = self.annotations(host, options);
+        self.client()
+            .await
+            .create_record(zone_id, params, annotations)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the comment & tags to set on the records of `host`, if they are configured.
+    fn annotations(&self, host: &str, options: &RecordOptions) -> PatchDnsRecordParams {
+        let tags = self.record_tags();
+        PatchDnsRecordParams {
+            comment: self.comment(host, options),
+            tags: (!tags.is_empty()).then_some(tags),
+        }
+    }
+
+    /// Sets the comment & tags of the record `id` of `host`, if they are configured.
+    async fn annotate(
+        &self,
+        id: &str,
+        host: &str,
+        options: &RecordOptions,
+    ) -> Result<(), CloudflareError> {
+        let params = self.annotations(host, options);
+        if params == PatchDnsRecordParams::default() {
+            return Ok(());
+        }
+        let zone_id = self.zone_id().await?;
+        self.client()
+            .await
+            .patch_record(zone_id, id, params)
+            .await?;
+        Ok(())
+    }
+
+    /// Replaces the A/AAAA records of `host` with the resolved addresses of the destination.
+    async fn replace_address_records(
+        &self,
+        resolver: &DestinationResolver,
+        host: &str,
+        options: &RecordOptions,
+    ) -> Result<(), CloudflareError> {
+        let existing = self.list_address_records(Some(host), false).await?;
+
+        for (record, _) in existing.iter().filter(|(_, a)| !resolver.is_current(a)) {
+            let zone_id = self.zone_id().await?;
+            self.client()
+                .await
+                .delete_record(zone_id, &record.id)
+                .await?;
+        }
+
+        for addr in resolver.addresses().into_iter().filter(|a| self.holds(a)) {
+            if existing.iter().any(|(_, a)| *a == addr) {
+                continue;
+            }
+            let content = match addr {
+                IpAddr::V4(content) => DnsContent::A { content },
+                IpAddr::V6(content) => DnsContent::AAAA { content },
+            };
+            self.create(host, content, options).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Points the CNAME record of `host` at the destination, updating it in place if it exists.
+    async fn replace_cname_record(
+        &self,
+        host: &str,
+        options: &RecordOptions,
+    ) -> Result<(), CloudflareError> {
+        let content = DnsContent::CNAME {
+            content: self.dest.clone(),
+        };
+
+        match self
+            .list_cname_records(Some(host), false)
+            .await?
+            .into_iter()
+            .next()
+        {
+            // Listings filtered by the owner tag miss records whose tagging failed, so they are
+            // tagged again rather than left unowned
+            Some((record, target)) if target == self.dest => {
+                self.annotate(&record.id, host, options).await?;
+            }
+            Some((record, _)) => {
+                let zone_id = self.zone_id().await?;
+                let params = UpdateDnsRecordParams {
+                    ttl: Some(options.ttl.unwrap_or(self.ttl)),
+                    proxied: Some(options.proxied.unwrap_or(self.proxied)),
+                    name: host,
+                    content,
+                };
+                self.client()
+                    .await
+                    .update_record(zone_id, &record.id, params)
+                    .await?;
+                self.annotate(&record.id, host, options).await?;
+            }
+            None => self.create(host, content, options).await?,
+        }
+
+        Ok(())
+    }
+
+    /// Lists ownership TXT markers, optionally only the one for `host`.
+    async fn list_markers(
+        &self,
+        ownership: &Ownership,
+        host: Option<&str>,
+    ) -> Result<Vec<CloudflareRecord>, CloudflareError> {
+        self.list_all(
+            ListDnsRecordsParams {
+                record_type: Some(DnsContent::TXT {
+                    content: ownership.value(),
                 }),
-                name: None,
-                page: None,
-                per_page: Some(5000),
-                order: None,
-                direction: None,
-                search_match: None,
+                name: host.map(Ownership::record_name),
+                ..Default::default()
             },
-        };
-        Ok(self.client.request(&request).await?.result)
+            None,
+        )
+        .await
     }
 }
 
@@ -91,52 +769,336 @@ impl super::Provider for CloudflareProvider {
     }
 
     #[tracing::instrument(skip(self))]
-    async fn list_records(&self) -> Result<Vec<String>, Self::Error> {
-        let records = self.list_records().await?;
-        Ok(records.into_iter().map(|r| r.name).collect())
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        // Hosts with a matching ownership marker
+        let owned: Option<HashSet<_>> = match &self.ownership {
+            Some(ownership) => Some(
+                self.list_markers(ownership, None)
+                    .await?
+                    .into_iter()
+                    .filter_map(|r| Ownership::marked_host(&r.name))
+                    .collect(),
+            ),
+            None => None,
+        };
+
+        if let Some(resolver) = &self.resolver {
+            resolver.refresh(&self.dest).await?;
+            return Ok(self
+                .list_address_records(None, true)
+                .await?
+                .into_iter()
+                .filter(|(_, addr)| resolver.is_known(addr))
+                .filter(|(r, _)| owned.as_ref().is_none_or(|owned| owned.contains(&r.name)))
+                .map(|(r, addr)| DnsRecord {
+                    host: normalize_host(&r.name),
+                    id: Some(r.id),
+                    ttl: Some(r.ttl),
+                    content: addr.to_string(),
+                })
+                .collect());
+        }
+
+        Ok(self
+            .list_cname_records(None, true)
+            .await?
+            .into_iter()
+            // Without markers or an owner tag, only records pointing at the destination are known
+            // to be ours
+            .filter(|(r, target)| match &owned {
+                Some(owned) => owned.contains(&r.name),
+                None => self.owner_tag.is_some() || *target == self.dest,
+            })
+            .map(|(r, target)| DnsRecord {
+                host: normalize_host(&r.name),
+                id: Some(r.id),
+                ttl: Some(r.ttl),
+                content: target,
+            })
+            .collect())
+    }
+
+    fn is_current(&self, record: &DnsRecord) -> bool {
+        match &self.resolver {
+            Some(resolver) => record
+                .content
+                .parse()
+                .is_ok_and(|addr| resolver.is_current(&addr)),
+            None => record.content == self.dest,
+        }
+    }
+
+    // Existing records are updated in place, and missing ownership markers added
+    async fn ensure_record(
+        &self,
+        host: &str,
+        _existing: &[DnsRecord],
+        options: &RecordOptions,
+    ) -> Result<(), Self::Error> {
+        self.create_record(host, options).await
     }
 
     #[tracing::instrument(skip(self))]
-    async fn create_record(&self, host: &str) -> Result<(), Self::Error> {
-        let request = CreateDnsRecord {
-            zone_identifier: &self.zone_id,
-            params: CreateDnsRecordParams {
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        match &self.resolver {
+            Some(resolver) => {
+                self.replace_address_records(resolver, host, options)
+                    .await?
+            }
+            None => self.replace_cname_record(host, options).await?,
+        }
+
+        // Stale hosts are created again, and may already have a marker
+        if let Some(ownership) = &self.ownership {
+            if !self.list_markers(ownership, Some(host)).await?.is_empty() {
+                return Ok(());
+            }
+        }
+
+        if let Some(ownership) = &self.ownership {
+            let name = Ownership::record_name(host);
+            let zone_id = self.zone_id().await?;
+            let params = CreateDnsRecordParams {
                 ttl: Some(self.ttl),
                 priority: None,
-                proxied: Some(self.proxied),
-                name: host,
-                content: DnsContent::CNAME {
-                    content: self.dest.clone(),
+                proxied: None,
+                name: &name,
+                content: DnsContent::TXT {
+                    content: ownership.value(),
                 },
-            },
-        };
-        self.client.request(&request).await?;
+            };
+            self.client()
+                .await
+                .create_record(zone_id, params, PatchDnsRecordParams::default())
+                .await?;
+        }
 
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
-    async fn delete_record(&self, host: &str) -> Result<(), Self::Error> {
-        let record = self
-            .list_records()
-            .await?
-            .into_iter()
-            .find(|r| r.name == host);
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        let id = match &record.id {
+            Some(id) => id.clone(),
+            None if self.resolver.is_some() => {
+                self.list_address_records(Some(&record.host), true)
+                    .await?
+                    .into_iter()
+                    .find(|(_, addr)| addr.to_string() == record.content)
+                    .ok_or(CloudflareError::RecordNotFound)?
+                    .0
+                    .id
+            }
+            None => {
+                self.list_cname_records(Some(&record.host), true)
+                    .await?
+                    .into_iter()
+                    .next()
+                    .ok_or(CloudflareError::RecordNotFound)?
+                    .0
+                    .id
+            }
+        };
 
-        if let Some(record) = record {
-            let request = DeleteDnsRecord {
-                zone_identifier: &self.zone_id,
-                identifier: &record.id,
-            };
-            self.client.request(&request).await?;
+        let zone_id = self.zone_id().await?;
+        self.client().await.delete_record(zone_id, &id).await?;
 
-            Ok(())
-        } else {
-            Err(CloudflareError::RecordNotFound)
+        if let Some(ownership) = &self.ownership {
+            for marker in self.list_markers(ownership, Some(&record.host)).await? {
+                let zone_id = self.zone_id().await?;
+                self.client()
+                    .await
+                    .delete_record(zone_id, &marker.id)
+                    .await?;
+            }
         }
+
+        Ok(())
     }
 }
 
+/// Builder of a [`CloudflareProvider`], validating its configuration.
+#[derive(Default)]
+pub struct CloudflareProviderBuilder {
+    credentials: Option<Credentials>,
+    zone_id: Option<String>,
+    zone_name: Option<String>,
+    dest: Option<String>,
+    ttl: Option<u32>,
+    proxied: Option<bool>,
+    ownership: Option<Ownership>,
+    resolver: Option<Arc<DestinationResolver>>,
+    record_type: Option<RecordType>,
+    comment_template: Option<String>,
+    tags: Vec<String>,
+    owner_tag: Option<String>,
+    limiter: Option<Arc<RateLimit>>,
+    base_url: Option<Url>,
+    http: HttpOptions,
+    client: Option<Arc<dyn CloudflareClient>>,
+}
+
+impl CloudflareProviderBuilder {
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    pub fn zone_id(mut self, zone_id: impl Into<String>) -> Self {
+        self.zone_id = Some(zone_id.into());
+        self
+    }
+
+    /// Name of the zone, to look up its id when `zone_id` isn't set.
+    pub fn zone_name(mut self, zone_name: impl Into<String>) -> Self {
+        self.zone_name = Some(zone_name.into());
+        self
+    }
+
+    pub fn destination(mut self, dest: impl Into<String>) -> Self {
+        self.dest = Some(dest.into());
+        self
+    }
+
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn proxied(mut self, proxied: bool) -> Self {
+        self.proxied = Some(proxied);
+        self
+    }
+
+    pub fn ownership(mut self, ownership: Option<Ownership>) -> Self {
+        self.ownership = ownership;
+        self
+    }
+
+    /// Resolver of the destination, to create A/AAAA records instead of CNAMEs.
+    pub fn resolver(mut self, resolver: Option<Arc<DestinationResolver>>) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Type of the created records. A & AAAA require a resolver, and CNAME forbids it.
+    pub fn record_type(mut self, record_type: RecordType) -> Self {
+        self.record_type = Some(record_type);
+        self
+    }
+
+    /// Template of the comment set on created records, like `managed by traefik-dns-rs ({router})`.
+    /// `{router}` & `{host}` are replaced by the id of the host's router & the host.
+    pub fn comment_template(mut self, template: impl Into<String>) -> Self {
+        self.comment_template = Some(template.into());
+        self
+    }
+
+    /// Tags set on created records.
+    pub fn tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Tag marking the records created by the provider, like `traefik-dns-rs`. Records without it
+    /// are neither listed nor deleted.
+    pub fn owner_tag(mut self, owner_tag: Option<String>) -> Self {
+        self.owner_tag = owner_tag;
+        self
+    }
+
+    /// Rate limiter of the API requests, shared by providers using the same credentials.
+    pub fn limiter(mut self, limiter: Arc<RateLimit>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Base URL of the API, defaulting to Cloudflare's.
+    pub fn base_url(mut self, base_url: Url) -> Self {
+        self.base_url = Some(base_url);
+        self
+    }
+
+    /// Proxy & user agent of the API requests.
+    pub fn http(mut self, http: HttpOptions) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Client of the API, replacing the one created from `credentials`, `base_url` & `http`.
+    pub fn client(mut self, client: Arc<dyn CloudflareClient>) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn build(self) -> Result<CloudflareProvider, CloudflareError> {
+        let zone_id = self.zone_id.filter(|id| !id.is_empty());
+        let zone_name = self.zone_name.filter(|name| !name.is_empty());
+        if zone_id.is_none() && zone_name.is_none() {
+            return Err(CloudflareError::MissingField("zone_id"));
+        }
+        let dest = self
+            .dest
+            .filter(|dest| !dest.is_empty())
+            .ok_or(CloudflareError::MissingField("destination"))?;
+
+        let ttl = self.ttl.unwrap_or(DEFAULT_TTL);
+        if !is_valid_ttl(ttl) {
+            return Err(CloudflareError::InvalidTtl(ttl));
+        }
+        if let Some(record_type) = self.record_type {
+            if record_type.is_address() != self.resolver.is_some() {
+                return Err(CloudflareError::RecordTypeMismatch(record_type));
+            }
+        }
+
+        let client = match self.client {
+            Some(client) => client,
+            None => {
+                let credentials = self
+                    .credentials
+                    .ok_or(CloudflareError::MissingField("credentials"))?;
+                let environment = match self.base_url {
+                    Some(url) => Environment::Custom(url),
+                    None => Environment::Production,
+                };
+                Arc::new(HttpClient::new(credentials, environment, &self.http)?)
+            }
+        };
+
+        Ok(CloudflareProvider {
+            dest,
+            zone_id: OnceCell::new_with(zone_id),
+            zone_name,
+            client,
+            ttl,
+            proxied: self.proxied.unwrap_or(DEFAULT_PROXIED),
+            ownership: self.ownership,
+            resolver: self.resolver,
+            record_type: self.record_type,
+            comment_template: self.comment_template,
+            tags: self.tags,
+            owner_tag: self.owner_tag.filter(|tag| !tag.is_empty()),
+            limiter: self
+                .limiter
+                .unwrap_or_else(|| Arc::new(RateLimit::new(REQUEST_LIMIT, REQUEST_PERIOD))),
+        })
+    }
+}
+
+/// Returns `true` if Cloudflare accepts `ttl`, either [`AUTO_TTL`] or between 60 and 86400.
+pub fn is_valid_ttl(ttl: u32) -> bool {
+    ttl == AUTO_TTL || (MIN_TTL..=MAX_TTL).contains(&ttl)
+}
+
+/// Reads the number of pages from the `result_info` of a list response.
+fn total_pages(result_info: Option<&serde_json::Value>) -> Option<u32> {
+    result_info?
+        .get("total_pages")?
+        .as_u64()
+        .and_then(|total| u32::try_from(total).ok())
+}
+
 #[derive(Debug, Error)]
 pub enum CloudflareError {
     #[error(transparent)]
@@ -145,4 +1107,834 @@ pub enum CloudflareError {
     ApiError(#[from] ApiFailure),
     #[error("record not found")]
     RecordNotFound,
+    #[error("failed to resolve destination: {0}")]
+    ResolveError(#[from] ResolveError),
+    #[error("missing {0}")]
+    MissingField(&'static str),
+    #[error("invalid TTL {0}, must be 1 (automatic) or between 60 and 86400")]
+    InvalidTtl(u32),
+    #[error("{0} records don't match whether the destination is resolved")]
+    RecordTypeMismatch(RecordType),
+    #[error("no zone named {0}")]
+    ZoneNotFound(String),
+    #[error("{count} zones named {name}")]
+    AmbiguousZone { name: String, count: usize },
+}
+
+impl Retryable for CloudflareError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            CloudflareError::ApiError(ApiFailure::Error(status, _)) => {
+                status.as_u16() == 429 || status.is_server_error()
+            }
+            CloudflareError::ApiError(ApiFailure::Invalid(e)) => e.is_timeout() || e.is_connect(),
+            CloudflareError::NewClientError(_)
+            | CloudflareError::RecordNotFound
+            | CloudflareError::MissingField(_)
+            | CloudflareError::InvalidTtl(_)
+            | CloudflareError::RecordTypeMismatch(_)
+            | CloudflareError::ZoneNotFound(_)
+            | CloudflareError::AmbiguousZone { .. } => false,
+            CloudflareError::ResolveError(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cloudflare::framework::response::ApiSuccess;
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    use super::*;
+    use crate::dns::Provider;
+
+    fn credentials() -> Credentials {
+        Credentials::UserAuthToken {
+            token: "token".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let provider = CloudflareProvider::builder()
+            .credentials(credentials())
+            .zone_id("zone")
+            .destination("dest")
+            .ttl(60)
+            .proxied(true)
+            .build()
+            .unwrap();
+        assert_eq!(provider.zone_id.get().map(String::as_str), Some("zone"));
+        assert_eq!(provider.ttl(), &60);
+        assert_eq!(provider.proxied(), &true);
+
+        let err = CloudflareProvider::builder()
+            .zone_id("zone")
+            .destination("dest")
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(err, CloudflareError::MissingField("credentials")));
+
+        for ttl in [1, 60, 86400] {
+            let provider = CloudflareProvider::builder()
+                .credentials(credentials())
+                .zone_id("zone")
+                .destination("dest")
+                .ttl(ttl)
+                .build()
+                .unwrap();
+            assert_eq!(provider.ttl(), &ttl);
+        }
+        for ttl in [0, 10, 30, 59, 86401, 100_000] {
+            let err = CloudflareProvider::builder()
+                .credentials(credentials())
+                .zone_id("zone")
+                .destination("dest")
+                .ttl(ttl)
+                .build()
+                .err()
+                .unwrap();
+            assert!(matches!(err, CloudflareError::InvalidTtl(t) if t == ttl));
+        }
+    }
+
+    #[test]
+    fn test_builder_record_type() {
+        let builder = || {
+            CloudflareProvider::builder()
+                .credentials(credentials())
+                .zone_id("zone")
+                .destination("dest")
+        };
+
+        let resolver = Arc::new(crate::dns::resolve::tests::resolver());
+        let provider = builder()
+            .resolver(Some(resolver))
+            .record_type(RecordType::A)
+            .build()
+            .unwrap();
+        assert_eq!(provider.record_type(), Some(RecordType::A));
+        assert!(provider.holds(&"192.0.2.1".parse().unwrap()));
+        assert!(!provider.holds(&"2001:db8::1".parse().unwrap()));
+
+        // Address records can't be created without resolving the destination
+        let err = builder()
+            .record_type(RecordType::Aaaa)
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            CloudflareError::RecordTypeMismatch(RecordType::Aaaa)
+        ));
+    }
+
+    #[test]
+    fn test_total_pages() {
+        let info = serde_json::json!({"page": 1, "per_page": 100, "total_pages": 2});
+        assert_eq!(total_pages(Some(&info)), Some(2));
+        assert_eq!(total_pages(Some(&serde_json::json!({}))), None);
+        assert_eq!(total_pages(None), None);
+    }
+
+    #[test]
+    fn test_create_params_proxied() {
+        let provider = CloudflareProvider::builder()
+            .credentials(credentials())
+            .zone_id("zone")
+            .destination("dest")
+            .build()
+            .unwrap();
+        let params = |host, proxied| {
+            let content = DnsContent::CNAME {
+                content: "dest".to_string(),
+            };
+            let options = RecordOptions {
+                proxied,
+                ..Default::default()
+            };
+            serde_json::to_value(provider.create_params(host, content, &options)).unwrap()
+        };
+
+        let dashboard = params("dashboard.example.com", Some(true));
+        let api = params("api.example.com", Some(false));
+        assert_eq!(dashboard["proxied"], true);
+        assert_eq!(api["proxied"], false);
+        assert_eq!(dashboard["ttl"], api["ttl"]);
+
+        // Unlabeled hosts use the provider's setting
+        assert_eq!(params("other.example.com", None)["proxied"], false);
+    }
+
+    /// A zone as returned by the API, with only the fields the client requires.
+    fn zone(id: &str, name: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "name": name,
+            "account": {"id": "account", "name": "Account"},
+            "created_on": "2023-01-01T00:00:00Z",
+            "development_mode": 0,
+            "meta": {
+                "custom_certificate_quota": 0,
+                "page_rule_quota": 3,
+                "phishing_detected": false,
+                "multiple_railguns_allowed": false
+            },
+            "modified_on": "2023-01-01T00:00:00Z",
+            "name_servers": [],
+            "owner": {"type": "user", "id": "user", "email": "user@example.com"},
+            "paused": false,
+            "permissions": [],
+            "status": "active",
+            "type": "full"
+        })
+    }
+
+    fn zone_lookup(server: &Server, zones: Vec<serde_json::Value>) {
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/zones"),
+                request::query(url_decoded(contains(("name", "example.com")))),
+            ])
+            .respond_with(json_encoded(serde_json::json!({
+                "success": true,
+                "errors": [],
+                "messages": [],
+                "result": zones
+            }))),
+        );
+    }
+
+    fn named_provider(server: &Server) -> CloudflareProvider {
+        CloudflareProvider::builder()
+            .credentials(credentials())
+            .zone_name("example.com")
+            .destination("dest")
+            .base_url(Url::parse(&server.url_str("/")).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_zone_lookup() {
+        let server = Server::run();
+        zone_lookup(
+            &server,
+            vec![
+                zone("other", "sub.example.com"),
+                zone("1234", "example.com"),
+            ],
+        );
+
+        let provider = named_provider(&server);
+        assert_eq!(provider.zone_id().await.unwrap(), "1234");
+        // The id is cached, the server only expects one request
+        assert_eq!(provider.zone_id().await.unwrap(), "1234");
+    }
+
+    #[tokio::test]
+    async fn test_zone_lookup_not_found() {
+        let server = Server::run();
+        zone_lookup(&server, vec![]);
+
+        let err = named_provider(&server).zone_id().await.unwrap_err();
+        assert!(matches!(err, CloudflareError::ZoneNotFound(name) if name == "example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_zone_lookup_ambiguous() {
+        let server = Server::run();
+        zone_lookup(
+            &server,
+            vec![zone("1234", "example.com"), zone("5678", "example.com")],
+        );
+
+        let err = named_provider(&server).zone_id().await.unwrap_err();
+        assert!(matches!(
+            err,
+            CloudflareError::AmbiguousZone { count: 2, .. }
+        ));
+    }
+
+    /// A record as returned by the API.
+    fn record(id: &str, name: &str, record_type: &str, content: &str) -> CloudflareRecord {
+        serde_json::from_value(record_json(id, name, record_type, content)).unwrap()
+    }
+
+    fn record_json(id: &str, name: &str, record_type: &str, content: &str) -> serde_json::Value {
+        serde_json::json!({
+            "meta": {"auto_added": false},
+            "locked": false,
+            "name": name,
+            "ttl": 300,
+            "zone_id": "zone",
+            "modified_on": "2023-01-01T00:00:00Z",
+            "created_on": "2023-01-01T00:00:00Z",
+            "proxiable": true,
+            "type": record_type,
+            "content": content,
+            "id": id,
+            "proxied": false,
+            "zone_name": "example.com"
+        })
+    }
+
+    fn success<T>(result: T) -> ApiResponse<T> {
+        Ok(ApiSuccess {
+            result,
+            result_info: None,
+            messages: serde_json::Value::Null,
+            errors: vec![],
+        })
+    }
+
+    fn mock_provider(client: MockCloudflareClient) -> CloudflareProvider {
+        CloudflareProvider::builder()
+            .zone_id("zone")
+            .destination("dest")
+            .client(Arc::new(client))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_list_records() {
+        let mut client = MockCloudflareClient::new();
+        client
+            .expect_list_records()
+            .withf(|zone_id, params, tag| {
+                zone_id == "zone" && params.name.is_none() && tag.is_none()
+            })
+            .times(1)
+            .returning(|_, _, _| {
+                success(vec![
+                    record("1", "test1.example.com", "CNAME", "dest"),
+                    record("2", "other.example.com", "CNAME", "other.dest"),
+                    record("3", "a.example.com", "A", "192.0.2.1"),
+                ])
+            });
+
+        let records = mock_provider(client).list_records().await.unwrap();
+        assert_eq!(
+            records,
+            vec![DnsRecord {
+                host: "test1.example.com".to_string(),
+                id: Some("1".to_string()),
+                ttl: Some(300),
+                content: "dest".to_string(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_records_owner_tag_stale() {
+        let mut client = MockCloudflareClient::new();
+        client
+            .expect_list_records()
+            .withf(|_, params, tag| {
+                params.name.is_none() && tag.as_deref() == Some("traefik-dns-rs")
+            })
+            .times(1)
+            .returning(|_, _, _| {
+                success(vec![record("1", "test.example.com", "CNAME", "old.dest")])
+            });
+
+        let provider = CloudflareProvider::builder()
+            .zone_id("zone")
+            .destination("dest")
+            .owner_tag(Some("traefik-dns-rs".to_string()))
+            .client(Arc::new(client))
+            .build()
+            .unwrap();
+        // The tag marks the record as ours, so its stale target is updated
+        let records = provider.list_records().await.unwrap();
+        assert_eq!(
+            records,
+            vec![DnsRecord {
+                host: "test.example.com".to_string(),
+                id: Some("1".to_string()),
+                ttl: Some(300),
+                content: "old.dest".to_string(),
+            }]
+        );
+        assert!(!provider.is_current(&records[0]));
+    }
+
+    #[tokio::test]
+    async fn test_create_record() {
+        let mut client = MockCloudflareClient::new();
+        client
+            .expect_list_records()
+            .withf(|_, params, _| params.name.as_deref() == Some("test.example.com"))
+            .times(1)
+            .returning(|_, _, _| success(vec![]));
+        client
+            .expect_create_record()
+            .withf(|zone_id, params, _| {
+                zone_id == "zone"
+                    && params.name == "test.example.com"
+                    && params.ttl == Some(60)
+                    && matches!(&params.content, DnsContent::CNAME { content } if content == "dest")
+            })
+            .times(1)
+            .returning(|_, _, _| success(record("1", "test.example.com", "CNAME", "dest")));
+
+        let options = RecordOptions {
+            ttl: Some(60),
+            ..Default::default()
+        };
+        mock_provider(client)
+            .create_record("test.example.com", &options)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_record_retags() {
+        let mut client = MockCloudflareClient::new();
+        // The record exists, but tagging it failed
+        client
+            .expect_list_records()
+            .withf(|_, params, tag| {
+                params.name.as_deref() == Some("test.example.com") && tag.is_none()
+            })
+            .times(2)
+            .returning(|_, _, _| success(vec![record("1", "test.example.com", "CNAME", "dest")]));
+        client.expect_create_record().never();
+        let mut seq = mockall::Sequence::new();
+        client
+            .expect_patch_record()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _, _| {
+                Err(ApiFailure::Error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ApiErrors::default(),
+                ))
+            });
+        client
+            .expect_patch_record()
+            .withf(|_, id, params| {
+                id == "1" && params.tags.as_deref() == Some(&["traefik-dns-rs".to_string()][..])
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _, _| success(record("1", "test.example.com", "CNAME", "dest")));
+
+        let provider = CloudflareProvider::builder()
+            .zone_id("zone")
+            .destination("dest")
+            .owner_tag(Some("traefik-dns-rs".to_string()))
+            .client(Arc::new(client))
+            .build()
+            .unwrap();
+        let options = RecordOptions::default();
+        // The retry tags the existing record instead of creating a duplicate
+        assert!(provider
+            .create_record("test.example.com", &options)
+            .await
+            .is_err());
+        provider
+            .create_record("test.example.com", &options)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_record_by_id() {
+        let mut client = MockCloudflareClient::new();
+        // The listed id is deleted without listing the records again
+        client.expect_list_records().never();
+        client
+            .expect_delete_record()
+            .withf(|zone_id, id| zone_id == "zone" && id == "1")
+            .once()
+            .returning(|_, id| success(DeleteDnsRecordResponse { id: id.to_string() }));
+
+        let record = DnsRecord {
+            host: "test.example.com".to_string(),
+            id: Some("1".to_string()),
+            ttl: Some(300),
+            content: "dest".to_string(),
+        };
+        mock_provider(client).delete_record(&record).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_record_not_found() {
+        let mut client = MockCloudflareClient::new();
+        client
+            .expect_list_records()
+            .times(1)
+            .returning(|_, _, _| success(vec![]));
+        client.expect_delete_record().never();
+
+        let record = DnsRecord {
+            host: "test.example.com".to_string(),
+            id: None,
+            ttl: None,
+            content: "dest".to_string(),
+        };
+        let err = mock_provider(client)
+            .delete_record(&record)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CloudflareError::RecordNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_create_record_comment() {
+        let mut client = MockCloudflareClient::new();
+        client
+            .expect_list_records()
+            .times(1)
+            .returning(|_, _, _| success(vec![]));
+        // The comment is sent along with the record
+        client
+            .expect_create_record()
+            .withf(|zone_id, _, annotations| {
+                zone_id == "zone"
+                    && annotations.comment.as_deref()
+                        == Some("managed by traefik-dns-rs (whoami@docker, test.example.com)")
+            })
+            .times(1)
+            .returning(|_, _, _| success(record("1", "test.example.com", "CNAME", "dest")));
+        client.expect_patch_record().never();
+
+        let provider = CloudflareProvider::builder()
+            .zone_id("zone")
+            .destination("dest")
+            .comment_template("managed by traefik-dns-rs ({router}, {host})")
+            .client(Arc::new(client))
+            .build()
+            .unwrap();
+        let options = RecordOptions {
+            router: Some("whoami@docker".to_string()),
+            ..Default::default()
+        };
+        provider
+            .create_record("test.example.com", &options)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_record_tags() {
+        let mut client = MockCloudflareClient::new();
+        client
+            .expect_list_records()
+            .withf(|_, _, tag| tag.is_none())
+            .times(1)
+            .returning(|_, _, _| success(vec![]));
+        client
+            .expect_create_record()
+            .withf(|_, _, annotations| {
+                annotations.comment.is_none()
+                    && annotations.tags.as_deref()
+                        == Some(&["env:prod".to_string(), "traefik-dns-rs".to_string()][..])
+            })
+            .times(1)
+            .returning(|_, _, _| success(record("1", "test.example.com", "CNAME", "dest")));
+        client.expect_patch_record().never();
+
+        let provider = CloudflareProvider::builder()
+            .zone_id("zone")
+            .destination("dest")
+            .tags(vec!["env:prod".to_string()])
+            .owner_tag(Some("traefik-dns-rs".to_string()))
+            .client(Arc::new(client))
+            .build()
+            .unwrap();
+        provider
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_owner_tag() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/zones/zone/dns_records"),
+                request::query(url_decoded(contains(("tag", "traefik-dns-rs")))),
+                request::query(url_decoded(contains(("page", "1")))),
+                request::query(url_decoded(not(contains(key("name"))))),
+            ])
+            .respond_with(json_encoded(serde_json::json!({
+                "success": true,
+                "errors": [],
+                "messages": [],
+                "result": [record_json("1", "test.example.com", "CNAME", "dest")]
+            }))),
+        );
+        // The untagged record of the host isn't found, so it isn't deleted
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/zones/zone/dns_records"),
+                request::query(url_decoded(contains(("tag", "traefik-dns-rs")))),
+                request::query(url_decoded(contains(("name", "untagged.example.com")))),
+            ])
+            .respond_with(json_encoded(serde_json::json!({
+                "success": true,
+                "errors": [],
+                "messages": [],
+                "result": []
+            }))),
+        );
+
+        let provider = CloudflareProvider::builder()
+            .credentials(credentials())
+            .zone_id("zone")
+            .destination("dest")
+            .owner_tag(Some("traefik-dns-rs".to_string()))
+            .base_url(Url::parse(&server.url_str("/")).unwrap())
+            .build()
+            .unwrap();
+
+        let records = provider.list_records().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].host, "test.example.com");
+
+        let record = DnsRecord {
+            host: "untagged.example.com".to_string(),
+            id: None,
+            ttl: None,
+            content: "dest".to_string(),
+        };
+        let err = provider.delete_record(&record).await.unwrap_err();
+        assert!(matches!(err, CloudflareError::RecordNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_create_record_annotated() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/zones/zone/dns_records"))
+                .respond_with(json_encoded(serde_json::json!({
+                    "success": true,
+                    "errors": [],
+                    "messages": [],
+                    "result": []
+                }))),
+        );
+        // The comment & tags are part of the create request, without a follow-up PATCH
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/zones/zone/dns_records"),
+                request::body(json_decoded(eq(serde_json::json!({
+                    "ttl": 300,
+                    "proxied": false,
+                    "name": "test.example.com",
+                    "type": "CNAME",
+                    "content": "dest",
+                    "comment": "managed for test.example.com",
+                    "tags": ["traefik-dns-rs"],
+                })))),
+            ])
+            .respond_with(json_encoded(serde_json::json!({
+                "success": true,
+                "errors": [],
+                "messages": [],
+                "result": record_json("1", "test.example.com", "CNAME", "dest")
+            }))),
+        );
+
+        let provider = CloudflareProvider::builder()
+            .credentials(credentials())
+            .zone_id("zone")
+            .destination("dest")
+            .comment_template("managed for {host}")
+            .owner_tag(Some("traefik-dns-rs".to_string()))
+            .base_url(Url::parse(&server.url_str("/")).unwrap())
+            .build()
+            .unwrap();
+        provider
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_records_base_url() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/client/v4/zones/zone/dns_records"),
+                request::headers(contains(("authorization", "Bearer token"))),
+            ])
+            .respond_with(json_encoded(serde_json::json!({
+                "success": true,
+                "errors": [],
+                "messages": [],
+                "result": [record_json("1", "test.example.com", "CNAME", "dest")]
+            }))),
+        );
+
+        let provider = CloudflareProvider::builder()
+            .credentials(credentials())
+            .zone_id("zone")
+            .destination("dest")
+            .base_url(Url::parse(&server.url_str("/client/v4/")).unwrap())
+            .build()
+            .unwrap();
+        let records = provider.list_records().await.unwrap();
+        assert_eq!(
+            records,
+            vec![DnsRecord {
+                host: "test.example.com".to_string(),
+                id: Some("1".to_string()),
+                ttl: Some(300),
+                content: "dest".to_string(),
+            }]
+        );
+    }
+
+    /// Expects a request listing records, responding with `responder`.
+    fn list_expectation(server: &Server, times: usize, responder: impl Responder + 'static) {
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/zones/zone/dns_records"))
+                .times(times)
+                .respond_with(responder),
+        );
+    }
+
+    fn base_url_provider(server: &Server) -> CloudflareProvider {
+        CloudflareProvider::builder()
+            .credentials(credentials())
+            .zone_id("zone")
+            .destination("dest")
+            .base_url(Url::parse(&server.url_str("/")).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_retry_after() {
+        let server = Server::run();
+        list_expectation(
+            &server,
+            2,
+            cycle![
+                status_code(429).insert_header("Retry-After", "1"),
+                json_encoded(serde_json::json!({
+                    "success": true,
+                    "errors": [],
+                    "messages": [],
+                    "result": [record_json("1", "test.example.com", "CNAME", "dest")]
+                })),
+            ],
+        );
+
+        let start = std::time::Instant::now();
+        let records = base_url_provider(&server).list_records().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_bounded() {
+        let server = Server::run();
+        list_expectation(
+            &server,
+            MAX_RATE_LIMITED_ATTEMPTS as usize,
+            status_code(429).insert_header("Retry-After", "0"),
+        );
+
+        let err = base_url_provider(&server).list_records().await.unwrap_err();
+        assert!(matches!(
+            err,
+            CloudflareError::ApiError(ApiFailure::Error(status, _)) if status == 429
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_server_error_not_retried() {
+        let server = Server::run();
+        list_expectation(&server, 1, status_code(500));
+
+        let err = base_url_provider(&server).list_records().await.unwrap_err();
+        assert!(matches!(
+            err,
+            CloudflareError::ApiError(ApiFailure::Error(status, _)) if status == 500
+        ));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(reqwest::header::RETRY_AFTER, value.parse().unwrap());
+            headers
+        };
+        assert_eq!(retry_after(&headers("5")), Some(Duration::from_secs(5)));
+        assert_eq!(retry_after(&headers("soon")), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_dual_stack() {
+        let mut client = MockCloudflareClient::new();
+        client
+            .expect_list_records()
+            .withf(|_, params, _| params.name.as_deref() == Some("test.example.com"))
+            .times(1)
+            .returning(|_, _, _| success(vec![]));
+        client
+            .expect_create_record()
+            .withf(|_, params, _| {
+                matches!(params.content, DnsContent::A { content } if content.to_string() == "192.0.2.1")
+            })
+            .times(1)
+            .returning(|_, _, _| success(record("1", "test.example.com", "A", "192.0.2.1")));
+        client
+            .expect_create_record()
+            .withf(|_, params, _| {
+                matches!(params.content, DnsContent::AAAA { content } if content.to_string() == "2001:db8::1")
+            })
+            .times(1)
+            .returning(|_, _, _| success(record("2", "test.example.com", "AAAA", "2001:db8::1")));
+        client
+            .expect_list_records()
+            .withf(|_, params, _| params.name.is_none())
+            .times(1)
+            .returning(|_, _, _| {
+                success(vec![
+                    record("1", "test.example.com", "A", "192.0.2.1"),
+                    record("2", "test.example.com", "AAAA", "2001:db8::1"),
+                    record("3", "other.example.com", "A", "192.0.2.99"),
+                ])
+            });
+        client
+            .expect_delete_record()
+            .withf(|_, id| id == "1" || id == "2")
+            .times(2)
+            .returning(|_, id| success(DeleteDnsRecordResponse { id: id.to_string() }));
+
+        let resolver = DestinationResolver::fixed(vec![
+            "192.0.2.1".parse().unwrap(),
+            "2001:db8::1".parse().unwrap(),
+        ]);
+        let provider = CloudflareProvider::builder()
+            .zone_id("zone")
+            .destination("192.0.2.1,2001:db8::1")
+            .resolver(Some(Arc::new(resolver)))
+            .client(Arc::new(client))
+            .build()
+            .unwrap();
+
+        provider
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+
+        // Both records of the host are listed, so both are deleted with it
+        let records = provider.list_records().await.unwrap();
+        let contents: Vec<_> = records.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(contents, vec!["192.0.2.1", "2001:db8::1"]);
+        for record in &records {
+            provider.delete_record(record).await.unwrap();
+        }
+    }
 }
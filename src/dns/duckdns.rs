@@ -0,0 +1,436 @@
+use std::{collections::BTreeSet, net::IpAddr};
+
+use reqwest::{Client, Url};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use super::{ApplyOptions, DnsRecord, FailedChange, Provider, RecordOptions};
+use crate::{
+    host::normalize_host,
+    retry::{retry, Retryable},
+};
+
+const BASE_URL: &str = "https://www.duckdns.org/";
+/// Domain of every DuckDNS subdomain.
+const DUCKDNS_DOMAIN: &str = "duckdns.org";
+
+/// Points a DuckDNS subdomain at the destination address.
+///
+/// DuckDNS only offers its update endpoint, which sets the A/AAAA (and TXT) records of a
+/// `*.duckdns.org` subdomain: it can't create CNAMEs, nor records of other domains. The
+/// destination must therefore be an IP address, and the only hosts managed are the subdomain and
+/// the hosts below it, which DuckDNS answers with the same address. Creating a host sets the
+/// address of the subdomain, and deleting the last one clears it.
+///
+/// DuckDNS can't list records either, so the listed records are the hosts created since startup.
+#[derive(Debug)]
+pub struct DuckDnsProvider {
+    /// IP address the subdomain points at.
+    dest: String,
+    /// Subdomain, without `.duckdns.org`.
+    domain: String,
+
+    base_url: Url,
+    token: String,
+    client: Client,
+
+    /// Hosts created since startup.
+    hosts: Mutex<BTreeSet<String>>,
+}
+
+impl DuckDnsProvider {
+    pub fn new(token: String, domain: String, dest: String) -> Self {
+        Self::with_base_url(token, domain, dest, Url::parse(BASE_URL).unwrap())
+    }
+
+    pub fn with_base_url(token: String, domain: String, dest: String, base_url: Url) -> Self {
+        let domain = normalize_host(&domain);
+        let domain = domain
+            .strip_suffix(DUCKDNS_DOMAIN)
+            .and_then(|d| d.strip_suffix('.'))
+            .map(str::to_owned)
+            .unwrap_or(domain);
+        Self {
+            dest,
+            domain,
+            base_url,
+            token,
+            client: crate::http::default_client(),
+            hosts: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// HTTP client of the API requests, e.g. to send them through a proxy.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+    pub fn client_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    /// Fully qualified name of the subdomain.
+    fn fqdn(&self) -> String {
+        format!("{}.{}", self.domain, DUCKDNS_DOMAIN)
+    }
+
+    /// Checks that `host` is the subdomain or below it.
+    fn check_host(&self, host: &str) -> Result<String, DuckDnsError> {
+        let host = normalize_host(host);
+        let fqdn = self.fqdn();
+        let below = host
+            .strip_suffix(&fqdn)
+            .is_some_and(|rest| rest.is_empty() || rest.ends_with('.'));
+        if below {
+            Ok(host)
+        } else {
+            Err(DuckDnsError::OutsideDomain(host))
+        }
+    }
+
+    /// Returns the URL updating the subdomain to the destination, or clearing it if `clear`.
+    fn update_url(&self, clear: bool) -> Result<Url, DuckDnsError> {
+        let mut url = self.base_url.join("update")?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("domains", &self.domain)
+                .append_pair("token", &self.token);
+            if clear {
+                query.append_pair("clear", "true");
+            } else {
+                match self.dest.parse::<IpAddr>() {
+                    Ok(IpAddr::V4(ip)) => query.append_pair("ip", &ip.to_string()),
+                    Ok(IpAddr::V6(ip)) => query.append_pair("ipv6", &ip.to_string()),
+                    Err(_) => return Err(DuckDnsError::InvalidDestination(self.dest.clone())),
+                };
+            }
+        }
+        Ok(url)
+    }
+
+    /// Sends an update, DuckDNS answering `OK` or `KO` without further details.
+    async fn update(&self, clear: bool) -> Result<(), DuckDnsError> {
+        let url = self.update_url(clear)?;
+        // The token is part of the URL, so it's kept out of the errors
+        let body = async {
+            self.client
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await
+        }
+        .await
+        .map_err(reqwest::Error::without_url)?;
+
+        match body.trim() {
+            "OK" => Ok(()),
+            _ => Err(DuckDnsError::Rejected),
+        }
+    }
+
+    fn record(&self, host: String) -> DnsRecord {
+        DnsRecord {
+            host,
+            id: None,
+            ttl: None,
+            content: self.dest.clone(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for DuckDnsProvider {
+    type Error = DuckDnsError;
+
+    fn destination(&self) -> &str {
+        &self.dest
+    }
+    fn destination_mut(&mut self) -> &mut String {
+        &mut self.dest
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        let hosts = self.hosts.lock().await;
+        Ok(hosts.iter().map(|host| self.record(host.clone())).collect())
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn create_record(&self, host: &str, _options: &RecordOptions) -> Result<(), Self::Error> {
+        let host = self.check_host(host)?;
+        let mut hosts = self.hosts.lock().await;
+        self.update(false).await?;
+        hosts.insert(host);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        let host = normalize_host(&record.host);
+        let mut hosts = self.hosts.lock().await;
+        // The subdomain is only cleared once none of its hosts are left
+        if hosts.len() == 1 && hosts.contains(&host) {
+            self.update(true).await?;
+        }
+        hosts.remove(&host);
+        Ok(())
+    }
+
+    /// Sets the subdomain once for every created host, then clears it if no host is left.
+    async fn apply_changes(
+        &self,
+        creates: &[String],
+        deletes: &[DnsRecord],
+        options: &ApplyOptions,
+    ) -> Result<(), Vec<FailedChange<Self::Error>>> {
+        let mut failures = Vec::new();
+        let mut created = Vec::with_capacity(creates.len());
+        for host in creates {
+            match self.check_host(host) {
+                Ok(host) => created.push(host),
+                Err(error) => failures.push(FailedChange {
+                    hosts: vec![host.clone()],
+                    error,
+                }),
+            }
+        }
+
+        let mut hosts = self.hosts.lock().await;
+        if !created.is_empty() {
+            options.ready().await;
+            match retry(&options.retry, || self.update(false)).await {
+                Ok(()) => hosts.extend(created),
+                Err(error) => failures.push(FailedChange {
+                    hosts: created,
+                    error,
+                }),
+            }
+        }
+
+        if !deletes.is_empty() {
+            let deleted: Vec<_> = deletes.iter().map(|r| normalize_host(&r.host)).collect();
+            let remaining = hosts.iter().filter(|host| !deleted.contains(host)).count();
+            if remaining > 0 {
+                for host in &deleted {
+                    hosts.remove(host);
+                }
+            } else {
+                options.ready().await;
+                match retry(&options.retry, || self.update(true)).await {
+                    Ok(()) => hosts.clear(),
+                    Err(error) => failures.push(FailedChange {
+                        hosts: deletes.iter().map(|r| r.host.clone()).collect(),
+                        error,
+                    }),
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DuckDnsError {
+    #[error(transparent)]
+    ReqwestError(#[from] reqwest::Error),
+    #[error(transparent)]
+    UrlParseError(#[from] url::ParseError),
+    #[error("DuckDNS rejected the update, check the token & domain")]
+    Rejected,
+    #[error("{0} isn't the DuckDNS subdomain or below it")]
+    OutsideDomain(String),
+    #[error("destination {0} isn't an IP address")]
+    InvalidDestination(String),
+}
+
+impl Retryable for DuckDnsError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            DuckDnsError::ReqwestError(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status().is_some_and(|s| s == 429 || s.is_server_error())
+            }
+            DuckDnsError::UrlParseError(_)
+            | DuckDnsError::Rejected
+            | DuckDnsError::OutsideDomain(_)
+            | DuckDnsError::InvalidDestination(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    use super::*;
+
+    fn provider(server: &Server) -> DuckDnsProvider {
+        DuckDnsProvider::with_base_url(
+            "token".to_string(),
+            "myhome".to_string(),
+            "192.0.2.1".to_string(),
+            Url::parse(&server.url_str("/")).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_update_url() {
+        let mut provider = DuckDnsProvider::new(
+            "token".to_string(),
+            "myhome.duckdns.org".to_string(),
+            "192.0.2.1".to_string(),
+        );
+        assert_eq!(
+            provider.update_url(false).unwrap().as_str(),
+            "https://www.duckdns.org/update?domains=myhome&token=token&ip=192.0.2.1"
+        );
+        assert_eq!(
+            provider.update_url(true).unwrap().as_str(),
+            "https://www.duckdns.org/update?domains=myhome&token=token&clear=true"
+        );
+
+        *provider.destination_mut() = "2001:db8::1".to_string();
+        assert_eq!(
+            provider.update_url(false).unwrap().as_str(),
+            "https://www.duckdns.org/update?domains=myhome&token=token&ipv6=2001%3Adb8%3A%3A1"
+        );
+
+        *provider.destination_mut() = "traefik.example.com".to_string();
+        assert!(matches!(
+            provider.update_url(false),
+            Err(DuckDnsError::InvalidDestination(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_host() {
+        let provider = DuckDnsProvider::new(
+            "token".to_string(),
+            "myhome".to_string(),
+            "192.0.2.1".to_string(),
+        );
+        assert_eq!(
+            provider.check_host("myhome.duckdns.org.").unwrap(),
+            "myhome.duckdns.org"
+        );
+        assert_eq!(
+            provider.check_host("app.myhome.duckdns.org").unwrap(),
+            "app.myhome.duckdns.org"
+        );
+        assert!(matches!(
+            provider.check_host("notmyhome.duckdns.org"),
+            Err(DuckDnsError::OutsideDomain(_))
+        ));
+        assert!(matches!(
+            provider.check_host("app.example.com"),
+            Err(DuckDnsError::OutsideDomain(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_delete_record() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/update"),
+                request::query(url_decoded(contains(("domains", "myhome")))),
+                request::query(url_decoded(contains(("token", "token")))),
+                request::query(url_decoded(contains(("ip", "192.0.2.1")))),
+            ])
+            .times(2)
+            .respond_with(status_code(200).body("OK")),
+        );
+        // Only the last host clears the subdomain
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/update"),
+                request::query(url_decoded(contains(("clear", "true")))),
+            ])
+            .respond_with(status_code(200).body("OK")),
+        );
+
+        let provider = provider(&server);
+        let options = RecordOptions::default();
+        provider
+            .create_record("myhome.duckdns.org", &options)
+            .await
+            .unwrap();
+        provider
+            .create_record("app.myhome.duckdns.org", &options)
+            .await
+            .unwrap();
+
+        let records = provider.list_records().await.unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| (r.host.as_str(), r.content.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("app.myhome.duckdns.org", "192.0.2.1"),
+                ("myhome.duckdns.org", "192.0.2.1"),
+            ]
+        );
+
+        for record in &records {
+            provider.delete_record(record).await.unwrap();
+        }
+        assert!(provider.list_records().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_record_rejected() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/update"))
+                .respond_with(status_code(200).body("KO")),
+        );
+
+        let provider = provider(&server);
+        let err = provider
+            .create_record("myhome.duckdns.org", &RecordOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DuckDnsError::Rejected));
+        assert!(provider.list_records().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_apply_changes() {
+        let server = Server::run();
+        // Every created host is set at once
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/update"),
+                request::query(url_decoded(contains(("ip", "192.0.2.1")))),
+            ])
+            .respond_with(status_code(200).body("OK")),
+        );
+
+        let provider = provider(&server);
+        let failures = provider
+            .apply_changes(
+                &[
+                    "myhome.duckdns.org".to_string(),
+                    "app.myhome.duckdns.org".to_string(),
+                    "app.example.com".to_string(),
+                ],
+                &[],
+                &ApplyOptions::default(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].hosts, vec!["app.example.com".to_string()]);
+        assert!(matches!(failures[0].error, DuckDnsError::OutsideDomain(_)));
+        assert_eq!(provider.list_records().await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,435 @@
+use std::{sync::Arc, time::Duration};
+
+use reqwest::{Client, Method, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+
+use super::{rate_limit::RateLimit, DnsRecord, Provider, RecordOptions};
+use crate::{host::normalize_host, retry::Retryable};
+
+const DEFAULT_TTL: u32 = 300;
+const BASE_URL: &str = "https://api.dynu.com/v2/";
+/// Dynu doesn't document a rate limit, so requests are paced conservatively.
+pub const REQUEST_LIMIT: u64 = 60;
+pub const REQUEST_PERIOD: Duration = Duration::from_secs(60);
+
+const CNAME_TYPE: &str = "CNAME";
+
+/// Manages the CNAME records of a domain hosted by Dynu, through its v2 API.
+#[derive(Debug)]
+pub struct DynuProvider {
+    dest: String,
+    domain_id: u64,
+
+    base_url: Url,
+    api_key: String,
+    client: Client,
+    limiter: Arc<RateLimit>,
+
+    /// Name of the domain, fetched on first use.
+    domain: OnceCell<String>,
+
+    ttl: u32,
+}
+
+impl DynuProvider {
+    pub fn new(api_key: String, domain_id: u64, dest: String) -> Self {
+        Self::with_base_url(api_key, domain_id, dest, Url::parse(BASE_URL).unwrap())
+    }
+
+    pub fn with_base_url(api_key: String, domain_id: u64, dest: String, base_url: Url) -> Self {
+        Self {
+            dest,
+            domain_id,
+            base_url,
+            api_key,
+            client: crate::http::default_client(),
+            limiter: Arc::new(RateLimit::new(REQUEST_LIMIT, REQUEST_PERIOD)),
+            domain: OnceCell::new(),
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    pub fn ttl(&self) -> &u32 {
+        &self.ttl
+    }
+    pub fn ttl_mut(&mut self) -> &mut u32 {
+        &mut self.ttl
+    }
+
+    /// HTTP client of the API requests, e.g. to send them through a proxy.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+    pub fn client_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    /// Rate limiter of the API requests, defaulting to one per provider.
+    pub fn limiter(&self) -> &Arc<RateLimit> {
+        &self.limiter
+    }
+    pub fn limiter_mut(&mut self) -> &mut Arc<RateLimit> {
+        &mut self.limiter
+    }
+
+    /// Starts an authenticated request to `path`, once the rate limit allows it.
+    async fn request(&self, method: Method, path: &str) -> Result<RequestBuilder, DynuError> {
+        let url = self.base_url.join(path)?;
+        self.limiter.ready().await;
+        Ok(self
+            .client
+            .request(method, url)
+            .header("API-Key", &self.api_key))
+    }
+
+    async fn domain(&self) -> Result<&str, DynuError> {
+        self.domain
+            .get_or_try_init(|| async {
+                let domain = self
+                    .request(Method::GET, &format!("dns/{}", self.domain_id))
+                    .await?
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<DynuDomain>()
+                    .await?;
+                Ok(normalize_host(&domain.name))
+            })
+            .await
+            .map(String::as_str)
+    }
+
+    /// Converts a fully qualified host into a node name relative to the domain.
+    fn node_name<'a>(host: &'a str, domain: &str) -> Result<&'a str, DynuError> {
+        if host == domain {
+            return Ok("");
+        }
+        host.strip_suffix(domain)
+            .and_then(|s| s.strip_suffix('.'))
+            .ok_or_else(|| DynuError::OutsideZone(host.to_owned()))
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for DynuProvider {
+    type Error = DynuError;
+
+    fn destination(&self) -> &str {
+        &self.dest
+    }
+    fn destination_mut(&mut self) -> &mut String {
+        &mut self.dest
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        let records = self
+            .request(Method::GET, &format!("dns/{}/record", self.domain_id))
+            .await?
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<DynuRecordList>()
+            .await?;
+
+        let dest = normalize_host(&self.dest);
+        Ok(records
+            .dns_records
+            .into_iter()
+            .filter(|r| r.record_type == CNAME_TYPE)
+            .filter(|r| {
+                r.text_data
+                    .as_deref()
+                    .is_some_and(|data| normalize_host(data) == dest)
+            })
+            .map(|r| DnsRecord {
+                host: normalize_host(&r.hostname),
+                id: Some(r.id.to_string()),
+                ttl: Some(r.ttl),
+                content: r.text_data.unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        let host = normalize_host(host);
+        let domain = self.domain().await?;
+        let record = DynuNewRecord {
+            node_name: Self::node_name(&host, domain)?,
+            record_type: CNAME_TYPE,
+            ttl: options.ttl.unwrap_or(self.ttl),
+            state: true,
+            text_data: &self.dest,
+        };
+
+        self.request(Method::POST, &format!("dns/{}/record", self.domain_id))
+            .await?
+            .json(&record)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        let id = record.id.as_deref().ok_or(DynuError::RecordNotFound)?;
+
+        self.request(
+            Method::DELETE,
+            &format!("dns/{}/record/{}", self.domain_id, id),
+        )
+        .await?
+        .send()
+        .await?
+        .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DynuError {
+    #[error(transparent)]
+    ReqwestError(#[from] reqwest::Error),
+    #[error(transparent)]
+    UrlParseError(#[from] url::ParseError),
+    #[error("host {0} is outside of the domain")]
+    OutsideZone(String),
+    #[error("record not found")]
+    RecordNotFound,
+}
+
+impl Retryable for DynuError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            DynuError::ReqwestError(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status()
+                        .is_some_and(|s| s.as_u16() == 429 || s.is_server_error())
+            }
+            DynuError::UrlParseError(_) | DynuError::OutsideZone(_) | DynuError::RecordNotFound => {
+                false
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DynuDomain {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DynuRecordList {
+    dns_records: Vec<DynuRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DynuRecord {
+    id: u64,
+    /// Fully qualified name of the record.
+    hostname: String,
+    record_type: String,
+    ttl: u32,
+    /// Target of a CNAME record.
+    text_data: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DynuNewRecord<'a> {
+    node_name: &'a str,
+    record_type: &'a str,
+    ttl: u32,
+    state: bool,
+    text_data: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    use super::*;
+
+    fn provider(server: &Server) -> DynuProvider {
+        DynuProvider::with_base_url(
+            "key".to_string(),
+            1,
+            "dest.example.com".to_string(),
+            Url::parse(&server.url_str("/v2/")).unwrap(),
+        )
+    }
+
+    fn record(id: u64, hostname: &str, record_type: &str, text_data: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "domainId": 1,
+            "domainName": "example.com",
+            "nodeName": hostname.trim_end_matches("example.com").trim_end_matches('.'),
+            "hostname": hostname,
+            "recordType": record_type,
+            "ttl": 300,
+            "state": true,
+            "textData": text_data,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_list_records() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/v2/dns/1/record"),
+                request::headers(contains(("api-key", "key"))),
+            ])
+            .respond_with(json_encoded(serde_json::json!({
+                "statusCode": 200,
+                "dnsRecords": [
+                    record(10, "test1.example.com", "CNAME", "dest.example.com"),
+                    record(11, "example.com", "CNAME", "dest.example.com."),
+                    record(12, "test2.example.com", "CNAME", "other.example.com"),
+                    record(13, "test3.example.com", "TXT", "dest.example.com"),
+                ],
+            }))),
+        );
+
+        let records = provider(&server).list_records().await.unwrap();
+        assert_eq!(
+            records,
+            vec![
+                DnsRecord {
+                    host: "test1.example.com".to_string(),
+                    id: Some("10".to_string()),
+                    ttl: Some(300),
+                    content: "dest.example.com".to_string(),
+                },
+                DnsRecord {
+                    host: "example.com".to_string(),
+                    id: Some("11".to_string()),
+                    ttl: Some(300),
+                    content: "dest.example.com.".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_record() {
+        let server = Server::run();
+        // The domain is only fetched once
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/v2/dns/1"))
+                .times(1)
+                .respond_with(json_encoded(serde_json::json!({
+                    "id": 1,
+                    "name": "example.com",
+                    "unicodeName": "example.com",
+                    "state": "Complete",
+                }))),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/v2/dns/1/record"),
+                request::headers(contains(("api-key", "key"))),
+                request::body(json_decoded(eq(serde_json::json!({
+                    "nodeName": "test",
+                    "recordType": "CNAME",
+                    "ttl": 60,
+                    "state": true,
+                    "textData": "dest.example.com",
+                })))),
+            ])
+            .respond_with(json_encoded(record(
+                10,
+                "test.example.com",
+                "CNAME",
+                "dest.example.com",
+            ))),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/v2/dns/1/record"),
+                request::body(json_decoded(eq(serde_json::json!({
+                    "nodeName": "",
+                    "recordType": "CNAME",
+                    "ttl": 300,
+                    "state": true,
+                    "textData": "dest.example.com",
+                })))),
+            ])
+            .respond_with(json_encoded(record(
+                11,
+                "example.com",
+                "CNAME",
+                "dest.example.com",
+            ))),
+        );
+
+        let provider = provider(&server);
+        let options = RecordOptions {
+            ttl: Some(60),
+            ..Default::default()
+        };
+        provider
+            .create_record("test.example.com", &options)
+            .await
+            .unwrap();
+        provider
+            .create_record("example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+        assert!(matches!(
+            provider
+                .create_record("test.example.org", &RecordOptions::default())
+                .await,
+            Err(DynuError::OutsideZone(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_delete_record() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("DELETE", "/v2/dns/1/record/10"),
+                request::headers(contains(("api-key", "key"))),
+            ])
+            .respond_with(json_encoded(serde_json::json!({"statusCode": 200}))),
+        );
+
+        let record = DnsRecord {
+            host: "test.example.com".to_string(),
+            id: Some("10".to_string()),
+            ttl: Some(300),
+            content: "dest.example.com".to_string(),
+        };
+        provider(&server).delete_record(&record).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/v2/dns/1/record"))
+                .times(2)
+                .respond_with(json_encoded(serde_json::json!({"dnsRecords": []}))),
+        );
+
+        let mut provider = provider(&server);
+        *provider.limiter_mut() = Arc::new(RateLimit::new(1, Duration::from_millis(100)));
+
+        // The second request waits for a token
+        let start = std::time::Instant::now();
+        provider.list_records().await.unwrap();
+        provider.list_records().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+}
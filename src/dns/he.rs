@@ -0,0 +1,598 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::{header, Client, Response, Url};
+use thiserror::Error;
+
+use super::{DnsRecord, Provider, RecordOptions};
+use crate::{host::normalize_host, retry::Retryable};
+
+const DEFAULT_TTL: u32 = 300;
+const BASE_URL: &str = "https://dns.he.net/";
+
+/// TTLs offered by HE.
+const VALID_TTLS: &[u32] = &[
+    300, 900, 1800, 3600, 7200, 14400, 28800, 43200, 86400, 172800,
+];
+
+const CNAME_TYPE: &str = "CNAME";
+
+/// Delete buttons of the zones of the account, named after the zone & valued with its id.
+static ZONE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"<img[^>]*delete_dom[^>]*>"#).unwrap());
+static NAME_ATTR_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\sname="([^"]*)""#).unwrap());
+static VALUE_ATTR_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\svalue="(\d+)""#).unwrap());
+/// Rows of the record table of a zone, with the record id.
+static ROW_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?s)<tr[^>]*class="dns_tr"[^>]*id="(\d+)"[^>]*>(.*?)</tr>"#).unwrap()
+});
+static CELL_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<td[^>]*>(.*?)</td>").unwrap());
+static TAG_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+/// Error shown after a rejected form submission.
+static ERROR_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?s)<div[^>]*id="dns_err"[^>]*>(.*?)</div>"#).unwrap());
+
+/// Manages the CNAME records of a zone of Hurricane Electric's free DNS at dns.he.net.
+///
+/// HE has no API for managing records: its dynamic update URL only changes the content of
+/// existing A, AAAA & TXT records flagged as dynamic, and can't create CNAMEs. Records are
+/// therefore managed like the web interface does, by logging in with the account credentials
+/// and submitting its forms, and listed by scraping the zone page. This is best-effort, and
+/// breaks if HE changes its pages.
+#[derive(Debug)]
+pub struct HeProvider {
+    dest: String,
+    domain: String,
+
+    base_url: Url,
+    username: String,
+    password: String,
+    client: Client,
+
+    ttl: u32,
+}
+
+/// A logged in session of the web interface.
+struct Session {
+    cookie: String,
+    zone_id: String,
+}
+
+impl HeProvider {
+    pub fn new(username: String, password: String, domain: String, dest: String) -> Self {
+        Self::with_base_url(
+            username,
+            password,
+            domain,
+            dest,
+            Url::parse(BASE_URL).unwrap(),
+        )
+    }
+
+    pub fn with_base_url(
+        username: String,
+        password: String,
+        domain: String,
+        dest: String,
+        base_url: Url,
+    ) -> Self {
+        Self {
+            dest,
+            domain: normalize_host(&domain),
+            base_url,
+            username,
+            password,
+            client: crate::http::default_client(),
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn ttl(&self) -> &u32 {
+        &self.ttl
+    }
+    pub fn ttl_mut(&mut self) -> &mut u32 {
+        &mut self.ttl
+    }
+
+    /// HTTP client of the API requests, e.g. to send them through a proxy.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+    pub fn client_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    /// Logs in, returning the session cookie & the id of the zone.
+    async fn login(&self) -> Result<Session, HeError> {
+        // The session cookie is set by the login page, and must be sent with the credentials
+        let res = self
+            .client
+            .get(self.base_url.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+        let cookie = session_cookie(&res).ok_or(HeError::LoginFailed)?;
+
+        let page = self
+            .client
+            .post(self.base_url.clone())
+            .header(header::COOKIE, &cookie)
+            .form(&[("email", &self.username), ("pass", &self.password)])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        if page.contains("Incorrect") || !page.contains("delete_dom") {
+            return Err(HeError::LoginFailed);
+        }
+
+        let zone_id = zone_id(&page, &self.domain)
+            .ok_or_else(|| HeError::ZoneNotFound(self.domain.clone()))?;
+        Ok(Session { cookie, zone_id })
+    }
+
+    /// Returns the records of the zone.
+    async fn get_records(&self, session: &Session) -> Result<Vec<HeRecord>, HeError> {
+        let mut url = self.base_url.join("index.cgi")?;
+        url.query_pairs_mut()
+            .append_pair("hosted_dns_zoneid", &session.zone_id)
+            .append_pair("menu", "edit_zone")
+            .append_pair("hosted_dns_editzone", "");
+
+        let page = self
+            .client
+            .get(url)
+            .header(header::COOKIE, &session.cookie)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(parse_records(&page))
+    }
+
+    /// Submits a form of the zone page, failing with the error it shows, if any.
+    async fn submit(&self, session: &Session, form: &[(&str, &str)]) -> Result<(), HeError> {
+        let url = self.base_url.join("index.cgi")?;
+        let page = self
+            .client
+            .post(url)
+            .header(header::COOKIE, &session.cookie)
+            .form(form)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        match ERROR_REGEX.captures(&page) {
+            Some(err) => Err(HeError::Rejected(text(&err[1]))),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that `host` is in the zone.
+    fn check_zone(&self, host: &str) -> Result<(), HeError> {
+        let in_zone = host == self.domain
+            || host
+                .strip_suffix(&self.domain)
+                .is_some_and(|s| s.ends_with('.'));
+        if !in_zone {
+            return Err(HeError::OutsideZone(host.to_owned()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for HeProvider {
+    type Error = HeError;
+
+    fn destination(&self) -> &str {
+        &self.dest
+    }
+    fn destination_mut(&mut self) -> &mut String {
+        &mut self.dest
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        let session = self.login().await?;
+        let dest = normalize_host(&self.dest);
+
+        Ok(self
+            .get_records(&session)
+            .await?
+            .into_iter()
+            .filter(|r| r.r#type == CNAME_TYPE && normalize_host(&r.content) == dest)
+            .map(|r| DnsRecord {
+                host: normalize_host(&r.name),
+                id: Some(r.id),
+                ttl: r.ttl,
+                content: r.content,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        self.check_zone(host)?;
+        let session = self.login().await?;
+        let ttl = options.ttl.unwrap_or(self.ttl).to_string();
+
+        self.submit(
+            &session,
+            &[
+                ("account", ""),
+                ("menu", "edit_zone"),
+                ("Type", CNAME_TYPE),
+                ("hosted_dns_zoneid", &session.zone_id),
+                ("hosted_dns_recordid", ""),
+                ("hosted_dns_editzone", "1"),
+                ("Priority", ""),
+                ("Name", host),
+                ("Content", &self.dest),
+                ("TTL", &ttl),
+                ("hosted_dns_editrecord", "Submit"),
+            ],
+        )
+        .await
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        let session = self.login().await?;
+        let id = match &record.id {
+            Some(id) => id.clone(),
+            None => {
+                let host = normalize_host(&record.host);
+                self.get_records(&session)
+                    .await?
+                    .into_iter()
+                    .find(|r| r.r#type == CNAME_TYPE && normalize_host(&r.name) == host)
+                    .ok_or(HeError::RecordNotFound)?
+                    .id
+            }
+        };
+
+        self.submit(
+            &session,
+            &[
+                ("menu", "edit_zone"),
+                ("hosted_dns_zoneid", &session.zone_id),
+                ("hosted_dns_recordid", &id),
+                ("hosted_dns_editzone", "1"),
+                ("hosted_dns_delrecord", "1"),
+                ("hosted_dns_delconfirm", "delete"),
+            ],
+        )
+        .await
+    }
+}
+
+/// Returns `true` if HE offers `ttl`.
+pub fn is_valid_ttl(ttl: u32) -> bool {
+    VALID_TTLS.contains(&ttl)
+}
+
+/// Returns the cookies set by `res`, as a `Cookie` header.
+fn session_cookie(res: &Response) -> Option<String> {
+    let cookies: Vec<_> = res
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok()?.split(';').next())
+        .collect();
+    (!cookies.is_empty()).then(|| cookies.join("; "))
+}
+
+/// Finds the id of the zone `domain` on the zone list of the account.
+fn zone_id(page: &str, domain: &str) -> Option<String> {
+    ZONE_REGEX
+        .find_iter(page)
+        .map(|img| img.as_str())
+        .filter(|img| {
+            NAME_ATTR_REGEX
+                .captures(img)
+                .is_some_and(|name| normalize_host(&name[1]) == domain)
+        })
+        .find_map(|img| Some(VALUE_ATTR_REGEX.captures(img)?[1].to_string()))
+}
+
+/// Parses the record table of a zone page.
+fn parse_records(page: &str) -> Vec<HeRecord> {
+    ROW_REGEX
+        .captures_iter(page)
+        .filter_map(|row| {
+            // Zone id, record id, name, type, TTL, priority, content, then the buttons
+            let cells: Vec<_> = CELL_REGEX
+                .captures_iter(&row[2])
+                .map(|cell| text(&cell[1]))
+                .collect();
+            if cells.len() < 7 {
+                tracing::warn!(
+                    "Ignoring unexpected record row {} of the HE zone page",
+                    &row[1]
+                );
+                return None;
+            }
+            Some(HeRecord {
+                id: row[1].to_string(),
+                name: cells[2].clone(),
+                r#type: cells[3].clone(),
+                ttl: cells[4].parse().ok(),
+                content: cells[6].clone(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the text of an HTML fragment, without its tags.
+fn text(html: &str) -> String {
+    TAG_REGEX.replace_all(html, "").trim().to_string()
+}
+
+#[derive(Debug, Error)]
+pub enum HeError {
+    #[error(transparent)]
+    ReqwestError(#[from] reqwest::Error),
+    #[error(transparent)]
+    UrlParseError(#[from] url::ParseError),
+    #[error("failed to log in to dns.he.net")]
+    LoginFailed,
+    #[error("zone {0} not found in the account")]
+    ZoneNotFound(String),
+    #[error("dns.he.net rejected the change: {0}")]
+    Rejected(String),
+    #[error("host {0} is outside of the zone")]
+    OutsideZone(String),
+    #[error("record not found")]
+    RecordNotFound,
+}
+
+impl Retryable for HeError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            HeError::ReqwestError(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status()
+                        .is_some_and(|s| s.as_u16() == 429 || s.is_server_error())
+            }
+            HeError::UrlParseError(_)
+            | HeError::LoginFailed
+            | HeError::ZoneNotFound(_)
+            | HeError::Rejected(_)
+            | HeError::OutsideZone(_)
+            | HeError::RecordNotFound => false,
+        }
+    }
+}
+
+/// A record scraped from the zone page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HeRecord {
+    id: String,
+    name: String,
+    r#type: String,
+    ttl: Option<u32>,
+    content: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    use super::*;
+
+    const ZONES_PAGE: &str = r#"
+        <table id="domains_table">
+            <tr>
+                <td><img src="/include/images/delete.png" alt="delete" name="other.com" value="111" onclick="delete_dom(this);" /></td>
+                <td>other.com</td>
+            </tr>
+            <tr>
+                <td><img src="/include/images/delete.png" alt="delete" name="example.com" value="123456" onclick="delete_dom(this);" /></td>
+                <td>example.com</td>
+            </tr>
+        </table>
+    "#;
+
+    const ZONE_PAGE: &str = r#"
+        <table>
+            <tr class="dns_tr" id="1001" onclick="editRow(this)">
+                <td class="hidden">123456</td>
+                <td class="hidden">1001</td>
+                <td class="dns_view">test1.example.com</td>
+                <td align="center"><span class="rrlabel CNAME" data="CNAME">CNAME</span></td>
+                <td align="left">300</td>
+                <td align="center">-</td>
+                <td align="left" data="dest">dest</td>
+                <td class="hidden">0</td>
+                <td><img src="/include/images/delete.png" alt="delete" /></td>
+            </tr>
+            <tr class="dns_tr" id="1002" onclick="editRow(this)">
+                <td class="hidden">123456</td>
+                <td class="hidden">1002</td>
+                <td class="dns_view">wrong-type.example.com</td>
+                <td align="center"><span class="rrlabel A" data="A">A</span></td>
+                <td align="left">300</td>
+                <td align="center">-</td>
+                <td align="left" data="192.0.2.1">192.0.2.1</td>
+                <td class="hidden">0</td>
+                <td><img src="/include/images/delete.png" alt="delete" /></td>
+            </tr>
+            <tr class="dns_tr" id="1003" onclick="editRow(this)">
+                <td class="hidden">123456</td>
+                <td class="hidden">1003</td>
+                <td class="dns_view">wrong-dest.example.com</td>
+                <td align="center"><span class="rrlabel CNAME" data="CNAME">CNAME</span></td>
+                <td align="left">300</td>
+                <td align="center">-</td>
+                <td align="left" data="wrong.dest.com">wrong.dest.com</td>
+                <td class="hidden">0</td>
+                <td><img src="/include/images/delete.png" alt="delete" /></td>
+            </tr>
+        </table>
+    "#;
+
+    fn provider(server: &Server) -> HeProvider {
+        let base_url = Url::parse(&server.url_str("/")).unwrap();
+        HeProvider::with_base_url(
+            "user".to_string(),
+            "pass".to_string(),
+            "example.com".to_string(),
+            "dest".to_string(),
+            base_url,
+        )
+    }
+
+    fn expect_login(server: &Server) {
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/")).respond_with(
+                status_code(200).insert_header("Set-Cookie", "CGISESSID=abc; path=/"),
+            ),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/"),
+                request::headers(contains(("cookie", "CGISESSID=abc"))),
+                request::body(url_decoded(contains(("email", "user")))),
+                request::body(url_decoded(contains(("pass", "pass")))),
+            ])
+            .respond_with(status_code(200).body(ZONES_PAGE)),
+        );
+    }
+
+    #[test]
+    fn test_zone_id() {
+        assert_eq!(
+            zone_id(ZONES_PAGE, "example.com"),
+            Some("123456".to_string())
+        );
+        assert_eq!(zone_id(ZONES_PAGE, "missing.com"), None);
+    }
+
+    #[test]
+    fn test_parse_records() {
+        let records = parse_records(ZONE_PAGE);
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[0],
+            HeRecord {
+                id: "1001".to_string(),
+                name: "test1.example.com".to_string(),
+                r#type: "CNAME".to_string(),
+                ttl: Some(300),
+                content: "dest".to_string(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_records() {
+        let server = Server::run();
+        expect_login(&server);
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/index.cgi"),
+                request::query(url_decoded(contains(("hosted_dns_zoneid", "123456")))),
+                request::headers(contains(("cookie", "CGISESSID=abc"))),
+            ])
+            .respond_with(status_code(200).body(ZONE_PAGE)),
+        );
+
+        let records = provider(&server).list_records().await.unwrap();
+        assert_eq!(
+            records,
+            vec![DnsRecord {
+                host: "test1.example.com".to_string(),
+                id: Some("1001".to_string()),
+                ttl: Some(300),
+                content: "dest".to_string(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_record() {
+        let server = Server::run();
+        expect_login(&server);
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/index.cgi"),
+                request::body(url_decoded(contains(("hosted_dns_zoneid", "123456")))),
+                request::body(url_decoded(contains(("Type", "CNAME")))),
+                request::body(url_decoded(contains(("Name", "test.example.com")))),
+                request::body(url_decoded(contains(("Content", "dest")))),
+                request::body(url_decoded(contains(("TTL", "300")))),
+            ])
+            .respond_with(status_code(200).body(ZONE_PAGE)),
+        );
+
+        provider(&server)
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_create_record_rejected() {
+        let server = Server::run();
+        expect_login(&server);
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/index.cgi"))
+                .respond_with(status_code(200).body(
+                r#"<div id="dns_err" onclick="hideThis(this);">The record already exists.</div>"#,
+            )),
+        );
+
+        let err = provider(&server)
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HeError::Rejected(msg) if msg == "The record already exists."));
+    }
+
+    #[tokio::test]
+    async fn test_delete_record() {
+        let server = Server::run();
+        expect_login(&server);
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/index.cgi"),
+                request::body(url_decoded(contains(("hosted_dns_recordid", "1001")))),
+                request::body(url_decoded(contains(("hosted_dns_delrecord", "1")))),
+            ])
+            .respond_with(status_code(200).body(ZONE_PAGE)),
+        );
+
+        let record = DnsRecord {
+            host: "test1.example.com".to_string(),
+            id: Some("1001".to_string()),
+            ttl: Some(300),
+            content: "dest".to_string(),
+        };
+        provider(&server).delete_record(&record).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_login_failed() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/")).respond_with(
+                status_code(200).insert_header("Set-Cookie", "CGISESSID=abc; path=/"),
+            ),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/"))
+                .respond_with(status_code(200).body("Incorrect username or password.")),
+        );
+
+        let err = provider(&server).list_records().await.unwrap_err();
+        assert!(matches!(err, HeError::LoginFailed));
+    }
+}
@@ -0,0 +1,498 @@
+use std::time::Duration;
+
+use reqwest::{Client, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{sync::Mutex, time::Instant};
+
+use super::{DnsRecord, Provider, RecordOptions};
+use crate::{host::normalize_host, retry::Retryable};
+
+const DEFAULT_TTL: u32 = 900;
+const BASE_URL: &str = "https://api.dns-svcs.cloud.ibm.com/v1/";
+const IAM_URL: &str = "https://iam.cloud.ibm.com/identity/token";
+const API_KEY_GRANT: &str = "urn:ibm:params:oauth:grant-type:apikey";
+/// Page size of record listings.
+const PAGE_SIZE: u32 = 200;
+
+const CNAME_TYPE: &str = "CNAME";
+
+/// Manages the CNAME records of a zone of an IBM Cloud DNS Services instance.
+///
+/// Requests are authenticated with IAM access tokens, exchanged for the API key and reused
+/// until they near expiry.
+#[derive(Debug)]
+pub struct IbmCloudProvider {
+    dest: String,
+    instance_id: String,
+    zone_id: String,
+
+    base_url: Url,
+    iam_url: Url,
+    api_key: String,
+    client: Client,
+    token: Mutex<Option<IamToken>>,
+
+    ttl: u32,
+}
+
+impl IbmCloudProvider {
+    pub fn new(api_key: String, instance_id: String, zone_id: String, dest: String) -> Self {
+        Self::with_base_url(
+            api_key,
+            instance_id,
+            zone_id,
+            dest,
+            Url::parse(BASE_URL).unwrap(),
+            Url::parse(IAM_URL).unwrap(),
+        )
+    }
+
+    pub fn with_base_url(
+        api_key: String,
+        instance_id: String,
+        zone_id: String,
+        dest: String,
+        base_url: Url,
+        iam_url: Url,
+    ) -> Self {
+        Self {
+            dest,
+            instance_id,
+            zone_id,
+            base_url,
+            iam_url,
+            api_key,
+            client: crate::http::default_client(),
+            token: Mutex::new(None),
+            ttl: DEFAULT_TTL,
+        }
+    }
+
+    pub fn ttl(&self) -> &u32 {
+        &self.ttl
+    }
+    pub fn ttl_mut(&mut self) -> &mut u32 {
+        &mut self.ttl
+    }
+
+    /// HTTP client of the API requests, e.g. to send them through a proxy.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+    pub fn client_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    /// Returns an access token, exchanging the API key for a new one if needed.
+    async fn token(&self) -> Result<String, IbmCloudError> {
+        let mut token = self.token.lock().await;
+        if let Some(token) = token.as_ref().filter(|t| Instant::now() < t.refresh_at) {
+            return Ok(token.access_token.clone());
+        }
+
+        let response = self
+            .client
+            .post(self.iam_url.clone())
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[("grant_type", API_KEY_GRANT), ("apikey", &self.api_key)])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(IbmCloudError::IamError)?
+            .json::<IamTokenResponse>()
+            .await
+            .map_err(IbmCloudError::IamError)?;
+
+        // Refreshed early, so tokens never expire in flight
+        let refresh_at = Instant::now() + Duration::from_secs(response.expires_in) * 4 / 5;
+        *token = Some(IamToken {
+            access_token: response.access_token.clone(),
+            refresh_at,
+        });
+        Ok(response.access_token)
+    }
+
+    /// URL of the records of the zone, or of the record `id`.
+    fn records_url(&self, id: Option<&str>) -> Result<Url, IbmCloudError> {
+        let mut path = format!(
+            "instances/{}/dnszones/{}/resource_records",
+            self.instance_id, self.zone_id
+        );
+        if let Some(id) = id {
+            path.push('/');
+            path.push_str(id);
+        }
+        Ok(self.base_url.join(&path)?)
+    }
+
+    /// Sends an authenticated request.
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response, IbmCloudError> {
+        Ok(request
+            .bearer_auth(self.token().await?)
+            .send()
+            .await?
+            .error_for_status()?)
+    }
+
+    /// Lists the CNAME records of the zone, of every page.
+    async fn list_cnames(&self) -> Result<Vec<IbmCloudRecord>, IbmCloudError> {
+        let url = self.records_url(None)?;
+        let mut records = Vec::new();
+        loop {
+            let page = self
+                .send(self.client.get(url.clone()).query(&[
+                    ("type", CNAME_TYPE.to_string()),
+                    ("offset", records.len().to_string()),
+                    ("limit", PAGE_SIZE.to_string()),
+                ]))
+                .await?
+                .json::<IbmCloudRecordList>()
+                .await?;
+
+            let len = page.resource_records.len();
+            records.extend(page.resource_records);
+            if len == 0 || records.len() as u64 >= page.total_count {
+                break;
+            }
+        }
+
+        Ok(records
+            .into_iter()
+            .filter(|r| r.r#type == CNAME_TYPE)
+            .collect())
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for IbmCloudProvider {
+    type Error = IbmCloudError;
+
+    fn destination(&self) -> &str {
+        &self.dest
+    }
+    fn destination_mut(&mut self) -> &mut String {
+        &mut self.dest
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        let dest = normalize_host(&self.dest);
+
+        Ok(self
+            .list_cnames()
+            .await?
+            .into_iter()
+            .filter(|r| normalize_host(&r.rdata.cname) == dest)
+            .map(|r| DnsRecord {
+                host: normalize_host(&r.name),
+                id: Some(r.id),
+                ttl: Some(r.ttl),
+                content: r.rdata.cname,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        let record = IbmCloudNewRecord {
+            name: host,
+            r#type: CNAME_TYPE,
+            ttl: options.ttl.unwrap_or(self.ttl),
+            rdata: IbmCloudRdata {
+                cname: self.dest.clone(),
+            },
+        };
+        self.send(self.client.post(self.records_url(None)?).json(&record))
+            .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        let id = match &record.id {
+            Some(id) => id.clone(),
+            None => {
+                let host = normalize_host(&record.host);
+                self.list_cnames()
+                    .await?
+                    .into_iter()
+                    .find(|r| normalize_host(&r.name) == host)
+                    .ok_or(IbmCloudError::RecordNotFound)?
+                    .id
+            }
+        };
+
+        self.send(self.client.delete(self.records_url(Some(&id))?))
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum IbmCloudError {
+    #[error(transparent)]
+    ReqwestError(#[from] reqwest::Error),
+    #[error("failed to get an IAM token: {0}")]
+    IamError(reqwest::Error),
+    #[error(transparent)]
+    UrlParseError(#[from] url::ParseError),
+    #[error("record not found")]
+    RecordNotFound,
+}
+
+impl Retryable for IbmCloudError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            IbmCloudError::ReqwestError(e) | IbmCloudError::IamError(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status()
+                        .is_some_and(|s| s.as_u16() == 429 || s.is_server_error())
+            }
+            IbmCloudError::UrlParseError(_) | IbmCloudError::RecordNotFound => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct IamToken {
+    access_token: String,
+    refresh_at: Instant,
+}
+
+#[derive(Debug, Deserialize)]
+struct IamTokenResponse {
+    access_token: String,
+    /// Lifetime of the token, in seconds.
+    expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct IbmCloudRecordList {
+    resource_records: Vec<IbmCloudRecord>,
+    total_count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct IbmCloudRecord {
+    id: String,
+    /// Fully qualified name of the record.
+    name: String,
+    r#type: String,
+    ttl: u32,
+    rdata: IbmCloudRdata,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IbmCloudRdata {
+    #[serde(default)]
+    cname: String,
+}
+
+#[derive(Debug, Serialize)]
+struct IbmCloudNewRecord<'a> {
+    name: &'a str,
+    r#type: &'a str,
+    ttl: u32,
+    rdata: IbmCloudRdata,
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    use super::*;
+
+    const RECORDS_PATH: &str = "/v1/instances/inst/dnszones/zone/resource_records";
+
+    fn provider(server: &Server) -> IbmCloudProvider {
+        IbmCloudProvider::with_base_url(
+            "key".to_string(),
+            "inst".to_string(),
+            "zone".to_string(),
+            "dest".to_string(),
+            Url::parse(&server.url_str("/v1/")).unwrap(),
+            Url::parse(&server.url_str("/identity/token")).unwrap(),
+        )
+    }
+
+    fn expect_token(server: &Server, times: usize) {
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/identity/token"),
+                request::body(url_decoded(contains(("grant_type", API_KEY_GRANT)))),
+                request::body(url_decoded(contains(("apikey", "key")))),
+            ])
+            .times(times)
+            .respond_with(json_encoded(serde_json::json!({
+                "access_token": "token",
+                "refresh_token": "not_supported",
+                "token_type": "Bearer",
+                "expires_in": 3600,
+            }))),
+        );
+    }
+
+    fn record(id: &str, name: &str, cname: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "name": name,
+            "type": "CNAME",
+            "ttl": 900,
+            "rdata": {"cname": cname},
+        })
+    }
+
+    #[tokio::test]
+    async fn test_list_records() {
+        let server = Server::run();
+        // The token is reused by every page
+        expect_token(&server, 1);
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", RECORDS_PATH),
+                request::headers(contains(("authorization", "Bearer token"))),
+                request::query(url_decoded(contains(("offset", "0")))),
+            ])
+            .respond_with(json_encoded(serde_json::json!({
+                "resource_records": [
+                    record("CNAME:1", "test1.example.com", "dest"),
+                    record("CNAME:2", "other.example.com", "other.dest"),
+                ],
+                "offset": 0,
+                "limit": 200,
+                "count": 2,
+                "total_count": 3,
+            }))),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", RECORDS_PATH),
+                request::query(url_decoded(contains(("offset", "2")))),
+            ])
+            .respond_with(json_encoded(serde_json::json!({
+                "resource_records": [record("CNAME:3", "Test2.Example.com", "dest")],
+                "offset": 2,
+                "limit": 200,
+                "count": 1,
+                "total_count": 3,
+            }))),
+        );
+
+        let records = provider(&server).list_records().await.unwrap();
+        assert_eq!(
+            records,
+            vec![
+                DnsRecord {
+                    host: "test1.example.com".to_string(),
+                    id: Some("CNAME:1".to_string()),
+                    ttl: Some(900),
+                    content: "dest".to_string(),
+                },
+                DnsRecord {
+                    host: "test2.example.com".to_string(),
+                    id: Some("CNAME:3".to_string()),
+                    ttl: Some(900),
+                    content: "dest".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_record() {
+        let server = Server::run();
+        expect_token(&server, 1);
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", RECORDS_PATH),
+                request::headers(contains(("authorization", "Bearer token"))),
+                request::body(json_decoded(eq(serde_json::json!({
+                    "name": "test1.example.com",
+                    "type": "CNAME",
+                    "ttl": 60,
+                    "rdata": {"cname": "dest"},
+                })))),
+            ])
+            .respond_with(json_encoded(record(
+                "CNAME:1",
+                "test1.example.com",
+                "dest",
+            ))),
+        );
+
+        let options = RecordOptions {
+            ttl: Some(60),
+            ..Default::default()
+        };
+        provider(&server)
+            .create_record("test1.example.com", &options)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_record() {
+        let server = Server::run();
+        expect_token(&server, 1);
+        server.expect(
+            Expectation::matching(request::method_path(
+                "DELETE",
+                format!("{}/CNAME:1", RECORDS_PATH),
+            ))
+            .respond_with(status_code(204)),
+        );
+
+        let record = DnsRecord {
+            host: "test1.example.com".to_string(),
+            id: Some("CNAME:1".to_string()),
+            ttl: None,
+            content: "dest".to_string(),
+        };
+        provider(&server).delete_record(&record).await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_refresh() {
+        let server = Server::run();
+        expect_token(&server, 2);
+        server.expect(
+            Expectation::matching(request::method_path(
+                "DELETE",
+                format!("{}/1", RECORDS_PATH),
+            ))
+            .times(3)
+            .respond_with(status_code(204)),
+        );
+
+        let provider = provider(&server);
+        let record = DnsRecord {
+            host: "test1.example.com".to_string(),
+            id: Some("1".to_string()),
+            ttl: None,
+            content: "dest".to_string(),
+        };
+        provider.delete_record(&record).await.unwrap();
+        provider.delete_record(&record).await.unwrap();
+
+        // Tokens are refreshed before they expire
+        tokio::time::advance(Duration::from_secs(2900)).await;
+        provider.delete_record(&record).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_token_error() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/identity/token"))
+                .respond_with(status_code(400)),
+        );
+
+        let err = provider(&server).list_records().await.unwrap_err();
+        assert!(matches!(err, IbmCloudError::IamError(_)));
+        assert!(!err.is_retryable());
+    }
+}
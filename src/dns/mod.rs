@@ -1,23 +1,441 @@
+#[cfg(feature = "alidns")]
+pub mod alidns;
+#[cfg(feature = "bunny")]
+pub mod bunny;
 #[cfg(feature = "cloudflare")]
 pub mod cloudflare;
+#[cfg(feature = "duckdns")]
+pub mod duckdns;
+#[cfg(feature = "dynu")]
+pub mod dynu;
+#[cfg(feature = "he")]
+pub mod he;
+#[cfg(feature = "ibmcloud")]
+pub mod ibmcloud;
+pub mod multi;
+#[cfg(feature = "namecheap")]
+pub mod namecheap;
+#[cfg(feature = "oci")]
+pub mod oci;
+pub mod ownership;
+pub mod rate_limit;
+pub mod resolve;
 #[cfg(feature = "aws")]
 pub mod route53;
+#[cfg(feature = "zonefile")]
+pub mod zonefile;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Formatter},
+    net::IpAddr,
+    str::FromStr,
+    sync::Arc,
+};
+
+use futures::{stream, StreamExt};
+use serde::Serialize;
+#[cfg(test)]
+pub use tests::MockProvider;
+use thiserror::Error;
+
+use self::rate_limit::RateLimit;
+use crate::retry::{retry, RetryPolicy, Retryable};
 
-#[cfg_attr(test, mockall::automock(type Error = tests::MockProviderError;))]
 #[async_trait::async_trait]
-pub trait Provider: Send {
-    type Error: std::error::Error + Send;
+pub trait Provider: Send + Sync {
+    type Error: std::error::Error + Retryable + Send;
 
     fn destination(&self) -> &str;
     fn destination_mut(&mut self) -> &mut String;
 
-    async fn list_records(&self) -> Result<Vec<String>, Self::Error>;
-    async fn create_record(&self, host: &str) -> Result<(), Self::Error>;
-    async fn delete_record(&self, host: &str) -> Result<(), Self::Error>;
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error>;
+    /// Creates the records of `host`, applying `options` over the provider's defaults.
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error>;
+    /// Deletes a record previously returned by [`Provider::list_records`].
+    ///
+    /// Implementations should use the identifying information on `record` rather than listing
+    /// the zone again.
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error>;
+
+    /// Returns `true` if `record`, as returned by [`Provider::list_records`], is up to date.
+    ///
+    /// Hosts with stale records are created again, so providers overriding this must replace
+    /// any existing records of the host in [`Provider::create_record`].
+    fn is_current(&self, _record: &DnsRecord) -> bool {
+        true
+    }
+
+    /// Makes sure `host` has up to date records, `existing` being its records as returned by
+    /// [`Provider::list_records`].
+    ///
+    /// The default implementation does nothing if every existing record is current, and calls
+    /// [`Provider::create_record`] otherwise, so providers rejecting duplicates don't fail on
+    /// hosts whose records already exist. Providers whose `create_record` upserts should
+    /// override it to call `create_record` directly, which also repairs what the listing doesn't
+    /// show, like missing ownership markers.
+    async fn ensure_record(
+        &self,
+        host: &str,
+        existing: &[DnsRecord],
+        options: &RecordOptions,
+    ) -> Result<(), Self::Error> {
+        if !existing.is_empty() && existing.iter().all(|r| self.is_current(r)) {
+            return Ok(());
+        }
+        self.create_record(host, options).await
+    }
+
+    /// Returns `true` if the provider can only replace every record of a host at once, rather
+    /// than create & delete single records.
+    ///
+    /// The default [`Provider::apply_changes`] then calls [`Provider::apply_host_records`] once
+    /// per changed host.
+    fn replaces_host_records(&self) -> bool {
+        false
+    }
+
+    /// Replaces the records of `host` pointing at the destination with `records`, deleting
+    /// them if it's empty.
+    ///
+    /// The default implementation emulates it by deleting the listed records of the host and
+    /// creating it again with the TTL of the first record. Providers returning `true` from
+    /// [`Provider::replaces_host_records`] should override it with their read-modify-write.
+    async fn apply_host_records(
+        &self,
+        host: &str,
+        records: &[DnsRecord],
+    ) -> Result<(), Self::Error> {
+        for record in self.list_records().await? {
+            if record.host == host {
+                self.delete_record(&record).await?;
+            }
+        }
+        if let Some(record) = records.first() {
+            let options = RecordOptions {
+                ttl: record.ttl,
+                ..Default::default()
+            };
+            self.create_record(host, &options).await?;
+        }
+        Ok(())
+    }
+
+    /// Creates records for `creates` and deletes `deletes` in as few requests as possible.
+    ///
+    /// The default implementation falls back to calling [`Provider::ensure_record`] and
+    /// [`Provider::delete_record`] for every host, or [`Provider::apply_host_records`] if the
+    /// provider [replaces host records](Provider::replaces_host_records). Providers with batch
+    /// APIs should override it.
+    async fn apply_changes(
+        &self,
+        creates: &[String],
+        deletes: &[DnsRecord],
+        options: &ApplyOptions,
+    ) -> Result<(), Vec<FailedChange<Self::Error>>> {
+        if self.replaces_host_records() {
+            return replace_host_records(self, creates, deletes, options).await;
+        }
+
+        // Every change is attempted, even if some of them fail. Each change takes a rate limit
+        // token once it has a slot, so changes are paced instead of all waiting at once.
+        let max_concurrency = options.max_concurrency.max(1);
+        let creates: Vec<_> = creates
+            .iter()
+            .map(|host| async move {
+                options.ready().await;
+                let record = options.record(host);
+                let existing = options.existing(host);
+                let res = retry(&options.retry, || {
+                    self.ensure_record(host, existing, &record)
+                })
+                .await;
+                (host, res)
+            })
+            .collect();
+        let created: Vec<_> = stream::iter(creates)
+            .buffer_unordered(max_concurrency)
+            .collect()
+            .await;
+
+        let deletes: Vec<_> = deletes
+            .iter()
+            .map(|record| async move {
+                options.ready().await;
+                let res = retry(&options.retry, || self.delete_record(record)).await;
+                (&record.host, res)
+            })
+            .collect();
+        let deleted: Vec<_> = stream::iter(deletes)
+            .buffer_unordered(max_concurrency)
+            .collect()
+            .await;
+
+        let failures: Vec<_> = created
+            .into_iter()
+            .chain(deleted)
+            .filter_map(|(host, res)| {
+                res.err().map(|error| FailedChange {
+                    hosts: vec![host.clone()],
+                    error,
+                })
+            })
+            .collect();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
+
+/// Applies changes with [`Provider::apply_host_records`], replacing the records of every
+/// changed host once.
+async fn replace_host_records<P: Provider + ?Sized>(
+    provider: &P,
+    creates: &[String],
+    deletes: &[DnsRecord],
+    options: &ApplyOptions,
+) -> Result<(), Vec<FailedChange<P::Error>>> {
+    // Deleted hosts are left without records, unless they're created again
+    let mut hosts: BTreeMap<&str, Vec<DnsRecord>> = deletes
+        .iter()
+        .map(|record| (record.host.as_str(), Vec::new()))
+        .collect();
+    for host in creates {
+        let record = DnsRecord {
+            host: host.clone(),
+            id: None,
+            ttl: options.record(host).ttl,
+            content: provider.destination().to_owned(),
+        };
+        hosts.insert(host, vec![record]);
+    }
+
+    let replaces: Vec<_> = hosts
+        .iter()
+        .map(|(host, records)| async move {
+            options.ready().await;
+            let res = retry(&options.retry, || {
+                provider.apply_host_records(host, records)
+            })
+            .await;
+            (host, res)
+        })
+        .collect();
+    let failures: Vec<_> = stream::iter(replaces)
+        .buffer_unordered(options.max_concurrency.max(1))
+        .filter_map(|(host, res)| async move {
+            res.err().map(|error| FailedChange {
+                hosts: vec![host.to_string()],
+                error,
+            })
+        })
+        .collect()
+        .await;
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+
+/// A request made by [`Provider::apply_changes`] that failed.
+#[derive(Debug)]
+pub struct FailedChange<E> {
+    /// Hosts whose changes were part of the failed request.
+    pub hosts: Vec<String>,
+    pub error: E,
+}
+
+/// Default maximum number of in-flight requests per phase of [`Provider::apply_changes`].
+pub const DEFAULT_MAX_CONCURRENCY: usize = 8;
+
+/// Controls how [`Provider::apply_changes`] issues requests.
+#[derive(Debug, Clone)]
+pub struct ApplyOptions {
+    /// Retry policy for each request.
+    pub retry: RetryPolicy,
+    /// Maximum number of in-flight requests, applied separately to creates & deletes.
+    pub max_concurrency: usize,
+    /// Per-host options of created records. Hosts without an entry use the defaults.
+    pub records: HashMap<String, RecordOptions>,
+    /// Rate limit of the requests, shared by every provider of a reconcile.
+    pub limiter: Option<Arc<RateLimit>>,
+    /// Listed records of the created hosts, passed to [`Provider::ensure_record`].
+    pub existing: HashMap<String, Vec<DnsRecord>>,
+}
+
+impl ApplyOptions {
+    /// Returns the options to create the records of `host` with.
+    pub fn record(&self, host: &str) -> RecordOptions {
+        self.records.get(host).cloned().unwrap_or_default()
+    }
+
+    /// Returns the listed records of `host`, or none if it had none.
+    pub fn existing(&self, host: &str) -> &[DnsRecord] {
+        self.existing.get(host).map_or(&[], Vec::as_slice)
+    }
+
+    /// Waits until the rate limit allows a request, if there is one.
+    pub async fn ready(&self) {
+        if let Some(limiter) = &self.limiter {
+            limiter.ready().await;
+        }
+    }
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        Self {
+            retry: RetryPolicy::default(),
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            records: HashMap::new(),
+            limiter: None,
+            existing: HashMap::new(),
+        }
+    }
+}
+
+/// Per-host overrides of a provider's record settings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordOptions {
+    /// TTL of the records, instead of the provider's.
+    pub ttl: Option<u32>,
+    /// Whether traffic is proxied through the provider, for providers supporting it.
+    pub proxied: Option<bool>,
+    /// Id of the router the host was read from, for providers annotating records with it.
+    pub router: Option<String>,
+}
+
+/// Type of the records pointing hosts at the destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    Cname,
+    A,
+    Aaaa,
+}
+
+impl RecordType {
+    /// Returns `true` for types holding addresses, which need the destination resolved.
+    pub fn is_address(self) -> bool {
+        !matches!(self, RecordType::Cname)
+    }
+
+    /// Returns `true` if records of this type can hold `addr`.
+    pub fn holds(self, addr: &IpAddr) -> bool {
+        match self {
+            RecordType::Cname => false,
+            RecordType::A => addr.is_ipv4(),
+            RecordType::Aaaa => addr.is_ipv6(),
+        }
+    }
+}
+
+impl Display for RecordType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RecordType::Cname => "CNAME",
+            RecordType::A => "A",
+            RecordType::Aaaa => "AAAA",
+        })
+    }
+}
+
+impl FromStr for RecordType {
+    type Err = UnknownRecordType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "CNAME" => Ok(RecordType::Cname),
+            "A" => Ok(RecordType::A),
+            "AAAA" => Ok(RecordType::Aaaa),
+            _ => Err(UnknownRecordType(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unknown record type {0}, expected CNAME, A or AAAA")]
+pub struct UnknownRecordType(pub String);
+
+/// A DNS record managed by a [`Provider`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct DnsRecord {
+    /// Fully qualified hostname of the record, without a trailing dot.
+    pub host: String,
+    /// Provider specific record identifier, if the provider has one.
+    pub id: Option<String>,
+    /// Current TTL of the record, if known.
+    pub ttl: Option<u32>,
+    /// Current content (target) of the record.
+    pub content: String,
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use mockall::predicate::{always, eq};
+
+    use super::{
+        rate_limit::RateLimit, ApplyOptions, DnsRecord, Provider, RecordOptions, RecordType,
+    };
+
+    // `mock!` rather than `automock` so that default methods keep their implementation
+    mockall::mock! {
+        pub Provider {}
+
+        #[async_trait::async_trait]
+        impl super::Provider for Provider {
+            type Error = MockProviderError;
+
+            fn destination(&self) -> &str;
+            fn destination_mut(&mut self) -> &mut String;
+
+            async fn list_records(&self) -> Result<Vec<DnsRecord>, MockProviderError>;
+            async fn create_record(
+                &self,
+                host: &str,
+                options: &RecordOptions,
+            ) -> Result<(), MockProviderError>;
+            async fn delete_record(&self, record: &DnsRecord) -> Result<(), MockProviderError>;
+        }
+    }
+
+    // Provider replacing the records of whole hosts
+    mockall::mock! {
+        pub HostSetProvider {}
+
+        #[async_trait::async_trait]
+        impl super::Provider for HostSetProvider {
+            type Error = MockProviderError;
+
+            fn destination(&self) -> &str;
+            fn destination_mut(&mut self) -> &mut String;
+
+            async fn list_records(&self) -> Result<Vec<DnsRecord>, MockProviderError>;
+            async fn create_record(
+                &self,
+                host: &str,
+                options: &RecordOptions,
+            ) -> Result<(), MockProviderError>;
+            async fn delete_record(&self, record: &DnsRecord) -> Result<(), MockProviderError>;
+            fn replaces_host_records(&self) -> bool;
+            async fn apply_host_records(
+                &self,
+                host: &str,
+                records: &[DnsRecord],
+            ) -> Result<(), MockProviderError>;
+        }
+    }
+
     /// Mock error type for testing
     #[derive(Debug)]
     pub struct MockProviderError;
@@ -29,4 +447,189 @@ mod tests {
     }
 
     impl std::error::Error for MockProviderError {}
+
+    impl crate::retry::Retryable for MockProviderError {
+        fn is_retryable(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_record_type() {
+        assert_eq!("cname".parse::<RecordType>().unwrap(), RecordType::Cname);
+        assert_eq!("A".parse::<RecordType>().unwrap(), RecordType::A);
+        assert_eq!("Aaaa".parse::<RecordType>().unwrap(), RecordType::Aaaa);
+        assert!("TXT".parse::<RecordType>().is_err());
+        assert_eq!(RecordType::Aaaa.to_string(), "AAAA");
+
+        let v4 = "192.0.2.1".parse().unwrap();
+        let v6 = "2001:db8::1".parse().unwrap();
+        assert!(RecordType::A.holds(&v4) && !RecordType::A.holds(&v6));
+        assert!(RecordType::Aaaa.holds(&v6) && !RecordType::Aaaa.holds(&v4));
+        assert!(!RecordType::Cname.holds(&v4));
+    }
+
+    #[tokio::test]
+    async fn test_apply_changes_ensure() {
+        let mut provider = MockProvider::new();
+        provider
+            .expect_create_record()
+            .with(eq("test2.example.com"), always())
+            .once()
+            .returning(|_, _| Ok(()));
+
+        // test1's record is already listed, so creating it again would be a duplicate
+        let existing = DnsRecord {
+            host: "test1.example.com".to_string(),
+            id: Some("1".to_string()),
+            ttl: None,
+            content: "dest".to_string(),
+        };
+        let options = ApplyOptions {
+            existing: HashMap::from([("test1.example.com".to_string(), vec![existing])]),
+            ..Default::default()
+        };
+        let creates = [
+            "test1.example.com".to_string(),
+            "test2.example.com".to_string(),
+        ];
+        provider
+            .apply_changes(&creates, &[], &options)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_apply_changes_rate_limit() {
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let mut provider = MockProvider::new();
+        let create_started = started.clone();
+        provider
+            .expect_create_record()
+            .times(4)
+            .returning(move |_, _| {
+                create_started.lock().unwrap().push(Instant::now());
+                Ok(())
+            });
+
+        let creates: Vec<_> = (0..4).map(|i| format!("test{}.example.com", i)).collect();
+        let options = ApplyOptions {
+            limiter: Some(Arc::new(RateLimit::new(1, Duration::from_millis(50)))),
+            ..Default::default()
+        };
+        provider
+            .apply_changes(&creates, &[], &options)
+            .await
+            .unwrap();
+
+        // Requests start one token apart, even though they could all run concurrently
+        let started = started.lock().unwrap();
+        assert_eq!(started.len(), 4);
+        for pair in started.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(45));
+        }
+    }
+
+    fn record(host: &str) -> DnsRecord {
+        DnsRecord {
+            host: host.to_string(),
+            id: Some(host.to_string()),
+            ttl: None,
+            content: "dest".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_changes_host_records() {
+        let mut provider = MockHostSetProvider::new();
+        provider.expect_replaces_host_records().return_const(true);
+        provider
+            .expect_destination()
+            .return_const("dest".to_string());
+        provider.expect_create_record().never();
+        provider.expect_delete_record().never();
+        // test1 is created, test2's stale record replaced, and test3 deleted, once per host
+        provider
+            .expect_apply_host_records()
+            .withf(|host, records| {
+                (host == "test1.example.com" || host == "test2.example.com")
+                    && records.len() == 1
+                    && records[0].host == host
+                    && records[0].content == "dest"
+                    && records[0].ttl == (host == "test1.example.com").then_some(60)
+            })
+            .times(2)
+            .returning(|_, _| Ok(()));
+        provider
+            .expect_apply_host_records()
+            .withf(|host, records| host == "test3.example.com" && records.is_empty())
+            .once()
+            .returning(|_, _| Err(MockProviderError));
+
+        let options = ApplyOptions {
+            retry: crate::retry::RetryPolicy {
+                max_attempts: 1,
+                ..Default::default()
+            },
+            records: HashMap::from([(
+                "test1.example.com".to_string(),
+                RecordOptions {
+                    ttl: Some(60),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let creates = [
+            "test1.example.com".to_string(),
+            "test2.example.com".to_string(),
+        ];
+        let deletes = [record("test2.example.com"), record("test3.example.com")];
+        let failures = provider
+            .apply_changes(&creates, &deletes, &options)
+            .await
+            .unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].hosts, vec!["test3.example.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_apply_host_records_default() {
+        let mut provider = MockProvider::new();
+        provider.expect_list_records().returning(|| {
+            Ok(vec![
+                record("test1.example.com"),
+                record("test2.example.com"),
+            ])
+        });
+        provider
+            .expect_delete_record()
+            .with(eq(record("test1.example.com")))
+            .times(2)
+            .returning(|_| Ok(()));
+        provider
+            .expect_create_record()
+            .with(
+                eq("test1.example.com"),
+                eq(RecordOptions {
+                    ttl: Some(60),
+                    ..Default::default()
+                }),
+            )
+            .once()
+            .returning(|_, _| Ok(()));
+
+        let replacement = DnsRecord {
+            ttl: Some(60),
+            ..record("test1.example.com")
+        };
+        provider
+            .apply_host_records("test1.example.com", &[replacement])
+            .await
+            .unwrap();
+        provider
+            .apply_host_records("test1.example.com", &[])
+            .await
+            .unwrap();
+    }
 }
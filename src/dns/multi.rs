@@ -0,0 +1,487 @@
+use std::fmt::{Debug, Display, Formatter};
+
+use tracing::warn;
+
+use super::{ApplyOptions, DnsRecord, FailedChange, Provider, RecordOptions};
+use crate::retry::Retryable;
+
+/// A type-erased [`Provider`].
+pub type DynProvider = Box<dyn Provider<Error = DynError>>;
+
+/// Erases the type of `provider`, so providers of different kinds can be combined.
+pub fn boxed<P>(provider: P) -> DynProvider
+where
+    P: Provider + 'static,
+    P::Error: 'static,
+{
+    Box::new(Erased(provider))
+}
+
+/// Type-erased provider error.
+pub struct DynError {
+    inner: Box<dyn std::error::Error + Send>,
+    retryable: bool,
+}
+
+impl DynError {
+    fn new<E: std::error::Error + Retryable + Send + 'static>(e: E) -> Self {
+        Self {
+            retryable: e.is_retryable(),
+            inner: Box::new(e),
+        }
+    }
+}
+
+impl Debug for DynError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.inner, f)
+    }
+}
+
+impl Display for DynError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.inner, f)
+    }
+}
+
+impl std::error::Error for DynError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner.source()
+    }
+}
+
+impl Retryable for DynError {
+    fn is_retryable(&self) -> bool {
+        self.retryable
+    }
+}
+
+/// Adapter mapping the errors of a provider to [`DynError`].
+struct Erased<P>(P);
+
+#[async_trait::async_trait]
+impl<P> Provider for Erased<P>
+where
+    P: Provider,
+    P::Error: 'static,
+{
+    type Error = DynError;
+
+    fn destination(&self) -> &str {
+        self.0.destination()
+    }
+    fn destination_mut(&mut self) -> &mut String {
+        self.0.destination_mut()
+    }
+
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        self.0.list_records().await.map_err(DynError::new)
+    }
+
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        self.0
+            .create_record(host, options)
+            .await
+            .map_err(DynError::new)
+    }
+
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        self.0.delete_record(record).await.map_err(DynError::new)
+    }
+
+    fn is_current(&self, record: &DnsRecord) -> bool {
+        self.0.is_current(record)
+    }
+
+    async fn ensure_record(
+        &self,
+        host: &str,
+        existing: &[DnsRecord],
+        options: &RecordOptions,
+    ) -> Result<(), Self::Error> {
+        self.0
+            .ensure_record(host, existing, options)
+            .await
+            .map_err(DynError::new)
+    }
+
+    fn replaces_host_records(&self) -> bool {
+        self.0.replaces_host_records()
+    }
+
+    async fn apply_host_records(
+        &self,
+        host: &str,
+        records: &[DnsRecord],
+    ) -> Result<(), Self::Error> {
+        self.0
+            .apply_host_records(host, records)
+            .await
+            .map_err(DynError::new)
+    }
+
+    async fn apply_changes(
+        &self,
+        creates: &[String],
+        deletes: &[DnsRecord],
+        options: &ApplyOptions,
+    ) -> Result<(), Vec<FailedChange<Self::Error>>> {
+        self.0
+            .apply_changes(creates, deletes, options)
+            .await
+            .map_err(|failures| {
+                failures
+                    .into_iter()
+                    .map(|f| FailedChange {
+                        hosts: f.hosts,
+                        error: DynError::new(f.error),
+                    })
+                    .collect()
+            })
+    }
+}
+
+/// A provider responsible for the hosts under a domain suffix.
+pub struct Zone {
+    /// Domain suffix of the hosts managed by `provider`, or `None` to manage any host.
+    pub suffix: Option<String>,
+    pub provider: DynProvider,
+}
+
+impl Zone {
+    pub fn new(suffix: Option<String>, provider: DynProvider) -> Self {
+        Self { suffix, provider }
+    }
+
+    /// Returns the length of the suffix matching `host`, if it matches.
+    fn match_len(&self, host: &str) -> Option<usize> {
+        match self.suffix.as_deref() {
+            None => Some(0),
+            Some(suffix) => {
+                let suffix = suffix.trim_matches('.');
+                let matches = host == suffix
+                    || host
+                        .strip_suffix(suffix)
+                        .is_some_and(|rest| rest.ends_with('.'));
+                matches.then_some(suffix.len())
+            }
+        }
+    }
+}
+
+/// Fans out records to a set of providers, by longest domain suffix match.
+///
+/// Hosts that don't match any provider are logged and skipped.
+pub struct MultiProvider {
+    zones: Vec<Zone>,
+}
+
+impl MultiProvider {
+    /// Creates a provider managing `zones`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `zones` is empty.
+    pub fn new(zones: Vec<Zone>) -> Self {
+        assert!(!zones.is_empty(), "at least one zone is required");
+        Self { zones }
+    }
+
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    /// Returns the index of the zone responsible for `host`.
+    fn zone_for(&self, host: &str) -> Option<usize> {
+        self.zones
+            .iter()
+            .enumerate()
+            .filter_map(|(i, zone)| zone.match_len(host).map(|len| (len, i)))
+            // Earlier zones win ties
+            .max_by(|(a, i), (b, j)| a.cmp(b).then(j.cmp(i)))
+            .map(|(_, i)| i)
+    }
+}
+
+impl Debug for MultiProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.zones.iter().map(|z| &z.suffix))
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for MultiProvider {
+    type Error = DynError;
+
+    /// Destination of the first zone.
+    fn destination(&self) -> &str {
+        self.zones[0].provider.destination()
+    }
+    fn destination_mut(&mut self) -> &mut String {
+        self.zones[0].provider.destination_mut()
+    }
+
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        let lists = futures::future::try_join_all(self.zones.iter().enumerate().map(
+            |(i, zone)| async move {
+                let records = zone.provider.list_records().await?;
+                // Only keep records this zone is responsible for
+                Ok::<_, DynError>(
+                    records
+                        .into_iter()
+                        .filter(|r| self.zone_for(&r.host) == Some(i))
+                        .collect::<Vec<_>>(),
+                )
+            },
+        ))
+        .await?;
+
+        Ok(lists.into_iter().flatten().collect())
+    }
+
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        match self.zone_for(host) {
+            Some(i) => self.zones[i].provider.create_record(host, options).await,
+            None => {
+                warn!(host, "no provider matches host, skipping");
+                Ok(())
+            }
+        }
+    }
+
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        match self.zone_for(&record.host) {
+            Some(i) => self.zones[i].provider.delete_record(record).await,
+            None => {
+                warn!(host = record.host, "no provider matches host, skipping");
+                Ok(())
+            }
+        }
+    }
+
+    fn is_current(&self, record: &DnsRecord) -> bool {
+        match self.zone_for(&record.host) {
+            Some(i) => self.zones[i].provider.is_current(record),
+            None => true,
+        }
+    }
+
+    async fn ensure_record(
+        &self,
+        host: &str,
+        existing: &[DnsRecord],
+        options: &RecordOptions,
+    ) -> Result<(), Self::Error> {
+        match self.zone_for(host) {
+            Some(i) => {
+                self.zones[i]
+                    .provider
+                    .ensure_record(host, existing, options)
+                    .await
+            }
+            None => {
+                warn!(host, "no provider matches host, skipping");
+                Ok(())
+            }
+        }
+    }
+
+    async fn apply_host_records(
+        &self,
+        host: &str,
+        records: &[DnsRecord],
+    ) -> Result<(), Self::Error> {
+        match self.zone_for(host) {
+            Some(i) => {
+                self.zones[i]
+                    .provider
+                    .apply_host_records(host, records)
+                    .await
+            }
+            None => {
+                warn!(host, "no provider matches host, skipping");
+                Ok(())
+            }
+        }
+    }
+
+    async fn apply_changes(
+        &self,
+        creates: &[String],
+        deletes: &[DnsRecord],
+        options: &ApplyOptions,
+    ) -> Result<(), Vec<FailedChange<Self::Error>>> {
+        let mut zone_creates = vec![Vec::new(); self.zones.len()];
+        let mut zone_deletes = vec![Vec::new(); self.zones.len()];
+
+        for host in creates {
+            match self.zone_for(host) {
+                Some(i) => zone_creates[i].push(host.clone()),
+                None => warn!(host, "no provider matches host, skipping"),
+            }
+        }
+        for record in deletes {
+            match self.zone_for(&record.host) {
+                Some(i) => zone_deletes[i].push(record.clone()),
+                None => warn!(host = record.host, "no provider matches host, skipping"),
+            }
+        }
+
+        let results = futures::future::join_all(
+            self.zones
+                .iter()
+                .zip(zone_creates.iter().zip(&zone_deletes))
+                .filter(|(_, (c, d))| !c.is_empty() || !d.is_empty())
+                .map(|(zone, (c, d))| zone.provider.apply_changes(c, d, options)),
+        )
+        .await;
+
+        let failures: Vec<_> = results
+            .into_iter()
+            .filter_map(Result::err)
+            .flatten()
+            .collect();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate::{always, eq};
+
+    use super::*;
+    use crate::dns::MockProvider;
+
+    fn record(host: &str) -> DnsRecord {
+        DnsRecord {
+            host: host.to_string(),
+            id: None,
+            ttl: None,
+            content: "dest".to_string(),
+        }
+    }
+
+    fn zone(suffix: Option<&str>, provider: MockProvider) -> Zone {
+        Zone::new(suffix.map(str::to_string), boxed(provider))
+    }
+
+    #[test]
+    fn test_zone_for() {
+        let provider = MultiProvider::new(vec![
+            zone(Some("example.com"), MockProvider::new()),
+            zone(Some("sub.example.com"), MockProvider::new()),
+            zone(Some("example.org."), MockProvider::new()),
+        ]);
+
+        assert_eq!(provider.zone_for("example.com"), Some(0));
+        assert_eq!(provider.zone_for("test.example.com"), Some(0));
+        assert_eq!(provider.zone_for("test.sub.example.com"), Some(1));
+        assert_eq!(provider.zone_for("test.example.org"), Some(2));
+        assert_eq!(provider.zone_for("testexample.com"), None);
+        assert_eq!(provider.zone_for("test.example.net"), None);
+
+        // A zone without suffix catches everything else
+        let provider = MultiProvider::new(vec![
+            zone(None, MockProvider::new()),
+            zone(Some("example.org"), MockProvider::new()),
+        ]);
+        assert_eq!(provider.zone_for("test.example.net"), Some(0));
+        assert_eq!(provider.zone_for("test.example.org"), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_apply_changes() {
+        let mut com = MockProvider::new();
+        com.expect_create_record()
+            .with(eq("test.example.com"), always())
+            .once()
+            .returning(|_, _| Ok(()));
+        com.expect_delete_record()
+            .with(eq(record("old.example.com")))
+            .once()
+            .returning(|_| Ok(()));
+
+        let mut org = MockProvider::new();
+        org.expect_create_record()
+            .with(eq("test.example.org"), always())
+            .once()
+            .returning(|_, _| Ok(()));
+
+        let provider = MultiProvider::new(vec![
+            zone(Some("example.com"), com),
+            zone(Some("example.org"), org),
+        ]);
+
+        // example.net matches no provider, and is skipped
+        provider
+            .apply_changes(
+                &[
+                    "test.example.com".to_string(),
+                    "test.example.org".to_string(),
+                    "test.example.net".to_string(),
+                ],
+                &[record("old.example.com"), record("old.example.net")],
+                &ApplyOptions::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_records() {
+        let mut com = MockProvider::new();
+        com.expect_list_records().once().returning(|| {
+            Ok(vec![
+                record("test.example.com"),
+                record("test.sub.example.com"),
+            ])
+        });
+
+        let mut sub = MockProvider::new();
+        sub.expect_list_records()
+            .once()
+            .returning(|| Ok(vec![record("other.sub.example.com")]));
+
+        let provider = MultiProvider::new(vec![
+            zone(Some("example.com"), com),
+            zone(Some("sub.example.com"), sub),
+        ]);
+
+        // test.sub.example.com belongs to the sub zone, so the parent zone doesn't report it
+        let hosts: Vec<_> = provider
+            .list_records()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.host)
+            .collect();
+        assert_eq!(hosts, vec!["test.example.com", "other.sub.example.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_records_no_match() {
+        let mut com = MockProvider::new();
+        com.expect_list_records()
+            .once()
+            .returning(|| Ok(vec![record("test.example.com"), record("test.example.net")]));
+
+        let provider = MultiProvider::new(vec![zone(Some("example.com"), com)]);
+
+        // Records outside of every zone are never reported, so never deleted
+        let hosts: Vec<_> = provider
+            .list_records()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.host)
+            .collect();
+        assert_eq!(hosts, vec!["test.example.com"]);
+    }
+}
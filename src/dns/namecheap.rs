@@ -0,0 +1,792 @@
+use reqwest::{Client, Url};
+use roxmltree::{Document, Node};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use super::{ApplyOptions, DnsRecord, FailedChange, Provider, RecordOptions};
+use crate::{
+    host::normalize_host,
+    retry::{retry, Retryable},
+};
+
+const DEFAULT_TTL: u32 = 1800;
+const BASE_URL: &str = "https://api.namecheap.com/xml.response";
+/// Name of the records of the domain itself.
+const APEX: &str = "@";
+const CNAME_TYPE: &str = "CNAME";
+
+/// Manages the CNAME records of a domain registered at Namecheap, using its XML API.
+///
+/// Namecheap's `setHosts` replaces every record of the domain at once, so each change reads
+/// the current records with `getHosts`, merges the change into them, and writes them all back.
+/// Records this provider doesn't manage are written back unchanged, and changes are serialized
+/// so concurrent ones don't overwrite each other. Changes made outside of this provider between
+/// the read & the write are lost, so batches are applied in a single round trip.
+///
+/// Namecheap only accepts API requests from whitelisted IPs, passed along as the client IP.
+#[derive(Debug)]
+pub struct NamecheapProvider {
+    dest: String,
+    domain: String,
+
+    base_url: Url,
+    api_user: String,
+    api_key: String,
+    username: String,
+    client_ip: String,
+    client: Client,
+
+    ttl: u32,
+    /// Held across the read-modify-write of the records.
+    lock: Mutex<()>,
+}
+
+impl NamecheapProvider {
+    pub fn new(
+        api_user: String,
+        api_key: String,
+        username: String,
+        client_ip: String,
+        domain: String,
+        dest: String,
+    ) -> Self {
+        Self::with_base_url(
+            api_user,
+            api_key,
+            username,
+            client_ip,
+            domain,
+            dest,
+            Url::parse(BASE_URL).unwrap(),
+        )
+    }
+
+    pub fn with_base_url(
+        api_user: String,
+        api_key: String,
+        username: String,
+        client_ip: String,
+        domain: String,
+        dest: String,
+        base_url: Url,
+    ) -> Self {
+        Self {
+            dest,
+            domain: normalize_host(&domain),
+            base_url,
+            api_user,
+            api_key,
+            username,
+            client_ip,
+            client: crate::http::default_client(),
+            ttl: DEFAULT_TTL,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn ttl(&self) -> &u32 {
+        &self.ttl
+    }
+    pub fn ttl_mut(&mut self) -> &mut u32 {
+        &mut self.ttl
+    }
+
+    /// HTTP client of the API requests, e.g. to send them through a proxy.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+    pub fn client_mut(&mut self) -> &mut Client {
+        &mut self.client
+    }
+
+    /// Calls the API `command` with `params`, returning the XML response.
+    ///
+    /// Requests are POSTed, as `setHosts` sends every record of the domain.
+    async fn call(
+        &self,
+        command: &str,
+        params: &[(String, String)],
+    ) -> Result<String, NamecheapError> {
+        // Second-level domain & TLD, like `example` & `co.uk`
+        let (sld, tld) = self
+            .domain
+            .split_once('.')
+            .ok_or_else(|| NamecheapError::InvalidDomain(self.domain.clone()))?;
+        let mut form = vec![
+            ("ApiUser", self.api_user.as_str()),
+            ("ApiKey", &self.api_key),
+            ("UserName", &self.username),
+            ("ClientIp", &self.client_ip),
+            ("Command", command),
+            ("SLD", sld),
+            ("TLD", tld),
+        ];
+        form.extend(params.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+
+        Ok(self
+            .client
+            .post(self.base_url.clone())
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    }
+
+    /// Reads every record of the domain.
+    async fn get_hosts(&self) -> Result<HostList, NamecheapError> {
+        let body = self.call("namecheap.domains.dns.getHosts", &[]).await?;
+        parse_hosts(&body)
+    }
+
+    /// Replaces every record of the domain with `list`.
+    async fn set_hosts(&self, list: &HostList) -> Result<(), NamecheapError> {
+        let mut params = Vec::with_capacity(list.hosts.len() * 5 + 1);
+        if let Some(email_type) = &list.email_type {
+            params.push(("EmailType".to_string(), email_type.clone()));
+        }
+        for (i, host) in list.hosts.iter().enumerate() {
+            let i = i + 1;
+            params.push((format!("HostName{}", i), host.name.clone()));
+            params.push((format!("RecordType{}", i), host.r#type.clone()));
+            params.push((format!("Address{}", i), host.address.clone()));
+            if let Some(mx_pref) = &host.mx_pref {
+                params.push((format!("MXPref{}", i), mx_pref.clone()));
+            }
+            if let Some(ttl) = host.ttl {
+                params.push((format!("TTL{}", i), ttl.to_string()));
+            }
+        }
+
+        let body = self.call("namecheap.domains.dns.setHosts", &params).await?;
+        let doc = parse(&body)?;
+        let success = find(&doc, "DomainDNSSetHostsResult")?.attribute("IsSuccess");
+        if success.is_some_and(|s| s.eq_ignore_ascii_case("true")) {
+            Ok(())
+        } else {
+            Err(NamecheapError::Rejected)
+        }
+    }
+
+    /// Reads the records, applies `change` to them, and writes them back if it changed them.
+    async fn update_hosts(
+        &self,
+        change: impl Fn(&mut Vec<NamecheapHost>) -> Result<(), NamecheapError>,
+    ) -> Result<(), NamecheapError> {
+        let _lock = self.lock.lock().await;
+        let mut list = self.get_hosts().await?;
+        let before = list.hosts.clone();
+        change(&mut list.hosts)?;
+        if list.hosts != before {
+            self.set_hosts(&list).await?;
+        }
+        Ok(())
+    }
+
+    /// Converts a fully qualified host into a record name relative to the domain.
+    fn relative_name<'a>(&self, host: &'a str) -> Result<&'a str, NamecheapError> {
+        if host == self.domain {
+            return Ok(APEX);
+        }
+        host.strip_suffix(&self.domain)
+            .and_then(|s| s.strip_suffix('.'))
+            .ok_or_else(|| NamecheapError::OutsideZone(host.to_owned()))
+    }
+
+    /// Converts a record name relative to the domain into a fully qualified host.
+    fn absolute_name(&self, name: &str) -> String {
+        if name == APEX {
+            self.domain.clone()
+        } else {
+            normalize_host(&format!("{}.{}", name, self.domain))
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for NamecheapProvider {
+    type Error = NamecheapError;
+
+    fn destination(&self) -> &str {
+        &self.dest
+    }
+    fn destination_mut(&mut self) -> &mut String {
+        &mut self.dest
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        let dest = normalize_host(&self.dest);
+
+        Ok(self
+            .get_hosts()
+            .await?
+            .hosts
+            .into_iter()
+            .filter(|h| h.is_cname() && normalize_host(&h.address) == dest)
+            .map(|h| DnsRecord {
+                host: self.absolute_name(&h.name),
+                id: h.id,
+                ttl: h.ttl,
+                content: h.address,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        let record = DnsRecord {
+            host: host.to_owned(),
+            id: None,
+            ttl: options.ttl,
+            content: self.dest.clone(),
+        };
+        self.apply_host_records(host, &[record]).await
+    }
+
+    /// Calls [`Provider::create_record`] directly, as it replaces the CNAME of the host.
+    async fn ensure_record(
+        &self,
+        host: &str,
+        _existing: &[DnsRecord],
+        options: &RecordOptions,
+    ) -> Result<(), Self::Error> {
+        self.create_record(host, options).await
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        let name = self
+            .relative_name(&normalize_host(&record.host))?
+            .to_owned();
+        self.update_hosts(|hosts| {
+            if remove_cname(hosts, &name, &record.content) {
+                Ok(())
+            } else {
+                Err(NamecheapError::RecordNotFound)
+            }
+        })
+        .await
+    }
+
+    fn replaces_host_records(&self) -> bool {
+        true
+    }
+
+    /// Replaces the CNAME of `host` in a single read & write of the records. Only the first of
+    /// `records` is kept, as a host has a single CNAME.
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn apply_host_records(
+        &self,
+        host: &str,
+        records: &[DnsRecord],
+    ) -> Result<(), Self::Error> {
+        let name = self.relative_name(host)?;
+        self.update_hosts(|hosts| {
+            match records.first() {
+                Some(record) => {
+                    upsert_cname(hosts, name, &self.dest, record.ttl.unwrap_or(self.ttl));
+                }
+                None => {
+                    remove_cname(hosts, name, &self.dest);
+                }
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Applies every change with a single read & write of the records, rather than one per host.
+    async fn apply_changes(
+        &self,
+        creates: &[String],
+        deletes: &[DnsRecord],
+        options: &ApplyOptions,
+    ) -> Result<(), Vec<FailedChange<Self::Error>>> {
+        let mut failures = Vec::new();
+        let mut upserts = Vec::with_capacity(creates.len());
+        for host in creates {
+            match self.relative_name(host) {
+                Ok(name) => {
+                    let ttl = options.record(host).ttl.unwrap_or(self.ttl);
+                    upserts.push((host, name, ttl));
+                }
+                Err(error) => failures.push(FailedChange {
+                    hosts: vec![host.clone()],
+                    error,
+                }),
+            }
+        }
+        let mut removals = Vec::with_capacity(deletes.len());
+        for record in deletes {
+            let host = normalize_host(&record.host);
+            match self.relative_name(&host) {
+                Ok(name) => removals.push((record, name.to_owned())),
+                Err(error) => failures.push(FailedChange {
+                    hosts: vec![record.host.clone()],
+                    error,
+                }),
+            }
+        }
+
+        if !upserts.is_empty() || !removals.is_empty() {
+            options.ready().await;
+            let res = retry(&options.retry, || {
+                self.update_hosts(|hosts| {
+                    for (record, name) in &removals {
+                        // Already deleted records are left alone
+                        remove_cname(hosts, name, &record.content);
+                    }
+                    for (_, name, ttl) in &upserts {
+                        upsert_cname(hosts, name, &self.dest, *ttl);
+                    }
+                    Ok(())
+                })
+            })
+            .await;
+            if let Err(error) = res {
+                failures.push(FailedChange {
+                    hosts: upserts
+                        .iter()
+                        .map(|(host, ..)| host.to_string())
+                        .chain(removals.iter().map(|(record, _)| record.host.clone()))
+                        .collect(),
+                    error,
+                });
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
+
+/// Replaces the CNAME of `name`, if any, with one to `dest`, keeping every other record.
+fn upsert_cname(hosts: &mut Vec<NamecheapHost>, name: &str, dest: &str, ttl: u32) {
+    let new = NamecheapHost {
+        id: None,
+        name: name.to_owned(),
+        r#type: CNAME_TYPE.to_owned(),
+        address: dest.to_owned(),
+        mx_pref: None,
+        ttl: Some(ttl),
+    };
+    match hosts
+        .iter_mut()
+        .find(|h| h.is_cname() && h.name.eq_ignore_ascii_case(name))
+    {
+        // Unchanged records are kept as is, so an up to date host list isn't written again
+        Some(host)
+            if normalize_host(&host.address) == normalize_host(dest) && host.ttl == Some(ttl) => {}
+        Some(host) => *host = new,
+        None => hosts.push(new),
+    }
+}
+
+/// Removes the CNAME of `name` to `content`, returning `false` if there is none.
+fn remove_cname(hosts: &mut Vec<NamecheapHost>, name: &str, content: &str) -> bool {
+    let len = hosts.len();
+    hosts.retain(|h| {
+        !(h.is_cname()
+            && h.name.eq_ignore_ascii_case(name)
+            && normalize_host(&h.address) == normalize_host(content))
+    });
+    hosts.len() != len
+}
+
+/// Parses an API response, returning its errors if it failed.
+fn parse(body: &str) -> Result<Document<'_>, NamecheapError> {
+    let doc = Document::parse(body).map_err(|e| NamecheapError::InvalidResponse(e.to_string()))?;
+    let root = doc.root_element();
+    if root.attribute("Status") != Some("OK") {
+        let errors: Vec<_> = root
+            .descendants()
+            .filter(|n| n.has_tag_name("Error"))
+            .map(|n| {
+                format!(
+                    "{} ({})",
+                    n.text().unwrap_or_default().trim(),
+                    n.attribute("Number").unwrap_or("unknown")
+                )
+            })
+            .collect();
+        return Err(NamecheapError::Api(errors.join(", ")));
+    }
+    Ok(doc)
+}
+
+/// Returns the first element named `name` of `doc`.
+fn find<'a, 'input: 'a>(
+    doc: &'a Document<'input>,
+    name: &str,
+) -> Result<Node<'a, 'input>, NamecheapError> {
+    doc.root()
+        .descendants()
+        .find(|n| n.has_tag_name(name))
+        .ok_or_else(|| NamecheapError::InvalidResponse(format!("missing {}", name)))
+}
+
+/// Parses a `getHosts` response.
+fn parse_hosts(body: &str) -> Result<HostList, NamecheapError> {
+    let doc = parse(body)?;
+    // Without the result, writing back an empty list would delete every record
+    let result = find(&doc, "DomainDNSGetHostsResult")?;
+
+    let hosts = result
+        .children()
+        .filter(|n| n.has_tag_name("host"))
+        .map(|n| {
+            let attr = |name| n.attribute(name).unwrap_or_default().to_owned();
+            NamecheapHost {
+                id: n.attribute("HostId").map(str::to_owned),
+                name: attr("Name"),
+                r#type: attr("Type"),
+                address: attr("Address"),
+                mx_pref: n.attribute("MXPref").map(str::to_owned),
+                ttl: n.attribute("TTL").and_then(|ttl| ttl.parse().ok()),
+            }
+        })
+        .collect();
+    Ok(HostList {
+        email_type: result.attribute("EmailType").map(str::to_owned),
+        hosts,
+    })
+}
+
+#[derive(Debug, Error)]
+pub enum NamecheapError {
+    #[error(transparent)]
+    ReqwestError(#[from] reqwest::Error),
+    #[error("Namecheap API error: {0}")]
+    Api(String),
+    #[error("invalid Namecheap API response: {0}")]
+    InvalidResponse(String),
+    #[error("Namecheap rejected the records")]
+    Rejected,
+    #[error("invalid domain {0}")]
+    InvalidDomain(String),
+    #[error("host {0} is outside of the zone")]
+    OutsideZone(String),
+    #[error("record not found")]
+    RecordNotFound,
+}
+
+impl Retryable for NamecheapError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            NamecheapError::ReqwestError(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status()
+                        .is_some_and(|s| s.as_u16() == 429 || s.is_server_error())
+            }
+            NamecheapError::Api(_)
+            | NamecheapError::InvalidResponse(_)
+            | NamecheapError::Rejected
+            | NamecheapError::InvalidDomain(_)
+            | NamecheapError::OutsideZone(_)
+            | NamecheapError::RecordNotFound => false,
+        }
+    }
+}
+
+/// Records of the domain, as read by `getHosts` & written by `setHosts`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HostList {
+    /// Mail settings of the domain, written back so they aren't reset.
+    email_type: Option<String>,
+    hosts: Vec<NamecheapHost>,
+}
+
+/// A record of the domain, of any type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NamecheapHost {
+    id: Option<String>,
+    /// Name relative to the domain, or `@`.
+    name: String,
+    r#type: String,
+    address: String,
+    mx_pref: Option<String>,
+    ttl: Option<u32>,
+}
+
+impl NamecheapHost {
+    fn is_cname(&self) -> bool {
+        self.r#type.eq_ignore_ascii_case(CNAME_TYPE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    use super::*;
+
+    const HOSTS_RESPONSE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<ApiResponse Status="OK" xmlns="http://api.namecheap.com/xml.response">
+  <Errors />
+  <RequestedCommand>namecheap.domains.dns.gethosts</RequestedCommand>
+  <CommandResponse Type="namecheap.domains.dns.getHosts">
+    <DomainDNSGetHostsResult Domain="example.com" EmailType="MX" IsUsingOurDNS="true">
+      <host HostId="1" Name="@" Type="A" Address="192.0.2.1" MXPref="10" TTL="1800" />
+      <host HostId="2" Name="@" Type="MX" Address="mail.example.com." MXPref="20" TTL="1800" />
+      <host HostId="3" Name="test1" Type="CNAME" Address="dest." MXPref="10" TTL="300" />
+      <host HostId="4" Name="other" Type="CNAME" Address="other.dest." MXPref="10" TTL="1800" />
+    </DomainDNSGetHostsResult>
+  </CommandResponse>
+</ApiResponse>"#;
+
+    const SET_HOSTS_RESPONSE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<ApiResponse Status="OK" xmlns="http://api.namecheap.com/xml.response">
+  <Errors />
+  <CommandResponse Type="namecheap.domains.dns.setHosts">
+    <DomainDNSSetHostsResult Domain="example.com" IsSuccess="true" />
+  </CommandResponse>
+</ApiResponse>"#;
+
+    fn provider(server: &Server) -> NamecheapProvider {
+        let base_url = Url::parse(&server.url_str("/xml.response")).unwrap();
+        NamecheapProvider::with_base_url(
+            "user".to_string(),
+            "key".to_string(),
+            "user".to_string(),
+            "192.0.2.10".to_string(),
+            "example.com".to_string(),
+            "dest".to_string(),
+            base_url,
+        )
+    }
+
+    fn expect_get_hosts(server: &Server) {
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/xml.response"),
+                request::body(url_decoded(contains((
+                    "Command",
+                    "namecheap.domains.dns.getHosts"
+                )))),
+                request::body(url_decoded(contains(("SLD", "example")))),
+                request::body(url_decoded(contains(("TLD", "com")))),
+                request::body(url_decoded(contains(("ClientIp", "192.0.2.10")))),
+            ])
+            .respond_with(status_code(200).body(HOSTS_RESPONSE)),
+        );
+    }
+
+    fn hosts() -> Vec<NamecheapHost> {
+        parse_hosts(HOSTS_RESPONSE).unwrap().hosts
+    }
+
+    #[test]
+    fn test_parse_hosts() {
+        let list = parse_hosts(HOSTS_RESPONSE).unwrap();
+        assert_eq!(list.email_type.as_deref(), Some("MX"));
+        assert_eq!(list.hosts.len(), 4);
+        assert_eq!(
+            list.hosts[1],
+            NamecheapHost {
+                id: Some("2".to_string()),
+                name: "@".to_string(),
+                r#type: "MX".to_string(),
+                address: "mail.example.com.".to_string(),
+                mx_pref: Some("20".to_string()),
+                ttl: Some(1800),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<ApiResponse Status="ERROR" xmlns="http://api.namecheap.com/xml.response">
+  <Errors>
+    <Error Number="1011150">Invalid request IP: 192.0.2.10</Error>
+  </Errors>
+</ApiResponse>"#;
+        let err = parse_hosts(body).unwrap_err();
+        assert!(
+            matches!(err, NamecheapError::Api(msg) if msg == "Invalid request IP: 192.0.2.10 (1011150)")
+        );
+
+        // A response without the records must never be written back as an empty list
+        let body = r#"<ApiResponse Status="OK"><CommandResponse /></ApiResponse>"#;
+        assert!(matches!(
+            parse_hosts(body),
+            Err(NamecheapError::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn test_upsert_cname() {
+        // New hosts are appended, leaving the other records alone
+        let mut hosts = hosts();
+        upsert_cname(&mut hosts, "new", "dest", 300);
+        assert_eq!(hosts.len(), 5);
+        assert_eq!(hosts[..4], self::hosts()[..]);
+        assert_eq!(hosts[4].name, "new");
+        assert_eq!(hosts[4].address, "dest");
+        assert_eq!(hosts[4].ttl, Some(300));
+
+        // Existing CNAMEs are replaced in place
+        let mut hosts = self::hosts();
+        upsert_cname(&mut hosts, "other", "dest", 300);
+        assert_eq!(hosts.len(), 4);
+        assert_eq!(hosts[3].address, "dest");
+        assert_eq!(hosts[3].id, None);
+
+        // Up to date CNAMEs are kept as is
+        let mut hosts = self::hosts();
+        upsert_cname(&mut hosts, "TEST1", "dest", 300);
+        assert_eq!(hosts, self::hosts());
+    }
+
+    #[test]
+    fn test_remove_cname() {
+        let mut hosts = hosts();
+        assert!(remove_cname(&mut hosts, "test1", "dest"));
+        assert_eq!(hosts.len(), 3);
+        assert!(hosts.iter().all(|h| h.name != "test1"));
+
+        // Records of other types or destinations are never removed
+        assert!(!remove_cname(&mut hosts, "@", "192.0.2.1"));
+        assert!(!remove_cname(&mut hosts, "other", "dest"));
+        assert_eq!(hosts.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_list_records() {
+        let server = Server::run();
+        expect_get_hosts(&server);
+
+        let records = provider(&server).list_records().await.unwrap();
+        assert_eq!(
+            records,
+            vec![DnsRecord {
+                host: "test1.example.com".to_string(),
+                id: Some("3".to_string()),
+                ttl: Some(300),
+                content: "dest.".to_string(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_changes() {
+        let server = Server::run();
+        expect_get_hosts(&server);
+        // Every record is written back once, with test1 deleted & test2 created
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/xml.response"),
+                request::body(url_decoded(contains((
+                    "Command",
+                    "namecheap.domains.dns.setHosts"
+                )))),
+                request::body(url_decoded(contains(("EmailType", "MX")))),
+                request::body(url_decoded(contains(("HostName1", "@")))),
+                request::body(url_decoded(contains(("RecordType1", "A")))),
+                request::body(url_decoded(contains(("Address1", "192.0.2.1")))),
+                request::body(url_decoded(contains(("HostName2", "@")))),
+                request::body(url_decoded(contains(("RecordType2", "MX")))),
+                request::body(url_decoded(contains(("MXPref2", "20")))),
+                request::body(url_decoded(contains(("HostName3", "other")))),
+                request::body(url_decoded(contains(("HostName4", "test2")))),
+                request::body(url_decoded(contains(("RecordType4", "CNAME")))),
+                request::body(url_decoded(contains(("Address4", "dest")))),
+                request::body(url_decoded(contains(("TTL4", "60")))),
+                request::body(url_decoded(not(contains(key("HostName5"))))),
+            ])
+            .respond_with(status_code(200).body(SET_HOSTS_RESPONSE)),
+        );
+
+        let record = DnsRecord {
+            host: "test1.example.com".to_string(),
+            id: Some("3".to_string()),
+            ttl: Some(300),
+            content: "dest.".to_string(),
+        };
+        let mut options = ApplyOptions::default();
+        options.records.insert(
+            "test2.example.com".to_string(),
+            RecordOptions {
+                ttl: Some(60),
+                ..Default::default()
+            },
+        );
+        let failures = provider(&server)
+            .apply_changes(
+                &["test2.example.com".to_string(), "outside.com".to_string()],
+                &[record],
+                &options,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].hosts, vec!["outside.com"]);
+        assert!(matches!(failures[0].error, NamecheapError::OutsideZone(_)));
+    }
+
+    #[tokio::test]
+    async fn test_create_record_unchanged() {
+        let server = Server::run();
+        // The CNAME is already up to date, so the records aren't written back
+        expect_get_hosts(&server);
+
+        provider(&server)
+            .create_record(
+                "test1.example.com",
+                &RecordOptions {
+                    ttl: Some(300),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_apply_host_records_delete() {
+        let server = Server::run();
+        expect_get_hosts(&server);
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/xml.response"),
+                request::body(url_decoded(contains((
+                    "Command",
+                    "namecheap.domains.dns.setHosts"
+                )))),
+                request::body(url_decoded(contains(("HostName3", "other")))),
+                request::body(url_decoded(not(contains(key("HostName4"))))),
+            ])
+            .respond_with(status_code(200).body(SET_HOSTS_RESPONSE)),
+        );
+
+        provider(&server)
+            .apply_host_records("test1.example.com", &[])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_record_not_found() {
+        let server = Server::run();
+        expect_get_hosts(&server);
+
+        let record = DnsRecord {
+            host: "missing.example.com".to_string(),
+            id: None,
+            ttl: None,
+            content: "dest".to_string(),
+        };
+        let err = provider(&server).delete_record(&record).await.unwrap_err();
+        assert!(matches!(err, NamecheapError::RecordNotFound));
+    }
+}
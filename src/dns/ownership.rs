@@ -0,0 +1,101 @@
+/// Prefix of the TXT record marking a host as owned.
+///
+/// The marker can't live on the host itself, as a CNAME can't coexist with other records.
+const RECORD_PREFIX: &str = "_traefik-dns.";
+const HERITAGE: &str = "heritage=traefik-dns-rs";
+/// Label replacing the `*` of wildcard hosts in marker names, as `*` is only a wildcard as the
+/// leftmost label.
+const WILDCARD_LABEL: &str = "_wildcard";
+
+/// Ownership marker written alongside every managed record.
+///
+/// When enabled, providers only report (and therefore only delete) records that carry a marker
+/// with a matching owner id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ownership {
+    owner_id: String,
+}
+
+impl Ownership {
+    pub fn new(owner_id: String) -> Self {
+        Self { owner_id }
+    }
+
+    pub fn owner_id(&self) -> &str {
+        &self.owner_id
+    }
+
+    /// Name of the TXT record marking `host`.
+    pub fn record_name(host: &str) -> String {
+        match host.strip_prefix("*.") {
+            Some(domain) => format!("{}{}.{}", RECORD_PREFIX, WILDCARD_LABEL, domain),
+            None => format!("{}{}", RECORD_PREFIX, host),
+        }
+    }
+
+    /// Returns the host marked by the TXT record named `name`, if it is a marker.
+    pub fn marked_host(name: &str) -> Option<String> {
+        let host = name.strip_prefix(RECORD_PREFIX)?;
+        match host
+            .strip_prefix(WILDCARD_LABEL)
+            .and_then(|rest| rest.strip_prefix('.'))
+        {
+            Some(domain) => Some(format!("*.{}", domain)),
+            None => Some(host.to_owned()),
+        }
+    }
+
+    /// Content of the TXT marker.
+    pub fn value(&self) -> String {
+        format!("{},owner={}", HERITAGE, self.owner_id)
+    }
+
+    /// Returns `true` if the TXT `value` marks a record owned by us.
+    ///
+    /// Surrounding quotes are ignored, as some providers return them.
+    pub fn matches(&self, value: &str) -> bool {
+        let value = value.trim_matches('"');
+        value
+            .strip_prefix(HERITAGE)
+            .and_then(|v| v.strip_prefix(",owner="))
+            == Some(self.owner_id.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_name() {
+        let name = Ownership::record_name("test.example.com");
+        assert_eq!(name, "_traefik-dns.test.example.com");
+        assert_eq!(
+            Ownership::marked_host(&name).as_deref(),
+            Some("test.example.com")
+        );
+        assert_eq!(Ownership::marked_host("test.example.com"), None);
+    }
+
+    #[test]
+    fn test_record_name_wildcard() {
+        let name = Ownership::record_name("*.example.com");
+        assert_eq!(name, "_traefik-dns._wildcard.example.com");
+        assert_eq!(
+            Ownership::marked_host(&name).as_deref(),
+            Some("*.example.com")
+        );
+    }
+
+    #[test]
+    fn test_matches() {
+        let ownership = Ownership::new("owner1".to_string());
+
+        assert_eq!(ownership.value(), "heritage=traefik-dns-rs,owner=owner1");
+        assert!(ownership.matches("heritage=traefik-dns-rs,owner=owner1"));
+        assert!(ownership.matches("\"heritage=traefik-dns-rs,owner=owner1\""));
+        assert!(!ownership.matches("heritage=traefik-dns-rs,owner=owner2"));
+        assert!(!ownership.matches("heritage=traefik-dns-rs,owner=owner12"));
+        assert!(!ownership.matches("heritage=external-dns,owner=owner1"));
+    }
+}
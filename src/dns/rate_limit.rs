@@ -0,0 +1,306 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Source of the current time, so the limiter can be tested deterministically.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// [`Clock`] reading the system's monotonic clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Upper bounds of the buckets of the wait time histogram, in seconds.
+pub const WAIT_BUCKETS: [f64; 8] = [0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 60.0];
+
+/// Token bucket rate limiter.
+///
+/// The bucket holds up to `capacity` tokens, and refills steadily at `capacity` tokens per
+/// `period`. Every request takes a token.
+#[derive(Debug)]
+pub struct RateLimit {
+    capacity: u64,
+    period: Duration,
+    clock: Arc<dyn Clock>,
+    state: Mutex<State>,
+    waits: Mutex<WaitStats>,
+}
+
+#[derive(Debug)]
+struct State {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Times [`RateLimit::ready`] had to wait for a token.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WaitStats {
+    pub count: u64,
+    /// Total time waited, in seconds.
+    pub sum: f64,
+    /// Cumulative number of waits at most as long as each of [`WAIT_BUCKETS`].
+    pub buckets: [u64; WAIT_BUCKETS.len()],
+}
+
+impl WaitStats {
+    fn record(&mut self, wait: Duration) {
+        let secs = wait.as_secs_f64();
+        self.count += 1;
+        self.sum += secs;
+        for (bucket, bound) in self.buckets.iter_mut().zip(WAIT_BUCKETS) {
+            if secs <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+}
+
+impl RateLimit {
+    /// Creates a full bucket of `capacity` tokens per `period`.
+    pub fn new(capacity: u64, period: Duration) -> Self {
+        Self::with_clock(capacity, period, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(capacity: u64, period: Duration, clock: Arc<dyn Clock>) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            period,
+            state: Mutex::new(State {
+                tokens: capacity as f64,
+                last_refill: clock.now(),
+            }),
+            clock,
+            waits: Mutex::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Takes a token if one is available, returning `false` otherwise.
+    pub fn try_ready(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Waits until a token is available, and takes it.
+    pub async fn ready(&self) {
+        if self.try_ready() {
+            return;
+        }
+
+        let start = self.clock.now();
+        loop {
+            tokio::time::sleep(self.next_token()).await;
+            if self.try_ready() {
+                break;
+            }
+        }
+        let waited = self.clock.now().saturating_duration_since(start);
+        self.waits.lock().unwrap().record(waited);
+    }
+
+    /// Returns the number of tokens currently available.
+    pub fn tokens(&self) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        state.tokens
+    }
+
+    /// Returns the times [`RateLimit::ready`] had to wait for a token.
+    pub fn waits(&self) -> WaitStats {
+        self.waits.lock().unwrap().clone()
+    }
+
+    /// Returns the time until the next token is available, which is at most the time it takes
+    /// to refill a single token.
+    pub fn next_token(&self) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+
+        let interval = self.period / self.capacity.min(u32::MAX as u64) as u32;
+        let missing = (1.0 - state.tokens).clamp(0.0, 1.0);
+        interval.mul_f64(missing)
+    }
+
+    fn refill(&self, state: &mut State) {
+        let now = self.clock.now();
+        // A clock going backwards refills nothing
+        let elapsed = now.saturating_duration_since(state.last_refill);
+        if elapsed.is_zero() {
+            return;
+        }
+
+        let refilled = match self.period.as_secs_f64() {
+            period if period > 0.0 => elapsed.as_secs_f64() / period * self.capacity as f64,
+            _ => f64::INFINITY,
+        };
+        state.tokens = (state.tokens + refilled).min(self.capacity as f64);
+        state.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Clock only moving when advanced or rewound.
+    #[derive(Debug)]
+    pub(crate) struct MockClock(Mutex<Instant>);
+
+    impl MockClock {
+        pub(crate) fn new() -> Arc<Self> {
+            Arc::new(Self(Mutex::new(Instant::now())))
+        }
+
+        pub(crate) fn advance(&self, duration: Duration) {
+            *self.0.lock().unwrap() += duration;
+        }
+
+        pub(crate) fn rewind(&self, duration: Duration) {
+            *self.0.lock().unwrap() -= duration;
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_refill() {
+        let clock = MockClock::new();
+        let limit = RateLimit::with_clock(2, Duration::from_secs(10), clock.clone());
+
+        assert!(limit.try_ready());
+        assert!(limit.try_ready());
+        assert!(!limit.try_ready());
+
+        // A token refills every 5s
+        clock.advance(Duration::from_secs(4));
+        assert!(!limit.try_ready());
+        clock.advance(Duration::from_secs(1));
+        assert!(limit.try_ready());
+        assert!(!limit.try_ready());
+
+        // The bucket never holds more than its capacity
+        clock.advance(Duration::from_secs(60));
+        assert!(limit.try_ready());
+        assert!(limit.try_ready());
+        assert!(!limit.try_ready());
+    }
+
+    #[test]
+    fn test_refill_boundary() {
+        let clock = MockClock::new();
+        let limit = RateLimit::with_clock(3, Duration::from_secs(3), clock.clone());
+        while limit.try_ready() {}
+
+        // The next token is available exactly one interval after the bucket emptied
+        clock.advance(Duration::from_millis(999));
+        assert!(!limit.try_ready());
+        clock.advance(Duration::from_millis(1));
+        assert!(limit.try_ready());
+        assert!(!limit.try_ready());
+    }
+
+    #[test]
+    fn test_next_token() {
+        let clock = MockClock::new();
+        let limit = RateLimit::with_clock(2, Duration::from_secs(10), clock.clone());
+
+        assert_eq!(limit.next_token(), Duration::ZERO);
+
+        limit.try_ready();
+        limit.try_ready();
+        assert_eq!(limit.next_token(), Duration::from_secs(5));
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(limit.next_token(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_next_token_bounded() {
+        let clock = MockClock::new();
+        let period = Duration::from_secs(10);
+        let limit = RateLimit::with_clock(1, period, clock.clone());
+
+        // Waiting for the next token never exceeds the period, even when the clock goes back
+        assert!(limit.try_ready());
+        assert_eq!(limit.next_token(), period);
+        clock.rewind(Duration::from_secs(5));
+        assert!(limit.next_token() <= period);
+        assert!(!limit.try_ready());
+
+        clock.advance(period);
+        assert!(limit.next_token() <= period);
+    }
+
+    #[tokio::test]
+    async fn test_ready() {
+        let limit = RateLimit::new(1, Duration::from_millis(20));
+
+        limit.ready().await;
+        assert!(!limit.try_ready());
+
+        let start = Instant::now();
+        limit.ready().await;
+        assert!(start.elapsed() <= Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ready_waits() {
+        let clock = MockClock::new();
+        let limit = Arc::new(RateLimit::with_clock(
+            1,
+            Duration::from_secs(2),
+            clock.clone(),
+        ));
+
+        // Tokens available immediately aren't waits
+        limit.ready().await;
+        assert_eq!(limit.waits(), WaitStats::default());
+        assert_eq!(limit.tokens(), 0.0);
+
+        let waiting = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.ready().await }
+        });
+        tokio::task::yield_now().await;
+        clock.advance(Duration::from_secs(2));
+        waiting.await.unwrap();
+
+        let waits = limit.waits();
+        assert_eq!(waits.count, 1);
+        assert_eq!(waits.sum, 2.0);
+        // Only the buckets of at least 2s hold the wait
+        assert_eq!(waits.buckets, [0, 0, 0, 0, 0, 1, 1, 1]);
+        assert_eq!(limit.tokens(), 0.0);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(limit.tokens(), 0.5);
+    }
+}
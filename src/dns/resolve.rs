@@ -0,0 +1,164 @@
+use std::{net::IpAddr, sync::Mutex};
+
+use hickory_resolver::{error::ResolveError, TokioAsyncResolver};
+
+/// Resolves the destination to the addresses A/AAAA records are created with.
+///
+/// Addresses are resolved once per reconcile by [`DestinationResolver::refresh`], and the
+/// previous addresses are remembered so records pointing at them can still be found after the
+/// destination moves.
+#[derive(Debug)]
+pub struct DestinationResolver {
+    source: Source,
+    addresses: Mutex<Addresses>,
+}
+
+#[derive(Debug)]
+enum Source {
+    Dns(Box<TokioAsyncResolver>),
+    /// Addresses of a dual-stack destination, which are never looked up.
+    Fixed(Vec<IpAddr>),
+}
+
+#[derive(Debug, Default)]
+struct Addresses {
+    previous: Vec<IpAddr>,
+    current: Vec<IpAddr>,
+}
+
+impl DestinationResolver {
+    /// Creates a resolver using the system's DNS configuration.
+    pub fn new() -> Result<Self, ResolveError> {
+        Ok(Self::with_resolver(
+            TokioAsyncResolver::tokio_from_system_conf()?,
+        ))
+    }
+
+    pub fn with_resolver(resolver: TokioAsyncResolver) -> Self {
+        Self {
+            source: Source::Dns(Box::new(resolver)),
+            addresses: Mutex::default(),
+        }
+    }
+
+    /// Creates a resolver always returning `addrs`, like the IPv4 & IPv6 addresses of a
+    /// dual-stack destination, to create both A & AAAA records of every host.
+    pub fn fixed(mut addrs: Vec<IpAddr>) -> Self {
+        addrs.sort();
+        addrs.dedup();
+        Self {
+            addresses: Mutex::new(Addresses {
+                previous: Vec::new(),
+                current: addrs.clone(),
+            }),
+            source: Source::Fixed(addrs),
+        }
+    }
+
+    /// Resolves `host`, replacing the current addresses. Fixed resolvers ignore `host`.
+    pub async fn refresh(&self, host: &str) -> Result<Vec<IpAddr>, ResolveError> {
+        let mut addrs: Vec<_> = match &self.source {
+            Source::Dns(resolver) => resolver.lookup_ip(host).await?.iter().collect(),
+            Source::Fixed(addrs) => addrs.clone(),
+        };
+        addrs.sort();
+        addrs.dedup();
+
+        let mut addresses = self.addresses.lock().unwrap();
+        if addresses.current != addrs {
+            addresses.previous = std::mem::replace(&mut addresses.current, addrs.clone());
+        }
+        Ok(addrs)
+    }
+
+    /// Returns the addresses of the last refresh.
+    pub fn addresses(&self) -> Vec<IpAddr> {
+        self.addresses.lock().unwrap().current.clone()
+    }
+
+    /// Returns `true` if `addr` is a current or previous address of the destination.
+    pub fn is_known(&self, addr: &IpAddr) -> bool {
+        let addresses = self.addresses.lock().unwrap();
+        addresses.current.contains(addr) || addresses.previous.contains(addr)
+    }
+
+    /// Returns `true` if `addr` is a current address of the destination.
+    pub fn is_current(&self, addr: &IpAddr) -> bool {
+        self.addresses.lock().unwrap().current.contains(addr)
+    }
+}
+
+/// Formats addresses as record content, for providers that group them in a single record.
+pub fn join_addresses(addrs: &[IpAddr]) -> String {
+    let mut addrs = addrs.to_vec();
+    addrs.sort();
+    addrs
+        .iter()
+        .map(IpAddr::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parses content formatted by [`join_addresses`], skipping invalid addresses.
+pub fn split_addresses(content: &str) -> Vec<IpAddr> {
+    content
+        .split(',')
+        .filter_map(|addr| addr.trim().parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+
+    use super::*;
+
+    /// Resolver that never queries the network, only resolving IP literals.
+    pub(crate) fn resolver() -> DestinationResolver {
+        DestinationResolver::with_resolver(TokioAsyncResolver::tokio(
+            ResolverConfig::new(),
+            ResolverOpts::default(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_refresh() {
+        let resolver = resolver();
+        let old: IpAddr = "192.0.2.1".parse().unwrap();
+        let new: IpAddr = "192.0.2.2".parse().unwrap();
+
+        assert_eq!(resolver.refresh("192.0.2.1").await.unwrap(), vec![old]);
+        assert_eq!(resolver.addresses(), vec![old]);
+
+        // The previous address is still known after a change
+        resolver.refresh("192.0.2.2").await.unwrap();
+        assert_eq!(resolver.addresses(), vec![new]);
+        assert!(resolver.is_known(&old));
+        assert!(!resolver.is_current(&old));
+        assert!(resolver.is_current(&new));
+
+        // Refreshing to the same addresses keeps the previous ones
+        resolver.refresh("192.0.2.2").await.unwrap();
+        assert!(resolver.is_known(&old));
+    }
+
+    #[tokio::test]
+    async fn test_fixed() {
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+
+        let resolver = DestinationResolver::fixed(vec![v6, v4]);
+        assert_eq!(resolver.addresses(), vec![v4, v6]);
+        assert_eq!(resolver.refresh("example.com").await.unwrap(), vec![v4, v6]);
+        assert!(resolver.is_current(&v4) && resolver.is_current(&v6));
+    }
+
+    #[test]
+    fn test_join_addresses() {
+        let addrs: Vec<IpAddr> = vec!["2001:db8::1".parse().unwrap(), "192.0.2.1".parse().unwrap()];
+
+        let content = join_addresses(&addrs);
+        assert_eq!(content, "192.0.2.1,2001:db8::1");
+        assert_eq!(split_addresses(&content), vec![addrs[1], addrs[0]]);
+    }
+}
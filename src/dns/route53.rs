@@ -1,17 +1,97 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use aws_config::sts::AssumeRoleProvider;
 use aws_sdk_route53::{
+    error::ProvideErrorMetadata,
     operation::{
-        change_resource_record_sets::ChangeResourceRecordSetsError,
+        change_resource_record_sets::ChangeResourceRecordSetsError, get_change::GetChangeError,
+        list_hosted_zones_by_name::ListHostedZonesByNameError,
         list_resource_record_sets::ListResourceRecordSetsError,
     },
-    types::{Change, ChangeAction, ChangeBatch, ResourceRecord, ResourceRecordSet, RrType},
+    types::{
+        Change, ChangeAction, ChangeBatch, ChangeStatus, ResourceRecord, ResourceRecordSet, RrType,
+    },
     Client,
 };
 use aws_smithy_http::result::SdkError;
+use hickory_resolver::error::ResolveError;
 use thiserror::Error;
+use tokio::time;
+use tracing::warn;
 
-use super::Provider;
+use super::{
+    ownership::Ownership,
+    resolve::{join_addresses, split_addresses, DestinationResolver},
+    ApplyOptions, DnsRecord, FailedChange, Provider, RecordOptions, RecordType,
+};
+use crate::{
+    host::normalize_host,
+    retry::{retry, retry_if, RetryPolicy, Retryable},
+};
 
 const DEFAULT_TTL: i64 = 300;
+/// Route53 accepts TTLs up to 2^31 - 1 seconds.
+const MAX_TTL: i64 = i32::MAX as i64;
+/// Maximum number of changes sent in a single `ChangeBatch`.
+///
+/// Route53 allows 1000 resource record elements per request, and UPSERTs count twice.
+const MAX_BATCH_CHANGES: usize = 500;
+/// Route53 processes a zone's changes one at a time, so throttled requests are retried for
+/// longer than other transient errors.
+const DEFAULT_THROTTLE_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 5,
+    base_delay: Duration::from_secs(1),
+};
+/// Default time to wait for changes to be in sync.
+pub const DEFAULT_SYNC_TIMEOUT: Duration = Duration::from_secs(120);
+/// Interval between checks of whether a change is in sync.
+const SYNC_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Error codes of requests rejected by Route53 while it is busy.
+const THROTTLING_CODES: &[&str] = &["PriorRequestNotComplete", "Throttling"];
+
+/// Target of alias records, pointing at an AWS resource like a load balancer or a CloudFront
+/// distribution. The DNS name of the target is the provider's destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AliasTarget {
+    /// Hosted zone of the AWS resource, not the zone of the records.
+    pub hosted_zone_id: String,
+    pub evaluate_target_health: bool,
+}
+
+/// Role assumed with the credentials of the environment, to manage zones of another AWS account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssumeRole {
+    pub role_arn: String,
+    /// External id required by the trust policy of the role, if any.
+    pub external_id: Option<String>,
+    /// Defaults to a name generated by the SDK.
+    pub session_name: Option<String>,
+}
+
+impl AssumeRole {
+    /// Returns a provider assuming the role with the credentials of `config`, or `None` if it
+    /// has none.
+    pub fn provider(&self, config: &aws_config::SdkConfig) -> Option<AssumeRoleProvider> {
+        let source = config.credentials_provider()?.clone();
+
+        let mut builder = AssumeRoleProvider::builder(&self.role_arn);
+        if let Some(id) = &self.external_id {
+            builder = builder.external_id(id);
+        }
+        if let Some(name) = &self.session_name {
+            builder = builder.session_name(name);
+        }
+        if let Some(region) = config.region() {
+            builder = builder.region(region.clone());
+        }
+        Some(builder.build(source))
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Route53Provider {
@@ -20,6 +100,18 @@ pub struct Route53Provider {
     client: Client,
 
     ttl: i64,
+    ownership: Option<Ownership>,
+    resolver: Option<Arc<DestinationResolver>>,
+    record_type: Option<RecordType>,
+    /// Keep the TTL of existing records instead of replacing it with `ttl`.
+    preserve_existing_ttl: bool,
+    /// TTLs of the record sets of the last listing, by host.
+    existing_ttls: Arc<Mutex<HashMap<String, i64>>>,
+    throttle_retry: RetryPolicy,
+    /// Wait up to this long for changes to be in sync, or `None` to not wait.
+    sync_timeout: Option<Duration>,
+    /// Create alias records instead of CNAMEs.
+    alias: Option<AliasTarget>,
 }
 
 impl Route53Provider {
@@ -29,6 +121,79 @@ impl Route53Provider {
             hosted_zone_id,
             client,
             ttl: DEFAULT_TTL,
+            ownership: None,
+            resolver: None,
+            record_type: None,
+            preserve_existing_ttl: false,
+            existing_ttls: Arc::default(),
+            throttle_retry: DEFAULT_THROTTLE_RETRY,
+            sync_timeout: None,
+            alias: None,
+        }
+    }
+
+    pub fn builder() -> Route53ProviderBuilder {
+        Route53ProviderBuilder::default()
+    }
+
+    /// Creates a client from `config`, sending requests to `endpoint_url` instead of the default
+    /// AWS endpoint if set, like LocalStack or GovCloud. With `assume_role`, requests use the
+    /// credentials of the role instead of the ones of `config`.
+    pub fn new_client(
+        config: &aws_config::SdkConfig,
+        endpoint_url: Option<&str>,
+        assume_role: Option<&AssumeRole>,
+    ) -> Client {
+        let mut builder = aws_sdk_route53::config::Builder::from(config);
+        if let Some(url) = endpoint_url {
+            builder = builder.endpoint_url(url);
+        }
+        if let Some(role) = assume_role {
+            match role.provider(config) {
+                Some(provider) => builder = builder.credentials_provider(provider),
+                None => warn!(
+                    role = role.role_arn,
+                    "no credentials to assume the role with"
+                ),
+            }
+        }
+        Client::from_conf(builder.build())
+    }
+
+    /// Finds the id of the hosted zone named `name`, either private or public if `private_zone`
+    /// is set, as a public & a private zone may share a name.
+    pub async fn find_zone(
+        client: &Client,
+        name: &str,
+        private_zone: Option<bool>,
+    ) -> Result<String, Route53Error> {
+        let name = normalize_host(name);
+        let zones = client
+            .list_hosted_zones_by_name()
+            .dns_name(&name)
+            .send()
+            .await?
+            .hosted_zones
+            .unwrap_or_default();
+
+        // Zones are listed from `name` onwards, so only the first ones can match
+        let mut ids = zones
+            .iter()
+            .filter(|zone| zone.name().is_some_and(|n| host_name(n) == name))
+            .filter(|zone| {
+                private_zone.is_none_or(|private| {
+                    zone.config().is_some_and(|c| c.private_zone()) == private
+                })
+            })
+            .filter_map(|zone| zone.id())
+            .map(|id| id.trim_start_matches("/hostedzone/").to_string());
+        match (ids.next(), ids.count()) {
+            (Some(id), 0) => Ok(id),
+            (Some(_), others) => Err(Route53Error::AmbiguousZone {
+                name,
+                count: others + 1,
+            }),
+            (None, _) => Err(Route53Error::ZoneNotFound(name)),
         }
     }
 
@@ -39,25 +204,488 @@ impl Route53Provider {
         &mut self.ttl
     }
 
-    fn change_batch(&self, action: ChangeAction, host: &str, ttl: Option<i64>) -> ChangeBatch {
-        ChangeBatch::builder()
-            .changes(
+    pub fn ownership(&self) -> &Option<Ownership> {
+        &self.ownership
+    }
+    pub fn ownership_mut(&mut self) -> &mut Option<Ownership> {
+        &mut self.ownership
+    }
+
+    /// Resolver of the destination, to create A/AAAA records instead of CNAMEs.
+    pub fn resolver(&self) -> &Option<Arc<DestinationResolver>> {
+        &self.resolver
+    }
+    pub fn resolver_mut(&mut self) -> &mut Option<Arc<DestinationResolver>> {
+        &mut self.resolver
+    }
+
+    /// Type of the created records, or `None` for CNAMEs, or A & AAAA records if the
+    /// destination is resolved. A & AAAA require a resolver.
+    pub fn record_type(&self) -> Option<RecordType> {
+        self.record_type
+    }
+    pub fn record_type_mut(&mut self) -> &mut Option<RecordType> {
+        &mut self.record_type
+    }
+
+    /// Whether UPSERTs keep the TTL of existing records, unless a route sets its own.
+    pub fn preserve_existing_ttl(&self) -> bool {
+        self.preserve_existing_ttl
+    }
+    pub fn preserve_existing_ttl_mut(&mut self) -> &mut bool {
+        &mut self.preserve_existing_ttl
+    }
+
+    /// Retry policy of requests throttled by Route53.
+    pub fn throttle_retry(&self) -> &RetryPolicy {
+        &self.throttle_retry
+    }
+    pub fn throttle_retry_mut(&mut self) -> &mut RetryPolicy {
+        &mut self.throttle_retry
+    }
+
+    /// How long changes are waited on to be in sync on every authoritative server, or `None`
+    /// to only wait for them to be accepted.
+    pub fn sync_timeout(&self) -> Option<Duration> {
+        self.sync_timeout
+    }
+    pub fn sync_timeout_mut(&mut self) -> &mut Option<Duration> {
+        &mut self.sync_timeout
+    }
+
+    /// Target of the created alias records, or `None` to create CNAMEs or address records.
+    pub fn alias(&self) -> Option<&AliasTarget> {
+        self.alias.as_ref()
+    }
+    pub fn alias_mut(&mut self) -> &mut Option<AliasTarget> {
+        &mut self.alias
+    }
+
+    /// Lists the record sets of the hosted zone, retrying throttled requests.
+    async fn list_sets(&self) -> Result<Vec<ResourceRecordSet>, Route53Error> {
+        retry_if(
+            &self.throttle_retry,
+            || async {
+                let sets = self
+                    .client
+                    .list_resource_record_sets()
+                    .hosted_zone_id(self.hosted_zone_id.clone())
+                    .send()
+                    .await?
+                    .resource_record_sets
+                    .unwrap_or_default();
+                Ok(sets)
+            },
+            Route53Error::is_throttling,
+        )
+        .await
+    }
+
+    /// Sends a change batch, retrying throttled requests, then waits for it to be in sync if
+    /// `sync_timeout` is set.
+    async fn send_changes(&self, batch: ChangeBatch) -> Result<(), Route53Error> {
+        let output = retry_if(
+            &self.throttle_retry,
+            || async {
+                self.client
+                    .change_resource_record_sets()
+                    .hosted_zone_id(self.hosted_zone_id.clone())
+                    .change_batch(batch.clone())
+                    .send()
+                    .await
+                    .map_err(Route53Error::from)
+            },
+            Route53Error::is_throttling,
+        )
+        .await?;
+
+        let Some(timeout) = self.sync_timeout else {
+            return Ok(());
+        };
+        match output.change_info().and_then(|info| info.id()) {
+            Some(id) => self.wait_for_sync(id, timeout).await,
+            None => {
+                warn!("change has no id, not waiting for it to be in sync");
+                Ok(())
+            }
+        }
+    }
+
+    /// Polls the change `id` until it is in sync, failing after `timeout`.
+    async fn wait_for_sync(&self, id: &str, timeout: Duration) -> Result<(), Route53Error> {
+        let poll = async {
+            loop {
+                let status = retry_if(
+                    &self.throttle_retry,
+                    || async {
+                        let output = self.client.get_change().id(id).send().await?;
+                        Ok(output.change_info().and_then(|info| info.status()).cloned())
+                    },
+                    Route53Error::is_throttling,
+                )
+                .await?;
+                if status == Some(ChangeStatus::Insync) {
+                    return Ok(());
+                }
+                time::sleep(SYNC_POLL_INTERVAL).await;
+            }
+        };
+
+        time::timeout(timeout, poll)
+            .await
+            .map_err(|_| Route53Error::SyncTimeout {
+                id: id.to_string(),
+                timeout,
+            })?
+    }
+
+    /// Returns the resolved addresses of the destination that records may hold.
+    fn addresses(&self, resolver: &DestinationResolver) -> Vec<IpAddr> {
+        resolver
+            .addresses()
+            .into_iter()
+            .filter(|addr| self.record_type.is_none_or(|t| t.holds(addr)))
+            .collect()
+    }
+
+    /// Builds the changes for `host` pointing at the destination.
+    ///
+    /// Without a `ttl`, the TTL of the listed record is kept if `preserve_existing_ttl` is set.
+    fn changes(&self, action: ChangeAction, host: &str, ttl: Option<i64>) -> Vec<Change> {
+        let ttl = ttl.or_else(|| {
+            self.preserve_existing_ttl
+                .then(|| self.existing_ttls.lock().unwrap().get(host).copied())
+                .flatten()
+        });
+        let content = match &self.resolver {
+            Some(resolver) => join_addresses(&self.addresses(resolver)),
+            None => self.dest.clone(),
+        };
+        self.changes_to(action, host, ttl, &content)
+    }
+
+    /// Builds the changes for `host` pointing at `content`: the CNAME (or A/AAAA records if the
+    /// destination is resolved), and its ownership marker if enabled.
+    fn changes_to(
+        &self,
+        action: ChangeAction,
+        host: &str,
+        ttl: Option<i64>,
+        content: &str,
+    ) -> Vec<Change> {
+        let ttl = ttl.unwrap_or(self.ttl);
+        let sets = match (&self.alias, &self.resolver) {
+            // Aliases take the TTL of their target
+            (Some(alias), _) => self
+                .record_types()
+                .iter()
+                .map(|r#type| {
+                    ResourceRecordSet::builder()
+                        .name(record_name(host))
+                        .r#type(r#type.clone())
+                        .alias_target(
+                            aws_sdk_route53::types::AliasTarget::builder()
+                                .hosted_zone_id(&alias.hosted_zone_id)
+                                .dns_name(content)
+                                .evaluate_target_health(alias.evaluate_target_health)
+                                .build(),
+                        )
+                        .build()
+                })
+                .collect(),
+            (None, Some(_)) => {
+                let (v4, v6): (Vec<_>, Vec<_>) = split_addresses(content)
+                    .into_iter()
+                    .partition(IpAddr::is_ipv4);
+                vec![(RrType::A, v4), (RrType::Aaaa, v6)]
+                    .into_iter()
+                    .filter(|(_, addrs)| !addrs.is_empty())
+                    .map(|(r#type, addrs)| {
+                        value_set(host, r#type, addrs.iter().map(IpAddr::to_string), ttl)
+                    })
+                    .collect()
+            }
+            (None, None) => vec![value_set(host, RrType::Cname, [content.to_string()], ttl)],
+        };
+
+        let mut changes: Vec<_> = sets
+            .into_iter()
+            .map(|set| {
+                Change::builder()
+                    .action(action.clone())
+                    .resource_record_set(set)
+                    .build()
+            })
+            .collect();
+
+        if let Some(ownership) = &self.ownership {
+            changes.push(
                 Change::builder()
                     .action(action)
                     .resource_record_set(
                         ResourceRecordSet::builder()
-                            .name(host)
-                            .r#type(RrType::Cname)
+                            .name(record_name(&Ownership::record_name(host)))
+                            .r#type(RrType::Txt)
                             .resource_records(
-                                ResourceRecord::builder().value(self.dest.clone()).build(),
+                                ResourceRecord::builder()
+                                    .value(format!("\"{}\"", ownership.value()))
+                                    .build(),
                             )
-                            .ttl(ttl.unwrap_or(self.ttl))
+                            .ttl(ttl)
                             .build(),
                     )
                     .build(),
-            )
+            );
+        }
+
+        changes
+    }
+
+    fn change_batch(&self, action: ChangeAction, host: &str, ttl: Option<i64>) -> ChangeBatch {
+        ChangeBatch::builder()
+            .set_changes(Some(self.changes(action, host, ttl)))
             .build()
     }
+
+    /// Builds the DELETE changes for `record`, looking up its TTL if it isn't known.
+    async fn delete_changes(&self, record: &DnsRecord) -> Result<Vec<Change>, Route53Error> {
+        // Route53 requires the exact TTL of the record set being deleted
+        let ttl = match record.ttl {
+            Some(ttl) => Some(i64::from(ttl)),
+            None if self.alias.is_some() => None,
+            None => self.find_record(&record.host).await?.ttl(),
+        };
+        Ok(self.changes_to(ChangeAction::Delete, &record.host, ttl, &record.content))
+    }
+
+    /// Record types of the records pointing at the destination.
+    fn record_types(&self) -> &'static [RrType] {
+        if self.alias.is_some() {
+            return match self.record_type {
+                Some(RecordType::Aaaa) => &[RrType::Aaaa],
+                _ => &[RrType::A],
+            };
+        }
+        match (&self.resolver, self.record_type) {
+            (None, _) => &[RrType::Cname],
+            (Some(_), Some(RecordType::A)) => &[RrType::A],
+            (Some(_), Some(RecordType::Aaaa)) => &[RrType::Aaaa],
+            (Some(_), _) => &[RrType::A, RrType::Aaaa],
+        }
+    }
+
+    /// Finds the record set for `host` in the hosted zone.
+    async fn find_record(&self, host: &str) -> Result<ResourceRecordSet, Route53Error> {
+        self.list_sets()
+            .await?
+            .into_iter()
+            .find(|r| {
+                r.name()
+                    .is_some_and(|name| name.ends_with('.') && same_host(name, host))
+                    && r.r#type().is_some_and(|t| self.record_types().contains(t))
+            })
+            .ok_or(Route53Error::MissingRecord)
+    }
+
+    /// Collects the alias record sets pointing at the destination.
+    fn alias_records(
+        &self,
+        sets: &[ResourceRecordSet],
+        owned: Option<&HashSet<String>>,
+    ) -> Vec<DnsRecord> {
+        let dest = normalize_host(&self.dest);
+        sets.iter()
+            .filter(|r| r.r#type().is_some_and(|t| self.record_types().contains(t)))
+            .filter_map(|r| {
+                let host = host_name(r.name()?);
+                let content = normalize_host(r.alias_target()?.dns_name()?);
+                match owned {
+                    Some(owned) if !owned.contains(&host) => return None,
+                    None if content != dest => return None,
+                    _ => {}
+                }
+                Some(DnsRecord {
+                    host,
+                    id: None,
+                    ttl: None,
+                    content,
+                })
+            })
+            .collect()
+    }
+
+    /// Collects the A/AAAA record sets pointing at the resolved destination, merging both sets
+    /// of a host into a single record.
+    fn address_records(
+        &self,
+        resolver: &DestinationResolver,
+        sets: &[ResourceRecordSet],
+        owned: Option<&HashSet<String>>,
+    ) -> Vec<DnsRecord> {
+        let mut records: BTreeMap<String, DnsRecord> = BTreeMap::new();
+        for set in sets {
+            if !set
+                .r#type()
+                .is_some_and(|t| self.record_types().contains(t))
+            {
+                continue;
+            }
+            let Some(host) = set.name().map(host_name) else {
+                continue;
+            };
+            let addrs: Vec<IpAddr> = set
+                .resource_records()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|v| v.value()?.parse().ok())
+                .collect();
+            // Skip records that never pointed at the destination, or that we don't own
+            if !addrs.iter().any(|addr| resolver.is_known(addr))
+                || owned.is_some_and(|owned| !owned.contains(&host))
+            {
+                continue;
+            }
+
+            let record = records.entry(host.clone()).or_insert_with(|| DnsRecord {
+                host,
+                id: None,
+                ttl: set.ttl().and_then(|t| u32::try_from(t).ok()),
+                content: String::new(),
+            });
+            let mut all = split_addresses(&record.content);
+            all.extend(addrs);
+            record.content = join_addresses(&all);
+        }
+        records.into_values().collect()
+    }
+}
+
+/// Builder of a [`Route53Provider`], validating its configuration.
+#[derive(Debug, Default)]
+pub struct Route53ProviderBuilder {
+    client: Option<Client>,
+    hosted_zone_id: Option<String>,
+    dest: Option<String>,
+    ttl: Option<i64>,
+    ownership: Option<Ownership>,
+    resolver: Option<Arc<DestinationResolver>>,
+    record_type: Option<RecordType>,
+    preserve_existing_ttl: bool,
+    max_throttle_attempts: Option<u32>,
+    sync_timeout: Option<Duration>,
+    alias: Option<AliasTarget>,
+}
+
+impl Route53ProviderBuilder {
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn zone_id(mut self, hosted_zone_id: impl Into<String>) -> Self {
+        self.hosted_zone_id = Some(hosted_zone_id.into());
+        self
+    }
+
+    pub fn destination(mut self, dest: impl Into<String>) -> Self {
+        self.dest = Some(dest.into());
+        self
+    }
+
+    pub fn ttl(mut self, ttl: i64) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn ownership(mut self, ownership: Option<Ownership>) -> Self {
+        self.ownership = ownership;
+        self
+    }
+
+    /// Resolver of the destination, to create A/AAAA records instead of CNAMEs.
+    pub fn resolver(mut self, resolver: Option<Arc<DestinationResolver>>) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Type of the created records. A & AAAA require a resolver, and CNAME forbids it.
+    pub fn record_type(mut self, record_type: RecordType) -> Self {
+        self.record_type = Some(record_type);
+        self
+    }
+
+    /// Keep the TTL of existing records instead of replacing it with the default TTL.
+    pub fn preserve_existing_ttl(mut self, preserve: bool) -> Self {
+        self.preserve_existing_ttl = preserve;
+        self
+    }
+
+    /// Maximum number of attempts of requests throttled by Route53, including the first one.
+    pub fn max_throttle_attempts(mut self, attempts: u32) -> Self {
+        self.max_throttle_attempts = Some(attempts);
+        self
+    }
+
+    /// Wait up to `timeout` for every change to be in sync.
+    pub fn wait_for_sync(mut self, timeout: Duration) -> Self {
+        self.sync_timeout = Some(timeout);
+        self
+    }
+
+    /// Create alias records to `alias` instead of CNAMEs. Forbids a resolver.
+    pub fn alias(mut self, alias: Option<AliasTarget>) -> Self {
+        self.alias = alias;
+        self
+    }
+
+    // Construction errors are small, but share the provider's error type
+    #[allow(clippy::result_large_err)]
+    pub fn build(self) -> Result<Route53Provider, Route53Error> {
+        let client = self.client.ok_or(Route53Error::MissingField("client"))?;
+        let hosted_zone_id = self
+            .hosted_zone_id
+            .filter(|id| !id.is_empty())
+            .ok_or(Route53Error::MissingField("zone_id"))?;
+        let dest = self
+            .dest
+            .filter(|dest| !dest.is_empty())
+            .ok_or(Route53Error::MissingField("destination"))?;
+
+        let ttl = self.ttl.unwrap_or(DEFAULT_TTL);
+        if !is_valid_ttl(ttl) {
+            return Err(Route53Error::InvalidTtl(ttl));
+        }
+        if self.alias.is_some() && self.resolver.is_some() {
+            return Err(Route53Error::ResolvedAlias);
+        }
+        if let Some(record_type) = self.record_type {
+            // Aliases are address records, without resolving the destination
+            let resolved = self.resolver.is_some() || self.alias.is_some();
+            if record_type.is_address() != resolved {
+                return Err(Route53Error::RecordTypeMismatch(record_type));
+            }
+        }
+
+        Ok(Route53Provider {
+            dest,
+            hosted_zone_id,
+            client,
+            ttl,
+            ownership: self.ownership,
+            resolver: self.resolver,
+            record_type: self.record_type,
+            preserve_existing_ttl: self.preserve_existing_ttl,
+            existing_ttls: Arc::default(),
+            throttle_retry: RetryPolicy {
+                max_attempts: self
+                    .max_throttle_attempts
+                    .unwrap_or(DEFAULT_THROTTLE_RETRY.max_attempts),
+                ..DEFAULT_THROTTLE_RETRY
+            },
+            sync_timeout: self.sync_timeout,
+            alias: self.alias,
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -72,81 +700,222 @@ impl Provider for Route53Provider {
     }
 
     #[tracing::instrument(skip(self), level = "info")]
-    async fn list_records(&self) -> Result<Vec<String>, Self::Error> {
-        Ok(self
-            .client
-            .list_resource_record_sets()
-            .hosted_zone_id(self.hosted_zone_id.clone())
-            .send()
-            .await?
-            .resource_record_sets
-            .unwrap_or_default()
-            .into_iter()
-            .filter(|r| {
-                // Filter out records that don't match the destination & aren't CNAMEs
-                let dest = r
-                    .resource_records()
-                    .unwrap_or_default()
-                    .iter()
-                    .find(|v| v.value() == Some(&self.dest));
-                r.r#type() == Some(&RrType::Cname) && dest.is_some()
-            })
-            .filter_map(|r| r.name)
-            .map(|mut s| {
-                // Remove last dot
-                if s.ends_with('.') {
-                    s.pop();
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        let sets = self.list_sets().await?;
+
+        if self.preserve_existing_ttl {
+            *self.existing_ttls.lock().unwrap() = sets
+                .iter()
+                .filter(|r| r.r#type().is_some_and(|t| self.record_types().contains(t)))
+                .filter_map(|r| Some((host_name(r.name()?), r.ttl()?)))
+                .collect();
+        }
+
+        // Hosts with a matching ownership marker
+        let owned: Option<HashSet<_>> = self.ownership.as_ref().map(|ownership| {
+            sets.iter()
+                .filter(|r| r.r#type() == Some(&RrType::Txt))
+                .filter(|r| {
+                    r.resource_records()
+                        .unwrap_or_default()
+                        .iter()
+                        .any(|v| v.value().is_some_and(|v| ownership.matches(v)))
+                })
+                .filter_map(|r| Ownership::marked_host(&host_name(r.name()?)))
+                .collect()
+        });
+
+        if self.alias.is_some() {
+            return Ok(self.alias_records(&sets, owned.as_ref()));
+        }
+        if let Some(resolver) = &self.resolver {
+            resolver.refresh(&self.dest).await?;
+            return Ok(self.address_records(resolver, &sets, owned.as_ref()));
+        }
+
+        Ok(sets
+            .iter()
+            .filter(|r| r.r#type() == Some(&RrType::Cname))
+            .filter_map(|r| {
+                let ttl = r.ttl().and_then(|t| u32::try_from(t).ok());
+                let host = host_name(r.name()?);
+                let content = r.resource_records().unwrap_or_default().first()?.value()?;
+                // Skip records we don't own. Without markers, only records pointing at the
+                // destination are known to be ours.
+                match &owned {
+                    Some(owned) if !owned.contains(&host) => return None,
+                    None if content != self.dest => return None,
+                    _ => {}
                 }
-                s
+                Some(DnsRecord {
+                    host,
+                    id: None,
+                    ttl,
+                    content: content.to_owned(),
+                })
             })
             .collect())
     }
 
-    #[tracing::instrument(skip(self), level = "debug")]
-    async fn create_record(&self, host: &str) -> Result<(), Self::Error> {
-        self.client
-            .change_resource_record_sets()
-            .hosted_zone_id(self.hosted_zone_id.clone())
-            .change_batch(self.change_batch(ChangeAction::Upsert, host, None))
-            .send()
-            .await?;
+    fn is_current(&self, record: &DnsRecord) -> bool {
+        if self.alias.is_some() {
+            return record.content == normalize_host(&self.dest);
+        }
+        match &self.resolver {
+            Some(resolver) => record.content == join_addresses(&self.addresses(resolver)),
+            None => record.content == self.dest,
+        }
+    }
+
+    // UPSERTs succeed whether or not the records exist
+    async fn ensure_record(
+        &self,
+        host: &str,
+        _existing: &[DnsRecord],
+        options: &RecordOptions,
+    ) -> Result<(), Self::Error> {
+        self.create_record(host, options).await
+    }
 
-        Ok(())
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        let ttl = options.ttl.map(i64::from);
+        self.send_changes(self.change_batch(ChangeAction::Upsert, host, ttl))
+            .await
     }
 
     #[tracing::instrument(skip(self), level = "info")]
-    async fn delete_record(&self, host: &str) -> Result<(), Self::Error> {
-        let record = self
-            .client
-            .list_resource_record_sets()
-            .hosted_zone_id(self.hosted_zone_id.clone())
-            .send()
-            .await?
-            .resource_record_sets
-            .unwrap_or_default()
-            .into_iter()
-            .find(|r| {
-                // Remove last dot & find matching record
-                let Some(name) = r.name() else { return false };
-                if name.ends_with('.') {
-                    let mut chars = name.chars();
-                    chars.next_back();
-                    chars.as_str() == host && r.r#type() == Some(&RrType::Cname)
-                } else {
-                    false
-                }
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        let changes = self.delete_changes(record).await?;
+        self.send_changes(ChangeBatch::builder().set_changes(Some(changes)).build())
+            .await
+    }
+
+    #[tracing::instrument(skip(self, options), level = "info")]
+    async fn apply_changes(
+        &self,
+        creates: &[String],
+        deletes: &[DnsRecord],
+        options: &ApplyOptions,
+    ) -> Result<(), Vec<FailedChange<Self::Error>>> {
+        let mut failures = Vec::new();
+
+        // Changes grouped by host, so a host's changes always land in the same batch
+        let mut changes = Vec::with_capacity(creates.len() + deletes.len());
+        changes.extend(creates.iter().map(|host| {
+            (
+                host.as_str(),
+                self.changes(
+                    ChangeAction::Upsert,
+                    host,
+                    options.record(host).ttl.map(i64::from),
+                ),
+            )
+        }));
+        for record in deletes {
+            match retry(&options.retry, || self.delete_changes(record)).await {
+                Ok(c) => changes.push((record.host.as_str(), c)),
+                Err(error) => failures.push(FailedChange {
+                    hosts: vec![record.host.clone()],
+                    error,
+                }),
+            }
+        }
+
+        // Batches are applied atomically, so they are safe to retry as a whole
+        for batch in batches(changes) {
+            options.ready().await;
+            let res = retry(&options.retry, || {
+                self.send_changes(
+                    ChangeBatch::builder()
+                        .set_changes(Some(batch.changes.clone()))
+                        .build(),
+                )
             })
-            .ok_or(Route53Error::MissingRecord)?;
+            .await;
 
-        self.client
-            .change_resource_record_sets()
-            .hosted_zone_id(self.hosted_zone_id.clone())
-            .change_batch(self.change_batch(ChangeAction::Delete, host, record.ttl()))
-            .send()
-            .await?;
+            if let Err(error) = res {
+                failures.push(FailedChange {
+                    hosts: batch.hosts,
+                    error,
+                });
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
+
+/// Changes sent in a single request, and the hosts they affect.
+struct Batch {
+    hosts: Vec<String>,
+    changes: Vec<Change>,
+}
 
-        Ok(())
+/// Packs per-host changes into batches of at most [`MAX_BATCH_CHANGES`] changes.
+fn batches<'a>(changes: impl IntoIterator<Item = (&'a str, Vec<Change>)>) -> Vec<Batch> {
+    let mut batches: Vec<Batch> = Vec::new();
+    for (host, host_changes) in changes {
+        match batches.last_mut() {
+            Some(batch) if batch.changes.len() + host_changes.len() <= MAX_BATCH_CHANGES => {
+                batch.hosts.push(host.to_string());
+                batch.changes.extend(host_changes);
+            }
+            _ => batches.push(Batch {
+                hosts: vec![host.to_string()],
+                changes: host_changes,
+            }),
+        }
     }
+    batches
+}
+
+/// Builds a record set of `host` holding `values`.
+fn value_set(
+    host: &str,
+    r#type: RrType,
+    values: impl IntoIterator<Item = String>,
+    ttl: i64,
+) -> ResourceRecordSet {
+    ResourceRecordSet::builder()
+        .name(record_name(host))
+        .r#type(r#type)
+        .set_resource_records(Some(
+            values
+                .into_iter()
+                .map(|v| ResourceRecord::builder().value(v).build())
+                .collect(),
+        ))
+        .ttl(ttl)
+        .build()
+}
+
+/// Route53's escape of `*`, which it returns in the names of wildcard records.
+const WILDCARD_ESCAPE: &str = "\\052";
+
+/// Converts a name returned by Route53 into a host: unescapes wildcards, and normalizes the
+/// fully qualified name.
+fn host_name(name: &str) -> String {
+    normalize_host(&name.replace(WILDCARD_ESCAPE, "*"))
+}
+
+/// Returns `true` if the Route53 `name` is the name of `host`. DNS names are case-insensitive.
+fn same_host(name: &str, host: &str) -> bool {
+    host_name(name) == normalize_host(host)
+}
+
+/// Converts a host into a Route53 name, escaping wildcards like Route53 does.
+fn record_name(host: &str) -> String {
+    host.replace('*', WILDCARD_ESCAPE)
+}
+
+/// Returns `true` if Route53 accepts `ttl`, between 0 and 2^31 - 1 seconds.
+pub fn is_valid_ttl(ttl: i64) -> bool {
+    (0..=MAX_TTL).contains(&ttl)
 }
 
 #[derive(Debug, Error)]
@@ -155,17 +924,95 @@ pub enum Route53Error {
     ChangeSetsError(#[from] SdkError<ChangeResourceRecordSetsError>),
     #[error(transparent)]
     ListSetsError(#[from] SdkError<ListResourceRecordSetsError>),
+    #[error(transparent)]
+    GetChangeError(#[from] SdkError<GetChangeError>),
+    #[error(transparent)]
+    ListZonesError(#[from] SdkError<ListHostedZonesByNameError>),
+    #[error("no hosted zone named {0}")]
+    ZoneNotFound(String),
+    #[error("{count} hosted zones named {name}")]
+    AmbiguousZone { name: String, count: usize },
+    #[error("change {id} not in sync after {timeout:?}")]
+    SyncTimeout { id: String, timeout: Duration },
     #[error("missing record")]
     MissingRecord,
+    #[error("failed to resolve destination: {0}")]
+    ResolveError(#[from] ResolveError),
+    #[error("missing {0}")]
+    MissingField(&'static str),
+    #[error("invalid TTL {0}")]
+    InvalidTtl(i64),
+    #[error("{0} records don't match whether the destination is resolved")]
+    RecordTypeMismatch(RecordType),
+    #[error("alias records can't point at a resolved destination")]
+    ResolvedAlias,
+}
+
+impl Route53Error {
+    /// Returns `true` if Route53 rejected the request because the zone is busy. These are
+    /// retried by the provider itself, with the throttle retry policy.
+    pub fn is_throttling(&self) -> bool {
+        let code = match self {
+            Route53Error::ChangeSetsError(e) => e.code(),
+            Route53Error::ListSetsError(e) => e.code(),
+            Route53Error::GetChangeError(e) => e.code(),
+            Route53Error::ListZonesError(e) => e.code(),
+            _ => None,
+        };
+        code.is_some_and(|code| THROTTLING_CODES.contains(&code))
+    }
+}
+
+impl Retryable for Route53Error {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Route53Error::ChangeSetsError(e) => is_retryable_sdk_error(e),
+            Route53Error::ListSetsError(e) => is_retryable_sdk_error(e),
+            Route53Error::ListZonesError(e) => is_retryable_sdk_error(e),
+            // The change was accepted, so it mustn't be sent again
+            Route53Error::GetChangeError(_) | Route53Error::SyncTimeout { .. } => false,
+            Route53Error::MissingRecord
+            | Route53Error::MissingField(_)
+            | Route53Error::InvalidTtl(_)
+            | Route53Error::RecordTypeMismatch(_)
+            | Route53Error::ResolvedAlias
+            | Route53Error::ZoneNotFound(_)
+            | Route53Error::AmbiguousZone { .. } => false,
+            Route53Error::ResolveError(_) => true,
+        }
+    }
+}
+
+fn is_retryable_sdk_error<E>(err: &SdkError<E>) -> bool {
+    match err {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) => true,
+        SdkError::ServiceError(e) => {
+            let status = e.raw().http().status();
+            status.as_u16() == 429 || status.is_server_error()
+        }
+        _ => false,
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use aws_credential_types::Credentials;
+    use std::{sync::Arc, time::Duration};
+
+    use aws_credential_types::{provider::SharedCredentialsProvider, Credentials};
+    use aws_sdk_route53::types::{ChangeAction, RrType};
     use aws_smithy_client::test_connection::TestConnection;
     use aws_smithy_http::body::SdkBody;
+    use httptest::{matchers::request, responders::status_code, Expectation, Server};
 
-    use crate::dns::{route53::Route53Provider, Provider};
+    use crate::dns::{
+        ownership::Ownership,
+        resolve::tests::resolver,
+        route53::{
+            batches, AliasTarget, AssumeRole, Route53Error, Route53Provider, MAX_BATCH_CHANGES,
+            SYNC_POLL_INTERVAL,
+        },
+        ApplyOptions, DnsRecord, Provider, RecordOptions, RecordType,
+    };
 
     /// Generates a mock client from a list of requests/responses.
     ///
@@ -175,14 +1022,24 @@ mod tests {
     ///
     /// returns: Client
     fn mock_client(events: Vec<(String, String)>) -> aws_sdk_route53::Client {
+        mock_client_status(
+            events
+                .into_iter()
+                .map(|(req, res)| (req, 200, res))
+                .collect(),
+        )
+    }
+
+    /// Like [`mock_client`], with the status of every response.
+    fn mock_client_status(events: Vec<(String, u16, String)>) -> aws_sdk_route53::Client {
         let creds = Credentials::from_keys("test", "test", None);
 
         let events = events
             .into_iter()
-            .map(|(req, res)| {
+            .map(|(req, status, res)| {
                 let req = http::Request::builder().body(SdkBody::from(req)).unwrap();
                 let res = http::Response::builder()
-                    .status(200)
+                    .status(status)
                     .body(SdkBody::from(res))
                     .unwrap();
                 (req, res)
@@ -201,6 +1058,15 @@ mod tests {
         aws_sdk_route53::Client::from_conf(cfg)
     }
 
+    fn record(host: &str, ttl: Option<u32>) -> DnsRecord {
+        DnsRecord {
+            host: host.to_string(),
+            id: None,
+            ttl,
+            content: "dest".to_string(),
+        }
+    }
+
     #[test]
     fn test_ttl() {
         let client = mock_client(vec![]);
@@ -213,6 +1079,36 @@ mod tests {
         assert_eq!(provider.ttl(), &600);
     }
 
+    #[test]
+    fn test_builder() {
+        let provider = Route53Provider::builder()
+            .client(mock_client(vec![]))
+            .zone_id("hosted_zone_id")
+            .destination("dest")
+            .ttl(60)
+            .build()
+            .unwrap();
+        assert_eq!(provider.hosted_zone_id, "hosted_zone_id");
+        assert_eq!(provider.destination(), "dest");
+        assert_eq!(provider.ttl(), &60);
+
+        let err = Route53Provider::builder()
+            .client(mock_client(vec![]))
+            .destination("dest")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Route53Error::MissingField("zone_id")));
+
+        let err = Route53Provider::builder()
+            .client(mock_client(vec![]))
+            .zone_id("hosted_zone_id")
+            .destination("dest")
+            .ttl(-1)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Route53Error::InvalidTtl(-1)));
+    }
+
     #[test]
     fn test_destination() {
         let client = mock_client(vec![]);
@@ -225,6 +1121,46 @@ mod tests {
         assert_eq!(provider.destination(), "newdest");
     }
 
+    #[tokio::test]
+    async fn test_wildcard() {
+        let client = mock_client(vec![(
+            r#"{"HostedZoneId": "hosted_zone_id", "MaxItems": "100"}"#.to_string(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+                <ListResourceRecordSetsResponse>
+                    <ResourceRecordSets>
+                        <ResourceRecordSet>
+                            <Name>\052.apps.example.com.</Name>
+                            <Type>CNAME</Type>
+                            <TTL>300</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>dest</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                    </ResourceRecordsSets>
+                </ListResourceRecordSetsResponse>
+                "#
+            .to_string(),
+        )]);
+        let provider =
+            Route53Provider::new(client, "hosted_zone_id".to_string(), "dest".to_string());
+
+        // Listed wildcards are unescaped
+        let records = provider.list_records().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].host, "*.apps.example.com");
+
+        // And escaped again when changed
+        let changes = provider.delete_changes(&records[0]).await.unwrap();
+        let set = changes[0].resource_record_set().unwrap();
+        assert_eq!(set.name(), Some("\\052.apps.example.com"));
+
+        let batch = provider.change_batch(ChangeAction::Upsert, "*.example.com", None);
+        let set = batch.changes().unwrap()[0].resource_record_set().unwrap();
+        assert_eq!(set.name(), Some("\\052.example.com"));
+    }
+
     #[tokio::test]
     async fn test_list_records() {
         let client = mock_client(vec![(
@@ -282,17 +1218,444 @@ mod tests {
 
         let records = provider.list_records().await.unwrap();
 
-        assert_eq!(records, vec!["test1.example.com", "test2.example.com",]);
+        let hosts: Vec<_> = records.iter().map(|r| r.host.as_str()).collect();
+        assert_eq!(hosts, vec!["test1.example.com", "test2.example.com"]);
+        assert_eq!(records[0].ttl, Some(300));
+        assert_eq!(records[0].content, "dest");
     }
 
     #[tokio::test]
-    async fn test_create_record() {
+    async fn test_list_records_ownership() {
         let client = mock_client(vec![(
-            r#"{
-                    "HostedZoneId": "hosted_zone_id",
-                    "ChangeBatch": {
-                        "Changes": [{
-                                "Action": "CREATE",
+            r#"{"HostedZoneId": "hosted_zone_id", "MaxItems": "100"}"#.to_string(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+                <ListResourceRecordSetsResponse>
+                    <ResourceRecordSets>
+                        <ResourceRecordSet>
+                            <Name>owned.example.com.</Name>
+                            <Type>CNAME</Type>
+                            <TTL>300</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>old.dest</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                        <ResourceRecordSet>
+                            <Name>_traefik-dns.owned.example.com.</Name>
+                            <Type>TXT</Type>
+                            <TTL>300</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>"heritage=traefik-dns-rs,owner=owner1"</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                        <ResourceRecordSet>
+                            <Name>other-owner.example.com.</Name>
+                            <Type>CNAME</Type>
+                            <TTL>300</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>dest</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                        <ResourceRecordSet>
+                            <Name>_traefik-dns.other-owner.example.com.</Name>
+                            <Type>TXT</Type>
+                            <TTL>300</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>"heritage=traefik-dns-rs,owner=owner2"</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                        <ResourceRecordSet>
+                            <Name>unmarked.example.com.</Name>
+                            <Type>CNAME</Type>
+                            <TTL>300</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>dest</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                    </ResourceRecordsSets>
+                </ListResourceRecordSetsResponse>
+                "#
+            .to_string(),
+        )]);
+        let mut provider =
+            Route53Provider::new(client, "hosted_zone_id".to_string(), "dest".to_string());
+        *provider.ownership_mut() = Some(Ownership::new("owner1".to_string()));
+
+        let records = provider.list_records().await.unwrap();
+
+        // Unmarked records are never reported, and so never deleted
+        let hosts: Vec<_> = records.iter().map(|r| r.host.as_str()).collect();
+        assert_eq!(hosts, vec!["owned.example.com"]);
+
+        // Owned records pointing elsewhere are reported as stale, to be updated
+        assert_eq!(records[0].content, "old.dest");
+        assert!(!provider.is_current(&records[0]));
+    }
+
+    #[test]
+    fn test_change_batch_ownership() {
+        let client = mock_client(vec![]);
+        let mut provider = Route53Provider::new(client, "".to_string(), "dest".to_string());
+        *provider.ownership_mut() = Some(Ownership::new("owner1".to_string()));
+
+        let batch = provider.change_batch(ChangeAction::Upsert, "test.example.com", None);
+        let changes = batch.changes().unwrap();
+        assert_eq!(changes.len(), 2);
+
+        let marker = changes[1].resource_record_set().unwrap();
+        assert_eq!(marker.name(), Some("_traefik-dns.test.example.com"));
+        assert_eq!(marker.r#type(), Some(&RrType::Txt));
+        assert_eq!(
+            marker.resource_records().unwrap()[0].value(),
+            Some("\"heritage=traefik-dns-rs,owner=owner1\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_records_resolved() {
+        let client = mock_client(vec![(
+            r#"{"HostedZoneId": "hosted_zone_id", "MaxItems": "100"}"#.to_string(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+                <ListResourceRecordSetsResponse>
+                    <ResourceRecordSets>
+                        <ResourceRecordSet>
+                            <Name>test.example.com.</Name>
+                            <Type>A</Type>
+                            <TTL>300</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>192.0.2.1</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                        <ResourceRecordSet>
+                            <Name>other.example.com.</Name>
+                            <Type>A</Type>
+                            <TTL>300</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>198.51.100.1</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                    </ResourceRecordsSets>
+                </ListResourceRecordSetsResponse>
+                "#
+            .to_string(),
+        )]);
+        let mut provider = Route53Provider::new(
+            client,
+            "hosted_zone_id".to_string(),
+            "192.0.2.1".to_string(),
+        );
+        *provider.resolver_mut() = Some(Arc::new(resolver()));
+
+        let records = provider.list_records().await.unwrap();
+
+        // Only records pointing at the resolved destination are listed
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].host, "test.example.com");
+        assert_eq!(records[0].content, "192.0.2.1");
+        assert!(provider.is_current(&records[0]));
+        assert!(!provider.is_current(&DnsRecord {
+            content: "192.0.2.1,2001:db8::1".to_string(),
+            ..records[0].clone()
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_change_batch_resolved() {
+        let client = mock_client(vec![]);
+        let resolver = resolver();
+        resolver.refresh("2001:db8::1").await.unwrap();
+
+        let mut provider = Route53Provider::new(client, "".to_string(), "dest".to_string());
+        *provider.resolver_mut() = Some(Arc::new(resolver));
+
+        let batch = provider.change_batch(ChangeAction::Upsert, "test.example.com", None);
+        let changes = batch.changes().unwrap();
+        assert_eq!(changes.len(), 1);
+
+        let set = changes[0].resource_record_set().unwrap();
+        assert_eq!(set.r#type(), Some(&RrType::Aaaa));
+        assert_eq!(
+            set.resource_records().unwrap()[0].value(),
+            Some("2001:db8::1")
+        );
+
+        // Deletes use the listed addresses
+        let changes = provider.changes_to(
+            ChangeAction::Delete,
+            "test.example.com",
+            Some(300),
+            "192.0.2.1,2001:db8::2",
+        );
+        let types: Vec<_> = changes
+            .iter()
+            .map(|c| c.resource_record_set().unwrap().r#type().unwrap().clone())
+            .collect();
+        assert_eq!(types, vec![RrType::A, RrType::Aaaa]);
+    }
+
+    #[tokio::test]
+    async fn test_record_type() {
+        let resolver = Arc::new(resolver());
+        resolver.refresh("2001:db8::1").await.unwrap();
+
+        let mut provider = Route53Provider::builder()
+            .client(mock_client(vec![]))
+            .zone_id("hosted_zone_id")
+            .destination("dest")
+            .resolver(Some(resolver.clone()))
+            .record_type(RecordType::Aaaa)
+            .build()
+            .unwrap();
+        let batch = provider.change_batch(ChangeAction::Upsert, "test.example.com", None);
+        let set = batch.changes().unwrap()[0].resource_record_set().unwrap();
+        assert_eq!(set.r#type(), Some(&RrType::Aaaa));
+
+        // IPv6 addresses never make it into A records
+        *provider.record_type_mut() = Some(RecordType::A);
+        let batch = provider.change_batch(ChangeAction::Upsert, "test.example.com", None);
+        assert!(batch.changes().unwrap().is_empty());
+
+        // Address records need a resolver, and CNAMEs can't have one
+        let builder = || {
+            Route53Provider::builder()
+                .client(mock_client(vec![]))
+                .zone_id("hosted_zone_id")
+                .destination("dest")
+        };
+        let err = builder().record_type(RecordType::A).build().unwrap_err();
+        assert!(matches!(
+            err,
+            Route53Error::RecordTypeMismatch(RecordType::A)
+        ));
+        let err = builder()
+            .resolver(Some(resolver))
+            .record_type(RecordType::Cname)
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Route53Error::RecordTypeMismatch(RecordType::Cname)
+        ));
+    }
+
+    fn hosted_zones(zones: &[(&str, &str, bool)]) -> String {
+        let zones: String = zones
+            .iter()
+            .map(|(id, name, private)| {
+                format!(
+                    "<HostedZone>
+                        <Id>/hostedzone/{}</Id>
+                        <Name>{}</Name>
+                        <CallerReference>ref</CallerReference>
+                        <Config><PrivateZone>{}</PrivateZone></Config>
+                    </HostedZone>",
+                    id, name, private
+                )
+            })
+            .collect();
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <ListHostedZonesByNameResponse>
+                <HostedZones>{}</HostedZones>
+                <IsTruncated>false</IsTruncated>
+                <MaxItems>100</MaxItems>
+            </ListHostedZonesByNameResponse>
+            "#,
+            zones
+        )
+    }
+
+    #[tokio::test]
+    async fn test_find_zone() {
+        let zones = hosted_zones(&[
+            ("PUBLIC", "example.com.", false),
+            ("PRIVATE", "example.com.", true),
+            ("OTHER", "example.net.", false),
+        ]);
+        let client = mock_client(vec![
+            (String::new(), zones.clone()),
+            (String::new(), zones.clone()),
+            (String::new(), zones),
+        ]);
+
+        let id = Route53Provider::find_zone(&client, "example.com", Some(true))
+            .await
+            .unwrap();
+        assert_eq!(id, "PRIVATE");
+        let id = Route53Provider::find_zone(&client, "Example.com.", Some(false))
+            .await
+            .unwrap();
+        assert_eq!(id, "PUBLIC");
+
+        let err = Route53Provider::find_zone(&client, "example.com", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Route53Error::AmbiguousZone { count: 2, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_find_zone_not_found() {
+        let client = mock_client(vec![(
+            String::new(),
+            hosted_zones(&[("PUBLIC", "example.com.", false)]),
+        )]);
+
+        let err = Route53Provider::find_zone(&client, "example.com", Some(true))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Route53Error::ZoneNotFound(name) if name == "example.com"));
+    }
+
+    fn alias() -> AliasTarget {
+        AliasTarget {
+            hosted_zone_id: "Z35SXDOTRQ7X7K".to_string(),
+            evaluate_target_health: true,
+        }
+    }
+
+    #[test]
+    fn test_alias_change_batch() {
+        let provider = Route53Provider::builder()
+            .client(mock_client(vec![]))
+            .zone_id("hosted_zone_id")
+            .destination("lb.us-east-1.elb.amazonaws.com")
+            .alias(Some(alias()))
+            .ownership(Some(Ownership::new("owner".to_string())))
+            .build()
+            .unwrap();
+
+        let batch = provider.change_batch(ChangeAction::Upsert, "example.com", Some(60));
+        let changes = batch.changes().unwrap();
+        assert_eq!(changes.len(), 2);
+
+        let set = changes[0].resource_record_set().unwrap();
+        assert_eq!(set.name(), Some("example.com"));
+        assert_eq!(set.r#type(), Some(&RrType::A));
+        assert_eq!(set.ttl(), None);
+        assert!(set.resource_records().is_none());
+        let target = set.alias_target().unwrap();
+        assert_eq!(target.hosted_zone_id(), Some("Z35SXDOTRQ7X7K"));
+        assert_eq!(target.dns_name(), Some("lb.us-east-1.elb.amazonaws.com"));
+        assert!(target.evaluate_target_health());
+
+        // Ownership markers aren't aliases, and keep their TTL
+        let marker = changes[1].resource_record_set().unwrap();
+        assert_eq!(marker.r#type(), Some(&RrType::Txt));
+        assert_eq!(marker.ttl(), Some(60));
+
+        // Aliases are address records, never resolved
+        let builder = || {
+            Route53Provider::builder()
+                .client(mock_client(vec![]))
+                .zone_id("hosted_zone_id")
+                .destination("dest")
+                .alias(Some(alias()))
+        };
+        let provider = builder().record_type(RecordType::Aaaa).build().unwrap();
+        let batch = provider.change_batch(ChangeAction::Upsert, "example.com", None);
+        let set = batch.changes().unwrap()[0].resource_record_set().unwrap();
+        assert_eq!(set.r#type(), Some(&RrType::Aaaa));
+
+        let err = builder()
+            .record_type(RecordType::Cname)
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Route53Error::RecordTypeMismatch(RecordType::Cname)
+        ));
+        let err = builder()
+            .resolver(Some(Arc::new(resolver())))
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Route53Error::ResolvedAlias));
+    }
+
+    #[tokio::test]
+    async fn test_alias_list_records() {
+        let client = mock_client(vec![(
+            r#"{"HostedZoneId": "hosted_zone_id", "MaxItems": "100"}"#.to_string(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+                <ListResourceRecordSetsResponse>
+                    <ResourceRecordSets>
+                        <ResourceRecordSet>
+                            <Name>example.com.</Name>
+                            <Type>A</Type>
+                            <AliasTarget>
+                                <HostedZoneId>Z35SXDOTRQ7X7K</HostedZoneId>
+                                <DNSName>LB.us-east-1.elb.amazonaws.com.</DNSName>
+                                <EvaluateTargetHealth>true</EvaluateTargetHealth>
+                            </AliasTarget>
+                        </ResourceRecordSet>
+                        <ResourceRecordSet>
+                            <Name>other.example.com.</Name>
+                            <Type>A</Type>
+                            <AliasTarget>
+                                <HostedZoneId>Z35SXDOTRQ7X7K</HostedZoneId>
+                                <DNSName>other.us-east-1.elb.amazonaws.com.</DNSName>
+                                <EvaluateTargetHealth>true</EvaluateTargetHealth>
+                            </AliasTarget>
+                        </ResourceRecordSet>
+                        <ResourceRecordSet>
+                            <Name>cname.example.com.</Name>
+                            <Type>CNAME</Type>
+                            <TTL>300</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>lb.us-east-1.elb.amazonaws.com</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                    </ResourceRecordSets>
+                </ListResourceRecordSetsResponse>
+                "#
+            .to_string(),
+        )]);
+        let provider = Route53Provider::builder()
+            .client(client)
+            .zone_id("hosted_zone_id")
+            .destination("lb.us-east-1.elb.amazonaws.com")
+            .alias(Some(alias()))
+            .build()
+            .unwrap();
+
+        let records = provider.list_records().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].host, "example.com");
+        assert_eq!(records[0].ttl, None);
+        assert!(provider.is_current(&records[0]));
+
+        // Deleting an alias doesn't need its TTL
+        let changes = provider.delete_changes(&records[0]).await.unwrap();
+        let set = changes[0].resource_record_set().unwrap();
+        assert_eq!(set.ttl(), None);
+        assert_eq!(
+            set.alias_target().unwrap().dns_name(),
+            Some("lb.us-east-1.elb.amazonaws.com")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_record() {
+        let client = mock_client(vec![(
+            r#"{
+                    "HostedZoneId": "hosted_zone_id",
+                    "ChangeBatch": {
+                        "Changes": [{
+                                "Action": "CREATE",
                                 "ResourceRecordSet": {
                                     "Name": "test.example.com.",
                                     "Type": "CNAME",
@@ -317,7 +1680,10 @@ mod tests {
         let provider =
             Route53Provider::new(client, "hosted_zone_id".to_string(), "dest".to_string());
 
-        provider.create_record("test.example.com").await.unwrap();
+        provider
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
@@ -374,7 +1740,359 @@ mod tests {
         let provider =
             Route53Provider::new(client, "hosted_zone_id".to_string(), "dest".to_string());
 
-        provider.delete_record("test.example.com").await.unwrap();
+        provider
+            .delete_record(&record("test.example.com", None))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_record_case_insensitive() {
+        let client = mock_client(vec![
+            (
+                r#"{"HostedZoneId": "hosted_zone_id", "MaxItems": "100"}"#.to_string(),
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+                <ListResourceRecordSetsResponse>
+                    <ResourceRecordSets>
+                        <ResourceRecordSet>
+                            <Name>Test.Example.com.</Name>
+                            <Type>CNAME</Type>
+                            <TTL>600</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>dest</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                    </ResourceRecordSets>
+                </ListResourceRecordSetsResponse>
+                "#
+                .to_string(),
+            ),
+            (
+                String::new(),
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+                <ChangeResourceRecordSetsResponse>
+                    <ChangeInfo>
+                        <Id>change_id</Id>
+                    </ChangeInfo>
+                </ChangeResourceRecordSetsResponse>
+                "#
+                .to_string(),
+            ),
+        ]);
+        let provider =
+            Route53Provider::new(client, "hosted_zone_id".to_string(), "dest".to_string());
+
+        provider
+            .delete_record(&record("test.example.com", None))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_record_known_ttl() {
+        // TTL is already known from listing, so no lookup should be made
+        let client = mock_client(vec![(
+            r#"{
+                    "HostedZoneId": "hosted_zone_id",
+                    "ChangeBatch": {
+                        "Changes": [{
+                                "Action": "DELETE",
+                                "ResourceRecordSet": {
+                                    "Name": "test.example.com.",
+                                    "Type": "CNAME",
+                                    "TTL": 600,
+                                    "ResourceRecords": [
+                                        {"Value": "dest"}
+                                    ]
+                                }
+                        }]
+                    }
+                }"#
+            .to_string(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+                <ChangeResourceRecordSetsResponse>
+                    <ChangeInfo>
+                        <Id>change_id</Id>
+                    </ChangeInfo>
+                </ChangeResourceRecordSetsResponse>
+                "#
+            .to_string(),
+        )]);
+        let provider =
+            Route53Provider::new(client, "hosted_zone_id".to_string(), "dest".to_string());
+
+        provider
+            .delete_record(&record("test.example.com", Some(600)))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_batches() {
+        let provider = Route53Provider::new(
+            mock_client(vec![]),
+            "hosted_zone_id".to_string(),
+            "dest".to_string(),
+        );
+        let changes = |host| provider.changes(ChangeAction::Upsert, host, None);
+
+        // A host's changes are never split across batches
+        let mut hosts = vec![changes("a.example.com"); MAX_BATCH_CHANGES - 1];
+        hosts.push([changes("b.example.com"), changes("b.example.com")].concat());
+        let batches = batches(hosts.iter().map(|c| ("host", c.clone())));
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].changes.len(), MAX_BATCH_CHANGES - 1);
+        assert_eq!(batches[0].hosts.len(), MAX_BATCH_CHANGES - 1);
+        assert_eq!(batches[1].changes.len(), 2);
+        assert_eq!(batches[1].hosts, vec!["host"]);
+    }
+
+    #[tokio::test]
+    async fn test_apply_changes() {
+        // Creates & deletes are sent in a single batch
+        let client = mock_client(vec![(
+            r#"{
+                    "HostedZoneId": "hosted_zone_id",
+                    "ChangeBatch": {
+                        "Changes": [{
+                                "Action": "UPSERT",
+                                "ResourceRecordSet": {
+                                    "Name": "new.example.com.",
+                                    "Type": "CNAME",
+                                    "TTL": 300,
+                                    "ResourceRecords": [
+                                        {"Value": "dest"}
+                                    ]
+                                }
+                        }, {
+                                "Action": "DELETE",
+                                "ResourceRecordSet": {
+                                    "Name": "old.example.com.",
+                                    "Type": "CNAME",
+                                    "TTL": 600,
+                                    "ResourceRecords": [
+                                        {"Value": "dest"}
+                                    ]
+                                }
+                        }]
+                    }
+                }"#
+            .to_string(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+                <ChangeResourceRecordSetsResponse>
+                    <ChangeInfo>
+                        <Id>change_id</Id>
+                    </ChangeInfo>
+                </ChangeResourceRecordSetsResponse>
+                "#
+            .to_string(),
+        )]);
+        let provider =
+            Route53Provider::new(client, "hosted_zone_id".to_string(), "dest".to_string());
+
+        provider
+            .apply_changes(
+                &["new.example.com".to_string()],
+                &[record("old.example.com", Some(600))],
+                &ApplyOptions::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_change_batch() {
+        let client = mock_client(vec![]);
+        let provider = Route53Provider::new(client, "".to_string(), "dest".to_string());
+
+        let batch = provider.change_batch(ChangeAction::Delete, "test.example.com", Some(600));
+        let changes = batch.changes().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].action(), Some(&ChangeAction::Delete));
+
+        let set = changes[0].resource_record_set().unwrap();
+        assert_eq!(set.name(), Some("test.example.com"));
+        assert_eq!(set.ttl(), Some(600));
+    }
+
+    #[tokio::test]
+    async fn test_preserve_existing_ttl() {
+        let list = || {
+            (
+                r#"{"HostedZoneId": "hosted_zone_id", "MaxItems": "100"}"#.to_string(),
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+                <ListResourceRecordSetsResponse>
+                    <ResourceRecordSets>
+                        <ResourceRecordSet>
+                            <Name>test.example.com.</Name>
+                            <Type>CNAME</Type>
+                            <TTL>3600</TTL>
+                            <ResourceRecords>
+                                <ResourceRecord>
+                                    <Value>old</Value>
+                                </ResourceRecord>
+                            </ResourceRecords>
+                        </ResourceRecordSet>
+                    </ResourceRecordSets>
+                </ListResourceRecordSetsResponse>
+                "#
+                .to_string(),
+            )
+        };
+        let ttl = |provider: &Route53Provider, host| {
+            let batch = provider.change_batch(ChangeAction::Upsert, host, None);
+            batch.changes().unwrap()[0]
+                .resource_record_set()
+                .unwrap()
+                .ttl()
+        };
+
+        let mut provider = Route53Provider::new(
+            mock_client(vec![list(), list()]),
+            "hosted_zone_id".to_string(),
+            "dest".to_string(),
+        );
+        provider.list_records().await.unwrap();
+        assert_eq!(ttl(&provider, "test.example.com"), Some(300));
+
+        *provider.preserve_existing_ttl_mut() = true;
+        provider.list_records().await.unwrap();
+        assert_eq!(ttl(&provider, "test.example.com"), Some(3600));
+        assert_eq!(ttl(&provider, "new.example.com"), Some(300));
+
+        // TTLs set by routes still apply
+        let batch = provider.change_batch(ChangeAction::Upsert, "test.example.com", Some(60));
+        let changes = batch.changes().unwrap();
+        assert_eq!(changes[0].resource_record_set().unwrap().ttl(), Some(60));
+    }
+
+    fn error_response(code: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <ErrorResponse>
+                <Error>
+                    <Type>Sender</Type>
+                    <Code>{}</Code>
+                    <Message>error</Message>
+                </Error>
+                <RequestId>request_id</RequestId>
+            </ErrorResponse>
+            "#,
+            code
+        )
+    }
+
+    #[tokio::test]
+    async fn test_create_record_throttled() {
+        let success = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <ChangeResourceRecordSetsResponse>
+                <ChangeInfo>
+                    <Id>change_id</Id>
+                </ChangeInfo>
+            </ChangeResourceRecordSetsResponse>
+            "#;
+        let client = mock_client_status(vec![
+            (
+                String::new(),
+                400,
+                error_response("PriorRequestNotComplete"),
+            ),
+            (String::new(), 400, error_response("Throttling")),
+            (String::new(), 200, success.to_string()),
+        ]);
+        let mut provider =
+            Route53Provider::new(client, "hosted_zone_id".to_string(), "dest".to_string());
+        provider.throttle_retry_mut().base_delay = Duration::from_millis(1);
+
+        provider
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+
+        // Other errors are returned right away
+        let client = mock_client_status(vec![
+            (String::new(), 400, error_response("InvalidChangeBatch")),
+            (String::new(), 200, success.to_string()),
+        ]);
+        let mut provider =
+            Route53Provider::new(client, "hosted_zone_id".to_string(), "dest".to_string());
+        provider.throttle_retry_mut().base_delay = Duration::from_millis(1);
+
+        let err = provider
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Route53Error::ChangeSetsError(_)));
+        assert!(!err.is_throttling());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_create_record_wait_for_sync() {
+        let change = |status| {
+            format!(
+                r#"<ChangeInfo>
+                    <Id>/change/change_id</Id>
+                    <Status>{}</Status>
+                    <SubmittedAt>2023-01-01T00:00:00Z</SubmittedAt>
+                </ChangeInfo>
+                "#,
+                status
+            )
+        };
+        let client = mock_client(vec![
+            (
+                String::new(),
+                format!(
+                    "<ChangeResourceRecordSetsResponse>{}</ChangeResourceRecordSetsResponse>",
+                    change("PENDING")
+                ),
+            ),
+            (
+                String::new(),
+                format!(
+                    "<GetChangeResponse>{}</GetChangeResponse>",
+                    change("PENDING")
+                ),
+            ),
+            (
+                String::new(),
+                format!(
+                    "<GetChangeResponse>{}</GetChangeResponse>",
+                    change("INSYNC")
+                ),
+            ),
+        ]);
+        let provider = Route53Provider::builder()
+            .client(client)
+            .zone_id("hosted_zone_id")
+            .destination("dest")
+            .wait_for_sync(Duration::from_secs(60))
+            .build()
+            .unwrap();
+
+        let start = tokio::time::Instant::now();
+        provider
+            .create_record("test.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+        // The change was polled twice
+        assert_eq!(start.elapsed(), SYNC_POLL_INTERVAL);
+    }
+
+    #[test]
+    fn test_builder_max_throttle_attempts() {
+        let provider = Route53Provider::builder()
+            .client(mock_client(vec![]))
+            .zone_id("hosted_zone_id")
+            .destination("dest")
+            .max_throttle_attempts(10)
+            .build()
+            .unwrap();
+
+        assert_eq!(provider.throttle_retry().max_attempts, 10);
     }
 
     #[tokio::test]
@@ -432,10 +2150,63 @@ mod tests {
             Route53Provider::new(client, "hosted_zone_id".to_string(), "dest".to_string());
 
         let err = provider
-            .delete_record("missing.example.com")
+            .delete_record(&record("missing.example.com", None))
             .await
             .unwrap_err();
 
         assert!(matches!(err, super::Route53Error::MissingRecord));
     }
+
+    #[tokio::test]
+    async fn test_new_client_endpoint_url() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/2013-04-01/hostedzonesbyname"))
+                .respond_with(status_code(200).body(hosted_zones(&[(
+                    "LOCAL",
+                    "example.com.",
+                    false,
+                )]))),
+        );
+
+        let config = aws_config::SdkConfig::builder()
+            .credentials_provider(SharedCredentialsProvider::new(Credentials::from_keys(
+                "test", "test", None,
+            )))
+            .region(aws_types::region::Region::new("us-east-1"))
+            .build();
+        let url = server.url_str("");
+        let client = Route53Provider::new_client(&config, Some(url.trim_end_matches('/')), None);
+
+        let id = Route53Provider::find_zone(&client, "example.com", None)
+            .await
+            .unwrap();
+        assert_eq!(id, "LOCAL");
+    }
+
+    #[tokio::test]
+    async fn test_assume_role_provider() {
+        let role = AssumeRole {
+            role_arn: "arn:aws:iam::123456789012:role/dns".to_string(),
+            external_id: Some("external".to_string()),
+            session_name: Some("traefik-dns".to_string()),
+        };
+
+        // Without source credentials, there is nothing to assume the role with
+        let config = aws_config::SdkConfig::builder()
+            .region(aws_types::region::Region::new("us-east-1"))
+            .build();
+        assert!(role.provider(&config).is_none());
+
+        let config = aws_config::SdkConfig::builder()
+            .credentials_provider(SharedCredentialsProvider::new(Credentials::from_keys(
+                "test", "test", None,
+            )))
+            .region(aws_types::region::Region::new("us-east-1"))
+            .build();
+        let provider = format!("{:?}", role.provider(&config).unwrap());
+        assert!(provider.contains("arn:aws:iam::123456789012:role/dns"));
+        assert!(provider.contains("external"));
+        assert!(provider.contains("traefik-dns"));
+    }
 }
@@ -0,0 +1,497 @@
+use std::{
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+use tokio::{process::Command, sync::Mutex};
+
+use super::{DnsRecord, Provider, RecordOptions};
+use crate::{host::normalize_host, retry::Retryable};
+
+const DEFAULT_TTL: u32 = 300;
+
+/// Comment starting the block of records managed in the zone file.
+const BEGIN_MARKER: &str = "; BEGIN traefik-dns-rs";
+/// Comment ending the block of records managed in the zone file.
+const END_MARKER: &str = "; END traefik-dns-rs";
+
+/// Maintains the CNAME records of a BIND zone file, between `; BEGIN traefik-dns-rs` and
+/// `; END traefik-dns-rs` comments.
+///
+/// Every line of the managed block belongs to this provider, and the rest of the file is left
+/// as-is, apart from the serial of the SOA record, which is incremented on every change.
+#[derive(Debug)]
+pub struct ZonefileProvider {
+    dest: String,
+    path: PathBuf,
+
+    ttl: u32,
+    /// Command run after every change, like `rndc reload example.com`.
+    reload_command: Option<Vec<String>>,
+
+    /// Serializes the rewrites of concurrent changes.
+    lock: Mutex<()>,
+}
+
+impl ZonefileProvider {
+    pub fn new(path: PathBuf, dest: String) -> Self {
+        Self {
+            dest,
+            path,
+            ttl: DEFAULT_TTL,
+            reload_command: None,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn ttl(&self) -> &u32 {
+        &self.ttl
+    }
+    pub fn ttl_mut(&mut self) -> &mut u32 {
+        &mut self.ttl
+    }
+
+    pub fn reload_command(&self) -> &Option<Vec<String>> {
+        &self.reload_command
+    }
+    pub fn reload_command_mut(&mut self) -> &mut Option<Vec<String>> {
+        &mut self.reload_command
+    }
+
+    fn read(&self) -> Result<String, ZonefileError> {
+        std::fs::read_to_string(&self.path).map_err(|source| ZonefileError::Io {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    /// Replaces the managed block with the records returned by `update`, then reloads the zone.
+    async fn update(
+        &self,
+        update: impl FnOnce(&mut Vec<ZoneRecord>) -> Result<(), ZonefileError>,
+    ) -> Result<(), ZonefileError> {
+        let _guard = self.lock.lock().await;
+
+        let contents = self.read()?;
+        let mut records = managed_records(&contents);
+        update(&mut records)?;
+        let contents = increment_serial(&replace_managed(&contents, &records))?;
+        self.write(&contents)?;
+
+        self.reload().await
+    }
+
+    /// Replaces the zone file atomically, keeping its permissions.
+    fn write(&self, contents: &str) -> Result<(), ZonefileError> {
+        let io_err = |source| ZonefileError::Io {
+            path: self.path.clone(),
+            source,
+        };
+
+        let permissions = std::fs::metadata(&self.path).map_err(io_err)?.permissions();
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, contents).map_err(io_err)?;
+        std::fs::set_permissions(&tmp, permissions).map_err(io_err)?;
+        std::fs::rename(&tmp, &self.path).map_err(io_err)
+    }
+
+    /// Runs the reload command, if any.
+    async fn reload(&self) -> Result<(), ZonefileError> {
+        let Some((program, args)) = self.reload_command.as_deref().and_then(<[_]>::split_first)
+        else {
+            return Ok(());
+        };
+
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .await
+            .map_err(ZonefileError::ReloadIo)?;
+        if !output.status.success() {
+            return Err(ZonefileError::ReloadFailed {
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for ZonefileProvider {
+    type Error = ZonefileError;
+
+    fn destination(&self) -> &str {
+        &self.dest
+    }
+    fn destination_mut(&mut self) -> &mut String {
+        &mut self.dest
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+        Ok(managed_records(&self.read()?)
+            .into_iter()
+            .map(|r| DnsRecord {
+                host: r.host,
+                id: None,
+                ttl: Some(r.ttl),
+                content: r.target,
+            })
+            .collect())
+    }
+
+    fn is_current(&self, record: &DnsRecord) -> bool {
+        record.content == normalize_host(&self.dest)
+    }
+
+    // Existing records of the host are replaced
+    async fn ensure_record(
+        &self,
+        host: &str,
+        _existing: &[DnsRecord],
+        options: &RecordOptions,
+    ) -> Result<(), Self::Error> {
+        self.create_record(host, options).await
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn create_record(&self, host: &str, options: &RecordOptions) -> Result<(), Self::Error> {
+        let record = ZoneRecord {
+            host: normalize_host(host),
+            ttl: options.ttl.unwrap_or(self.ttl),
+            target: normalize_host(&self.dest),
+        };
+        self.update(|records| {
+            // Stale records of the host are replaced
+            records.retain(|r| r.host != record.host);
+            records.push(record);
+            Ok(())
+        })
+        .await
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn delete_record(&self, record: &DnsRecord) -> Result<(), Self::Error> {
+        let host = normalize_host(&record.host);
+        self.update(|records| {
+            let len = records.len();
+            records.retain(|r| r.host != host);
+            if records.len() == len {
+                return Err(ZonefileError::RecordNotFound);
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ZonefileError {
+    #[error("zone file {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("zone file has no SOA record with a valid serial")]
+    MissingSoa,
+    #[error("record not found")]
+    RecordNotFound,
+    #[error("failed to run the reload command: {0}")]
+    ReloadIo(io::Error),
+    #[error("reload command failed with {status}: {stderr}")]
+    ReloadFailed {
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
+}
+
+impl Retryable for ZonefileError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            // The name server may be restarting
+            ZonefileError::ReloadFailed { .. } => true,
+            ZonefileError::Io { .. }
+            | ZonefileError::MissingSoa
+            | ZonefileError::RecordNotFound
+            | ZonefileError::ReloadIo(_) => false,
+        }
+    }
+}
+
+/// A CNAME record of the managed block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ZoneRecord {
+    host: String,
+    ttl: u32,
+    target: String,
+}
+
+impl ZoneRecord {
+    /// Parses a `host. ttl IN CNAME target.` line.
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.split(';').next().unwrap_or_default();
+        let [host, ttl, class, ty, target] = line
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .try_into()
+            .ok()?;
+        if !class.eq_ignore_ascii_case("IN") || !ty.eq_ignore_ascii_case("CNAME") {
+            return None;
+        }
+        Some(Self {
+            host: normalize_host(host),
+            ttl: ttl.parse().ok()?,
+            target: normalize_host(target),
+        })
+    }
+
+    /// Formats the record as a line of the zone file, with fully qualified names.
+    fn line(&self) -> String {
+        format!("{}. {} IN CNAME {}.", self.host, self.ttl, self.target)
+    }
+}
+
+/// Returns the line range of the managed block, excluding its markers.
+fn managed_block(lines: &[&str]) -> Option<Range<usize>> {
+    let begin = lines.iter().position(|l| l.trim() == BEGIN_MARKER)?;
+    let end = lines[begin..].iter().position(|l| l.trim() == END_MARKER)? + begin;
+    Some(begin + 1..end)
+}
+
+/// Parses the records of the managed block of `contents`.
+fn managed_records(contents: &str) -> Vec<ZoneRecord> {
+    let lines: Vec<_> = contents.lines().collect();
+    let Some(block) = managed_block(&lines) else {
+        return Vec::new();
+    };
+    lines[block]
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| {
+            let record = ZoneRecord::parse(l);
+            if record.is_none() {
+                tracing::warn!("Ignoring invalid line in managed zone file block: {}", l);
+            }
+            record
+        })
+        .collect()
+}
+
+/// Replaces the managed block of `contents` with `records`, appending it if there is none.
+fn replace_managed(contents: &str, records: &[ZoneRecord]) -> String {
+    let lines: Vec<_> = contents.lines().collect();
+    let managed = records.iter().map(ZoneRecord::line);
+
+    let mut out: Vec<String> = match managed_block(&lines) {
+        Some(block) => lines[..block.start]
+            .iter()
+            .map(|l| l.to_string())
+            .chain(managed)
+            .chain(lines[block.end..].iter().map(|l| l.to_string()))
+            .collect(),
+        None => lines
+            .iter()
+            .map(|l| l.to_string())
+            .chain([String::new(), BEGIN_MARKER.to_string()])
+            .chain(managed)
+            .chain([END_MARKER.to_string()])
+            .collect(),
+    };
+    out.push(String::new());
+    out.join("\n")
+}
+
+/// Increments the serial of the SOA record of `contents`, wrapping around like RFC 1982.
+fn increment_serial(contents: &str) -> Result<String, ZonefileError> {
+    // The serial is the third field of the SOA record, which may span lines in parentheses
+    let mut tokens =
+        tokens(contents).skip_while(|t| !contents[t.clone()].eq_ignore_ascii_case("SOA"));
+    let span = tokens.nth(3).ok_or(ZonefileError::MissingSoa)?;
+    let serial: u32 = contents[span.clone()]
+        .parse()
+        .map_err(|_| ZonefileError::MissingSoa)?;
+
+    let mut out = String::with_capacity(contents.len());
+    out.push_str(&contents[..span.start]);
+    out.push_str(&serial.wrapping_add(1).to_string());
+    out.push_str(&contents[span.end..]);
+    Ok(out)
+}
+
+/// Returns the byte ranges of the tokens of `contents`, skipping comments & parentheses.
+fn tokens(contents: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut chars = contents.char_indices().peekable();
+    std::iter::from_fn(move || loop {
+        let (start, c) = chars.next()?;
+        match c {
+            ';' => while chars.next_if(|&(_, c)| c != '\n').is_some() {},
+            c if c.is_whitespace() || c == '(' || c == ')' => {}
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) =
+                    chars.next_if(|&(_, c)| !c.is_whitespace() && !matches!(c, '(' | ')' | ';'))
+                {
+                    end = i + c.len_utf8();
+                }
+                return Some(start..end);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZONE: &str = "\
+$ORIGIN example.com.
+$TTL 3600
+@   IN SOA ns1.example.com. admin.example.com. (
+        2024010101 ; serial
+        7200       ; refresh
+        3600       ; retry
+        1209600    ; expire
+        3600 )     ; minimum
+@   IN NS  ns1.example.com.
+ns1 IN A   192.0.2.53
+
+; BEGIN traefik-dns-rs
+test1.example.com. 300 IN CNAME dest.
+test2.example.com. 600 IN CNAME old.dest.
+; END traefik-dns-rs
+
+www IN A   192.0.2.80
+";
+
+    fn record(host: &str, ttl: u32, target: &str) -> ZoneRecord {
+        ZoneRecord {
+            host: host.to_string(),
+            ttl,
+            target: target.to_string(),
+        }
+    }
+
+    fn zone_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("traefik-dns-test-zone-{}.db", name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_managed_records() {
+        assert_eq!(
+            managed_records(ZONE),
+            vec![
+                record("test1.example.com", 300, "dest"),
+                record("test2.example.com", 600, "old.dest"),
+            ]
+        );
+        assert_eq!(managed_records("@ IN NS ns1.example.com.\n"), vec![]);
+    }
+
+    #[test]
+    fn test_replace_managed() {
+        let records = [record("test3.example.com", 300, "dest")];
+        let replaced = replace_managed(ZONE, &records);
+        assert!(replaced.contains(
+            "; BEGIN traefik-dns-rs\ntest3.example.com. 300 IN CNAME dest.\n; END traefik-dns-rs\n"
+        ));
+        assert!(!replaced.contains("test1"));
+        // The rest of the file is untouched
+        assert!(replaced.starts_with("$ORIGIN example.com.\n"));
+        assert!(replaced.ends_with("\nwww IN A   192.0.2.80\n"));
+        assert_eq!(managed_records(&replaced), records);
+    }
+
+    #[test]
+    fn test_replace_managed_appends_block() {
+        let contents = "@ IN NS ns1.example.com.";
+        let records = [record("test.example.com", 300, "dest")];
+        assert_eq!(
+            replace_managed(contents, &records),
+            "@ IN NS ns1.example.com.\n\n\
+             ; BEGIN traefik-dns-rs\n\
+             test.example.com. 300 IN CNAME dest.\n\
+             ; END traefik-dns-rs\n"
+        );
+    }
+
+    #[test]
+    fn test_increment_serial() {
+        let incremented = increment_serial(ZONE).unwrap();
+        assert_eq!(incremented, ZONE.replace("2024010101", "2024010102"));
+
+        let single_line = "@ 3600 IN SOA ns1 admin 4294967295 7200 3600 1209600 3600\n";
+        assert_eq!(
+            increment_serial(single_line).unwrap(),
+            "@ 3600 IN SOA ns1 admin 0 7200 3600 1209600 3600\n"
+        );
+
+        assert!(matches!(
+            increment_serial("@ IN NS ns1.example.com.\n"),
+            Err(ZonefileError::MissingSoa)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_list_records() {
+        let path = zone_file("list", ZONE);
+        let provider = ZonefileProvider::new(path.clone(), "dest".to_string());
+
+        let records = provider.list_records().await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(provider.is_current(&records[0]));
+        assert!(!provider.is_current(&records[1]));
+    }
+
+    #[tokio::test]
+    async fn test_create_delete_record() {
+        let path = zone_file("create", ZONE);
+        let mut provider = ZonefileProvider::new(path.clone(), "dest".to_string());
+        *provider.reload_command_mut() = Some(vec!["true".to_string()]);
+
+        // Stale records are replaced
+        provider
+            .create_record("test2.example.com", &RecordOptions::default())
+            .await
+            .unwrap();
+        provider
+            .delete_record(&DnsRecord {
+                host: "test1.example.com".to_string(),
+                id: None,
+                ttl: Some(300),
+                content: "dest".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            managed_records(&contents),
+            vec![record("test2.example.com", 300, "dest")]
+        );
+        assert!(contents.contains("2024010103 ; serial"));
+    }
+
+    #[tokio::test]
+    async fn test_reload_failed() {
+        let path = zone_file("reload", ZONE);
+        let mut provider = ZonefileProvider::new(path.clone(), "dest".to_string());
+        *provider.reload_command_mut() = Some(vec!["false".to_string()]);
+
+        let err = provider
+            .create_record("test3.example.com", &RecordOptions::default())
+            .await
+            .unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(err, ZonefileError::ReloadFailed { .. }));
+        assert!(err.is_retryable());
+    }
+}
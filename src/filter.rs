@@ -0,0 +1,88 @@
+use regex::Regex;
+
+/// Restricts the hosts managed by the updater.
+///
+/// Hosts that don't pass the filter are out of scope: they are neither created nor deleted.
+#[derive(Debug, Clone, Default)]
+pub struct HostFilter {
+    /// Only manage hosts under these suffixes, or every host if empty.
+    pub include_suffixes: Vec<String>,
+    /// Never manage these hosts.
+    pub exclude_hosts: Vec<String>,
+    /// Never manage hosts matching this regex.
+    pub exclude_regex: Option<Regex>,
+}
+
+impl HostFilter {
+    /// Returns `true` if `host` should be managed.
+    pub fn matches(&self, host: &str) -> bool {
+        let included = self.include_suffixes.is_empty()
+            || self
+                .include_suffixes
+                .iter()
+                .any(|suffix| has_suffix(host, suffix));
+
+        included
+            && !self.exclude_hosts.iter().any(|h| h == host)
+            && !self
+                .exclude_regex
+                .as_ref()
+                .is_some_and(|re| re.is_match(host))
+    }
+}
+
+/// Returns `true` if `host` is `suffix` or one of its subdomains.
+fn has_suffix(host: &str, suffix: &str) -> bool {
+    let suffix = suffix.trim_matches('.');
+    host == suffix
+        || host
+            .strip_suffix(suffix)
+            .is_some_and(|rest| rest.ends_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default() {
+        assert!(HostFilter::default().matches("example.com"));
+    }
+
+    #[test]
+    fn test_include_suffixes() {
+        let filter = HostFilter {
+            include_suffixes: vec!["example.com".to_string(), ".example.org".to_string()],
+            ..Default::default()
+        };
+
+        assert!(filter.matches("example.com"));
+        assert!(filter.matches("test.example.com"));
+        assert!(filter.matches("test.example.org"));
+        assert!(!filter.matches("testexample.com"));
+        assert!(!filter.matches("test.example.net"));
+    }
+
+    #[test]
+    fn test_exclude_hosts() {
+        let filter = HostFilter {
+            exclude_hosts: vec!["test.example.com".to_string()],
+            ..Default::default()
+        };
+
+        assert!(!filter.matches("test.example.com"));
+        assert!(filter.matches("sub.test.example.com"));
+    }
+
+    #[test]
+    fn test_exclude_regex() {
+        let filter = HostFilter {
+            include_suffixes: vec!["example.com".to_string()],
+            exclude_regex: Some(Regex::new("^internal-").unwrap()),
+            ..Default::default()
+        };
+
+        assert!(!filter.matches("internal-api.example.com"));
+        assert!(filter.matches("api.example.com"));
+    }
+}
@@ -0,0 +1,424 @@
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    fmt::Write,
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+
+use crate::{
+    dns::rate_limit::{RateLimit, WAIT_BUCKETS},
+    version::BuildInfo,
+};
+
+const LAST_SUCCESS_GAUGE: &str = "traefik_dns_last_success_timestamp_seconds";
+const RATE_LIMIT_WAITS_COUNTER: &str = "traefik_dns_rate_limit_waits_total";
+const RATE_LIMIT_WAIT_HISTOGRAM: &str = "traefik_dns_rate_limit_wait_seconds";
+const RATE_LIMIT_TOKENS_GAUGE: &str = "traefik_dns_rate_limit_tokens";
+const ROUTES_SKIPPED_COUNTER: &str = "traefik_dns_routes_skipped_total";
+
+/// Tracks reconcile success for the liveness & readiness probes.
+#[derive(Debug, Default)]
+pub struct Health {
+    ready: AtomicBool,
+    /// Set while the circuit breaker of the providers is open.
+    circuit_open: AtomicBool,
+    last_success: Mutex<Option<Instant>>,
+    /// Wall clock time of the last success, in seconds since the Unix epoch, or 0.
+    last_success_timestamp: AtomicU64,
+
+    /// Readiness fails if the last success is older than this.
+    max_staleness: Option<Duration>,
+
+    /// Rate limits whose waits are exported, by name.
+    rate_limits: Mutex<Vec<(String, Arc<RateLimit>)>>,
+    /// Number of routes producing no record, by reason.
+    skipped_routes: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Health {
+    pub fn new(max_staleness: Option<Duration>) -> Self {
+        Self {
+            max_staleness,
+            ..Default::default()
+        }
+    }
+
+    /// Records a successful reconcile.
+    pub fn record_success(&self) {
+        *self.last_success.lock().unwrap() = Some(Instant::now());
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.last_success_timestamp
+            .store(timestamp, Ordering::Release);
+        self.ready.store(true, Ordering::Release);
+    }
+
+    /// Records whether the circuit breaker of the providers is open, failing readiness while it is.
+    pub fn set_circuit_open(&self, open: bool) {
+        self.circuit_open.store(open, Ordering::Release);
+    }
+
+    /// Returns the time of the last successful reconcile.
+    pub fn last_success(&self) -> Option<Instant> {
+        *self.last_success.lock().unwrap()
+    }
+
+    /// Returns the wall clock time of the last successful reconcile, in seconds since the Unix
+    /// epoch.
+    pub fn last_success_timestamp(&self) -> Option<u64> {
+        match self.last_success_timestamp.load(Ordering::Acquire) {
+            0 => None,
+            timestamp => Some(timestamp),
+        }
+    }
+
+    /// Exports the waits & tokens of `limit`, labelled `limiter="<name>"`.
+    pub fn register_rate_limit(&self, name: impl Into<String>, limit: Arc<RateLimit>) {
+        self.rate_limits.lock().unwrap().push((name.into(), limit));
+    }
+
+    /// Counts `count` routes producing no record because of `reason`.
+    pub fn record_skipped_routes(&self, reason: &'static str, count: u64) {
+        *self
+            .skipped_routes
+            .lock()
+            .unwrap()
+            .entry(reason)
+            .or_default() += count;
+    }
+
+    /// Returns the number of routes skipped because of `reason`.
+    pub fn skipped_routes(&self, reason: &str) -> u64 {
+        self.skipped_routes
+            .lock()
+            .unwrap()
+            .get(reason)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn metrics(&self) -> String {
+        let mut metrics = format!(
+            "# HELP {name} Unix time of the last successful reconcile.\n\
+             # TYPE {name} gauge\n\
+             {name} {}\n",
+            self.last_success_timestamp().unwrap_or(0),
+            name = LAST_SUCCESS_GAUGE,
+        );
+
+        let skipped_routes = self.skipped_routes.lock().unwrap();
+        if !skipped_routes.is_empty() {
+            // Writing to a String never fails
+            let _ = write!(
+                metrics,
+                "# HELP {name} Number of routes producing no record.\n\
+                 # TYPE {name} counter\n",
+                name = ROUTES_SKIPPED_COUNTER,
+            );
+            for (reason, count) in skipped_routes.iter() {
+                let _ = writeln!(
+                    metrics,
+                    "{}{{reason=\"{}\"}} {}",
+                    ROUTES_SKIPPED_COUNTER, reason, count
+                );
+            }
+        }
+        drop(skipped_routes);
+
+        let rate_limits = self.rate_limits.lock().unwrap();
+        if rate_limits.is_empty() {
+            return metrics;
+        }
+        let waits: Vec<_> = rate_limits
+            .iter()
+            .map(|(name, limit)| (name, limit.waits()))
+            .collect();
+
+        let _ = write!(
+            metrics,
+            "# HELP {name} Number of requests that waited for a rate limit token.\n\
+             # TYPE {name} counter\n",
+            name = RATE_LIMIT_WAITS_COUNTER,
+        );
+        for (limiter, waits) in &waits {
+            let _ = writeln!(
+                metrics,
+                "{}{{limiter=\"{}\"}} {}",
+                RATE_LIMIT_WAITS_COUNTER, limiter, waits.count
+            );
+        }
+
+        let _ = write!(
+            metrics,
+            "# HELP {name} Time requests waited for a rate limit token.\n\
+             # TYPE {name} histogram\n",
+            name = RATE_LIMIT_WAIT_HISTOGRAM,
+        );
+        for (limiter, waits) in &waits {
+            for (bound, count) in WAIT_BUCKETS.iter().zip(waits.buckets) {
+                let _ = writeln!(
+                    metrics,
+                    "{}_bucket{{limiter=\"{}\",le=\"{}\"}} {}",
+                    RATE_LIMIT_WAIT_HISTOGRAM, limiter, bound, count
+                );
+            }
+            let _ = write!(
+                metrics,
+                "{name}_bucket{{limiter=\"{limiter}\",le=\"+Inf\"}} {count}\n\
+                 {name}_sum{{limiter=\"{limiter}\"}} {sum}\n\
+                 {name}_count{{limiter=\"{limiter}\"}} {count}\n",
+                name = RATE_LIMIT_WAIT_HISTOGRAM,
+                count = waits.count,
+                sum = waits.sum,
+            );
+        }
+
+        let _ = write!(
+            metrics,
+            "# HELP {name} Rate limit tokens currently available.\n\
+             # TYPE {name} gauge\n",
+            name = RATE_LIMIT_TOKENS_GAUGE,
+        );
+        for (limiter, limit) in rate_limits.iter() {
+            let _ = writeln!(
+                metrics,
+                "{}{{limiter=\"{}\"}} {}",
+                RATE_LIMIT_TOKENS_GAUGE,
+                limiter,
+                limit.tokens()
+            );
+        }
+        metrics
+    }
+
+    /// Returns `true` once a reconcile has succeeded, the last success isn't stale, and the
+    /// circuit breaker isn't open.
+    pub fn is_ready(&self) -> bool {
+        if !self.ready.load(Ordering::Acquire) || self.circuit_open.load(Ordering::Acquire) {
+            return false;
+        }
+        match (self.max_staleness, self.last_success()) {
+            (Some(max), Some(last)) => last.elapsed() <= max,
+            _ => true,
+        }
+    }
+}
+
+/// Serves `/healthz`, `/readyz`, `/metrics` & `/version` on `listener` until an error occurs.
+pub async fn serve(listener: TcpListener, health: Arc<Health>) -> Result<(), hyper::Error> {
+    let make_svc = make_service_fn(move |_| {
+        let health = health.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let health = health.clone();
+                async move { Ok::<_, Infallible>(handle(&req, &health)) }
+            }))
+        }
+    });
+
+    Server::from_tcp(listener)?.serve(make_svc).await
+}
+
+fn handle(req: &Request<Body>, health: &Health) -> Response<Body> {
+    if req.uri().path() == "/metrics" {
+        return Response::new(Body::from(health.metrics()));
+    }
+    if req.uri().path() == "/version" {
+        let body = serde_json::to_vec(&BuildInfo::current()).unwrap_or_default();
+        let mut res = Response::new(Body::from(body));
+        res.headers_mut().insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        return res;
+    }
+
+    let status = match req.uri().path() {
+        "/healthz" => StatusCode::OK,
+        "/readyz" if health.is_ready() => StatusCode::OK,
+        "/readyz" => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::NOT_FOUND,
+    };
+
+    let mut res = Response::new(Body::from(status.canonical_reason().unwrap_or_default()));
+    *res.status_mut() = status;
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dns::MockProvider, router::MockRouter, updater::Updater};
+
+    async fn get(addr: std::net::SocketAddr, path: &str) -> u16 {
+        reqwest::get(format!("http://{}{}", addr, path))
+            .await
+            .unwrap()
+            .status()
+            .as_u16()
+    }
+
+    #[test]
+    fn test_staleness() {
+        let health = Health::new(Some(Duration::ZERO));
+        assert!(!health.is_ready());
+
+        health.record_success();
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(!health.is_ready());
+
+        let health = Health::new(None);
+        health.record_success();
+        assert!(health.is_ready());
+    }
+
+    #[test]
+    fn test_last_success_timestamp() {
+        let health = Health::default();
+        assert_eq!(health.last_success_timestamp(), None);
+        assert!(health
+            .metrics()
+            .contains("\ntraefik_dns_last_success_timestamp_seconds 0\n"));
+
+        let before = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        health.record_success();
+        let timestamp = health.last_success_timestamp().unwrap();
+        assert!(timestamp >= before);
+        assert!(health.metrics().contains(&format!(
+            "\ntraefik_dns_last_success_timestamp_seconds {}\n",
+            timestamp
+        )));
+    }
+
+    #[test]
+    fn test_rate_limit_metrics() {
+        let health = Health::default();
+        assert!(!health.metrics().contains("rate_limit"));
+
+        let limit = Arc::new(RateLimit::new(2, Duration::from_secs(3600)));
+        health.register_rate_limit("changes", limit.clone());
+        limit.try_ready();
+
+        let metrics = health.metrics();
+        assert!(metrics.contains("\ntraefik_dns_rate_limit_waits_total{limiter=\"changes\"} 0\n"));
+        assert!(metrics.contains(
+            "\ntraefik_dns_rate_limit_wait_seconds_bucket{limiter=\"changes\",le=\"+Inf\"} 0\n"
+        ));
+        assert!(metrics
+            .contains("\ntraefik_dns_rate_limit_wait_seconds_count{limiter=\"changes\"} 0\n"));
+        assert!(metrics.contains("\ntraefik_dns_rate_limit_tokens{limiter=\"changes\"} 1"));
+        assert_eq!(
+            metrics
+                .matches("# TYPE traefik_dns_rate_limit_wait_seconds histogram")
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_endpoints_stale() {
+        let health = Arc::new(Health::new(Some(Duration::from_millis(50))));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, health.clone()));
+
+        health.record_success();
+        assert_eq!(get(addr, "/readyz").await, 200);
+
+        // Reconciles stopped succeeding, while the process is still alive
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(get(addr, "/readyz").await, 503);
+        assert_eq!(get(addr, "/healthz").await, 200);
+
+        let metrics = reqwest::get(format!("http://{}/metrics", addr))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(metrics.contains(&format!(
+            "traefik_dns_last_success_timestamp_seconds {}",
+            health.last_success_timestamp().unwrap()
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_endpoints() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router
+            .expect_get_routes()
+            .once()
+            .returning(|| Ok(vec![]));
+        mock_provider
+            .expect_list_records()
+            .once()
+            .returning(|| Ok(vec![]));
+
+        let updater = Updater::new(mock_provider, mock_router);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, updater.health().clone()));
+
+        // Before the first reconcile
+        assert_eq!(get(addr, "/healthz").await, 200);
+        assert_eq!(get(addr, "/readyz").await, 503);
+
+        updater.run_once().await.unwrap();
+
+        // After the first reconcile
+        assert_eq!(get(addr, "/healthz").await, 200);
+        assert_eq!(get(addr, "/readyz").await, 200);
+
+        assert_eq!(get(addr, "/unknown").await, 404);
+    }
+
+    #[tokio::test]
+    async fn test_version() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, Arc::new(Health::default())));
+
+        let res = reqwest::get(format!("http://{}/version", addr))
+            .await
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 200);
+        assert_eq!(
+            res.headers()[reqwest::header::CONTENT_TYPE],
+            "application/json"
+        );
+
+        let info: serde_json::Value = res.json().await.unwrap();
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            info["git_sha"],
+            serde_json::json!(BuildInfo::current().git_sha)
+        );
+        assert_eq!(
+            info["features"],
+            serde_json::json!(BuildInfo::current().features)
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use reqwest::{Client, Url};
+
+use crate::http::HttpOptions;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pings an external dead man's switch, like healthchecks.io, after every successful reconcile,
+/// so it alarms once reconciles stop succeeding.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    client: Client,
+    url: Url,
+}
+
+impl Heartbeat {
+    /// Creates a client of `url`, configured by `options`.
+    pub fn new(url: Url, options: &HttpOptions) -> Result<Self, reqwest::Error> {
+        let client = options.builder()?.timeout(DEFAULT_TIMEOUT).build()?;
+        Ok(Self { client, url })
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// GETs the heartbeat URL.
+    pub async fn ping(&self) -> Result<(), reqwest::Error> {
+        self.client
+            .get(self.url.clone())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
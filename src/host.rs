@@ -0,0 +1,98 @@
+use thiserror::Error;
+
+/// Lowercases `host`, strips its trailing dot & encodes it as punycode, so equal hosts compare
+/// equal and providers receive ASCII hosts.
+///
+/// Wildcard hosts are only valid with `*` as the whole leftmost label, like `*.example.com`.
+pub fn try_normalize_host(host: &str) -> Result<String, HostError> {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    let (wildcard, domain) = match host.strip_prefix("*.") {
+        Some(domain) => (true, domain),
+        None => (false, host),
+    };
+    if domain.contains('*') {
+        return Err(HostError::Wildcard);
+    }
+
+    let domain = idna::domain_to_ascii(domain).map_err(HostError::Idna)?;
+    Ok(if wildcard {
+        format!("*.{}", domain)
+    } else {
+        domain
+    })
+}
+
+/// Normalizes `host` like [`try_normalize_host`], only lowercasing it & stripping its trailing
+/// dot if it isn't a valid host.
+///
+/// Used to compare hosts read from providers, which may be stored in any case.
+pub fn normalize_host(host: &str) -> String {
+    try_normalize_host(host)
+        .unwrap_or_else(|_| host.strip_suffix('.').unwrap_or(host).to_lowercase())
+}
+
+#[derive(Debug, Error)]
+pub enum HostError {
+    #[error("wildcards must be the whole leftmost label")]
+    Wildcard,
+    #[error("{0}")]
+    Idna(idna::Errors),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_normalize_host_wildcard() {
+        assert_eq!(
+            try_normalize_host("*.Example.com.").unwrap(),
+            "*.example.com"
+        );
+        assert_eq!(
+            try_normalize_host("*.münchen.example.com").unwrap(),
+            "*.xn--mnchen-3ya.example.com"
+        );
+
+        for host in ["*", "*example.com", "a*.example.com", "test.*.example.com"] {
+            assert!(
+                matches!(try_normalize_host(host), Err(HostError::Wildcard)),
+                "{}",
+                host
+            );
+        }
+    }
+
+    #[test]
+    fn test_try_normalize_host() {
+        assert_eq!(try_normalize_host("Example.COM").unwrap(), "example.com");
+        assert_eq!(try_normalize_host("example.com.").unwrap(), "example.com");
+        assert_eq!(
+            try_normalize_host("münchen.example.com").unwrap(),
+            "xn--mnchen-3ya.example.com"
+        );
+        assert_eq!(
+            try_normalize_host("MÜNCHEN.example.com").unwrap(),
+            "xn--mnchen-3ya.example.com"
+        );
+        // Already encoded hosts are unchanged
+        assert_eq!(
+            try_normalize_host("xn--mnchen-3ya.example.com").unwrap(),
+            "xn--mnchen-3ya.example.com"
+        );
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host("Test.Example.com."), "test.example.com");
+        assert_eq!(normalize_host("*.Example.com"), "*.example.com");
+        assert_eq!(
+            normalize_host("München.example.com"),
+            "xn--mnchen-3ya.example.com"
+        );
+        // Only a single trailing dot is stripped
+        assert_eq!(normalize_host("example.com.."), "example.com.");
+        // Invalid hosts are still lowercased
+        assert_eq!(normalize_host("A*.Example.com."), "a*.example.com");
+    }
+}
@@ -0,0 +1,45 @@
+use reqwest::{Client, ClientBuilder, Proxy, Url};
+
+/// User agent of every request, identifying us in the logs of the APIs called.
+pub const DEFAULT_USER_AGENT: &str = concat!("traefik-dns-rs/", env!("CARGO_PKG_VERSION"));
+
+/// Settings shared by the HTTP clients of Traefik, the DNS providers & notifications.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpOptions {
+    /// HTTP proxy the requests are sent through. Without one, reqwest's default of the
+    /// `HTTP_PROXY` & `HTTPS_PROXY` environment variables applies.
+    pub proxy: Option<Url>,
+    pub user_agent: String,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            user_agent: DEFAULT_USER_AGENT.to_owned(),
+        }
+    }
+}
+
+impl HttpOptions {
+    /// Returns a client builder configured by these options.
+    pub fn builder(&self) -> reqwest::Result<ClientBuilder> {
+        let mut builder = Client::builder().user_agent(&self.user_agent);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy.clone())?);
+        }
+        Ok(builder)
+    }
+
+    pub fn client(&self) -> reqwest::Result<Client> {
+        self.builder()?.build()
+    }
+}
+
+/// Creates a client with the default options, panicking like [`Client::new`] if TLS can't be
+/// initialized.
+pub fn default_client() -> Client {
+    HttpOptions::default()
+        .client()
+        .expect("failed to create the HTTP client")
+}
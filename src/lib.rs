@@ -0,0 +1,17 @@
+//! Keeps DNS records in sync with the hosts routed by Traefik.
+
+pub mod dns;
+pub mod filter;
+pub mod health;
+pub mod heartbeat;
+pub mod host;
+pub mod http;
+#[cfg(feature = "aws")]
+pub mod proxy;
+pub mod retry;
+pub mod router;
+pub mod settings;
+pub mod state;
+pub mod updater;
+pub mod version;
+pub mod webhook;
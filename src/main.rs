@@ -1,27 +1,183 @@
-#![allow(dead_code)]
+// Without providers, most of the setup is unreachable
+#![cfg_attr(
+    not(any(
+        feature = "cf",
+        feature = "aws",
+        feature = "bunny",
+        feature = "alidns",
+        feature = "duckdns",
+        feature = "dynu",
+        feature = "he",
+        feature = "ibmcloud",
+        feature = "namecheap",
+        feature = "oci",
+        feature = "zonefile"
+    )),
+    allow(dead_code, unused_imports)
+)]
 
-use std::{mem, time::Duration};
+#[cfg(any(feature = "aws", feature = "cf", feature = "bunny"))]
+use std::net::IpAddr;
+use std::{mem, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 
-use crate::{router::traefik::TraefikRouter, settings::Settings};
+use clap::{Parser, Subcommand};
+use tracing::error;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+#[cfg(any(feature = "aws", feature = "cf", feature = "bunny"))]
+use traefik_dns::dns::resolve::{join_addresses, DestinationResolver};
+use traefik_dns::{
+    dns,
+    dns::{
+        multi::{MultiProvider, Zone},
+        ownership::Ownership,
+        rate_limit::RateLimit,
+        Provider,
+    },
+    filter::HostFilter,
+    health::{self, Health},
+    heartbeat::Heartbeat,
+    http::{self, HttpOptions},
+    retry::{CircuitBreaker, ErrorBackoff, RetryPolicy},
+    router::{
+        multi::MultiRouter,
+        traefik::{self, ClientOptions, TraefikAuth, TraefikRouter},
+        Router,
+    },
+    settings::{self, Settings},
+    updater::Updater,
+    webhook::Webhook,
+};
 
-mod dns;
-mod router;
-mod settings;
-mod updater;
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Path of the config file, instead of searching the default locations. May be repeated, later
+    /// files being merged over earlier ones
+    #[arg(long, value_name = "PATH")]
+    config: Vec<PathBuf>,
+
+    /// Reconcile routes exactly once and exit, instead of running continuously
+    #[arg(long)]
+    once: bool,
+
+    /// Print the changes of the reconcile as JSON instead of applying them
+    #[arg(long, requires = "once")]
+    dry_run: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Load & validate the config and build its providers, without changing any record. Exits
+    /// with a non-zero status if the config is invalid
+    Check {
+        /// Also list the routes & records once, checking that Traefik & the providers are reachable
+        #[arg(long)]
+        connect: bool,
+    },
+
+    /// Write a commented config template, to the platform's config directory by default
+    GenerateConfig {
+        /// Path to write the config to
+        #[arg(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Replace an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let subscriber = get_subscriber();
+    let args = Args::parse();
+
+    if let Some(Command::GenerateConfig { output, force }) = &args.command {
+        let path = output.clone().unwrap_or_else(settings::default_config_path);
+        settings::generate_config(&path, *force)?;
+        println!("Wrote config template to {}", path.display());
+        return Ok(());
+    }
+
+    // Keep stdout clean for the plan
+    let writer = if args.dry_run {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    let subscriber = get_subscriber(writer);
     tracing::subscriber::set_global_default(subscriber)?;
 
-    let cfg = Settings::new()?;
+    if let Some(Command::Check { connect }) = args.command {
+        let res = async {
+            let cfg = Settings::new(&args.config)?;
+            cfg.validate()?;
+            check(cfg, &args, connect).await
+        };
+        if let Err(e) = res.await {
+            eprintln!("Check failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let cfg = Settings::new(&args.config)?;
+    cfg.validate()?;
+
+    run(cfg, &args).await
+}
 
-    run(cfg).await
+/// Owner id used when ownership is enabled without an explicit `owner_id`.
+const DEFAULT_OWNER_ID: &str = "default";
+
+/// Options shared by the updater of every provider.
+struct RunOptions {
+    update_interval: Duration,
+    once: bool,
+    dry_run: bool,
+    retry: RetryPolicy,
+    error_backoff: ErrorBackoff,
+    /// Failure threshold & cooldown of the circuit breaker, if enabled.
+    circuit_breaker: Option<(u32, Duration)>,
+    list_cache_ttl: Option<Duration>,
+    max_concurrency: usize,
+    rate_limit: Option<Arc<RateLimit>>,
+    filter: HostFilter,
+    health_addr: Option<SocketAddr>,
+    /// Health & metrics of the updater, exporting the rate limits of the providers.
+    health: Arc<Health>,
+    webhook: Option<Webhook>,
+    heartbeat: Option<Heartbeat>,
+    /// Proxy & user agent of every request to the DNS providers.
+    #[cfg(any(
+        feature = "cf",
+        feature = "aws",
+        feature = "bunny",
+        feature = "alidns",
+        feature = "duckdns",
+        feature = "dynu",
+        feature = "he",
+        feature = "ibmcloud",
+        feature = "namecheap",
+        feature = "oci"
+    ))]
+    http: HttpOptions,
+    ownership: Option<Ownership>,
+    #[cfg(any(feature = "aws", feature = "cf", feature = "bunny"))]
+    resolve_destination: bool,
+    cleanup_on_exit: bool,
+    state_file: Option<PathBuf>,
+    max_delete_ratio: Option<f64>,
+    prune: bool,
+    initial_delay: Duration,
 }
 
 #[cfg(debug_assertions)]
-fn get_subscriber() -> impl tracing::Subscriber + Send + Sync + 'static {
+fn get_subscriber(writer: BoxMakeWriter) -> impl tracing::Subscriber + Send + Sync + 'static {
     tracing_subscriber::FmtSubscriber::builder()
+        .with_writer(writer)
         .with_thread_names(true)
         .with_thread_ids(true)
         .with_level(true)
@@ -34,8 +190,9 @@ fn get_subscriber() -> impl tracing::Subscriber + Send + Sync + 'static {
 }
 
 #[cfg(not(debug_assertions))]
-fn get_subscriber() -> impl tracing::Subscriber + Send + Sync + 'static {
+fn get_subscriber(writer: BoxMakeWriter) -> impl tracing::Subscriber + Send + Sync + 'static {
     tracing_subscriber::FmtSubscriber::builder()
+        .with_writer(writer)
         .with_thread_names(false)
         .with_thread_ids(false)
         .with_level(true)
@@ -51,72 +208,744 @@ fn get_subscriber() -> impl tracing::Subscriber + Send + Sync + 'static {
         .finish()
 }
 
-#[cfg_attr(not(any(feature = "cf", feature = "aws")), allow(unused_variables))]
-async fn run(mut cfg: Settings) -> Result<(), Box<dyn std::error::Error>> {
-    let router = TraefikRouter::new(mem::take(&mut cfg.traefik_url))?;
+#[cfg_attr(
+    not(any(
+        feature = "cf",
+        feature = "aws",
+        feature = "bunny",
+        feature = "alidns",
+        feature = "duckdns",
+        feature = "dynu",
+        feature = "he",
+        feature = "ibmcloud",
+        feature = "namecheap",
+        feature = "oci",
+        feature = "zonefile"
+    )),
+    allow(unused_variables, unused_mut, unreachable_code, clippy::never_loop)
+)]
+async fn build(
+    mut cfg: Settings,
+    args: &Args,
+) -> Result<(MultiProvider, MultiRouter<TraefikRouter>, RunOptions), Box<dyn std::error::Error>> {
+    let http = HttpOptions {
+        proxy: cfg.http_proxy.as_deref().map(str::parse).transpose()?,
+        user_agent: cfg
+            .user_agent
+            .take()
+            .unwrap_or_else(|| http::DEFAULT_USER_AGENT.to_owned()),
+    };
+    let client = ClientOptions {
+        timeout: match &cfg.traefik.timeout {
+            Some(timeout) => timeout.parse::<humantime::Duration>()?.into(),
+            None => traefik::DEFAULT_TIMEOUT,
+        },
+        ca_cert: cfg.traefik.ca_cert.take(),
+        danger_accept_invalid_certs: cfg.traefik.danger_accept_invalid_certs,
+        http: http.clone(),
+        unix_socket: cfg.traefik.unix_socket.take(),
+    };
+    let routers = cfg
+        .traefik_urls()
+        .map(|url| traefik_router(url, &cfg.traefik, &client))
+        .collect::<Result<_, _>>()?;
+    let mut router = MultiRouter::new(routers);
 
     let update_interval: Duration = cfg.update_interval.parse::<humantime::Duration>()?.into();
+    let opts = RunOptions {
+        update_interval,
+        once: args.once,
+        dry_run: args.dry_run,
+        retry: RetryPolicy {
+            max_attempts: cfg.retry.max_attempts,
+            base_delay: cfg.retry.base_delay.parse::<humantime::Duration>()?.into(),
+        },
+        error_backoff: ErrorBackoff {
+            max_interval: cfg
+                .error_backoff
+                .max_interval
+                .parse::<humantime::Duration>()?
+                .into(),
+            multiplier: cfg.error_backoff.multiplier,
+        },
+        circuit_breaker: match &cfg.circuit_breaker {
+            Some(breaker) => Some((
+                breaker.failure_threshold,
+                breaker.cooldown.parse::<humantime::Duration>()?.into(),
+            )),
+            None => None,
+        },
+        list_cache_ttl: cfg
+            .list_cache_ttl
+            .as_deref()
+            .map(str::parse::<humantime::Duration>)
+            .transpose()?
+            .map(Into::into),
+        max_concurrency: cfg.max_concurrency,
+        rate_limit: cfg.rate_limit()?.map(Arc::new),
+        filter: HostFilter {
+            include_suffixes: mem::take(&mut cfg.filters.include_suffixes),
+            exclude_hosts: mem::take(&mut cfg.filters.exclude_hosts),
+            exclude_regex: cfg
+                .filters
+                .exclude_regex
+                .as_deref()
+                .map(regex::Regex::new)
+                .transpose()?,
+        },
+        health_addr: cfg.health_addr,
+        health: Arc::new(Health::new(
+            cfg.max_reconcile_staleness
+                .as_deref()
+                .map(str::parse::<humantime::Duration>)
+                .transpose()?
+                .map(Into::into),
+        )),
+        webhook: match &cfg.webhook_url {
+            Some(url) => Some(Webhook::new(url.parse()?, &http)?),
+            None => None,
+        },
+        heartbeat: match &cfg.heartbeat_url {
+            Some(url) => Some(Heartbeat::new(url.parse()?, &http)?),
+            None => None,
+        },
+        #[cfg(any(
+            feature = "cf",
+            feature = "aws",
+            feature = "bunny",
+            feature = "alidns",
+            feature = "duckdns",
+            feature = "dynu",
+            feature = "he",
+            feature = "ibmcloud",
+            feature = "namecheap",
+            feature = "oci"
+        ))]
+        http,
+        ownership: cfg.owner_id.take().map(Ownership::new).or_else(|| {
+            cfg.manage_ownership
+                .then(|| Ownership::new(DEFAULT_OWNER_ID.into()))
+        }),
+        #[cfg(any(feature = "aws", feature = "cf", feature = "bunny"))]
+        resolve_destination: cfg.resolve_destination,
+        cleanup_on_exit: cfg.cleanup_on_exit,
+        state_file: cfg.state_file.take(),
+        max_delete_ratio: cfg.max_delete_ratio,
+        prune: cfg.prune,
+        initial_delay: cfg
+            .initial_delay
+            .as_deref()
+            .map(str::parse::<humantime::Duration>)
+            .transpose()?
+            .map_or(Duration::ZERO, Into::into),
+    };
+    if let Some(limit) = &opts.rate_limit {
+        opts.health.register_rate_limit("changes", limit.clone());
+    }
+    for router in router.routers_mut() {
+        *router.health_mut() = Some(opts.health.clone());
+    }
+
+    let mut zones: Vec<Zone> = Vec::with_capacity(cfg.providers.len());
+    for (i, provider) in mem::take(&mut cfg.providers).into_iter().enumerate() {
+        // Only some providers name their rate limits by index
+        #[cfg(not(any(feature = "cf", feature = "dynu")))]
+        let _ = i;
+        let provider_zones: Vec<Zone> = match provider {
+            #[cfg(feature = "aws")]
+            settings::Provider::Route53(cfg) => build_route53(cfg, &opts).await?,
+            #[cfg(feature = "cf")]
+            settings::Provider::Cloudflare(cfg) => build_cloudflare(cfg, i, &opts).await?,
+            #[cfg(feature = "bunny")]
+            settings::Provider::Bunny(cfg) => build_bunny(cfg, &opts)?,
+            #[cfg(feature = "alidns")]
+            settings::Provider::AliDns(cfg) => build_alidns(cfg, &opts)?,
+            #[cfg(feature = "zonefile")]
+            settings::Provider::Zonefile(cfg) => build_zonefile(cfg, &opts),
+            #[cfg(feature = "he")]
+            settings::Provider::He(cfg) => build_he(cfg, &opts)?,
+            #[cfg(feature = "namecheap")]
+            settings::Provider::Namecheap(cfg) => build_namecheap(cfg, &opts)?,
+            #[cfg(feature = "ibmcloud")]
+            settings::Provider::IbmCloud(cfg) => build_ibmcloud(cfg, &opts)?,
+            #[cfg(feature = "oci")]
+            settings::Provider::Oci(cfg) => build_oci(cfg, &opts)?,
+            #[cfg(feature = "duckdns")]
+            settings::Provider::DuckDns(cfg) => build_duckdns(cfg, &opts)?,
+            #[cfg(feature = "dynu")]
+            settings::Provider::Dynu(cfg) => build_dynu(cfg, i, &opts)?,
+        };
+        zones.extend(provider_zones);
+    }
+
+    Ok((MultiProvider::new(zones), router, opts))
+}
+
+async fn run(cfg: Settings, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let (provider, router, opts) = build(cfg, args).await?;
+    run_updater(Updater::new(provider, router), &opts).await
+}
+
+/// Builds the providers & routers of `cfg` without changing any record, and prints a summary.
+/// With `connect`, routes & records are listed once too.
+async fn check(
+    cfg: Settings,
+    args: &Args,
+    connect: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (provider, router, _) = build(cfg, args).await?;
+    println!(
+        "Config is valid: {} Traefik instance(s), {} DNS zone(s)",
+        router.routers().len(),
+        provider.zones().len()
+    );
+
+    if connect {
+        let routes = router.get_routes().await?;
+        println!("Listed {} route(s) from Traefik", routes.len());
+        let records = provider.list_records().await?;
+        println!("Listed {} record(s) from the DNS providers", records.len());
+    }
+    Ok(())
+}
+
+/// Creates the router of the Traefik instance at `url`.
+fn traefik_router(
+    url: &str,
+    cfg: &settings::TraefikSettings,
+    client: &ClientOptions,
+) -> Result<TraefikRouter, traefik::TraefikError> {
+    let mut router = TraefikRouter::with_options(url, client)?;
+    if let Some(path) = &cfg.api_path {
+        *router.api_path_mut() = path.clone();
+    }
+    if let Some(retries) = cfg.max_retries {
+        router.retry_mut().max_attempts = retries.saturating_add(1);
+    }
+    *router.hostregexp_subdomains_mut() = cfg.hostregexp_subdomains.clone();
+    *router.include_internal_mut() = cfg.include_internal;
+    if let Some(label) = &cfg.ttl_label {
+        *router.ttl_label_mut() = label.clone();
+    }
+    if let Some(label) = &cfg.proxied_label {
+        *router.proxied_label_mut() = label.clone();
+    }
+    *router.auth_mut() = match &cfg.basic_auth {
+        Some(basic) => Some(TraefikAuth::Basic {
+            username: basic.username.clone(),
+            password: basic.password.clone(),
+        }),
+        None => cfg.auth.token.clone().map(TraefikAuth::Bearer),
+    };
+    Ok(router)
+}
+
+async fn run_updater<D, R>(
+    mut updater: Updater<D, R>,
+    opts: &RunOptions,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    D: Provider + 'static,
+    R: Router + 'static,
+{
+    *updater.retry_mut() = opts.retry;
+    *updater.error_backoff_mut() = opts.error_backoff;
+    *updater.circuit_breaker_mut() = opts
+        .circuit_breaker
+        .map(|(threshold, cooldown)| CircuitBreaker::new(threshold, cooldown));
+    *updater.list_cache_ttl_mut() = opts.list_cache_ttl;
+    *updater.max_concurrency_mut() = opts.max_concurrency;
+    *updater.rate_limit_mut() = opts.rate_limit.clone();
+    *updater.filter_mut() = opts.filter.clone();
+    *updater.health_mut() = opts.health.clone();
+    *updater.cleanup_on_exit_mut() = opts.cleanup_on_exit;
+    *updater.state_file_mut() = opts.state_file.clone();
+    *updater.max_delete_ratio_mut() = opts.max_delete_ratio;
+    *updater.prune_mut() = opts.prune;
+    *updater.initial_delay_mut() = opts.initial_delay;
+    *updater.webhook_mut() = opts.webhook.clone();
+    *updater.heartbeat_mut() = opts.heartbeat.clone();
+
+    if let Some(addr) = opts.health_addr {
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let health = updater.health().clone();
+        tokio::spawn(async move {
+            if let Err(e) = health::serve(listener, health).await {
+                error!("health server returned an error: {}", e);
+            }
+        });
+    }
+
+    if let Err(e) = updater.restore_state().await {
+        error!("failed to restore state: {}", e);
+    }
+
+    if opts.dry_run {
+        let plan = updater.plan().await?;
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+        Ok(())
+    } else if opts.once {
+        Ok(updater.run_once().await?)
+    } else {
+        Ok(updater
+            .run_triggered(opts.update_interval, shutdown_signal(), reconcile_trigger())
+            .await?)
+    }
+}
+
+/// Returns a channel receiving on SIGUSR1, to reconcile on demand on Unix.
+fn reconcile_trigger() -> tokio::sync::mpsc::Receiver<()> {
+    // Triggers sent while one is pending are dropped, as a single reconcile covers them all
+    let (trigger, receiver) = tokio::sync::mpsc::channel(1);
+
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::user_defined1()) {
+            Ok(mut signal) => {
+                while signal.recv().await.is_some() {
+                    let _ = trigger.try_send(());
+                }
+            }
+            Err(e) => error!("failed to listen for SIGUSR1: {}", e),
+        }
+    });
+    // A closed channel never triggers
+    #[cfg(not(unix))]
+    drop(trigger);
+
+    receiver
+}
 
-    match cfg.provider {
-        #[cfg(feature = "aws")]
-        Some(settings::Provider::Route53(cfg)) => run_route53(router, update_interval, cfg).await,
-        #[cfg(feature = "cf")]
-        Some(settings::Provider::Cloudflare(cfg)) => {
-            run_cloudflare(router, update_interval, cfg).await
+/// Completes on Ctrl-C, or on SIGTERM on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
         }
-        #[cfg(not(any(feature = "cf", feature = "aws")))]
-        Some(_) => panic!("Unsupported provider"),
-        None => Err("No provider configured")?,
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                error!("failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
     }
 }
 
 #[cfg(feature = "aws")]
-async fn run_route53(
-    router: TraefikRouter,
-    update_interval: Duration,
+async fn build_route53(
     cfg: settings::Route53Settings,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let aws_cfg = aws_config::from_env().load().await;
-    let client = aws_sdk_route53::Client::new(&aws_cfg);
-    let mut provider = dns::route53::Route53Provider::new(client, cfg.zone_id, cfg.destination);
+    opts: &RunOptions,
+) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+    let mut loader = aws_config::from_env();
+    if let Some(proxy) = &opts.http.proxy {
+        loader = loader.http_connector(traefik_dns::proxy::aws_connector(proxy));
+    }
+    let aws_cfg = loader.load().await;
+    let client = dns::route53::Route53Provider::new_client(
+        &aws_cfg,
+        cfg.endpoint_url.as_deref(),
+        cfg.assume_role().as_ref(),
+    );
 
-    if let Some(ttl) = cfg.ttl {
-        *provider.ttl_mut() = ttl;
+    let mut zone_id = cfg.zone_id.clone();
+    if zone_id.is_empty() {
+        if let Some(name) = &cfg.zone_name {
+            zone_id =
+                dns::route53::Route53Provider::find_zone(&client, name, cfg.private_zone).await?;
+            tracing::info!("found hosted zone {} for {}", zone_id, name);
+        }
     }
 
-    let updater = updater::Updater::new(provider, router);
+    let record_type = cfg.record_type()?;
+    let sync_timeout = cfg.sync_timeout()?;
+    let dual_stack = settings::dual_stack(cfg.destination_v4, cfg.destination_v6);
+    let destination = provider_destination(cfg.destination, &dual_stack);
+    let alias = cfg
+        .alias_target
+        .as_ref()
+        .map(|alias| dns::route53::AliasTarget {
+            hosted_zone_id: alias.hosted_zone_id.clone(),
+            evaluate_target_health: alias.evaluate_target_health,
+        });
 
-    Ok(updater.run(update_interval).await?)
+    let build = |zone_id, destination, ttl: Option<i64>| {
+        // Aliases point at their target, which is never resolved
+        let resolver = match alias {
+            Some(_) => None,
+            None => resolver(opts, record_type, &dual_stack)?,
+        };
+        let mut builder = dns::route53::Route53Provider::builder()
+            .client(client.clone())
+            .zone_id(zone_id)
+            .destination(destination)
+            .ownership(opts.ownership.clone())
+            .resolver(resolver)
+            .alias(alias.clone())
+            .preserve_existing_ttl(cfg.preserve_existing_ttl);
+        if let Some(ttl) = ttl {
+            builder = builder.ttl(ttl);
+        }
+        if let Some(record_type) = record_type {
+            builder = builder.record_type(record_type);
+        }
+        if let Some(attempts) = cfg.max_throttle_attempts {
+            builder = builder.max_throttle_attempts(attempts);
+        }
+        if let Some(timeout) = sync_timeout {
+            builder = builder.wait_for_sync(timeout);
+        }
+        Ok::<_, Box<dyn std::error::Error>>(dns::multi::boxed(builder.build()?))
+    };
+
+    let mut zones = vec![Zone::new(
+        cfg.suffix,
+        build(zone_id, destination.clone(), cfg.ttl)?,
+    )];
+    for zone in cfg.zones {
+        let provider = build(
+            zone.id,
+            zone.destination.unwrap_or_else(|| destination.clone()),
+            zone.ttl.map(i64::from).or(cfg.ttl),
+        )?;
+        zones.push(Zone::new(Some(zone.suffix), provider));
+    }
+
+    Ok(zones)
 }
 
+/// Builds the zones of the Cloudflare provider at index `i` of the settings.
 #[cfg(feature = "cf")]
-async fn run_cloudflare(
-    router: TraefikRouter,
-    update_interval: Duration,
+async fn build_cloudflare(
     cfg: settings::CloudflareSettings,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let credentials = if let Some(token) = cfg.token {
-        cloudflare::framework::auth::Credentials::UserAuthToken { token }
-    } else if cfg.email.is_some() && cfg.api_key.is_some() {
-        cloudflare::framework::auth::Credentials::UserAuthKey {
-            email: cfg.email.unwrap(),
-            key: cfg.api_key.unwrap(),
+    i: usize,
+    opts: &RunOptions,
+) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+    let credentials = cfg.credentials()?;
+    // Zones share the rate limit of the credentials
+    let limiter = Arc::new(cfg.rate_limit()?);
+    opts.health
+        .register_rate_limit(format!("providers[{}]", i), limiter.clone());
+    let record_type = cfg.record_type()?;
+    let base_url = cfg.base_url()?;
+    let dual_stack = settings::dual_stack(cfg.destination_v4, cfg.destination_v6);
+    let destination = provider_destination(cfg.destination.clone(), &dual_stack);
+
+    let build = |zone_id, destination, ttl: Option<u32>| {
+        let mut builder = dns::cloudflare::CloudflareProvider::builder()
+            .credentials(credentials.clone())
+            .zone_id(zone_id)
+            .destination(destination)
+            .limiter(limiter.clone())
+            .ownership(opts.ownership.clone())
+            .resolver(resolver(opts, record_type, &dual_stack)?)
+            .http(opts.http.clone());
+        if let Some(ttl) = ttl {
+            builder = builder.ttl(ttl);
         }
-    } else {
-        panic!("missing cloudflare credentials");
+        if let Some(proxied) = cfg.proxied {
+            builder = builder.proxied(proxied);
+        }
+        if let Some(record_type) = record_type {
+            builder = builder.record_type(record_type);
+        }
+        if let Some(template) = &cfg.comment_template {
+            builder = builder.comment_template(template.clone());
+        }
+        builder = builder
+            .tags(cfg.tags.clone())
+            .owner_tag(cfg.owner_tag.clone());
+        if let Some(base_url) = &base_url {
+            builder = builder.base_url(base_url.clone());
+        }
+        Ok::<_, Box<dyn std::error::Error>>(builder)
+    };
+
+    let mut builder = build(cfg.zone_id, destination.clone(), cfg.ttl)?;
+    if let Some(zone_name) = cfg.zone_name {
+        builder = builder.zone_name(zone_name);
+    }
+    let provider = builder.build()?;
+    // Looked up now, so an unknown zone name fails at startup
+    provider.zone_id().await?;
+
+    let mut zones = vec![Zone::new(cfg.suffix, dns::multi::boxed(provider))];
+    for zone in cfg.zones {
+        let provider = build(
+            zone.id,
+            zone.destination.unwrap_or_else(|| destination.clone()),
+            zone.ttl.or(cfg.ttl),
+        )?
+        .build()?;
+        zones.push(Zone::new(Some(zone.suffix), dns::multi::boxed(provider)));
+    }
+
+    Ok(zones)
+}
+
+#[cfg(feature = "bunny")]
+fn build_bunny(
+    cfg: settings::BunnySettings,
+    opts: &RunOptions,
+) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+    let dual_stack = settings::dual_stack(cfg.destination_v4, cfg.destination_v6);
+    let destination = provider_destination(cfg.destination.clone(), &dual_stack);
+
+    let build = |zone_id, destination, ttl: Option<u32>| {
+        let mut provider =
+            dns::bunny::BunnyProvider::new(cfg.api_key.clone(), zone_id, destination);
+        if let Some(ttl) = ttl {
+            *provider.ttl_mut() = ttl;
+        }
+        *provider.client_mut() = opts.http.client()?;
+        *provider.ownership_mut() = opts.ownership.clone();
+        *provider.resolver_mut() = resolver(opts, None, &dual_stack)?;
+        Ok::<_, Box<dyn std::error::Error>>(dns::multi::boxed(provider))
     };
 
+    let mut zones = vec![Zone::new(
+        cfg.suffix.clone(),
+        build(cfg.zone_id, destination.clone(), cfg.ttl)?,
+    )];
+    for zone in &cfg.zones {
+        let provider = build(
+            zone.id,
+            zone.destination
+                .clone()
+                .unwrap_or_else(|| destination.clone()),
+            zone.ttl.or(cfg.ttl),
+        )?;
+        zones.push(Zone::new(Some(zone.suffix.clone()), provider));
+    }
+
+    Ok(zones)
+}
+
+#[cfg(feature = "alidns")]
+fn build_alidns(
+    cfg: settings::AliDnsSettings,
+    opts: &RunOptions,
+) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+    warn_without_ownership(opts, "AliDNS");
+
+    let mut provider = match cfg.region_url()? {
+        Some(url) => dns::alidns::AliDnsProvider::with_base_url(
+            cfg.access_key_id,
+            cfg.access_key_secret,
+            cfg.domain,
+            cfg.destination,
+            url,
+        ),
+        None => dns::alidns::AliDnsProvider::new(
+            cfg.access_key_id,
+            cfg.access_key_secret,
+            cfg.domain,
+            cfg.destination,
+        ),
+    };
+    if let Some(ttl) = cfg.ttl {
+        *provider.ttl_mut() = ttl;
+    }
+    *provider.client_mut() = opts.http.client()?;
+
+    Ok(vec![Zone::new(cfg.suffix, dns::multi::boxed(provider))])
+}
+
+#[cfg(feature = "zonefile")]
+fn build_zonefile(cfg: settings::ZonefileSettings, opts: &RunOptions) -> Vec<Zone> {
+    warn_without_ownership(opts, "the zone file provider");
+
+    let mut provider = dns::zonefile::ZonefileProvider::new(cfg.path, cfg.destination);
+    if let Some(ttl) = cfg.ttl {
+        *provider.ttl_mut() = ttl;
+    }
+    *provider.reload_command_mut() = cfg.reload_command;
+
+    vec![Zone::new(cfg.suffix, dns::multi::boxed(provider))]
+}
+
+#[cfg(feature = "he")]
+fn build_he(
+    cfg: settings::HeSettings,
+    opts: &RunOptions,
+) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+    warn_without_ownership(opts, "HE");
+
+    let mut provider =
+        dns::he::HeProvider::new(cfg.username, cfg.password, cfg.domain, cfg.destination);
+    if let Some(ttl) = cfg.ttl {
+        *provider.ttl_mut() = ttl;
+    }
+    *provider.client_mut() = opts.http.client()?;
+
+    Ok(vec![Zone::new(cfg.suffix, dns::multi::boxed(provider))])
+}
+
+#[cfg(feature = "namecheap")]
+fn build_namecheap(
+    cfg: settings::NamecheapSettings,
+    opts: &RunOptions,
+) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+    warn_without_ownership(opts, "Namecheap");
+
+    let username = cfg.username.unwrap_or_else(|| cfg.api_user.clone());
+    let mut provider = dns::namecheap::NamecheapProvider::new(
+        cfg.api_user,
+        cfg.api_key,
+        username,
+        cfg.client_ip,
+        cfg.domain,
+        cfg.destination,
+    );
+    if let Some(ttl) = cfg.ttl {
+        *provider.ttl_mut() = ttl;
+    }
+    *provider.client_mut() = opts.http.client()?;
+
+    Ok(vec![Zone::new(cfg.suffix, dns::multi::boxed(provider))])
+}
+
+#[cfg(feature = "ibmcloud")]
+fn build_ibmcloud(
+    cfg: settings::IbmCloudSettings,
+    opts: &RunOptions,
+) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+    warn_without_ownership(opts, "IBM Cloud DNS");
+
+    let mut provider = dns::ibmcloud::IbmCloudProvider::new(
+        cfg.api_key,
+        cfg.instance_id,
+        cfg.zone_id,
+        cfg.destination,
+    );
+    if let Some(ttl) = cfg.ttl {
+        *provider.ttl_mut() = ttl;
+    }
+    *provider.client_mut() = opts.http.client()?;
+
+    Ok(vec![Zone::new(cfg.suffix, dns::multi::boxed(provider))])
+}
+
+#[cfg(feature = "oci")]
+fn build_oci(
+    cfg: settings::OciSettings,
+    opts: &RunOptions,
+) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+    warn_without_ownership(opts, "OCI DNS");
+
+    let url = cfg.region_url()?;
+    let credentials = dns::oci::OciCredentials {
+        tenancy: cfg.tenancy,
+        user: cfg.user,
+        fingerprint: cfg.fingerprint,
+        private_key: cfg.private_key,
+    };
     let mut provider =
-        dns::cloudflare::CloudflareProvider::new(credentials, cfg.zone_id, cfg.destination)?;
+        dns::oci::OciProvider::with_base_url(&credentials, cfg.zone, cfg.destination, url)?;
+    if let Some(ttl) = cfg.ttl {
+        *provider.ttl_mut() = ttl;
+    }
+    *provider.client_mut() = opts.http.client()?;
+
+    Ok(vec![Zone::new(cfg.suffix, dns::multi::boxed(provider))])
+}
+
+#[cfg(feature = "duckdns")]
+fn build_duckdns(
+    cfg: settings::DuckDnsSettings,
+    opts: &RunOptions,
+) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+    warn_without_ownership(opts, "DuckDNS");
+
+    let mut provider = dns::duckdns::DuckDnsProvider::new(cfg.token, cfg.domain, cfg.destination);
+    *provider.client_mut() = opts.http.client()?;
+
+    Ok(vec![Zone::new(cfg.suffix, dns::multi::boxed(provider))])
+}
+
+/// Builds the Dynu provider at index `i` of the settings.
+#[cfg(feature = "dynu")]
+fn build_dynu(
+    cfg: settings::DynuSettings,
+    i: usize,
+    opts: &RunOptions,
+) -> Result<Vec<Zone>, Box<dyn std::error::Error>> {
+    warn_without_ownership(opts, "Dynu");
 
+    let mut provider = dns::dynu::DynuProvider::new(cfg.api_key, cfg.domain_id, cfg.destination);
     if let Some(ttl) = cfg.ttl {
         *provider.ttl_mut() = ttl;
     }
-    if let Some(proxied) = cfg.proxied {
-        *provider.proxied_mut() = proxied;
+    *provider.client_mut() = opts.http.client()?;
+    opts.health
+        .register_rate_limit(format!("providers[{}]", i), provider.limiter().clone());
+
+    Ok(vec![Zone::new(cfg.suffix, dns::multi::boxed(provider))])
+}
+
+/// Warns that `provider` can't write ownership markers, if they are enabled.
+#[cfg(any(
+    feature = "alidns",
+    feature = "duckdns",
+    feature = "dynu",
+    feature = "he",
+    feature = "ibmcloud",
+    feature = "namecheap",
+    feature = "oci",
+    feature = "zonefile"
+))]
+fn warn_without_ownership(opts: &RunOptions, provider: &str) {
+    if opts.ownership.is_some() {
+        tracing::warn!(
+            "Ownership markers aren't supported by {}, managing its records without them",
+            provider
+        );
     }
+}
 
-    let updater = updater::Updater::new(provider, router);
+/// Returns the destination of a provider, naming a dual-stack destination by its addresses if
+/// `destination` is omitted.
+#[cfg(any(feature = "aws", feature = "cf", feature = "bunny"))]
+fn provider_destination(destination: String, dual_stack: &[IpAddr]) -> String {
+    if destination.is_empty() && !dual_stack.is_empty() {
+        join_addresses(dual_stack)
+    } else {
+        destination
+    }
+}
 
-    Ok(updater.run(update_interval).await?)
+/// Creates a resolver for the destination of a provider, if destinations are resolved or
+/// `record_type` holds addresses. Dual-stack destinations always use their addresses.
+#[cfg(any(feature = "aws", feature = "cf", feature = "bunny"))]
+fn resolver(
+    opts: &RunOptions,
+    record_type: Option<dns::RecordType>,
+    dual_stack: &[IpAddr],
+) -> Result<Option<Arc<DestinationResolver>>, Box<dyn std::error::Error>> {
+    if !dual_stack.is_empty() {
+        return Ok(Some(Arc::new(DestinationResolver::fixed(
+            dual_stack.to_vec(),
+        ))));
+    }
+    if !opts.resolve_destination && !record_type.is_some_and(dns::RecordType::is_address) {
+        return Ok(None);
+    }
+    Ok(Some(Arc::new(DestinationResolver::new()?)))
 }
@@ -0,0 +1,194 @@
+use reqwest::Url;
+
+/// Returns an HTTP connector of the AWS SDK tunneling its requests through `proxy`.
+#[cfg(feature = "aws")]
+pub fn aws_connector(proxy: &Url) -> aws_smithy_client::http_connector::HttpConnector {
+    use std::sync::Arc;
+
+    use aws_smithy_client::{erase::DynConnector, http_connector::HttpConnector, hyper_ext};
+
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(tunnel::TunnelConnector::new(proxy.clone()));
+    HttpConnector::ConnectorFn(Arc::new(move |settings, sleep| {
+        let mut adapter = hyper_ext::Adapter::builder().connector_settings(settings.clone());
+        if let Some(sleep) = sleep {
+            adapter = adapter.sleep_impl(sleep);
+        }
+        Some(DynConnector::new(adapter.build(https.clone())))
+    }))
+}
+
+#[cfg(feature = "aws")]
+mod tunnel {
+    use std::{
+        future::Future,
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use hyper::Uri;
+    use percent_encoding::percent_decode_str;
+    use reqwest::Url;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    /// Longest response to a `CONNECT` accepted from the proxy.
+    const MAX_RESPONSE_LEN: usize = 8192;
+
+    /// Connects to hosts through an HTTP proxy, with a `CONNECT` tunnel that TLS runs over.
+    #[derive(Debug, Clone)]
+    pub struct TunnelConnector {
+        proxy: Url,
+    }
+
+    impl TunnelConnector {
+        pub fn new(proxy: Url) -> Self {
+            Self { proxy }
+        }
+
+        async fn connect(self, dst: Uri) -> io::Result<TcpStream> {
+            let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, msg.to_owned());
+            let host = dst.host().ok_or_else(|| invalid("missing host"))?;
+            let port = dst
+                .port_u16()
+                .unwrap_or(if dst.scheme_str() == Some("https") {
+                    443
+                } else {
+                    80
+                });
+            let proxy_host = self
+                .proxy
+                .host_str()
+                .ok_or_else(|| invalid("proxy URL has no host"))?;
+            let proxy_port = self.proxy.port_or_known_default().unwrap_or(80);
+
+            let mut stream = TcpStream::connect((proxy_host, proxy_port)).await?;
+            let mut request = format!(
+                "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n",
+                host = host,
+                port = port
+            );
+            if !self.proxy.username().is_empty() {
+                let decode = |s| percent_decode_str(s).decode_utf8_lossy().into_owned();
+                let credentials = format!(
+                    "{}:{}",
+                    decode(self.proxy.username()),
+                    decode(self.proxy.password().unwrap_or_default())
+                );
+                request.push_str(&format!(
+                    "Proxy-Authorization: Basic {}\r\n",
+                    BASE64.encode(credentials)
+                ));
+            }
+            request.push_str("\r\n");
+            stream.write_all(request.as_bytes()).await?;
+
+            // Nothing follows the response until TLS starts, but read byte by byte to be sure
+            let mut response = Vec::new();
+            while !response.ends_with(b"\r\n\r\n") {
+                if response.len() >= MAX_RESPONSE_LEN {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "proxy response too long",
+                    ));
+                }
+                let byte = stream.read_u8().await?;
+                response.push(byte);
+            }
+
+            let status = response
+                .split(|&b| b == b' ')
+                .nth(1)
+                .map(String::from_utf8_lossy);
+            match status.as_deref() {
+                Some("200") => Ok(stream),
+                status => Err(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!(
+                        "proxy refused to connect to {}:{} with status {}",
+                        host,
+                        port,
+                        status.unwrap_or("unknown")
+                    ),
+                )),
+            }
+        }
+    }
+
+    impl tower_service::Service<Uri> for TunnelConnector {
+        type Response = TcpStream;
+        type Error = io::Error;
+        type Future = Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, dst: Uri) -> Self::Future {
+            Box::pin(self.clone().connect(dst))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use tokio::{io::AsyncBufReadExt, net::TcpListener};
+
+        use super::*;
+
+        /// Runs a proxy accepting one `CONNECT`, answering `status`, and returning the request.
+        async fn proxy(status: &'static str) -> (Url, tokio::task::JoinHandle<String>) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = Url::parse(&format!(
+                "http://user:p%40ss@{}",
+                listener.local_addr().unwrap()
+            ))
+            .unwrap();
+            let handle = tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = tokio::io::BufReader::new(stream);
+                let mut request = String::new();
+                while !request.ends_with("\r\n\r\n") {
+                    stream.read_line(&mut request).await.unwrap();
+                }
+                stream
+                    .get_mut()
+                    .write_all(format!("HTTP/1.1 {}\r\n\r\n", status).as_bytes())
+                    .await
+                    .unwrap();
+                request
+            });
+            (url, handle)
+        }
+
+        #[tokio::test]
+        async fn test_tunnel() {
+            let (url, handle) = proxy("200 Connection established").await;
+            TunnelConnector::new(url)
+                .connect("https://route53.amazonaws.com".parse().unwrap())
+                .await
+                .unwrap();
+
+            let request = handle.await.unwrap();
+            assert!(request.starts_with("CONNECT route53.amazonaws.com:443 HTTP/1.1\r\n"));
+            // user:p@ss
+            assert!(request.contains("Proxy-Authorization: Basic dXNlcjpwQHNz\r\n"));
+        }
+
+        #[tokio::test]
+        async fn test_tunnel_refused() {
+            let (url, _) = proxy("403 Forbidden").await;
+            let err = TunnelConnector::new(url)
+                .connect("https://route53.amazonaws.com".parse().unwrap())
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Display,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::Mutex,
+    time::Duration,
+};
+
+use tokio::{time, time::Instant};
+use tracing::warn;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(500);
+const DEFAULT_BACKOFF_MAX_INTERVAL: Duration = Duration::from_secs(300);
+const DEFAULT_BACKOFF_MULTIPLIER: f64 = 2.0;
+
+/// Errors that can tell whether the failed operation is worth retrying.
+pub trait Retryable {
+    /// Returns `true` if the error is transient (timeouts, 5xx, 429, ...).
+    fn is_retryable(&self) -> bool;
+}
+
+/// Exponential backoff retry policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every following retry.
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: DEFAULT_BASE_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before retrying after the given (1-based) failed attempt.
+    ///
+    /// The delay is jittered between 50% and 100% of the exponential backoff.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let jitter = random_fraction() / 2.0 + 0.5;
+        backoff.mul_f64(jitter)
+    }
+}
+
+/// Exponential backoff of the reconcile interval after consecutive failures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorBackoff {
+    /// The interval never grows past this, or past the base interval if it is larger.
+    pub max_interval: Duration,
+    /// Factor the interval is multiplied by after every consecutive failure.
+    pub multiplier: f64,
+}
+
+impl Default for ErrorBackoff {
+    fn default() -> Self {
+        Self {
+            max_interval: DEFAULT_BACKOFF_MAX_INTERVAL,
+            multiplier: DEFAULT_BACKOFF_MULTIPLIER,
+        }
+    }
+}
+
+impl ErrorBackoff {
+    /// Returns the interval before the next reconcile, after `failures` consecutive failures.
+    pub fn interval(&self, base: Duration, failures: u32) -> Duration {
+        let max = self.max_interval.max(base);
+        let factor = self
+            .multiplier
+            .max(1.0)
+            .powi(failures.min(i32::MAX as u32) as i32);
+
+        Duration::try_from_secs_f64(base.as_secs_f64() * factor).map_or(max, |d| d.min(max))
+    }
+}
+
+/// Stops calling a failing dependency for `cooldown` after `failure_threshold` consecutive
+/// failures. Once the cooldown elapses, it half-opens: a single call is let through, closing it
+/// on success and opening it again on failure.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<CircuitState>,
+}
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    failures: u32,
+    /// Calls are refused until then, while open. Kept once elapsed, while half-open.
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Mutex::new(CircuitState::default()),
+        }
+    }
+
+    pub fn failure_threshold(&self) -> u32 {
+        self.failure_threshold
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Checks whether a call is allowed, returning the remaining cooldown if the breaker is open.
+    pub fn check(&self) -> Result<(), Duration> {
+        let now = Instant::now();
+        match self.state.lock().unwrap().open_until {
+            Some(until) if until > now => Err(until - now),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns `true` while calls are refused or being tested after the cooldown.
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().open_until.is_some()
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = CircuitState::default();
+    }
+
+    /// Counts a failure, returning `true` if it opened the breaker.
+    pub fn record_failure(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.failures = state.failures.saturating_add(1);
+        if state.failures < self.failure_threshold {
+            return false;
+        }
+        state.open_until = Some(Instant::now() + self.cooldown);
+        true
+    }
+}
+
+/// Runs `op`, retrying retryable errors according to `policy`.
+///
+/// Non-retryable errors, and the error of the last attempt, are returned immediately.
+pub async fn retry<F, Fut, T, E>(policy: &RetryPolicy, op: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Retryable + Display,
+{
+    retry_if(policy, op, E::is_retryable).await
+}
+
+/// Runs `op`, retrying the errors matching `should_retry` according to `policy`.
+pub async fn retry_if<F, Fut, T, E>(
+    policy: &RetryPolicy,
+    mut op: F,
+    should_retry: impl Fn(&E) -> bool,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt < policy.max_attempts && should_retry(&e) => {
+                let delay = policy.delay(attempt);
+                warn!(attempt, ?delay, "retrying after error: {}", e);
+                time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Returns a random number in `[0, 1)`.
+fn random_fraction() -> f64 {
+    // RandomState is randomly seeded, which is plenty for jitter
+    let hash = RandomState::new().build_hasher().finish();
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestError(bool);
+
+    impl Display for TestError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "TestError")
+        }
+    }
+
+    impl Retryable for TestError {
+        fn is_retryable(&self) -> bool {
+            self.0
+        }
+    }
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+    };
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+        };
+
+        for attempt in 1..5 {
+            let max = Duration::from_millis(100 * 2u64.pow(attempt - 1));
+            let delay = policy.delay(attempt);
+            assert!(delay <= max, "{:?} > {:?}", delay, max);
+            assert!(delay >= max / 2, "{:?} < {:?}", delay, max / 2);
+        }
+    }
+
+    #[test]
+    fn test_error_backoff() {
+        let backoff = ErrorBackoff {
+            max_interval: Duration::from_secs(60),
+            multiplier: 2.0,
+        };
+        let base = Duration::from_secs(10);
+
+        assert_eq!(backoff.interval(base, 0), base);
+        assert_eq!(backoff.interval(base, 1), Duration::from_secs(20));
+        assert_eq!(backoff.interval(base, 2), Duration::from_secs(40));
+        assert_eq!(backoff.interval(base, 3), Duration::from_secs(60));
+        assert_eq!(backoff.interval(base, u32::MAX), Duration::from_secs(60));
+
+        // The cap never shortens the base interval
+        assert_eq!(
+            backoff.interval(Duration::from_secs(120), 3),
+            Duration::from_secs(120)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        assert!(!breaker.record_failure());
+        assert_eq!(breaker.check(), Ok(()));
+        assert!(breaker.record_failure());
+        assert!(breaker.is_open());
+        assert_eq!(breaker.check(), Err(Duration::from_secs(60)));
+
+        // Half-open after the cooldown, a single failure opens it again
+        time::advance(Duration::from_secs(60)).await;
+        assert_eq!(breaker.check(), Ok(()));
+        assert!(breaker.is_open());
+        assert!(breaker.record_failure());
+        assert_eq!(breaker.check(), Err(Duration::from_secs(60)));
+
+        time::advance(Duration::from_secs(60)).await;
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert!(!breaker.record_failure());
+    }
+
+    #[tokio::test]
+    async fn test_retry_then_success() {
+        let calls = AtomicU32::new(0);
+
+        let res = retry(&POLICY, || async {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(TestError(true))
+            } else {
+                Ok(())
+            }
+        })
+        .await;
+
+        assert!(res.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry_exhausted() {
+        let calls = AtomicU32::new(0);
+
+        let res: Result<(), _> = retry(&POLICY, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(TestError(true))
+        })
+        .await;
+
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_fail_fast() {
+        let calls = AtomicU32::new(0);
+
+        let res: Result<(), _> = retry(&POLICY, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(TestError(false))
+        })
+        .await;
+
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
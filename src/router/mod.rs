@@ -1,4 +1,7 @@
+pub mod multi;
 pub mod traefik;
+#[cfg(unix)]
+pub mod unix;
 
 #[cfg_attr(test, mockall::automock(type Error = tests::MockRouterError;))]
 #[async_trait::async_trait]
@@ -8,10 +11,22 @@ pub trait Router {
     async fn get_routes(&self) -> Result<Vec<Route>, Self::Error>;
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct Route {
     pub id: String,
     pub host: String,
+    /// TTL of the host's records, overriding the provider's.
+    pub ttl: Option<u32>,
+    /// Whether the host's traffic is proxied, overriding the provider's setting.
+    pub proxied: Option<bool>,
+
+    /// Entrypoints the router listens on.
+    pub entrypoints: Vec<String>,
+    /// Provider the router was configured by, like `docker` or `file`.
+    pub provider: String,
+    /// Status of the router, like `enabled` or `disabled`.
+    pub status: String,
+    pub priority: Option<i64>,
 }
 
 #[cfg(test)]
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+use super::{Route, Router};
+
+/// Combines the routes of several routers, like multiple Traefik instances.
+///
+/// Any failing router fails the whole listing, as partial routes would delete the records of
+/// the failed router's hosts.
+pub struct MultiRouter<R> {
+    routers: Vec<R>,
+}
+
+impl<R> MultiRouter<R> {
+    pub fn new(routers: Vec<R>) -> Self {
+        Self { routers }
+    }
+
+    pub fn routers(&self) -> &[R] {
+        &self.routers
+    }
+    pub fn routers_mut(&mut self) -> &mut Vec<R> {
+        &mut self.routers
+    }
+}
+
+#[async_trait::async_trait]
+impl<R> Router for MultiRouter<R>
+where
+    R: Router + Send + Sync,
+    R::Error: Send,
+{
+    type Error = R::Error;
+
+    async fn get_routes(&self) -> Result<Vec<Route>, Self::Error> {
+        let routes =
+            futures::future::try_join_all(self.routers.iter().map(Router::get_routes)).await?;
+
+        // Hosts routed by multiple routers keep the route of the first one
+        let mut seen = HashSet::new();
+        Ok(routes
+            .into_iter()
+            .flatten()
+            .filter(|r| seen.insert(r.host.clone()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::{matchers::*, responders::*, Expectation, Server};
+
+    use super::*;
+    use crate::router::traefik::TraefikRouter;
+
+    fn traefik(rules: &[&str]) -> Server {
+        let routers: Vec<_> = rules
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| serde_json::json!({"rule": rule, "name": format!("router{}", i)}))
+            .collect();
+
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/http/routers"))
+                .respond_with(json_encoded(routers)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/api/tcp/routers"))
+                .respond_with(status_code(200).body("[]")),
+        );
+        server
+    }
+
+    #[tokio::test]
+    async fn test_get_routes() {
+        let internal = traefik(&["Host(`a.example.com`)", "Host(`shared.example.com`)"]);
+        let dmz = traefik(&["Host(`shared.example.com`)", "Host(`b.example.com`)"]);
+
+        let router = MultiRouter::new(vec![
+            TraefikRouter::new(internal.url_str("/")).unwrap(),
+            TraefikRouter::new(dmz.url_str("/")).unwrap(),
+        ]);
+
+        let routes = router.get_routes().await.unwrap();
+        let hosts: Vec<_> = routes.iter().map(|r| r.host.as_str()).collect();
+        assert_eq!(
+            hosts,
+            vec!["a.example.com", "shared.example.com", "b.example.com"]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    future::Future,
+    io,
+    path::PathBuf,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use hyper::{
+    client::connect::{Connected, Connection},
+    header::USER_AGENT,
+    service::Service,
+    Body, Uri,
+};
+use reqwest::RequestBuilder;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::UnixStream,
+};
+
+use super::traefik::TraefikError;
+
+/// Client of an HTTP API listening on a Unix socket, which reqwest can't connect to.
+///
+/// Requests are still built with reqwest, their URL only giving the `Host` header & path.
+#[derive(Debug, Clone)]
+pub struct UnixClient {
+    client: hyper::Client<UnixConnector>,
+    user_agent: String,
+    timeout: Duration,
+}
+
+impl UnixClient {
+    pub fn new(path: PathBuf, user_agent: String, timeout: Duration) -> Self {
+        Self {
+            client: hyper::Client::builder().build(UnixConnector(Arc::new(path))),
+            user_agent,
+            timeout,
+        }
+    }
+
+    /// Sends `request`, returning the body of a successful response.
+    pub async fn send(&self, request: RequestBuilder) -> Result<String, TraefikError> {
+        let request = request.header(USER_AGENT, &self.user_agent).build()?;
+        // Only GET requests are sent, without a body
+        let request = hyper::Request::try_from(request)?.map(|_| Body::empty());
+
+        let (status, body) = tokio::time::timeout(self.timeout, async {
+            let response = self.client.request(request).await?;
+            let status = response.status();
+            let body = hyper::body::to_bytes(response.into_body()).await?;
+            Ok::<_, hyper::Error>((status, body))
+        })
+        .await
+        .map_err(|_| TraefikError::Timeout)??;
+
+        if !status.is_success() {
+            return Err(TraefikError::Status(status));
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+/// Connects to the socket at its path, whatever the URI.
+#[derive(Debug, Clone)]
+pub struct UnixConnector(Arc<PathBuf>);
+
+impl Service<Uri> for UnixConnector {
+    type Response = UnixConnection;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<UnixConnection>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _dst: Uri) -> Self::Future {
+        let path = self.0.clone();
+        Box::pin(async move { Ok(UnixConnection(UnixStream::connect(&*path).await?)) })
+    }
+}
+
+/// [`UnixStream`] implementing hyper's [`Connection`].
+#[derive(Debug)]
+pub struct UnixConnection(UnixStream);
+
+impl Connection for UnixConnection {
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
+
+impl AsyncRead for UnixConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
@@ -1,9 +1,33 @@
-use std::path::{Path, PathBuf};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+};
 
 use directories::ProjectDirs;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info};
+use toml::{Table, Value};
+use tracing::{debug, info, warn};
+
+/// Prefix of the environment variables overriding settings.
+const ENV_PREFIX: &str = "TDR_";
+/// Variable listing the config files to load, instead of searching the default locations.
+const CONFIG_PATHS_VAR: &str = "TDR_CONFIG_PATHS";
+
+/// Provider types, as written in the `type` tag of a provider.
+const PROVIDER_TYPES: &[&str] = &[
+    "Route53",
+    "Cloudflare",
+    "Bunny",
+    "AliDns",
+    "Zonefile",
+    "He",
+    "Namecheap",
+    "IbmCloud",
+    "Oci",
+    "DuckDns",
+    "Dynu",
+];
 
 static PROJECT_DIRS: Lazy<ProjectDirs> = Lazy::new(|| {
     ProjectDirs::from("com", "anshulg", "traefik-dns-rs")
@@ -13,24 +37,466 @@ static PROJECT_DIRS: Lazy<ProjectDirs> = Lazy::new(|| {
 #[cfg(feature = "aws")]
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Route53Settings {
+    #[serde(default)]
     pub zone_id: String,
+    /// Name of the hosted zone, looked up at startup if `zone_id` isn't set.
+    pub zone_name: Option<String>,
+    /// Look up the private (or public) hosted zone named `zone_name`, if both exist.
+    pub private_zone: Option<bool>,
+    #[serde(default)]
     pub destination: String,
+    /// IPv4 & IPv6 addresses of a dual-stack destination, creating both A & AAAA records of every
+    /// host instead of pointing at `destination`, which may then be omitted.
+    pub destination_v4: Option<Ipv4Addr>,
+    pub destination_v6: Option<Ipv6Addr>,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
 
     pub ttl: Option<i64>,
+    /// `CNAME`, `A` or `AAAA`, defaulting to `CNAME`, or address records if destinations are resolved.
+    pub record_type: Option<String>,
+    /// Keep the TTL of existing records instead of replacing it with `ttl`.
+    #[serde(default)]
+    pub preserve_existing_ttl: bool,
+    /// Maximum number of attempts of requests throttled by Route53, including the first one.
+    pub max_throttle_attempts: Option<u32>,
+    /// Wait for changes to be in sync on every Route53 server, up to `sync_timeout`.
+    #[serde(default)]
+    pub wait_for_sync: bool,
+    /// Defaults to 2 minutes.
+    pub sync_timeout: Option<String>,
+    /// Create alias records to `destination`, an AWS resource, instead of CNAMEs.
+    pub alias_target: Option<AliasTargetSettings>,
+    /// Endpoint of the Route53 API, like LocalStack's, instead of the default AWS endpoint.
+    pub endpoint_url: Option<String>,
+    /// Role assumed with the credentials of the environment, for zones of another AWS account.
+    pub assume_role_arn: Option<String>,
+    /// External id required by the trust policy of `assume_role_arn`, if any.
+    pub assume_role_external_id: Option<String>,
+    pub assume_role_session_name: Option<String>,
+
+    /// Additional zones managed with the same credentials.
+    #[serde(default)]
+    pub zones: Vec<ZoneSettings<String>>,
+}
+
+#[cfg(feature = "aws")]
+impl Route53Settings {
+    /// Returns the role to assume, if any.
+    pub fn assume_role(&self) -> Option<crate::dns::route53::AssumeRole> {
+        Some(crate::dns::route53::AssumeRole {
+            role_arn: self.assume_role_arn.clone()?,
+            external_id: self.assume_role_external_id.clone(),
+            session_name: self.assume_role_session_name.clone(),
+        })
+    }
+
+    /// Returns the configured record type, if any.
+    pub fn record_type(&self) -> Result<Option<crate::dns::RecordType>, ConfigError> {
+        parse_record_type(self.record_type.as_deref())
+    }
+
+    /// Returns how long changes are waited on to be in sync, or `None` if they aren't.
+    pub fn sync_timeout(&self) -> Result<Option<std::time::Duration>, ConfigError> {
+        if !self.wait_for_sync {
+            return Ok(None);
+        }
+        match &self.sync_timeout {
+            Some(timeout) => humantime::parse_duration(timeout)
+                .map(Some)
+                .map_err(|source| ConfigError::InvalidDuration {
+                    field: "sync_timeout",
+                    source,
+                }),
+            None => Ok(Some(crate::dns::route53::DEFAULT_SYNC_TIMEOUT)),
+        }
+    }
+}
+
+/// Target of Route53 alias records, like a load balancer or a CloudFront distribution.
+#[cfg(feature = "aws")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AliasTargetSettings {
+    /// Hosted zone of the target, not the zone of the records.
+    pub hosted_zone_id: String,
+    #[serde(default)]
+    pub evaluate_target_health: bool,
+}
+
+#[cfg(feature = "bunny")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BunnySettings {
+    pub zone_id: u64,
+    #[serde(default)]
+    pub destination: String,
+    /// IPv4 & IPv6 addresses of a dual-stack destination, creating both A & AAAA records of every
+    /// host instead of pointing at `destination`, which may then be omitted.
+    pub destination_v4: Option<Ipv4Addr>,
+    pub destination_v6: Option<Ipv6Addr>,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
+
+    #[serde(default)]
+    pub api_key: String,
+    /// File to read `api_key` from, taking precedence over it.
+    pub api_key_file: Option<PathBuf>,
+
+    pub ttl: Option<u32>,
+
+    /// Additional zones managed with the same credentials.
+    #[serde(default)]
+    pub zones: Vec<ZoneSettings<u64>>,
+}
+
+#[cfg(feature = "alidns")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AliDnsSettings {
+    /// Domain of the records, as added to Alibaba Cloud DNS.
+    #[serde(default)]
+    pub domain: String,
+    #[serde(default)]
+    pub destination: String,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
+
+    #[serde(default)]
+    pub access_key_id: String,
+    #[serde(default)]
+    pub access_key_secret: String,
+    /// File to read `access_key_secret` from, taking precedence over it.
+    pub access_key_secret_file: Option<PathBuf>,
+
+    pub ttl: Option<u32>,
+    /// Region of the API endpoint, like `ap-southeast-1`, instead of the central endpoint.
+    pub region: Option<String>,
+}
+
+#[cfg(feature = "alidns")]
+impl AliDnsSettings {
+    /// Returns the API endpoint of `region`, or `None` for the central endpoint.
+    pub fn region_url(&self) -> Result<Option<url::Url>, ConfigError> {
+        let Some(region) = &self.region else {
+            return Ok(None);
+        };
+        crate::dns::alidns::AliDnsProvider::region_url(region)
+            .map(Some)
+            .map_err(|_| ConfigError::InvalidRegion(region.clone()))
+    }
+}
+
+#[cfg(feature = "he")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HeSettings {
+    /// Domain of the records, as added to dns.he.net.
+    #[serde(default)]
+    pub domain: String,
+    #[serde(default)]
+    pub destination: String,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
+
+    /// Account credentials, as HE has no API keys.
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    /// File to read `password` from, taking precedence over it.
+    pub password_file: Option<PathBuf>,
+
+    pub ttl: Option<u32>,
+}
+
+#[cfg(feature = "namecheap")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NamecheapSettings {
+    /// Domain of the records, as registered at Namecheap.
+    #[serde(default)]
+    pub domain: String,
+    #[serde(default)]
+    pub destination: String,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
+
+    #[serde(default)]
+    pub api_user: String,
+    #[serde(default)]
+    pub api_key: String,
+    /// File to read `api_key` from, taking precedence over it.
+    pub api_key_file: Option<PathBuf>,
+    /// Account the domain belongs to, `api_user` if omitted.
+    pub username: Option<String>,
+    /// Public IP of this host, which must be whitelisted for API access.
+    #[serde(default)]
+    pub client_ip: String,
+
+    pub ttl: Option<u32>,
+}
+
+#[cfg(feature = "ibmcloud")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IbmCloudSettings {
+    /// GUID of the DNS Services instance.
+    #[serde(default)]
+    pub instance_id: String,
+    /// Id of the DNS zone within the instance.
+    #[serde(default)]
+    pub zone_id: String,
+    #[serde(default)]
+    pub destination: String,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
+
+    /// IAM API key, exchanged for access tokens.
+    #[serde(default)]
+    pub api_key: String,
+    /// File to read `api_key` from, taking precedence over it.
+    pub api_key_file: Option<PathBuf>,
+
+    pub ttl: Option<u32>,
+}
+
+#[cfg(feature = "oci")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct OciSettings {
+    /// Name or OCID of the DNS zone.
+    #[serde(default)]
+    pub zone: String,
+    #[serde(default)]
+    pub destination: String,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
+    /// Region of the API endpoint, like `us-ashburn-1`.
+    #[serde(default)]
+    pub region: String,
+
+    /// OCID of the tenancy.
+    #[serde(default)]
+    pub tenancy: String,
+    /// OCID of the user owning the API signing key.
+    #[serde(default)]
+    pub user: String,
+    /// Fingerprint of the API signing key.
+    #[serde(default)]
+    pub fingerprint: String,
+    /// Unencrypted PEM private key of the API signing key.
+    #[serde(default)]
+    pub private_key: String,
+    /// File to read `private_key` from, taking precedence over it.
+    pub private_key_file: Option<PathBuf>,
+
+    pub ttl: Option<u32>,
+}
+
+#[cfg(feature = "oci")]
+impl OciSettings {
+    /// Returns the API endpoint of `region`.
+    pub fn region_url(&self) -> Result<url::Url, ConfigError> {
+        crate::dns::oci::OciProvider::region_url(&self.region)
+            .map_err(|_| ConfigError::InvalidRegion(self.region.clone()))
+    }
+}
+
+#[cfg(feature = "duckdns")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DuckDnsSettings {
+    /// DuckDNS subdomain, like `myhome` or `myhome.duckdns.org`.
+    #[serde(default)]
+    pub domain: String,
+    /// IP address the subdomain points at.
+    #[serde(default)]
+    pub destination: String,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
+
+    #[serde(default)]
+    pub token: String,
+    /// File to read `token` from, taking precedence over it.
+    pub token_file: Option<PathBuf>,
+}
+
+#[cfg(feature = "dynu")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DynuSettings {
+    /// Id of the domain, as listed by `GET /v2/dns`.
+    pub domain_id: u64,
+    #[serde(default)]
+    pub destination: String,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
+
+    #[serde(default)]
+    pub api_key: String,
+    /// File to read `api_key` from, taking precedence over it.
+    pub api_key_file: Option<PathBuf>,
+
+    pub ttl: Option<u32>,
+}
+
+#[cfg(feature = "zonefile")]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ZonefileSettings {
+    /// BIND zone file whose managed block holds the records.
+    #[serde(default)]
+    pub path: PathBuf,
+    #[serde(default)]
+    pub destination: String,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
+
+    pub ttl: Option<u32>,
+    /// Command run after every change of the zone file, like `["rndc", "reload", "example.com"]`.
+    pub reload_command: Option<Vec<String>>,
 }
 
 #[cfg(feature = "cf")]
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct CloudflareSettings {
+    #[serde(default)]
     pub zone_id: String,
+    /// Name of the zone, looked up at startup if `zone_id` isn't set.
+    pub zone_name: Option<String>,
+    #[serde(default)]
     pub destination: String,
+    /// IPv4 & IPv6 addresses of a dual-stack destination, creating both A & AAAA records of every
+    /// host instead of pointing at `destination`, which may then be omitted.
+    pub destination_v4: Option<Ipv4Addr>,
+    pub destination_v6: Option<Ipv6Addr>,
+    /// Domain suffix of the hosts managed by this provider, if there are multiple providers.
+    pub suffix: Option<String>,
 
     pub token: Option<String>,
+    /// File to read `token` from, taking precedence over it.
+    pub token_file: Option<PathBuf>,
     pub email: Option<String>,
     pub api_key: Option<String>,
+    /// File to read `api_key` from, taking precedence over it.
+    pub api_key_file: Option<PathBuf>,
 
+    /// TTL of the records, `auto` or 1 letting Cloudflare choose it.
+    #[serde(default, deserialize_with = "deserialize_cloudflare_ttl")]
     pub ttl: Option<u32>,
     pub proxied: Option<bool>,
+    /// `CNAME`, `A` or `AAAA`, defaulting to `CNAME`, or address records if destinations are resolved.
+    pub record_type: Option<String>,
+    /// Comment set on created records, with `{router}` & `{host}` replaced by the id of the
+    /// host's router & the host.
+    pub comment_template: Option<String>,
+    /// Tags set on created records.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Tag marking the records created by traefik-dns-rs, like `traefik-dns-rs`. Records without
+    /// it are never deleted.
+    pub owner_tag: Option<String>,
+
+    /// Maximum number of API requests per `rate_limit_period`, shared by all zones.
+    pub rate_limit: Option<u64>,
+    pub rate_limit_period: Option<String>,
+
+    /// Base URL of the API, like `https://api.cloudflare.com/client/v4/`, to use a mock or
+    /// partner endpoint.
+    pub base_url: Option<String>,
+
+    /// Additional zones managed with the same credentials.
+    #[serde(default)]
+    pub zones: Vec<ZoneSettings<String>>,
+}
+
+#[cfg(feature = "cf")]
+impl CloudflareSettings {
+    /// Returns the API credentials, preferring `token` over `email` & `api_key`.
+    pub fn credentials(&self) -> Result<cloudflare::framework::auth::Credentials, ConfigError> {
+        use cloudflare::framework::auth::Credentials;
+
+        match (&self.token, &self.email, &self.api_key) {
+            (Some(token), _, _) => Ok(Credentials::UserAuthToken {
+                token: token.clone(),
+            }),
+            (None, Some(email), Some(key)) => Ok(Credentials::UserAuthKey {
+                email: email.clone(),
+                key: key.clone(),
+            }),
+            _ => Err(ConfigError::MissingCredentials {
+                provider: "Cloudflare",
+                required: "`token`, or both `email` and `api_key`",
+            }),
+        }
+    }
+
+    /// Returns the API rate limiter, defaulting to Cloudflare's global limit.
+    pub fn rate_limit(&self) -> Result<crate::dns::rate_limit::RateLimit, ConfigError> {
+        use crate::dns::cloudflare::{REQUEST_LIMIT, REQUEST_PERIOD};
+
+        let period = match &self.rate_limit_period {
+            Some(period) => humantime::parse_duration(period).map_err(|source| {
+                ConfigError::InvalidDuration {
+                    field: "rate_limit_period",
+                    source,
+                }
+            })?,
+            None => REQUEST_PERIOD,
+        };
+        Ok(crate::dns::rate_limit::RateLimit::new(
+            self.rate_limit.unwrap_or(REQUEST_LIMIT),
+            period,
+        ))
+    }
+
+    /// Returns the configured record type, if any.
+    pub fn record_type(&self) -> Result<Option<crate::dns::RecordType>, ConfigError> {
+        parse_record_type(self.record_type.as_deref())
+    }
+
+    /// Returns the base URL of the API, or `None` for Cloudflare's.
+    pub fn base_url(&self) -> Result<Option<url::Url>, ConfigError> {
+        let Some(url) = &self.base_url else {
+            return Ok(None);
+        };
+        // Paths are joined to the base URL, which would drop its last segment without a slash
+        let url = if url.ends_with('/') {
+            url::Url::parse(url)
+        } else {
+            url::Url::parse(&format!("{}/", url))
+        };
+        url.map(Some).map_err(ConfigError::InvalidBaseUrl)
+    }
+}
+
+/// Deserializes a Cloudflare TTL, either a number of seconds or `auto`.
+#[cfg(feature = "cf")]
+fn deserialize_cloudflare_ttl<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Ttl {
+        Seconds(u32),
+        Name(String),
+    }
+
+    match Option::<Ttl>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Ttl::Seconds(ttl)) => Ok(Some(ttl)),
+        Some(Ttl::Name(name)) if name.eq_ignore_ascii_case("auto") => {
+            Ok(Some(crate::dns::cloudflare::AUTO_TTL))
+        }
+        Some(Ttl::Name(name)) => Err(serde::de::Error::custom(format!(
+            "invalid TTL {}, expected a number of seconds or auto",
+            name
+        ))),
+    }
+}
+
+/// An additional zone of a provider, managing the hosts under `suffix`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ZoneSettings<Id> {
+    pub id: Id,
+    pub suffix: String,
+
+    /// Defaults to the destination of the provider.
+    pub destination: Option<String>,
+    /// Defaults to the TTL of the provider.
+    pub ttl: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,24 +506,882 @@ pub enum Provider {
     Route53(Route53Settings),
     #[cfg(feature = "cf")]
     Cloudflare(CloudflareSettings),
+    #[cfg(feature = "bunny")]
+    Bunny(BunnySettings),
+    #[cfg(feature = "alidns")]
+    AliDns(AliDnsSettings),
+    #[cfg(feature = "zonefile")]
+    Zonefile(ZonefileSettings),
+    #[cfg(feature = "he")]
+    He(HeSettings),
+    #[cfg(feature = "namecheap")]
+    Namecheap(NamecheapSettings),
+    #[cfg(feature = "ibmcloud")]
+    IbmCloud(IbmCloudSettings),
+    #[cfg(feature = "oci")]
+    Oci(OciSettings),
+    #[cfg(feature = "duckdns")]
+    DuckDns(DuckDnsSettings),
+    #[cfg(feature = "dynu")]
+    Dynu(DynuSettings),
+}
+
+impl Provider {
+    /// Checks the settings of the provider at index `i`.
+    #[cfg_attr(
+        not(any(
+            feature = "cf",
+            feature = "aws",
+            feature = "alidns",
+            feature = "dynu",
+            feature = "he",
+            feature = "ibmcloud",
+            feature = "namecheap",
+            feature = "oci",
+            feature = "zonefile"
+        )),
+        allow(unused_variables)
+    )]
+    fn validate(&self, i: usize, resolve_destination: bool) -> Result<(), ConfigError> {
+        let field = |name: &str| format!("providers[{}].{}", i, name);
+
+        match *self {
+            #[cfg(feature = "aws")]
+            Provider::Route53(ref cfg) => {
+                if cfg
+                    .zone_name
+                    .as_deref()
+                    .is_none_or(|name| name.trim().is_empty())
+                {
+                    require(&cfg.zone_id, || field("zone_id"))?;
+                }
+                let dual_stack = dual_stack(cfg.destination_v4, cfg.destination_v6);
+                if dual_stack.is_empty() {
+                    require(&cfg.destination, || field("destination"))?;
+                }
+                if let Some(alias) = &cfg.alias_target {
+                    require(&alias.hosted_zone_id, || {
+                        field("alias_target.hosted_zone_id")
+                    })?;
+                }
+                cfg.sync_timeout()?;
+                let check_ttl = |field: String, ttl: Option<i64>| match ttl {
+                    Some(ttl) if !crate::dns::route53::is_valid_ttl(ttl) => {
+                        Err(ConfigError::InvalidTtl {
+                            field,
+                            ttl,
+                            expected: "between 0 and 2147483647",
+                        })
+                    }
+                    _ => Ok(()),
+                };
+                check_ttl(field("ttl"), cfg.ttl)?;
+                for (j, zone) in cfg.zones.iter().enumerate() {
+                    check_ttl(field(&format!("zones[{}].ttl", j)), zone.ttl.map(i64::from))?;
+                }
+                if let Some(url) = &cfg.endpoint_url {
+                    url::Url::parse(url).map_err(ConfigError::InvalidEndpointUrl)?;
+                }
+                if cfg.assume_role_arn.is_none()
+                    && (cfg.assume_role_external_id.is_some()
+                        || cfg.assume_role_session_name.is_some())
+                {
+                    return Err(ConfigError::MissingField(field("assume_role_arn")));
+                }
+                validate_record_type(
+                    cfg.record_type()?,
+                    &cfg.destination,
+                    &dual_stack,
+                    &cfg.zones,
+                    resolve_destination,
+                    field,
+                )?;
+                validate_zones(&cfg.zones, field, |id| !id.is_empty())
+            }
+            #[cfg(feature = "cf")]
+            Provider::Cloudflare(ref cfg) => {
+                if cfg
+                    .zone_name
+                    .as_deref()
+                    .is_none_or(|name| name.trim().is_empty())
+                {
+                    require(&cfg.zone_id, || field("zone_id"))?;
+                }
+                let dual_stack = dual_stack(cfg.destination_v4, cfg.destination_v6);
+                if dual_stack.is_empty() {
+                    require(&cfg.destination, || field("destination"))?;
+                }
+                cfg.credentials()?;
+                cfg.rate_limit()?;
+                cfg.base_url()?;
+                let check_ttl = |field: String, ttl: Option<u32>| match ttl {
+                    Some(ttl) if !crate::dns::cloudflare::is_valid_ttl(ttl) => {
+                        Err(ConfigError::InvalidTtl {
+                            field,
+                            ttl: ttl.into(),
+                            expected: "auto (1) or between 60 and 86400",
+                        })
+                    }
+                    _ => Ok(()),
+                };
+                check_ttl(field("ttl"), cfg.ttl)?;
+                for (j, zone) in cfg.zones.iter().enumerate() {
+                    check_ttl(field(&format!("zones[{}].ttl", j)), zone.ttl)?;
+                }
+                validate_record_type(
+                    cfg.record_type()?,
+                    &cfg.destination,
+                    &dual_stack,
+                    &cfg.zones,
+                    resolve_destination,
+                    field,
+                )?;
+                validate_zones(&cfg.zones, field, |id| !id.is_empty())
+            }
+            #[cfg(feature = "bunny")]
+            Provider::Bunny(ref cfg) => {
+                if cfg.zone_id == 0 {
+                    return Err(ConfigError::MissingField(field("zone_id")));
+                }
+                if dual_stack(cfg.destination_v4, cfg.destination_v6).is_empty() {
+                    require(&cfg.destination, || field("destination"))?;
+                }
+                require(&cfg.api_key, || field("api_key"))?;
+                validate_zones(&cfg.zones, field, |id| *id != 0)
+            }
+            #[cfg(feature = "alidns")]
+            Provider::AliDns(ref cfg) => {
+                require(&cfg.domain, || field("domain"))?;
+                require(&cfg.destination, || field("destination"))?;
+                require(&cfg.access_key_id, || field("access_key_id"))?;
+                require(&cfg.access_key_secret, || field("access_key_secret"))?;
+                // Only CNAMEs are created
+                if resolve_destination {
+                    return Err(ConfigError::CnameResolved(field("type")));
+                }
+                if let Some(ttl) = cfg.ttl.filter(|ttl| !(1..=86400).contains(ttl)) {
+                    return Err(ConfigError::InvalidTtl {
+                        field: field("ttl"),
+                        ttl: ttl.into(),
+                        expected: "between 1 and 86400",
+                    });
+                }
+                cfg.region_url()?;
+                Ok(())
+            }
+            #[cfg(feature = "zonefile")]
+            Provider::Zonefile(ref cfg) => {
+                if cfg.path.as_os_str().is_empty() {
+                    return Err(ConfigError::MissingField(field("path")));
+                }
+                require(&cfg.destination, || field("destination"))?;
+                if cfg.reload_command.as_ref().is_some_and(Vec::is_empty) {
+                    return Err(ConfigError::MissingField(field("reload_command")));
+                }
+                // Only CNAMEs are written
+                if resolve_destination {
+                    return Err(ConfigError::CnameResolved(field("type")));
+                }
+                Ok(())
+            }
+            #[cfg(feature = "he")]
+            Provider::He(ref cfg) => {
+                require(&cfg.domain, || field("domain"))?;
+                require(&cfg.destination, || field("destination"))?;
+                require(&cfg.username, || field("username"))?;
+                require(&cfg.password, || field("password"))?;
+                // Only CNAMEs are created
+                if resolve_destination {
+                    return Err(ConfigError::CnameResolved(field("type")));
+                }
+                if let Some(ttl) = cfg.ttl.filter(|ttl| !crate::dns::he::is_valid_ttl(*ttl)) {
+                    return Err(ConfigError::InvalidTtl {
+                        field: field("ttl"),
+                        ttl: ttl.into(),
+                        expected: "one of 300, 900, 1800, 3600, 7200, 14400, 28800, 43200, 86400 or 172800",
+                    });
+                }
+                Ok(())
+            }
+            #[cfg(feature = "namecheap")]
+            Provider::Namecheap(ref cfg) => {
+                require(&cfg.domain, || field("domain"))?;
+                require(&cfg.destination, || field("destination"))?;
+                require(&cfg.api_user, || field("api_user"))?;
+                require(&cfg.api_key, || field("api_key"))?;
+                require(&cfg.client_ip, || field("client_ip"))?;
+                if cfg.client_ip.parse::<std::net::IpAddr>().is_err() {
+                    return Err(ConfigError::InvalidClientIp(cfg.client_ip.clone()));
+                }
+                // Only CNAMEs are created
+                if resolve_destination {
+                    return Err(ConfigError::CnameResolved(field("type")));
+                }
+                if let Some(ttl) = cfg.ttl.filter(|ttl| !(60..=60000).contains(ttl)) {
+                    return Err(ConfigError::InvalidTtl {
+                        field: field("ttl"),
+                        ttl: ttl.into(),
+                        expected: "between 60 and 60000",
+                    });
+                }
+                Ok(())
+            }
+            #[cfg(feature = "ibmcloud")]
+            Provider::IbmCloud(ref cfg) => {
+                require(&cfg.instance_id, || field("instance_id"))?;
+                require(&cfg.zone_id, || field("zone_id"))?;
+                require(&cfg.destination, || field("destination"))?;
+                require(&cfg.api_key, || field("api_key"))?;
+                // Only CNAMEs are created
+                if resolve_destination {
+                    return Err(ConfigError::CnameResolved(field("type")));
+                }
+                if let Some(ttl) = cfg.ttl.filter(|ttl| !(60..=2147483647).contains(ttl)) {
+                    return Err(ConfigError::InvalidTtl {
+                        field: field("ttl"),
+                        ttl: ttl.into(),
+                        expected: "between 60 and 2147483647",
+                    });
+                }
+                Ok(())
+            }
+            #[cfg(feature = "oci")]
+            Provider::Oci(ref cfg) => {
+                require(&cfg.zone, || field("zone"))?;
+                require(&cfg.destination, || field("destination"))?;
+                require(&cfg.region, || field("region"))?;
+                require(&cfg.tenancy, || field("tenancy"))?;
+                require(&cfg.user, || field("user"))?;
+                require(&cfg.fingerprint, || field("fingerprint"))?;
+                require(&cfg.private_key, || field("private_key"))?;
+                // Only CNAMEs are created
+                if resolve_destination {
+                    return Err(ConfigError::CnameResolved(field("type")));
+                }
+                cfg.region_url()?;
+                Ok(())
+            }
+            #[cfg(feature = "duckdns")]
+            Provider::DuckDns(ref cfg) => {
+                require(&cfg.domain, || field("domain"))?;
+                require(&cfg.destination, || field("destination"))?;
+                require(&cfg.token, || field("token"))?;
+                // DuckDNS only holds addresses
+                if cfg.destination.parse::<IpAddr>().is_err() {
+                    return Err(ConfigError::InvalidDestination(cfg.destination.clone()));
+                }
+                Ok(())
+            }
+            #[cfg(feature = "dynu")]
+            Provider::Dynu(ref cfg) => {
+                if cfg.domain_id == 0 {
+                    return Err(ConfigError::MissingField(field("domain_id")));
+                }
+                require(&cfg.destination, || field("destination"))?;
+                require(&cfg.api_key, || field("api_key"))?;
+                // Only CNAMEs are created
+                if resolve_destination {
+                    return Err(ConfigError::CnameResolved(field("type")));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Checks the additional zones of a provider, `is_set` telling whether an id is present.
+#[cfg(any(feature = "cf", feature = "aws", feature = "bunny"))]
+fn validate_zones<Id>(
+    zones: &[ZoneSettings<Id>],
+    field: impl Fn(&str) -> String,
+    is_set: impl Fn(&Id) -> bool,
+) -> Result<(), ConfigError> {
+    for (j, zone) in zones.iter().enumerate() {
+        if !is_set(&zone.id) {
+            return Err(ConfigError::MissingField(field(&format!(
+                "zones[{}].id",
+                j
+            ))));
+        }
+        require(&zone.suffix, || field(&format!("zones[{}].suffix", j)))?;
+    }
+    Ok(())
+}
+
+/// Parses a provider's `record_type` setting.
+#[cfg(any(feature = "cf", feature = "aws"))]
+fn parse_record_type(value: Option<&str>) -> Result<Option<crate::dns::RecordType>, ConfigError> {
+    value.map(str::parse).transpose().map_err(ConfigError::from)
+}
+
+/// Returns the addresses of a provider's dual-stack destination, empty if it isn't configured.
+pub fn dual_stack(v4: Option<Ipv4Addr>, v6: Option<Ipv6Addr>) -> Vec<IpAddr> {
+    v4.map(IpAddr::V4)
+        .into_iter()
+        .chain(v6.map(IpAddr::V6))
+        .collect()
+}
+
+/// Checks that the destinations of a provider can be held by records of `record_type`.
+#[cfg(any(feature = "cf", feature = "aws"))]
+fn validate_record_type<Id>(
+    record_type: Option<crate::dns::RecordType>,
+    destination: &str,
+    dual_stack: &[IpAddr],
+    zones: &[ZoneSettings<Id>],
+    resolve_destination: bool,
+    field: impl Fn(&str) -> String,
+) -> Result<(), ConfigError> {
+    use crate::dns::RecordType;
+
+    let Some(record_type) = record_type else {
+        return Ok(());
+    };
+    if record_type == RecordType::Cname && (resolve_destination || !dual_stack.is_empty()) {
+        return Err(ConfigError::CnameResolved(field("record_type")));
+    }
+
+    let destinations = std::iter::once(destination.to_string())
+        .chain(dual_stack.iter().map(IpAddr::to_string))
+        .chain(zones.iter().filter_map(|zone| zone.destination.clone()));
+    for destination in destinations {
+        // Hostnames are resolved, and only their addresses of the right family are used
+        let Ok(addr) = destination.parse::<IpAddr>() else {
+            continue;
+        };
+        if !record_type.holds(&addr) {
+            return Err(ConfigError::RecordTypeMismatch {
+                field: field("record_type"),
+                record_type,
+                destination,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Reads the secret `name` from `path`, trimming surrounding whitespace.
+fn read_secret(name: &str, inline: bool, path: &Path) -> Result<String, ConfigError> {
+    if inline {
+        warn!(
+            "Both {} and {}_file are set, using {}_file",
+            name, name, name
+        );
+    }
+    debug!("Reading {} from {}", name, path.display());
+
+    let secret = std::fs::read_to_string(path).map_err(|source| ConfigError::SecretFile {
+        path: path.to_owned(),
+        source,
+    })?;
+    Ok(secret.trim().to_string())
+}
+
+/// Returns an error naming `field` if `value` is empty.
+fn require(value: &str, field: impl FnOnce() -> String) -> Result<(), ConfigError> {
+    if value.trim().is_empty() {
+        return Err(ConfigError::MissingField(field()));
+    }
+    Ok(())
+}
+
+fn parse_duration(field: &'static str, value: &str) -> Result<(), ConfigError> {
+    humantime::parse_duration(value)
+        .map(drop)
+        .map_err(|source| ConfigError::InvalidDuration { field, source })
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraefikSettings {
+    /// Subdomains tried against `HostRegexp` rules to find concrete hosts.
+    pub hostregexp_subdomains: Vec<String>,
+    /// Keep routers of Traefik's `internal` provider, like `api@internal`.
+    pub include_internal: bool,
+    /// Router label holding the TTL of its records, `traefik-dns-rs.ttl` by default.
+    pub ttl_label: Option<String>,
+    /// Router label holding whether its records are proxied, `traefik-dns-rs.proxied` by default.
+    pub proxied_label: Option<String>,
+    /// Path of the Traefik API relative to `traefik_url`, `api` by default. Routers are listed
+    /// from `<api_path>/http/routers` & `<api_path>/tcp/routers`.
+    pub api_path: Option<String>,
+    /// Timeout of requests to the Traefik API, `30s` by default.
+    pub timeout: Option<String>,
+    /// Retries of Traefik API requests failing with connection errors or 5xx, 2 by default.
+    pub max_retries: Option<u32>,
+    /// PEM file of a CA trusted in addition to the system's, for Traefik behind an internal CA.
+    pub ca_cert: Option<PathBuf>,
+    /// Skips TLS certificate verification of the Traefik API. Anyone able to intercept its
+    /// traffic can then inject routes, so prefer `ca_cert`.
+    pub danger_accept_invalid_certs: bool,
+    /// Unix socket of the Traefik API, connected to instead of the host of `traefik_url`, which
+    /// then only gives the `Host` header & path of requests, e.g. `http://localhost`.
+    pub unix_socket: Option<PathBuf>,
+    /// Credentials of the Traefik API, if it's protected.
+    pub auth: TraefikAuthSettings,
+    /// Basic auth credentials of the Traefik API, instead of `auth`.
+    pub basic_auth: Option<BasicAuthSettings>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraefikAuthSettings {
+    /// Bearer token sent in the `Authorization` header.
+    pub token: Option<String>,
+    /// File to read `token` from, taking precedence over it.
+    pub token_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BasicAuthSettings {
+    pub username: String,
+    pub password: Option<String>,
+    /// File to read `password` from, taking precedence over it.
+    pub password_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterSettings {
+    /// Only manage hosts under these suffixes, or every host if empty.
+    pub include_suffixes: Vec<String>,
+    /// Never manage these hosts.
+    pub exclude_hosts: Vec<String>,
+    /// Never manage hosts matching this regex.
+    pub exclude_regex: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrySettings {
+    pub max_attempts: u32,
+    pub base_delay: String,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: "500ms".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErrorBackoffSettings {
+    pub max_interval: String,
+    pub multiplier: f64,
+}
+
+impl Default for ErrorBackoffSettings {
+    fn default() -> Self {
+        Self {
+            max_interval: "5m".to_string(),
+            multiplier: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerSettings {
+    /// Consecutive reconciles failing with a provider error that open the breaker.
+    pub failure_threshold: u32,
+    /// Reconciles are skipped for this long once open, before the provider is tried again.
+    pub cooldown: String,
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: "5m".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Settings {
+    #[serde(default)]
     pub traefik_url: String,
+    /// Further Traefik instances, whose routes are merged with the ones of `traefik_url`.
+    #[serde(default)]
+    pub traefik_urls: Vec<String>,
     pub update_interval: String,
-    pub provider: Option<Provider>,
+    /// Wait this long before the first reconcile, for a Traefik starting alongside us.
+    pub initial_delay: Option<String>,
+    #[serde(default)]
+    pub traefik: TraefikSettings,
+    #[serde(default)]
+    pub providers: Vec<Provider>,
+
+    #[serde(default)]
+    pub filters: FilterSettings,
+    #[serde(default)]
+    pub retry: RetrySettings,
+    /// Backoff of the update interval after consecutive failed reconciles.
+    #[serde(default)]
+    pub error_backoff: ErrorBackoffSettings,
+    /// Stop reconciling for a while after consecutive provider errors, failing readiness.
+    pub circuit_breaker: Option<CircuitBreakerSettings>,
+    /// Reuse the listed records for this long, instead of listing them on every reconcile.
+    pub list_cache_ttl: Option<String>,
+    /// Maximum number of concurrent creates (and, separately, deletes).
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    /// Maximum number of record changes started per `rate_limit_period`, shared by every
+    /// provider. Unset doesn't pace the changes.
+    pub rate_limit: Option<u64>,
+    /// Defaults to `1s`.
+    pub rate_limit_period: Option<String>,
+    /// Skip the deletions of a reconcile that would delete more than this fraction of the
+    /// managed records. `1.0` or unset never skips them.
+    pub max_delete_ratio: Option<f64>,
+    /// Delete the records of removed routes. If disabled, records are only ever created.
+    #[serde(default = "default_prune")]
+    pub prune: bool,
+
+    /// Address to serve the `/healthz`, `/readyz` & `/metrics` endpoints on.
+    pub health_addr: Option<SocketAddr>,
+    /// `/readyz` fails if the last successful reconcile is older than this.
+    pub max_reconcile_staleness: Option<String>,
+    /// URL POSTed the created & deleted hosts after every reconcile changing records.
+    pub webhook_url: Option<String>,
+    /// URL GET after every successful reconcile, like a healthchecks.io check, which alarms
+    /// once reconciles stop succeeding.
+    pub heartbeat_url: Option<String>,
+    /// HTTP proxy every request to Traefik, the DNS providers & the above URLs is sent through.
+    pub http_proxy: Option<String>,
+    /// User agent of every HTTP request, defaulting to `traefik-dns-rs/<version>`.
+    pub user_agent: Option<String>,
+
+    /// Only manage records carrying an ownership marker with this id.
+    pub owner_id: Option<String>,
+    /// Write & require ownership markers, using the default owner id if `owner_id` is unset.
+    #[serde(default)]
+    pub manage_ownership: bool,
+
+    /// Resolve the destination and create A/AAAA records to its addresses instead of CNAMEs.
+    #[serde(default)]
+    pub resolve_destination: bool,
+
+    /// Delete the records of every managed host on shutdown.
+    #[serde(default)]
+    pub cleanup_on_exit: bool,
+    /// Save the managed hosts to this file, so restarts don't create every record again.
+    pub state_file: Option<PathBuf>,
+}
+
+fn default_max_concurrency() -> usize {
+    crate::dns::DEFAULT_MAX_CONCURRENCY
 }
 
+fn default_prune() -> bool {
+    true
+}
+
+const DEFAULT_RATE_LIMIT_PERIOD: std::time::Duration = std::time::Duration::from_secs(1);
+
 impl Settings {
-    pub fn new() -> Result<Self, ConfigError> {
-        let path = Self::find_config().ok_or(ConfigError::NoConfigFound)?;
+    /// Loads settings from `paths`, each deep-merged over the previous ones, see
+    /// [`Settings::parse_merged`]. If `paths` is empty, they are read from `TDR_CONFIG_PATHS`, a
+    /// list separated like `PATH`, or else the first config found is loaded.
+    ///
+    /// Environment variables prefixed with `TDR_` override the files, see [`Settings::parse`].
+    pub fn new(paths: &[PathBuf]) -> Result<Self, ConfigError> {
+        let paths = if !paths.is_empty() {
+            paths.to_vec()
+        } else if let Some(paths) = std::env::var_os(CONFIG_PATHS_VAR) {
+            std::env::split_paths(&paths)
+                .filter(|path| !path.as_os_str().is_empty())
+                .collect()
+        } else {
+            vec![Self::find_config().ok_or(ConfigError::NoConfigFound)?]
+        };
+
+        let mut layers = Vec::with_capacity(paths.len());
+        for path in &paths {
+            if !path.exists() {
+                return Err(ConfigError::ConfigNotFound(path.clone()));
+            }
+            info!("Loading settings from {}", path.display());
+            layers.push((
+                ConfigFormat::from_path(path)?,
+                std::fs::read_to_string(path)?,
+            ));
+        }
+        let vars = std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)));
+
+        let layers = layers
+            .iter()
+            .map(|(format, contents)| (*format, contents.as_str()));
+        let mut settings = Self::parse_merged(layers, vars)?;
+        settings.load_secrets()?;
+        Ok(settings)
+    }
+
+    /// Parses the TOML `contents`, overridden by the `TDR_` prefixed variables of `vars`.
+    ///
+    /// Nested keys are separated by `__`, e.g. `TDR_TRAEFIK__INCLUDE_INTERNAL`. Provider keys
+    /// are set on the first provider of the given type, which is added if missing, e.g.
+    /// `TDR_PROVIDER__CLOUDFLARE__TOKEN`.
+    ///
+    /// Values are parsed as TOML values, falling back to strings, unless they override a string.
+    pub fn parse(
+        contents: &str,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        Self::parse_as(ConfigFormat::Toml, contents, vars)
+    }
+
+    /// Parses `contents` in `format`, overridden by the `TDR_` prefixed variables of `vars` like
+    /// [`Settings::parse`].
+    pub fn parse_as(
+        format: ConfigFormat,
+        contents: &str,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        Self::parse_merged([(format, contents)], vars)
+    }
+
+    /// Parses the config files `layers`, each deep-merged over the previous ones, then overridden
+    /// by the `TDR_` prefixed variables of `vars` like [`Settings::parse`].
+    ///
+    /// Tables are merged key by key. A provider is merged into the earlier provider of the same
+    /// `type`, or the one at the same index if it has no `type`, so a file can hold only the
+    /// credentials of the providers. Other values, including arrays, replace earlier ones.
+    pub fn parse_merged<'a>(
+        layers: impl IntoIterator<Item = (ConfigFormat, &'a str)>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let mut settings = Table::new();
+        for (format, contents) in layers {
+            let layer: Table = match format {
+                ConfigFormat::Toml => toml::from_str(contents)?,
+                #[cfg(feature = "yaml")]
+                ConfigFormat::Yaml => serde_yaml::from_str(contents)?,
+            };
+            merge(&mut settings, layer);
+        }
+
+        for (key, value) in vars {
+            if key == CONFIG_PATHS_VAR {
+                continue;
+            }
+            if let Some(path) = key.strip_prefix(ENV_PREFIX) {
+                debug!("Overriding settings with {}", key);
+                apply_env(&mut settings, path, value);
+            }
+        }
 
-        info!("Loading settings from {}", path.display());
+        Ok(Value::Table(settings).try_into()?)
+    }
+
+    /// Reads the provider secrets configured as `*_file` paths.
+    ///
+    /// AWS credentials are loaded by the SDK, which reads `AWS_SHARED_CREDENTIALS_FILE`.
+    #[cfg_attr(
+        not(any(
+            feature = "cf",
+            feature = "aws",
+            feature = "bunny",
+            feature = "alidns",
+            feature = "duckdns",
+            feature = "dynu",
+            feature = "he",
+            feature = "ibmcloud",
+            feature = "namecheap",
+            feature = "oci",
+            feature = "zonefile"
+        )),
+        allow(clippy::never_loop)
+    )]
+    pub fn load_secrets(&mut self) -> Result<(), ConfigError> {
+        let auth = &mut self.traefik.auth;
+        if let Some(path) = &auth.token_file {
+            auth.token = Some(read_secret("token", auth.token.is_some(), path)?);
+        }
+        if let Some(basic) = &mut self.traefik.basic_auth {
+            if let Some(path) = &basic.password_file {
+                basic.password = Some(read_secret("password", basic.password.is_some(), path)?);
+            }
+        }
 
-        let contents = std::fs::read_to_string(&path)?;
+        for provider in &mut self.providers {
+            match *provider {
+                #[cfg(feature = "aws")]
+                Provider::Route53(_) => {}
+                #[cfg(feature = "cf")]
+                Provider::Cloudflare(ref mut cfg) => {
+                    if let Some(path) = &cfg.token_file {
+                        cfg.token = Some(read_secret("token", cfg.token.is_some(), path)?);
+                    }
+                    if let Some(path) = &cfg.api_key_file {
+                        cfg.api_key = Some(read_secret("api_key", cfg.api_key.is_some(), path)?);
+                    }
+                }
+                #[cfg(feature = "bunny")]
+                Provider::Bunny(ref mut cfg) => {
+                    if let Some(path) = &cfg.api_key_file {
+                        cfg.api_key = read_secret("api_key", !cfg.api_key.is_empty(), path)?;
+                    }
+                }
+                #[cfg(feature = "alidns")]
+                Provider::AliDns(ref mut cfg) => {
+                    if let Some(path) = &cfg.access_key_secret_file {
+                        cfg.access_key_secret = read_secret(
+                            "access_key_secret",
+                            !cfg.access_key_secret.is_empty(),
+                            path,
+                        )?;
+                    }
+                }
+                #[cfg(feature = "zonefile")]
+                Provider::Zonefile(_) => {}
+                #[cfg(feature = "he")]
+                Provider::He(ref mut cfg) => {
+                    if let Some(path) = &cfg.password_file {
+                        cfg.password = read_secret("password", !cfg.password.is_empty(), path)?;
+                    }
+                }
+                #[cfg(feature = "namecheap")]
+                Provider::Namecheap(ref mut cfg) => {
+                    if let Some(path) = &cfg.api_key_file {
+                        cfg.api_key = read_secret("api_key", !cfg.api_key.is_empty(), path)?;
+                    }
+                }
+                #[cfg(feature = "ibmcloud")]
+                Provider::IbmCloud(ref mut cfg) => {
+                    if let Some(path) = &cfg.api_key_file {
+                        cfg.api_key = read_secret("api_key", !cfg.api_key.is_empty(), path)?;
+                    }
+                }
+                #[cfg(feature = "oci")]
+                Provider::Oci(ref mut cfg) => {
+                    if let Some(path) = &cfg.private_key_file {
+                        cfg.private_key =
+                            read_secret("private_key", !cfg.private_key.is_empty(), path)?;
+                    }
+                }
+                #[cfg(feature = "duckdns")]
+                Provider::DuckDns(ref mut cfg) => {
+                    if let Some(path) = &cfg.token_file {
+                        cfg.token = read_secret("token", !cfg.token.is_empty(), path)?;
+                    }
+                }
+                #[cfg(feature = "dynu")]
+                Provider::Dynu(ref mut cfg) => {
+                    if let Some(path) = &cfg.api_key_file {
+                        cfg.api_key = read_secret("api_key", !cfg.api_key.is_empty(), path)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 
-        Ok(toml::from_str(&contents)?)
+    /// Returns the URLs of all Traefik instances, `traefik_url` first.
+    pub fn traefik_urls(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.traefik_url.as_str())
+            .chain(self.traefik_urls.iter().map(String::as_str))
+            .filter(|url| !url.trim().is_empty())
+    }
+
+    /// Returns the rate limit of the record changes, or `None` if they aren't paced.
+    pub fn rate_limit(&self) -> Result<Option<crate::dns::rate_limit::RateLimit>, ConfigError> {
+        let Some(limit) = self.rate_limit else {
+            return Ok(None);
+        };
+        let period = match &self.rate_limit_period {
+            Some(period) => humantime::parse_duration(period).map_err(|source| {
+                ConfigError::InvalidDuration {
+                    field: "rate_limit_period",
+                    source,
+                }
+            })?,
+            None => DEFAULT_RATE_LIMIT_PERIOD,
+        };
+        Ok(Some(crate::dns::rate_limit::RateLimit::new(limit, period)))
+    }
+
+    /// Checks the settings, so mistakes are reported at startup rather than on first use.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut urls = self.traefik_urls().peekable();
+        if urls.peek().is_none() {
+            return Err(ConfigError::MissingField("traefik_url".to_string()));
+        }
+        for url in urls {
+            url::Url::parse(url)?;
+        }
+
+        require(&self.update_interval, || "update_interval".to_string())?;
+        parse_duration("update_interval", &self.update_interval)?;
+        parse_duration("retry.base_delay", &self.retry.base_delay)?;
+        parse_duration(
+            "error_backoff.max_interval",
+            &self.error_backoff.max_interval,
+        )?;
+        if self.error_backoff.multiplier.is_nan() || self.error_backoff.multiplier < 1.0 {
+            return Err(ConfigError::InvalidMultiplier(
+                self.error_backoff.multiplier,
+            ));
+        }
+        if let Some(breaker) = &self.circuit_breaker {
+            if breaker.failure_threshold == 0 {
+                return Err(ConfigError::InvalidFailureThreshold);
+            }
+            parse_duration("circuit_breaker.cooldown", &breaker.cooldown)?;
+        }
+        if let Some(ratio) = self.max_delete_ratio {
+            if !(0.0..=1.0).contains(&ratio) {
+                return Err(ConfigError::InvalidDeleteRatio(ratio));
+            }
+        }
+        if let Some(staleness) = &self.max_reconcile_staleness {
+            parse_duration("max_reconcile_staleness", staleness)?;
+        }
+        if let Some(url) = &self.webhook_url {
+            url::Url::parse(url).map_err(ConfigError::InvalidWebhookUrl)?;
+        }
+        if let Some(url) = &self.heartbeat_url {
+            url::Url::parse(url).map_err(ConfigError::InvalidHeartbeatUrl)?;
+        }
+        if let Some(url) = &self.http_proxy {
+            url::Url::parse(url).map_err(ConfigError::InvalidProxyUrl)?;
+        }
+        if let Some(user_agent) = &self.user_agent {
+            reqwest::header::HeaderValue::from_str(user_agent)
+                .map_err(|_| ConfigError::InvalidUserAgent(user_agent.clone()))?;
+        }
+        if let Some(ttl) = &self.list_cache_ttl {
+            parse_duration("list_cache_ttl", ttl)?;
+        }
+        if let Some(delay) = &self.initial_delay {
+            parse_duration("initial_delay", delay)?;
+        }
+        self.rate_limit()?;
+        if let Some(timeout) = &self.traefik.timeout {
+            parse_duration("traefik.timeout", timeout)?;
+        }
+        if let Some(basic) = &self.traefik.basic_auth {
+            if self.traefik.auth.token.is_some() {
+                return Err(ConfigError::ConflictingTraefikAuth);
+            }
+            require(&basic.username, || {
+                "traefik.basic_auth.username".to_string()
+            })?;
+        }
+
+        if self.providers.is_empty() {
+            return Err(ConfigError::NoProvider);
+        }
+        for (i, provider) in self.providers.iter().enumerate() {
+            provider.validate(i, self.resolve_destination)?;
+        }
+
+        Ok(())
     }
 
     fn find_config() -> Option<PathBuf> {
@@ -68,25 +1392,1415 @@ impl Settings {
             Path::new("/etc/traefik-dns-rs"),
         ];
         for path in paths {
-            let config_path = path.join("config.toml");
-            debug!("Checking for config at {}", config_path.display());
-            if config_path.exists() {
-                debug!("Found config at {}", config_path.display());
-                return Some(config_path);
-            } else {
-                debug!("No config found at {}", config_path.display());
+            for name in CONFIG_NAMES {
+                let config_path = path.join(name);
+                debug!("Checking for config at {}", config_path.display());
+                if config_path.exists() {
+                    debug!("Found config at {}", config_path.display());
+                    return Some(config_path);
+                } else {
+                    debug!("No config found at {}", config_path.display());
+                }
             }
         }
         None
     }
 }
 
+/// Commented template of a config file, written by `generate-config`.
+pub const CONFIG_TEMPLATE: &str = include_str!("config.template.toml");
+
+/// Returns the path of the config file in the platform's config directory.
+pub fn default_config_path() -> PathBuf {
+    PROJECT_DIRS.config_dir().join("config.toml")
+}
+
+/// Writes [`CONFIG_TEMPLATE`] to `path`, creating its parent directories. Existing files are only
+/// replaced if `force` is set.
+pub fn generate_config(path: &Path, force: bool) -> Result<(), ConfigError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    let mut file = options.open(path).map_err(|err| match err.kind() {
+        std::io::ErrorKind::AlreadyExists => ConfigError::ConfigExists(path.to_owned()),
+        _ => ConfigError::IoError(err),
+    })?;
+    std::io::Write::write_all(&mut file, CONFIG_TEMPLATE.as_bytes())?;
+    Ok(())
+}
+
+/// Names of the config files searched for, in order of preference.
+const CONFIG_NAMES: &[&str] = &[
+    "config.toml",
+    #[cfg(feature = "yaml")]
+    "config.yaml",
+    #[cfg(feature = "yaml")]
+    "config.yml",
+];
+
+/// Format of a config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    /// YAML, which can't use `null` values as TOML has none.
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detects the format of the config file at `path` from its extension.
+    pub fn from_path(path: &Path) -> Result<Self, ConfigError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Ok(Self::Toml),
+            #[cfg(feature = "yaml")]
+            Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+                Ok(Self::Yaml)
+            }
+            _ => Err(ConfigError::UnsupportedFormat(path.to_owned())),
+        }
+    }
+}
+
+/// Deep-merges `overlay` over `base`, see [`Settings::parse_merged`].
+fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        let value = match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => {
+                merge(base, overlay);
+                continue;
+            }
+            (Some(Value::Array(base)), Value::Array(overlay)) if key == "providers" => {
+                merge_providers(base, overlay);
+                continue;
+            }
+            (_, value) => value,
+        };
+        base.insert(key, value);
+    }
+}
+
+/// Merges every provider of `overlay` into the provider of `base` with the same `type`, or at
+/// the same index if it has none, appending it if there is no such provider.
+fn merge_providers(base: &mut Vec<Value>, overlay: Vec<Value>) {
+    for (i, provider) in overlay.into_iter().enumerate() {
+        let Value::Table(provider) = provider else {
+            base.push(provider);
+            continue;
+        };
+
+        let position = match provider.get("type").and_then(Value::as_str) {
+            Some(ty) => base.iter().position(|p| {
+                p.get("type")
+                    .and_then(Value::as_str)
+                    .is_some_and(|t| t.eq_ignore_ascii_case(ty))
+            }),
+            None => (i < base.len()).then_some(i),
+        };
+        match position.map(|i| &mut base[i]) {
+            Some(Value::Table(base)) => merge(base, provider),
+            _ => base.push(Value::Table(provider)),
+        }
+    }
+}
+
+/// Sets the key at the `__` separated `path` to `value`.
+fn apply_env(settings: &mut Table, path: &str, value: String) {
+    let path: Vec<_> = path.split("__").map(str::to_lowercase).collect();
+
+    match path.as_slice() {
+        [providers, ty, rest @ ..] if providers == "provider" || providers == "providers" => {
+            let Some(ty) = PROVIDER_TYPES.iter().find(|t| t.eq_ignore_ascii_case(ty)) else {
+                warn!("Unknown provider type {} in environment override", ty);
+                return;
+            };
+            let Value::Array(providers) = settings
+                .entry("providers")
+                .or_insert_with(|| Value::Array(Vec::new()))
+            else {
+                warn!("Cannot override providers, as it is not an array");
+                return;
+            };
+
+            let position = providers.iter().position(|p| {
+                p.get("type")
+                    .and_then(Value::as_str)
+                    .is_some_and(|t| t.eq_ignore_ascii_case(ty))
+            });
+            let provider = match position {
+                Some(i) => &mut providers[i],
+                None => {
+                    let mut provider = Table::new();
+                    provider.insert("type".to_string(), Value::String(ty.to_string()));
+                    providers.push(Value::Table(provider));
+                    providers.last_mut().unwrap()
+                }
+            };
+
+            match provider {
+                Value::Table(provider) => set_path(provider, rest, value),
+                _ => warn!("Cannot override {} provider, as it is not a table", ty),
+            }
+        }
+        path => set_path(settings, path, value),
+    }
+}
+
+fn set_path(table: &mut Table, path: &[String], value: String) {
+    let Some((key, parents)) = path.split_last() else {
+        return;
+    };
+
+    let mut table = table;
+    for parent in parents {
+        match table
+            .entry(parent.as_str())
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            Value::Table(t) => table = t,
+            _ => {
+                warn!(
+                    "Cannot override {}, as {} is not a table",
+                    path.join("."),
+                    parent
+                );
+                return;
+            }
+        }
+    }
+
+    let value = match table.get(key) {
+        Some(Value::String(_)) => Value::String(value),
+        _ => parse_value(value),
+    };
+    table.insert(key.clone(), value);
+}
+
+/// Parses `value` as a TOML value, or as a string if it isn't one.
+fn parse_value(value: String) -> Value {
+    toml::from_str::<Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut t| t.remove("value"))
+        .unwrap_or(Value::String(value))
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
     #[error("No config file found")]
     NoConfigFound,
+    #[error("Config file {} does not exist", .0.display())]
+    ConfigNotFound(PathBuf),
+    #[error("Config file {} already exists, use --force to replace it", .0.display())]
+    ConfigExists(PathBuf),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
+    #[error("Unable to read secret file {}: {source}", path.display())]
+    SecretFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Unsupported config file format {}", .0.display())]
+    UnsupportedFormat(PathBuf),
     #[error(transparent)]
     TomlError(#[from] toml::de::Error),
+    #[cfg(feature = "yaml")]
+    #[error(transparent)]
+    YamlError(#[from] serde_yaml::Error),
+    #[error("Missing required setting {0}")]
+    MissingField(String),
+    #[error("Invalid traefik_url: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+    #[error("Invalid webhook_url: {0}")]
+    InvalidWebhookUrl(url::ParseError),
+    #[error("Invalid heartbeat_url: {0}")]
+    InvalidHeartbeatUrl(url::ParseError),
+    #[error("Invalid http_proxy: {0}")]
+    InvalidProxyUrl(url::ParseError),
+    #[error("Invalid user_agent {0:?}")]
+    InvalidUserAgent(String),
+    #[error("Invalid {field} {ttl}: must be {expected}")]
+    InvalidTtl {
+        field: String,
+        ttl: i64,
+        expected: &'static str,
+    },
+    #[error("Invalid base_url: {0}")]
+    InvalidBaseUrl(url::ParseError),
+    #[error("Invalid endpoint_url: {0}")]
+    InvalidEndpointUrl(url::ParseError),
+    #[error("Invalid region {0}")]
+    InvalidRegion(String),
+    #[error("Invalid client_ip {0}")]
+    InvalidClientIp(String),
+    #[error("Invalid destination {0}: must be an IP address")]
+    InvalidDestination(String),
+    #[error("Invalid duration for {field}: {source}")]
+    InvalidDuration {
+        field: &'static str,
+        source: humantime::DurationError,
+    },
+    #[error("Invalid error_backoff.multiplier {0}: must be at least 1")]
+    InvalidMultiplier(f64),
+    #[error("Invalid circuit_breaker.failure_threshold: must be at least 1")]
+    InvalidFailureThreshold,
+    #[error("Invalid max_delete_ratio {0}: must be between 0 and 1")]
+    InvalidDeleteRatio(f64),
+    #[error("Invalid record_type: {0}")]
+    InvalidRecordType(#[from] crate::dns::UnknownRecordType),
+    #[error("{field} is {record_type}, which can't point at {destination}")]
+    RecordTypeMismatch {
+        field: String,
+        record_type: crate::dns::RecordType,
+        destination: String,
+    },
+    #[error(
+        "{0} is CNAME, which can't be used with resolve_destination or a dual-stack destination"
+    )]
+    CnameResolved(String),
+    #[error("traefik.auth.token and traefik.basic_auth can't both be set")]
+    ConflictingTraefikAuth,
+    #[error("No provider configured")]
+    NoProvider,
+    #[error("Missing {provider} credentials: {required} must be set")]
+    MissingCredentials {
+        provider: &'static str,
+        required: &'static str,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_config() {
+        let path = Path::new("/nonexistent/config.toml");
+        let err = Settings::new(&[path.to_owned()]).unwrap_err();
+        assert!(matches!(err, ConfigError::ConfigNotFound(p) if p == path));
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            traefik_url: "http://traefik:8080".to_string(),
+            update_interval: "1m".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(feature = "cf")]
+    fn cloudflare() -> CloudflareSettings {
+        CloudflareSettings {
+            zone_id: "zone".to_string(),
+            destination: "example.com".to_string(),
+            token: Some("token".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_traefik_url() {
+        let mut cfg = settings();
+        cfg.traefik_url = String::new();
+        assert!(matches!(cfg.validate(), Err(ConfigError::MissingField(f)) if f == "traefik_url"));
+
+        cfg.traefik_url = "traefik".to_string();
+        assert!(matches!(cfg.validate(), Err(ConfigError::InvalidUrl(_))));
+
+        // Further instances replace the main one
+        cfg.traefik_url = String::new();
+        cfg.traefik_urls = vec!["http://traefik-dmz:8080".to_string()];
+        assert!(matches!(cfg.validate(), Err(ConfigError::NoProvider)));
+        cfg.traefik_urls.push("traefik".to_string());
+        assert!(matches!(cfg.validate(), Err(ConfigError::InvalidUrl(_))));
+    }
+
+    #[test]
+    fn test_validate_durations() {
+        let mut cfg = settings();
+        cfg.update_interval = "soon".to_string();
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidDuration {
+                field: "update_interval",
+                ..
+            })
+        ));
+
+        let mut cfg = settings();
+        cfg.max_reconcile_staleness = Some("5".to_string());
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidDuration {
+                field: "max_reconcile_staleness",
+                ..
+            })
+        ));
+
+        let mut cfg = settings();
+        cfg.traefik.timeout = Some("forever".to_string());
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidDuration {
+                field: "traefik.timeout",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_validate_error_backoff() {
+        let mut cfg = settings();
+        cfg.error_backoff.multiplier = 0.5;
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidMultiplier(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_circuit_breaker() {
+        let mut cfg = settings();
+        cfg.circuit_breaker = Some(CircuitBreakerSettings::default());
+        assert!(!matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidFailureThreshold | ConfigError::InvalidDuration { .. })
+        ));
+
+        cfg.circuit_breaker = Some(CircuitBreakerSettings {
+            failure_threshold: 0,
+            ..Default::default()
+        });
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidFailureThreshold)
+        ));
+
+        cfg.circuit_breaker = Some(CircuitBreakerSettings {
+            cooldown: "soon".to_string(),
+            ..Default::default()
+        });
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidDuration {
+                field: "circuit_breaker.cooldown",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_validate_max_delete_ratio() {
+        let mut cfg = settings();
+        cfg.max_delete_ratio = Some(1.0);
+        assert!(!matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidDeleteRatio(_))
+        ));
+
+        for ratio in [-0.1, 1.5, f64::NAN] {
+            cfg.max_delete_ratio = Some(ratio);
+            assert!(matches!(
+                cfg.validate(),
+                Err(ConfigError::InvalidDeleteRatio(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validate_no_provider() {
+        assert!(matches!(
+            settings().validate(),
+            Err(ConfigError::NoProvider)
+        ));
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_validate_cloudflare() {
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::Cloudflare(cloudflare())];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            token: None,
+            email: Some("user@example.com".to_string()),
+            ..cloudflare()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::MissingCredentials {
+                provider: "Cloudflare",
+                ..
+            })
+        ));
+
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            zone_id: String::new(),
+            ..cloudflare()
+        })];
+        assert!(
+            matches!(cfg.validate(), Err(ConfigError::MissingField(f)) if f == "providers[0].zone_id")
+        );
+
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            zone_id: String::new(),
+            zone_name: Some("example.com".to_string()),
+            ..cloudflare()
+        })];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            destination: " ".to_string(),
+            ..cloudflare()
+        })];
+        assert!(
+            matches!(cfg.validate(), Err(ConfigError::MissingField(f)) if f == "providers[0].destination")
+        );
+
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            ttl: Some(30),
+            ..cloudflare()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidTtl { field, ttl: 30, .. }) if field == "providers[0].ttl"
+        ));
+
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            zones: vec![ZoneSettings {
+                id: "other".to_string(),
+                suffix: "example.net".to_string(),
+                ttl: Some(100_000),
+                ..Default::default()
+            }],
+            ..cloudflare()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidTtl { field, ttl: 100_000, .. }) if field == "providers[0].zones[0].ttl"
+        ));
+
+        for ttl in [1, 60, 86400] {
+            cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+                ttl: Some(ttl),
+                ..cloudflare()
+            })];
+            cfg.validate().unwrap();
+        }
+    }
+
+    #[cfg(feature = "aws")]
+    #[test]
+    fn test_validate_route53_ttl() {
+        let route53 = |ttl| Route53Settings {
+            zone_id: "zone".to_string(),
+            destination: "example.com".to_string(),
+            ttl,
+            ..Default::default()
+        };
+        let mut cfg = settings();
+
+        for ttl in [None, Some(0), Some(300), Some(i64::from(i32::MAX))] {
+            cfg.providers = vec![Provider::Route53(route53(ttl))];
+            cfg.validate().unwrap();
+        }
+
+        cfg.providers = vec![Provider::Route53(route53(Some(-1)))];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidTtl { field, ttl: -1, .. }) if field == "providers[0].ttl"
+        ));
+
+        cfg.providers = vec![Provider::Route53(route53(Some(i64::from(i32::MAX) + 1)))];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidTtl { .. })
+        ));
+    }
+
+    #[cfg(feature = "aws")]
+    #[test]
+    fn test_route53_assume_role() {
+        let route53 = Route53Settings {
+            zone_id: "zone".to_string(),
+            destination: "example.com".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(route53.assume_role(), None);
+
+        let cfg = Route53Settings {
+            assume_role_arn: Some("arn:aws:iam::123456789012:role/dns".to_string()),
+            assume_role_external_id: Some("external".to_string()),
+            ..route53
+        };
+        assert_eq!(
+            cfg.assume_role(),
+            Some(crate::dns::route53::AssumeRole {
+                role_arn: "arn:aws:iam::123456789012:role/dns".to_string(),
+                external_id: Some("external".to_string()),
+                session_name: None,
+            })
+        );
+        let mut settings = settings();
+        settings.providers = vec![Provider::Route53(cfg)];
+        settings.validate().unwrap();
+
+        // An external id without a role to assume is a mistake
+        settings.providers = vec![Provider::Route53(Route53Settings {
+            zone_id: "zone".to_string(),
+            destination: "example.com".to_string(),
+            assume_role_external_id: Some("external".to_string()),
+            ..Default::default()
+        })];
+        assert!(matches!(
+            settings.validate(),
+            Err(ConfigError::MissingField(field)) if field == "providers[0].assume_role_arn"
+        ));
+    }
+
+    #[cfg(feature = "alidns")]
+    #[test]
+    fn test_validate_alidns() {
+        let alidns = || AliDnsSettings {
+            domain: "example.com".to_string(),
+            destination: "traefik.example.com".to_string(),
+            access_key_id: "id".to_string(),
+            access_key_secret: "secret".to_string(),
+            ..Default::default()
+        };
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::AliDns(AliDnsSettings {
+            ttl: Some(600),
+            region: Some("ap-southeast-1".to_string()),
+            ..alidns()
+        })];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::AliDns(AliDnsSettings {
+            access_key_secret: String::new(),
+            ..alidns()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::MissingField(field)) if field == "providers[0].access_key_secret"
+        ));
+
+        cfg.providers = vec![Provider::AliDns(AliDnsSettings {
+            ttl: Some(0),
+            ..alidns()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidTtl { ttl: 0, .. })
+        ));
+
+        cfg.providers = vec![Provider::AliDns(AliDnsSettings {
+            region: Some("ap southeast".to_string()),
+            ..alidns()
+        })];
+        assert!(matches!(cfg.validate(), Err(ConfigError::InvalidRegion(_))));
+
+        cfg.providers = vec![Provider::AliDns(alidns())];
+        cfg.resolve_destination = true;
+        assert!(matches!(cfg.validate(), Err(ConfigError::CnameResolved(_))));
+    }
+
+    #[cfg(feature = "he")]
+    #[test]
+    fn test_validate_he() {
+        let he = || HeSettings {
+            domain: "example.com".to_string(),
+            destination: "traefik.example.com".to_string(),
+            username: "user".to_string(),
+            password: "pass".to_string(),
+            ..Default::default()
+        };
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::He(HeSettings {
+            ttl: Some(3600),
+            ..he()
+        })];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::He(HeSettings {
+            password: String::new(),
+            ..he()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::MissingField(field)) if field == "providers[0].password"
+        ));
+
+        cfg.providers = vec![Provider::He(HeSettings {
+            ttl: Some(600),
+            ..he()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidTtl { ttl: 600, .. })
+        ));
+    }
+
+    #[cfg(feature = "namecheap")]
+    #[test]
+    fn test_validate_namecheap() {
+        let namecheap = || NamecheapSettings {
+            domain: "example.com".to_string(),
+            destination: "traefik.example.com".to_string(),
+            api_user: "user".to_string(),
+            api_key: "key".to_string(),
+            client_ip: "192.0.2.10".to_string(),
+            ..Default::default()
+        };
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::Namecheap(NamecheapSettings {
+            ttl: Some(300),
+            ..namecheap()
+        })];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::Namecheap(NamecheapSettings {
+            client_ip: String::new(),
+            ..namecheap()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::MissingField(field)) if field == "providers[0].client_ip"
+        ));
+
+        cfg.providers = vec![Provider::Namecheap(NamecheapSettings {
+            client_ip: "localhost".to_string(),
+            ..namecheap()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidClientIp(_))
+        ));
+
+        cfg.providers = vec![Provider::Namecheap(NamecheapSettings {
+            ttl: Some(30),
+            ..namecheap()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidTtl { ttl: 30, .. })
+        ));
+    }
+
+    #[cfg(feature = "ibmcloud")]
+    #[test]
+    fn test_validate_ibmcloud() {
+        let ibmcloud = || IbmCloudSettings {
+            instance_id: "instance".to_string(),
+            zone_id: "zone".to_string(),
+            destination: "traefik.example.com".to_string(),
+            api_key: "key".to_string(),
+            ..Default::default()
+        };
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::IbmCloud(IbmCloudSettings {
+            ttl: Some(300),
+            ..ibmcloud()
+        })];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::IbmCloud(IbmCloudSettings {
+            zone_id: String::new(),
+            ..ibmcloud()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::MissingField(field)) if field == "providers[0].zone_id"
+        ));
+
+        cfg.providers = vec![Provider::IbmCloud(IbmCloudSettings {
+            ttl: Some(30),
+            ..ibmcloud()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidTtl { ttl: 30, .. })
+        ));
+    }
+
+    #[cfg(feature = "oci")]
+    #[test]
+    fn test_validate_oci() {
+        let oci = || OciSettings {
+            zone: "example.com".to_string(),
+            destination: "traefik.example.com".to_string(),
+            region: "us-ashburn-1".to_string(),
+            tenancy: "ocid1.tenancy".to_string(),
+            user: "ocid1.user".to_string(),
+            fingerprint: "aa:bb".to_string(),
+            private_key: "key".to_string(),
+            ..Default::default()
+        };
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::Oci(oci())];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::Oci(OciSettings {
+            fingerprint: String::new(),
+            ..oci()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::MissingField(field)) if field == "providers[0].fingerprint"
+        ));
+
+        cfg.providers = vec![Provider::Oci(OciSettings {
+            region: "not a region".to_string(),
+            ..oci()
+        })];
+        assert!(matches!(cfg.validate(), Err(ConfigError::InvalidRegion(_))));
+
+        cfg.resolve_destination = true;
+        cfg.providers = vec![Provider::Oci(oci())];
+        assert!(matches!(cfg.validate(), Err(ConfigError::CnameResolved(_))));
+    }
+
+    #[cfg(feature = "duckdns")]
+    #[test]
+    fn test_validate_duckdns() {
+        let duckdns = || DuckDnsSettings {
+            domain: "myhome".to_string(),
+            destination: "192.0.2.1".to_string(),
+            token: "token".to_string(),
+            ..Default::default()
+        };
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::DuckDns(duckdns())];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::DuckDns(DuckDnsSettings {
+            token: String::new(),
+            ..duckdns()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::MissingField(field)) if field == "providers[0].token"
+        ));
+
+        cfg.providers = vec![Provider::DuckDns(DuckDnsSettings {
+            destination: "traefik.example.com".to_string(),
+            ..duckdns()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidDestination(_))
+        ));
+    }
+
+    #[cfg(feature = "dynu")]
+    #[test]
+    fn test_validate_dynu() {
+        let dynu = || DynuSettings {
+            domain_id: 1,
+            destination: "traefik.example.com".to_string(),
+            api_key: "key".to_string(),
+            ..Default::default()
+        };
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::Dynu(DynuSettings {
+            ttl: Some(60),
+            ..dynu()
+        })];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::Dynu(DynuSettings {
+            domain_id: 0,
+            ..dynu()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::MissingField(field)) if field == "providers[0].domain_id"
+        ));
+
+        cfg.resolve_destination = true;
+        cfg.providers = vec![Provider::Dynu(dynu())];
+        assert!(matches!(cfg.validate(), Err(ConfigError::CnameResolved(_))));
+    }
+
+    #[cfg(feature = "zonefile")]
+    #[test]
+    fn test_validate_zonefile() {
+        let zonefile = || ZonefileSettings {
+            path: PathBuf::from("/etc/bind/db.example.com"),
+            destination: "traefik.example.com".to_string(),
+            ..Default::default()
+        };
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::Zonefile(ZonefileSettings {
+            reload_command: Some(vec!["rndc".to_string(), "reload".to_string()]),
+            ..zonefile()
+        })];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::Zonefile(ZonefileSettings {
+            path: PathBuf::new(),
+            ..zonefile()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::MissingField(field)) if field == "providers[0].path"
+        ));
+
+        cfg.providers = vec![Provider::Zonefile(ZonefileSettings {
+            reload_command: Some(Vec::new()),
+            ..zonefile()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::MissingField(field)) if field == "providers[0].reload_command"
+        ));
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_validate_dual_stack() {
+        let dual_stack = || CloudflareSettings {
+            destination: String::new(),
+            destination_v4: Some("192.0.2.1".parse().unwrap()),
+            destination_v6: Some("2001:db8::1".parse().unwrap()),
+            ..cloudflare()
+        };
+        let mut cfg = settings();
+
+        // The destination is optional with dual-stack addresses
+        cfg.providers = vec![Provider::Cloudflare(dual_stack())];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            record_type: Some("CNAME".to_string()),
+            ..dual_stack()
+        })];
+        assert!(matches!(cfg.validate(), Err(ConfigError::CnameResolved(_))));
+
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            record_type: Some("A".to_string()),
+            ..dual_stack()
+        })];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::RecordTypeMismatch { destination, .. }) if destination == "2001:db8::1"
+        ));
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_cloudflare_ttl_auto() {
+        let parse = |ttl: &str| {
+            toml::from_str::<CloudflareSettings>(&format!(
+                "zone_id = \"zone\"\ndestination = \"example.com\"\n{}",
+                ttl
+            ))
+        };
+        assert_eq!(parse("ttl = \"auto\"").unwrap().ttl, Some(1));
+        assert_eq!(parse("ttl = \"AUTO\"").unwrap().ttl, Some(1));
+        assert_eq!(parse("ttl = 120").unwrap().ttl, Some(120));
+        assert_eq!(parse("").unwrap().ttl, None);
+        assert!(parse("ttl = \"soon\"").is_err());
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_validate_record_type() {
+        use crate::dns::RecordType;
+
+        let provider = |record_type: &str, destination: &str| {
+            Provider::Cloudflare(CloudflareSettings {
+                record_type: Some(record_type.to_string()),
+                destination: destination.to_string(),
+                ..cloudflare()
+            })
+        };
+
+        let mut cfg = settings();
+        cfg.providers = vec![provider("aaaa", "2001:db8::1")];
+        cfg.validate().unwrap();
+
+        cfg.providers = vec![provider("MX", "example.com")];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::InvalidRecordType(_))
+        ));
+
+        cfg.providers = vec![provider("A", "2001:db8::1")];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::RecordTypeMismatch {
+                record_type: RecordType::A,
+                ..
+            })
+        ));
+
+        cfg.providers = vec![provider("CNAME", "192.0.2.1")];
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::RecordTypeMismatch { field, .. }) if field == "providers[0].record_type"
+        ));
+
+        cfg.providers = vec![provider("CNAME", "example.com")];
+        cfg.resolve_destination = true;
+        assert!(matches!(cfg.validate(), Err(ConfigError::CnameResolved(_))));
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_cloudflare_credentials() {
+        use cloudflare::framework::auth::Credentials;
+
+        let cfg = CloudflareSettings {
+            token: None,
+            ..cloudflare()
+        };
+        let err = cfg.credentials().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Missing Cloudflare credentials: `token`, or both `email` and `api_key` must be set"
+        );
+
+        let cfg = CloudflareSettings {
+            token: None,
+            email: Some("user@example.com".to_string()),
+            api_key: Some("key".to_string()),
+            ..cloudflare()
+        };
+        assert!(matches!(
+            cfg.credentials(),
+            Ok(Credentials::UserAuthKey { email, key }) if email == "user@example.com" && key == "key"
+        ));
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_cloudflare_rate_limit() {
+        let limit = cloudflare().rate_limit().unwrap();
+        assert_eq!(limit.capacity(), crate::dns::cloudflare::REQUEST_LIMIT);
+        assert_eq!(limit.period(), crate::dns::cloudflare::REQUEST_PERIOD);
+
+        let cfg = CloudflareSettings {
+            rate_limit: Some(100),
+            rate_limit_period: Some("1m".to_string()),
+            ..cloudflare()
+        };
+        let limit = cfg.rate_limit().unwrap();
+        assert_eq!(limit.capacity(), 100);
+        assert_eq!(limit.period(), std::time::Duration::from_secs(60));
+
+        let cfg = CloudflareSettings {
+            rate_limit_period: Some("soon".to_string()),
+            ..cloudflare()
+        };
+        assert!(matches!(
+            cfg.rate_limit(),
+            Err(ConfigError::InvalidDuration {
+                field: "rate_limit_period",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        assert!(settings().rate_limit().unwrap().is_none());
+
+        let cfg = Settings {
+            rate_limit: Some(5),
+            ..settings()
+        };
+        let limit = cfg.rate_limit().unwrap().unwrap();
+        assert_eq!(limit.capacity(), 5);
+        assert_eq!(limit.period(), std::time::Duration::from_secs(1));
+
+        let cfg = Settings {
+            rate_limit: Some(5),
+            rate_limit_period: Some("soon".to_string()),
+            ..settings()
+        };
+        assert!(matches!(
+            cfg.rate_limit(),
+            Err(ConfigError::InvalidDuration {
+                field: "rate_limit_period",
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_cloudflare_base_url() {
+        assert_eq!(cloudflare().base_url().unwrap(), None);
+
+        for base_url in [
+            "http://localhost:8080/client/v4",
+            "http://localhost:8080/client/v4/",
+        ] {
+            let cfg = CloudflareSettings {
+                base_url: Some(base_url.to_string()),
+                ..cloudflare()
+            };
+            assert_eq!(
+                cfg.base_url().unwrap().unwrap().as_str(),
+                "http://localhost:8080/client/v4/"
+            );
+        }
+
+        let cfg = CloudflareSettings {
+            base_url: Some("not a url".to_string()),
+            ..cloudflare()
+        };
+        assert!(matches!(
+            cfg.base_url(),
+            Err(ConfigError::InvalidBaseUrl(_))
+        ));
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_load_secrets() {
+        let path = std::env::temp_dir().join("traefik-dns-test-token");
+        std::fs::write(&path, "file-token\n").unwrap();
+
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            token_file: Some(path.clone()),
+            ..cloudflare()
+        })];
+        cfg.load_secrets().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The file wins over the inline token
+        let [Provider::Cloudflare(cf)] = cfg.providers.as_slice() else {
+            panic!("expected a single cloudflare provider");
+        };
+        assert_eq!(cf.token.as_deref(), Some("file-token"));
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_load_secrets_missing_file() {
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            api_key_file: Some(PathBuf::from("/nonexistent/api_key")),
+            ..cloudflare()
+        })];
+        assert!(matches!(
+            cfg.load_secrets(),
+            Err(ConfigError::SecretFile { path, .. }) if path == Path::new("/nonexistent/api_key")
+        ));
+    }
+
+    #[test]
+    fn test_validate_traefik_auth() {
+        let mut cfg = settings();
+        cfg.traefik.basic_auth = Some(BasicAuthSettings {
+            username: String::new(),
+            password: Some("password".to_string()),
+            ..Default::default()
+        });
+        assert!(
+            matches!(cfg.validate(), Err(ConfigError::MissingField(f)) if f == "traefik.basic_auth.username")
+        );
+
+        cfg.traefik.auth.token = Some("token".to_string());
+        assert!(matches!(
+            cfg.validate(),
+            Err(ConfigError::ConflictingTraefikAuth)
+        ));
+    }
+
+    #[test]
+    fn test_load_secrets_traefik() {
+        let path = std::env::temp_dir().join("traefik-dns-test-traefik-token");
+        std::fs::write(&path, "file-token\n").unwrap();
+
+        let mut cfg = settings();
+        cfg.traefik.auth.token_file = Some(path.clone());
+        cfg.load_secrets().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(cfg.traefik.auth.token.as_deref(), Some("file-token"));
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_validate_zones() {
+        let mut cfg = settings();
+        cfg.providers = vec![Provider::Cloudflare(CloudflareSettings {
+            zones: vec![ZoneSettings {
+                id: "zone2".to_string(),
+                suffix: String::new(),
+                ..Default::default()
+            }],
+            ..cloudflare()
+        })];
+        assert!(
+            matches!(cfg.validate(), Err(ConfigError::MissingField(f)) if f == "providers[0].zones[0].suffix")
+        );
+    }
+
+    const CONFIG: &str = r#"
+        traefik_url = "http://traefik:8080"
+        update_interval = "1m"
+
+        [[providers]]
+        type = "Cloudflare"
+        zone_id = "zone"
+        destination = "example.com"
+        token = "file-token"
+    "#;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_env_override() {
+        let settings = Settings::parse(
+            CONFIG,
+            vars(&[
+                ("TDR_TRAEFIK_URL", "http://localhost:8080"),
+                ("TDR_MAX_CONCURRENCY", "2"),
+                ("TDR_TRAEFIK__INCLUDE_INTERNAL", "true"),
+                ("TDR_FILTERS__INCLUDE_SUFFIXES", r#"["example.com"]"#),
+                ("OTHER_UPDATE_INTERVAL", "5m"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(settings.traefik_url, "http://localhost:8080");
+        assert_eq!(settings.update_interval, "1m");
+        assert_eq!(settings.max_concurrency, 2);
+        assert!(settings.traefik.include_internal);
+        assert_eq!(settings.filters.include_suffixes, vec!["example.com"]);
+        assert!(settings.prune);
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_env_override_provider() {
+        let settings = Settings::parse(
+            CONFIG,
+            vars(&[
+                ("TDR_PROVIDER__CLOUDFLARE__TOKEN", "env-token"),
+                // Strings in the file stay strings
+                ("TDR_PROVIDER__CLOUDFLARE__ZONE_ID", "1234"),
+            ]),
+        )
+        .unwrap();
+
+        let [Provider::Cloudflare(cf)] = settings.providers.as_slice() else {
+            panic!("expected a single cloudflare provider");
+        };
+        assert_eq!(cf.token.as_deref(), Some("env-token"));
+        assert_eq!(cf.zone_id, "1234");
+    }
+
+    #[cfg(feature = "bunny")]
+    #[test]
+    fn test_env_override_new_provider() {
+        let settings = Settings::parse(
+            CONFIG,
+            vars(&[
+                ("TDR_PROVIDER__BUNNY__ZONE_ID", "42"),
+                ("TDR_PROVIDER__BUNNY__DESTINATION", "example.com"),
+                ("TDR_PROVIDER__BUNNY__API_KEY", "key"),
+            ]),
+        )
+        .unwrap();
+
+        let [_, Provider::Bunny(bunny)] = settings.providers.as_slice() else {
+            panic!("expected a second, bunny provider");
+        };
+        assert_eq!(bunny.zone_id, 42);
+        assert_eq!(bunny.api_key, "key");
+    }
+
+    #[test]
+    fn test_env_override_from_env() {
+        let path = std::env::temp_dir().join("traefik-dns-test-env-config.toml");
+        std::fs::write(
+            &path,
+            "traefik_url = \"http://traefik:8080\"\nupdate_interval = \"1m\"\n",
+        )
+        .unwrap();
+        std::env::set_var("TDR_UPDATE_INTERVAL", "30s");
+
+        let settings = Settings::new(std::slice::from_ref(&path)).unwrap();
+        std::env::remove_var("TDR_UPDATE_INTERVAL");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(settings.update_interval, "30s");
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_config() {
+        const YAML: &str = r#"
+traefik_url: http://traefik:8080
+update_interval: 1m
+traefik:
+  include_internal: true
+providers:
+  - type: Cloudflare
+    zone_id: zone
+    destination: example.com
+    token: file-token
+"#;
+        let toml_path = std::env::temp_dir().join("traefik-dns-test-format.toml");
+        let yaml_path = std::env::temp_dir().join("traefik-dns-test-format.yml");
+        std::fs::write(
+            &toml_path,
+            format!("{}\n[traefik]\ninclude_internal = true\n", CONFIG),
+        )
+        .unwrap();
+        std::fs::write(&yaml_path, YAML).unwrap();
+
+        let from_toml = Settings::new(std::slice::from_ref(&toml_path)).unwrap();
+        let from_yaml = Settings::new(std::slice::from_ref(&yaml_path)).unwrap();
+        std::fs::remove_file(&toml_path).unwrap();
+        std::fs::remove_file(&yaml_path).unwrap();
+
+        assert!(from_yaml.traefik.include_internal);
+        assert_eq!(
+            serde_json::to_value(&from_yaml).unwrap(),
+            serde_json::to_value(&from_toml).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_config_format() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config.TOML")).unwrap(),
+            ConfigFormat::Toml
+        );
+        #[cfg(feature = "yaml")]
+        for path in ["config.yaml", "config.yml"] {
+            assert_eq!(
+                ConfigFormat::from_path(Path::new(path)).unwrap(),
+                ConfigFormat::Yaml
+            );
+        }
+
+        for path in ["config.json", "config"] {
+            assert!(matches!(
+                ConfigFormat::from_path(Path::new(path)),
+                Err(ConfigError::UnsupportedFormat(p)) if p == Path::new(path)
+            ));
+        }
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_generate_config() {
+        let dir = std::env::temp_dir().join("traefik-dns-test-generate");
+        let path = dir.join("nested").join("config.toml");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        generate_config(&path, false).unwrap();
+        let settings = Settings::new(std::slice::from_ref(&path)).unwrap();
+        settings.validate().unwrap();
+        assert_eq!(settings.traefik_url, "http://traefik:8080");
+        assert_eq!(
+            settings.max_concurrency,
+            crate::dns::DEFAULT_MAX_CONCURRENCY
+        );
+        assert!(matches!(
+            settings.providers.as_slice(),
+            [Provider::Cloudflare(_)]
+        ));
+
+        // Existing files are only replaced with force
+        std::fs::write(&path, "edited").unwrap();
+        assert!(matches!(
+            generate_config(&path, false),
+            Err(ConfigError::ConfigExists(p)) if p == path
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "edited");
+
+        generate_config(&path, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), CONFIG_TEMPLATE);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_parse_merged() {
+        const SECRETS: &str = r#"
+            [traefik.auth]
+            token = "traefik-token"
+
+            [[providers]]
+            token = "secret-token"
+        "#;
+        const OVERLAY: &str = r#"
+            update_interval = "30s"
+
+            [[providers]]
+            type = "Cloudflare"
+            destination = "other.example.com"
+            tags = ["overlay"]
+        "#;
+
+        let settings = Settings::parse_merged(
+            [
+                (ConfigFormat::Toml, CONFIG),
+                (ConfigFormat::Toml, SECRETS),
+                (ConfigFormat::Toml, OVERLAY),
+            ],
+            vars(&[("TDR_UPDATE_INTERVAL", "10s")]),
+        )
+        .unwrap();
+
+        // Variables override every file, and later files override earlier ones
+        assert_eq!(settings.update_interval, "10s");
+        assert_eq!(settings.traefik_url, "http://traefik:8080");
+        assert_eq!(
+            settings.traefik.auth.token.as_deref(),
+            Some("traefik-token")
+        );
+
+        let [Provider::Cloudflare(cfg)] = settings.providers.as_slice() else {
+            panic!("expected a single Cloudflare provider");
+        };
+        assert_eq!(cfg.zone_id, "zone");
+        assert_eq!(cfg.token.as_deref(), Some("secret-token"));
+        assert_eq!(cfg.destination, "other.example.com");
+        assert_eq!(cfg.tags, vec!["overlay"]);
+    }
+
+    #[cfg(feature = "cf")]
+    #[test]
+    fn test_new_merged_files() {
+        let base = std::env::temp_dir().join("traefik-dns-test-merge-base.toml");
+        let secrets = std::env::temp_dir().join("traefik-dns-test-merge-secrets.toml");
+        std::fs::write(&base, CONFIG).unwrap();
+        std::fs::write(
+            &secrets,
+            "[[providers]]\ntype = \"Cloudflare\"\ntoken = \"secret-token\"\n",
+        )
+        .unwrap();
+
+        let settings = Settings::new(&[base.clone(), secrets.clone()]).unwrap();
+        std::fs::remove_file(&base).unwrap();
+        std::fs::remove_file(&secrets).unwrap();
+
+        let [Provider::Cloudflare(cfg)] = settings.providers.as_slice() else {
+            panic!("expected a single Cloudflare provider");
+        };
+        assert_eq!(cfg.destination, "example.com");
+        assert_eq!(cfg.token.as_deref(), Some("secret-token"));
+    }
 }
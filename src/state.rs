@@ -0,0 +1,114 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Reconcile state persisted across restarts.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    /// Hosts whose records were created by the last successful reconcile.
+    pub routes: Vec<String>,
+}
+
+impl State {
+    pub fn new<'a>(routes: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut routes: Vec<_> = routes.into_iter().cloned().collect();
+        routes.sort();
+        Self { routes }
+    }
+
+    /// Loads the state at `path`.
+    pub fn load(path: &Path) -> Result<Self, StateError> {
+        let contents = std::fs::read(path).map_err(|source| StateError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        serde_json::from_slice(&contents).map_err(|source| StateError::Json {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    /// Saves the state to `path`, replacing it atomically so a crash never leaves it corrupt.
+    pub fn save(&self, path: &Path) -> Result<(), StateError> {
+        let io_err = |source| StateError::Io {
+            path: path.to_owned(),
+            source,
+        };
+
+        let contents = serde_json::to_vec(self).map_err(|source| StateError::Json {
+            path: path.to_owned(),
+            source,
+        })?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, contents).map_err(io_err)?;
+        std::fs::rename(&tmp, path).map_err(io_err)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("state file {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("invalid state file {path}: {source}")]
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl StateError {
+    /// Returns `true` if the state file doesn't exist yet.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, StateError::Io { source, .. } if source.kind() == io::ErrorKind::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("traefik-dns-test-{}.json", name))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_path("state-round-trip");
+        let routes: HashSet<_> = ["b.example.com", "a.example.com"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+
+        let state = State::new(&routes);
+        assert_eq!(state.routes, vec!["a.example.com", "b.example.com"]);
+        state.save(&path).unwrap();
+        assert_eq!(State::load(&path).unwrap(), state);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_missing() {
+        let err = State::load(&temp_path("state-missing")).unwrap_err();
+        assert!(err.is_not_found());
+    }
+
+    #[test]
+    fn test_load_corrupt() {
+        let path = temp_path("state-corrupt");
+        std::fs::write(&path, "{\"routes\": [").unwrap();
+
+        let err = State::load(&path).unwrap_err();
+        assert!(matches!(err, StateError::Json { .. }));
+        assert!(!err.is_not_found());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
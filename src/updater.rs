@@ -1,20 +1,71 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display, Formatter},
+    future::Future,
+    path::PathBuf,
+    sync::Arc,
     time::Duration,
 };
 
-use tokio::{sync::Mutex, time, time::MissedTickBehavior};
-use tracing::{error, info};
+use serde::Serialize;
+use tokio::{
+    sync::{mpsc, Mutex},
+    time,
+    time::Instant,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    dns::{
+        rate_limit::RateLimit, ApplyOptions, DnsRecord, FailedChange, Provider, RecordOptions,
+        DEFAULT_MAX_CONCURRENCY,
+    },
+    filter::HostFilter,
+    health::Health,
+    heartbeat::Heartbeat,
+    host::normalize_host,
+    retry::{retry, CircuitBreaker, ErrorBackoff, RetryPolicy},
+    router::{Route, Router},
+    state::State,
+    webhook::Webhook,
+};
 
-use crate::{dns::Provider, router::Router};
+/// Reason of routes skipped by the host filter, as reported in metrics.
+const SKIPPED_FILTERED: &str = "filtered";
 
 #[derive(Debug)]
 pub struct Updater<D: Provider, R: Router> {
     provider: D,
     router: R,
 
-    current_routes: Mutex<HashSet<String>>,
+    /// Routes of the tracked hosts, by normalized host.
+    current_routes: Mutex<HashMap<String, Route>>,
+    /// Records of the last listing, reused while younger than `list_cache_ttl`.
+    record_cache: std::sync::Mutex<Option<CachedRecords>>,
+    list_cache_ttl: Option<Duration>,
+
+    retry: RetryPolicy,
+    error_backoff: ErrorBackoff,
+    /// Short-circuits reconciles while the provider keeps failing.
+    circuit_breaker: Option<CircuitBreaker>,
+    max_concurrency: usize,
+    rate_limit: Option<Arc<RateLimit>>,
+    filter: HostFilter,
+    health: Arc<Health>,
+    /// Delete the records of tracked hosts when shutting down.
+    cleanup_on_exit: bool,
+    /// File the tracked hosts are saved to, so they survive restarts.
+    state_file: Option<PathBuf>,
+    /// Deletions are skipped if they exceed this fraction of the managed records.
+    max_delete_ratio: Option<f64>,
+    /// Delete the records of removed routes, or only ever create records.
+    prune: bool,
+    /// Wait before the first reconcile, for routers starting alongside us.
+    initial_delay: Duration,
+    /// Notified of the records changed by every successful reconcile.
+    webhook: Option<Webhook>,
+    /// Pinged after every successful reconcile.
+    heartbeat: Option<Heartbeat>,
 }
 
 impl<D: Provider, R: Router> Updater<D, R> {
@@ -22,86 +73,687 @@ impl<D: Provider, R: Router> Updater<D, R> {
         Self {
             provider,
             router,
-            current_routes: Mutex::new(HashSet::new()),
+            current_routes: Mutex::new(HashMap::new()),
+            record_cache: std::sync::Mutex::new(None),
+            list_cache_ttl: None,
+            retry: RetryPolicy::default(),
+            error_backoff: ErrorBackoff::default(),
+            circuit_breaker: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            rate_limit: None,
+            filter: HostFilter::default(),
+            health: Arc::new(Health::default()),
+            cleanup_on_exit: false,
+            state_file: None,
+            max_delete_ratio: None,
+            prune: true,
+            initial_delay: Duration::ZERO,
+            webhook: None,
+            heartbeat: None,
+        }
+    }
+
+    pub fn retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+    pub fn retry_mut(&mut self) -> &mut RetryPolicy {
+        &mut self.retry
+    }
+
+    pub fn error_backoff(&self) -> &ErrorBackoff {
+        &self.error_backoff
+    }
+    pub fn error_backoff_mut(&mut self) -> &mut ErrorBackoff {
+        &mut self.error_backoff
+    }
+
+    /// Circuit breaker pausing reconciles while the provider keeps failing, if enabled.
+    pub fn circuit_breaker(&self) -> Option<&CircuitBreaker> {
+        self.circuit_breaker.as_ref()
+    }
+    pub fn circuit_breaker_mut(&mut self) -> &mut Option<CircuitBreaker> {
+        &mut self.circuit_breaker
+    }
+
+    /// How long listed records are reused for, or `None` to list them on every reconcile.
+    pub fn list_cache_ttl(&self) -> Option<Duration> {
+        self.list_cache_ttl
+    }
+    pub fn list_cache_ttl_mut(&mut self) -> &mut Option<Duration> {
+        &mut self.list_cache_ttl
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+    pub fn max_concurrency_mut(&mut self) -> &mut usize {
+        &mut self.max_concurrency
+    }
+
+    /// Rate limit of the record changes, shared by every provider of a reconcile.
+    pub fn rate_limit(&self) -> Option<&Arc<RateLimit>> {
+        self.rate_limit.as_ref()
+    }
+    pub fn rate_limit_mut(&mut self) -> &mut Option<Arc<RateLimit>> {
+        &mut self.rate_limit
+    }
+
+    pub fn filter(&self) -> &HostFilter {
+        &self.filter
+    }
+    pub fn filter_mut(&mut self) -> &mut HostFilter {
+        &mut self.filter
+    }
+
+    pub fn health(&self) -> &Arc<Health> {
+        &self.health
+    }
+    pub fn health_mut(&mut self) -> &mut Arc<Health> {
+        &mut self.health
+    }
+
+    pub fn cleanup_on_exit(&self) -> bool {
+        self.cleanup_on_exit
+    }
+    pub fn cleanup_on_exit_mut(&mut self) -> &mut bool {
+        &mut self.cleanup_on_exit
+    }
+
+    pub fn state_file(&self) -> Option<&PathBuf> {
+        self.state_file.as_ref()
+    }
+    pub fn state_file_mut(&mut self) -> &mut Option<PathBuf> {
+        &mut self.state_file
+    }
+
+    /// Maximum fraction of the managed records a reconcile may delete, or `None` for no limit.
+    pub fn max_delete_ratio(&self) -> Option<f64> {
+        self.max_delete_ratio
+    }
+    pub fn max_delete_ratio_mut(&mut self) -> &mut Option<f64> {
+        &mut self.max_delete_ratio
+    }
+
+    /// Whether the records of removed routes are deleted.
+    pub fn prune(&self) -> bool {
+        self.prune
+    }
+    pub fn prune_mut(&mut self) -> &mut bool {
+        &mut self.prune
+    }
+
+    /// Delay before the first reconcile of [`Updater::run`].
+    pub fn initial_delay(&self) -> Duration {
+        self.initial_delay
+    }
+    pub fn initial_delay_mut(&mut self) -> &mut Duration {
+        &mut self.initial_delay
+    }
+
+    pub fn webhook(&self) -> Option<&Webhook> {
+        self.webhook.as_ref()
+    }
+    pub fn webhook_mut(&mut self) -> &mut Option<Webhook> {
+        &mut self.webhook
+    }
+
+    pub fn heartbeat(&self) -> Option<&Heartbeat> {
+        self.heartbeat.as_ref()
+    }
+    pub fn heartbeat_mut(&mut self) -> &mut Option<Heartbeat> {
+        &mut self.heartbeat
+    }
+
+    /// Restores the tracked hosts saved to `state_file`.
+    ///
+    /// Only hosts whose records are listed & up to date are trusted. A missing or corrupt
+    /// state file is ignored, so every host is created again.
+    pub async fn restore_state(&self) -> Result<(), UpdateRoutesError<D, R>> {
+        let Some(path) = &self.state_file else {
+            return Ok(());
+        };
+        let state = match State::load(path) {
+            Ok(state) => state,
+            Err(e) if e.is_not_found() => return Ok(()),
+            Err(e) => {
+                warn!("ignoring state: {}", e);
+                return Ok(());
+            }
+        };
+
+        let records = self.list_records().await?;
+        let (current, stale): (Vec<_>, Vec<_>) = records
+            .iter()
+            .filter(|r| self.filter.matches(&r.host))
+            .partition(|r| self.provider.is_current(r));
+        let current: HashSet<_> = current.into_iter().map(|r| r.host.as_str()).collect();
+        let stale: HashSet<_> = stale.into_iter().map(|r| r.host.as_str()).collect();
+
+        let mut current_routes = self.current_routes.lock().await;
+        // Only hosts are saved, so the routers of restored routes are unknown
+        *current_routes = state
+            .routes
+            .into_iter()
+            .filter(|host| current.contains(host.as_str()) && !stale.contains(host.as_str()))
+            .map(|host| {
+                let route = Route {
+                    host: host.clone(),
+                    ..Default::default()
+                };
+                (host, route)
+            })
+            .collect();
+        info!("restored {} routes from state", current_routes.len());
+
+        Ok(())
+    }
+
+    /// Saves the tracked hosts to `state_file`, if set.
+    fn save_state(&self, routes: &HashMap<String, Route>) {
+        if let Some(path) = &self.state_file {
+            if let Err(e) = State::new(routes.keys()).save(path) {
+                warn!("failed to save state: {}", e);
+            }
         }
     }
 
     pub async fn run(&self, update_interval: Duration) -> Result<(), UpdateRoutesError<D, R>> {
-        let mut interval = time::interval(update_interval);
-        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        self.run_until(update_interval, std::future::pending())
+            .await
+    }
+
+    /// Reconciles routes every `update_interval` until `shutdown` completes, starting after
+    /// `initial_delay`.
+    ///
+    /// Shutdown is only checked between reconciles, so changes are never interrupted. If
+    /// `cleanup_on_exit` is set, the records of tracked hosts are then deleted.
+    pub async fn run_until(
+        &self,
+        update_interval: Duration,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), UpdateRoutesError<D, R>> {
+        // Never triggered, as the sender is kept until we return
+        let (_trigger, receiver) = mpsc::channel(1);
+        self.run_triggered(update_interval, shutdown, receiver)
+            .await
+    }
+
+    /// Like [`Self::run_until`], but also reconciles as soon as `trigger` receives, instead of
+    /// waiting for the next interval.
+    ///
+    /// Triggers received during a reconcile start another one once it completes, as it may have
+    /// listed the routes before them. Pending triggers are coalesced into a single reconcile.
+    pub async fn run_triggered(
+        &self,
+        update_interval: Duration,
+        shutdown: impl Future<Output = ()>,
+        mut trigger: mpsc::Receiver<()>,
+    ) -> Result<(), UpdateRoutesError<D, R>> {
+        tokio::pin!(shutdown);
+        let mut failures = 0u32;
+
+        if !self.initial_delay.is_zero() {
+            info!(delay = ?self.initial_delay, "waiting before the first reconcile");
+            tokio::select! {
+                _ = time::sleep(self.initial_delay) => {}
+                Some(()) = trigger.recv() => info!("reconcile triggered"),
+                _ = &mut shutdown => return self.shut_down().await,
+            }
+        }
 
         loop {
-            interval.tick().await;
+            let start = Instant::now();
+            // This reconcile covers every trigger received until now
+            while trigger.try_recv().is_ok() {}
 
             match time::timeout(update_interval, self.update_routes()).await {
-                Ok(Ok(_)) => (),
+                Ok(Ok(_)) => failures = 0,
                 Ok(Err(e)) => {
                     error!("route updating returned an error: {}", e);
+                    failures = failures.saturating_add(1);
                 }
                 Err(_) => {
                     error!("route updating timed out");
+                    failures = failures.saturating_add(1);
                 }
             }
+
+            let interval = self.error_backoff.interval(update_interval, failures);
+            if failures > 0 {
+                warn!(
+                    failures,
+                    ?interval,
+                    "backing off after consecutive failures"
+                );
+            }
+            tokio::select! {
+                _ = time::sleep_until(start + interval) => {}
+                Some(()) = trigger.recv() => info!("reconcile triggered"),
+                _ = &mut shutdown => break,
+            }
         }
+
+        self.shut_down().await
     }
 
-    #[tracing::instrument(skip(self), level = "info")]
-    async fn update_routes(&self) -> Result<(), UpdateRoutesError<D, R>> {
-        info!("updating routes");
+    async fn shut_down(&self) -> Result<(), UpdateRoutesError<D, R>> {
+        info!("shutting down");
+        if self.cleanup_on_exit {
+            self.cleanup().await?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the records of every tracked host.
+    ///
+    /// Only records listed by the provider are deleted, so records without a matching
+    /// ownership marker are left alone when ownership is enabled.
+    pub async fn cleanup(&self) -> Result<(), UpdateRoutesError<D, R>> {
         let mut current_routes = self.current_routes.lock().await;
 
-        let routes: HashSet<_> = self
+        // The cache may miss records created since it was filled
+        *self.record_cache.lock().unwrap() = None;
+        let records: Vec<_> = self
+            .list_records()
+            .await?
+            .into_iter()
+            .filter(|r| current_routes.contains_key(&r.host) && self.filter.matches(&r.host))
+            .collect();
+
+        let hosts: Vec<_> = records.iter().map(|r| &r.host).collect();
+        info!(routes = ?hosts, "Cleaning up {} routes", records.len());
+
+        let options = ApplyOptions {
+            retry: self.retry,
+            max_concurrency: self.max_concurrency,
+            limiter: self.rate_limit.clone(),
+            ..Default::default()
+        };
+        let res = self.provider.apply_changes(&[], &records, &options).await;
+        *self.record_cache.lock().unwrap() = None;
+
+        // Keep tracking hosts whose records couldn't be deleted
+        let mut failed = HashSet::new();
+        if let Err(failures) = &res {
+            for failure in failures {
+                error!(hosts = ?failure.hosts, "failed to clean up: {}", failure.error);
+                failed.extend(failure.hosts.iter().cloned());
+            }
+        }
+        current_routes.retain(|host, _| failed.contains(host));
+        self.save_state(&current_routes);
+
+        res.map_err(UpdateRoutesError::<D, R>::Partial)
+    }
+
+    /// Reconciles routes exactly once, returning any error instead of logging it.
+    pub async fn run_once(&self) -> Result<(), UpdateRoutesError<D, R>> {
+        self.update_routes().await
+    }
+
+    /// Lists the provider's records, or returns the cached ones if they are fresh.
+    async fn list_records(&self) -> Result<Vec<DnsRecord>, UpdateRoutesError<D, R>> {
+        if let Some(ttl) = self.list_cache_ttl {
+            if let Some(cache) = &*self.record_cache.lock().unwrap() {
+                if cache.listed_at.elapsed() < ttl {
+                    return Ok(cache.records.clone());
+                }
+            }
+        }
+
+        let mut records = retry(&self.retry, || self.provider.list_records())
+            .await
+            .map_err(UpdateRoutesError::<D, R>::ProviderError)?;
+        // Providers may store hosts in another case than routers report them
+        for record in &mut records {
+            record.host = normalize_host(&record.host);
+        }
+
+        if self.list_cache_ttl.is_some() {
+            *self.record_cache.lock().unwrap() = Some(CachedRecords {
+                listed_at: Instant::now(),
+                records: records.clone(),
+            });
+        }
+        Ok(records)
+    }
+
+    /// Computes the changes the next reconcile would make, without applying them.
+    pub async fn plan(&self) -> Result<ReconcilePlan, UpdateRoutesError<D, R>> {
+        let current_routes = self.current_routes.lock().await;
+        self.plan_with(&current_routes).await
+    }
+
+    async fn plan_with(
+        &self,
+        current_routes: &HashMap<String, Route>,
+    ) -> Result<ReconcilePlan, UpdateRoutesError<D, R>> {
+        let mut record_options = HashMap::new();
+        let (routes, filtered): (Vec<_>, Vec<_>) = self
             .router
             .get_routes()
             .await
             .map_err(UpdateRoutesError::<D, R>::RouterError)?
             .into_iter()
-            .map(|r| r.host)
+            .partition(|r| self.filter.matches(&r.host));
+        if !filtered.is_empty() {
+            let skipped: Vec<_> = filtered
+                .iter()
+                .map(|r| (r.id.as_str(), r.host.as_str()))
+                .collect();
+            debug!(?skipped, "filtered out {} routes", skipped.len());
+        }
+        self.health
+            .record_skipped_routes(SKIPPED_FILTERED, filtered.len() as u64);
+
+        let routes: HashMap<_, _> = routes
+            .into_iter()
+            .map(|mut route| {
+                route.host = normalize_host(&route.host);
+                let options = RecordOptions {
+                    ttl: route.ttl,
+                    proxied: route.proxied,
+                    router: Some(route.id.clone()),
+                };
+                record_options.insert(route.host.clone(), options);
+                (route.host.clone(), route)
+            })
             .collect();
 
-        // Add all active routes
-        futures::future::try_join_all(
-            routes
-                .iter()
-                .filter(|&domain| !current_routes.contains(domain))
-                .map(|domain| self.provider.create_record(domain)),
-        )
-        .await
-        .map_err(UpdateRoutesError::<D, R>::ProviderError)?;
-
-        // Get routes to delete
-        let routes_to_delete: Vec<_> = self
-            .provider
-            .list_records()
-            .await
-            .map_err(UpdateRoutesError::<D, R>::ProviderError)?
+        // Get routes to delete, and routes whose records are stale
+        let records = self.list_records().await?;
+        let (mut delete, existing): (Vec<_>, Vec<_>) = records
             .into_iter()
-            .filter(|s| !routes.contains(s))
+            .filter(|r| self.filter.matches(&r.host))
+            .partition(|r| !routes.contains_key(&r.host));
+
+        if !self.prune && !delete.is_empty() {
+            let hosts: Vec<_> = delete.iter().map(|r| r.host.as_str()).collect();
+            info!(
+                ?hosts,
+                "pruning is disabled, keeping {} records",
+                delete.len()
+            );
+            delete.clear();
+        }
+
+        // An outage of the router may look like every route was removed
+        let managed = delete.len() + existing.len();
+        if let Some(ratio) = self.max_delete_ratio {
+            if !delete.is_empty() && delete.len() as f64 > ratio * managed as f64 {
+                error!(
+                    deletes = delete.len(),
+                    managed,
+                    max_delete_ratio = ratio,
+                    "refusing to delete more than the allowed fraction of records, skipping deletions"
+                );
+                delete.clear();
+            }
+        }
+
+        let stale: HashSet<_> = existing
+            .iter()
+            .filter(|r| !self.provider.is_current(r))
+            .map(|r| r.host.as_str())
             .collect();
+        let mut existing_records: HashMap<_, Vec<_>> = HashMap::new();
+        for record in &existing {
+            existing_records
+                .entry(record.host.clone())
+                .or_default()
+                .push(record.clone());
+        }
 
-        if !routes_to_delete.is_empty() {
-            info!(routes = ?routes_to_delete, "Deleting {} routes", routes_to_delete.len());
+        let mut hosts: Vec<_> = routes.keys().collect();
+        hosts.sort();
+
+        let mut plan = ReconcilePlan {
+            delete,
+            ..Default::default()
+        };
+        for host in hosts {
+            if stale.contains(host.as_str()) {
+                plan.update.push(host.clone());
+            } else if current_routes.contains_key(host) {
+                continue;
+            } else if existing_records.contains_key(host) {
+                plan.refresh.push(host.clone());
+            } else {
+                plan.create.push(host.clone());
+            }
         }
+        plan.routes = routes;
+        plan.records = record_options;
+        plan.existing = existing_records;
 
-        // Delete inactive routes
-        futures::future::try_join_all(
-            routes_to_delete
-                .iter()
-                .map(|domain| self.provider.delete_record(domain)),
-        )
-        .await
-        .map_err(UpdateRoutesError::<D, R>::ProviderError)?;
+        Ok(plan)
+    }
 
-        // Update current routes
+    /// Reconciles routes, unless the circuit breaker is open.
+    async fn update_routes(&self) -> Result<(), UpdateRoutesError<D, R>> {
+        let Some(breaker) = &self.circuit_breaker else {
+            return self.reconcile().await;
+        };
+        breaker.check().map_err(UpdateRoutesError::CircuitOpen)?;
+
+        let res = self.reconcile().await;
+        match &res {
+            // Routers fail before the provider is reached
+            Err(UpdateRoutesError::RouterError(_)) => {}
+            // Partial failures count too, so a provider failing most changes still opens it
+            Err(UpdateRoutesError::ProviderError(_) | UpdateRoutesError::Partial(_)) => {
+                if breaker.record_failure() {
+                    warn!(
+                        cooldown = ?breaker.cooldown(),
+                        "provider failed {} consecutive reconciles, pausing reconciles",
+                        breaker.failure_threshold()
+                    );
+                }
+            }
+            _ => {
+                if breaker.is_open() {
+                    info!("provider recovered, resuming reconciles");
+                }
+                breaker.record_success();
+            }
+        }
+        self.health.set_circuit_open(breaker.is_open());
+        res
+    }
+
+    #[tracing::instrument(skip(self), level = "info")]
+    async fn reconcile(&self) -> Result<(), UpdateRoutesError<D, R>> {
+        info!("updating routes");
+        let start = Instant::now();
+        let mut current_routes = self.current_routes.lock().await;
+
+        let mut plan = self.plan_with(&current_routes).await?;
+
+        if !plan.delete.is_empty() {
+            let hosts: Vec<_> = plan.delete.iter().map(|r| &r.host).collect();
+            info!(routes = ?hosts, "Deleting {} routes", plan.delete.len());
+        }
+        if !plan.update.is_empty() {
+            info!(routes = ?plan.update, "Updating {} stale routes", plan.update.len());
+        }
+
+        let unchanged =
+            plan.routes.len() - plan.create.len() - plan.update.len() - plan.refresh.len();
+
+        // Add all active routes & delete inactive routes
+        let routes_to_create: Vec<_> = plan
+            .create
+            .iter()
+            .chain(&plan.update)
+            .chain(&plan.refresh)
+            .cloned()
+            .collect();
+
+        // Per-host options only apply to created records, existing records keep theirs
+        let options = ApplyOptions {
+            retry: self.retry,
+            max_concurrency: self.max_concurrency,
+            records: std::mem::take(&mut plan.records),
+            limiter: self.rate_limit.clone(),
+            existing: std::mem::take(&mut plan.existing),
+        };
+        let res = self
+            .provider
+            .apply_changes(&routes_to_create, &plan.delete, &options)
+            .await;
+
+        // The zone changed, so the cached records are outdated
+        if !routes_to_create.is_empty() || !plan.delete.is_empty() {
+            *self.record_cache.lock().unwrap() = None;
+        }
+
+        // Update current routes, leaving out failed creates so they are retried next time
+        let mut routes = std::mem::take(&mut plan.routes);
+        let mut failed = HashSet::new();
+        if let Err(failures) = &res {
+            for failure in failures {
+                error!(hosts = ?failure.hosts, "failed to apply changes: {}", failure.error);
+                for host in &failure.hosts {
+                    routes.remove(host);
+                    failed.insert(host.as_str());
+                }
+            }
+        }
+
+        self.log_changes(&plan, &routes, &current_routes, &failed);
+
+        // Keep tracking hosts whose records couldn't be deleted
+        for record in &plan.delete {
+            if let Some(route) = current_routes.get(&record.host) {
+                if failed.contains(record.host.as_str()) {
+                    routes.insert(record.host.clone(), route.clone());
+                }
+            }
+        }
+
+        let applied = |hosts: &[String]| {
+            hosts
+                .iter()
+                .filter(|host| !failed.contains(host.as_str()))
+                .count()
+        };
+        info!(
+            created = applied(&plan.create) + applied(&plan.refresh),
+            updated = applied(&plan.update),
+            deleted = plan
+                .delete
+                .iter()
+                .filter(|r| !failed.contains(r.host.as_str()))
+                .count(),
+            unchanged,
+            failed = failed.len(),
+            elapsed_ms = start.elapsed().as_millis() as u64,
+            "reconcile finished"
+        );
         *current_routes = routes;
 
+        res.map_err(UpdateRoutesError::<D, R>::Partial)?;
+        self.save_state(&current_routes);
+
+        self.health.record_success();
+
+        if let Some(webhook) = &self.webhook {
+            // Refreshed records already existed, so only new & replaced records are reported
+            let created: Vec<_> = plan.create.into_iter().chain(plan.update).collect();
+            let deleted: Vec<_> = plan.delete.into_iter().map(|r| r.host).collect();
+            if !created.is_empty() || !deleted.is_empty() {
+                if let Err(e) = webhook.notify(&created, &deleted).await {
+                    warn!(url = %webhook.url(), "failed to deliver webhook: {}", e);
+                }
+            }
+        }
+
+        if let Some(heartbeat) = &self.heartbeat {
+            if let Err(e) = heartbeat.ping().await {
+                warn!(url = %heartbeat.url(), "failed to ping heartbeat: {}", e);
+            }
+        }
+
         Ok(())
     }
+
+    /// Logs an event for every applied change, with the router that caused it.
+    ///
+    /// Deleted hosts are no longer routed, so their router is the one of `previous`, the
+    /// routes tracked before the reconcile.
+    fn log_changes(
+        &self,
+        plan: &ReconcilePlan,
+        routes: &HashMap<String, Route>,
+        previous: &HashMap<String, Route>,
+        failed: &HashSet<&str>,
+    ) {
+        // Routes restored from the state file don't know their router
+        fn router<'a>(routes: &'a HashMap<String, Route>, host: &str) -> Option<&'a str> {
+            routes
+                .get(host)
+                .map(|route| route.id.as_str())
+                .filter(|id| !id.is_empty())
+        }
+
+        let changes = [("create", &plan.create), ("update", &plan.update)];
+        for (change, hosts) in changes {
+            for host in hosts.iter().filter(|host| !failed.contains(host.as_str())) {
+                info!(
+                    change,
+                    host,
+                    router = router(routes, host),
+                    "changed record"
+                );
+            }
+        }
+        for record in &plan.delete {
+            let host = record.host.as_str();
+            if !failed.contains(host) {
+                let router = router(previous, host);
+                info!(change = "delete", host, router, "changed record");
+            }
+        }
+    }
+}
+
+/// Changes computed by a reconcile.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ReconcilePlan {
+    /// Hosts without records, whose records are created.
+    pub create: Vec<String>,
+    /// Records of hosts that are no longer routed.
+    pub delete: Vec<DnsRecord>,
+    /// Hosts with stale records, which are replaced.
+    pub update: Vec<String>,
+    /// Hosts with up to date records that aren't tracked yet, passed to
+    /// [`Provider::ensure_record`] again.
+    #[serde(skip)]
+    pub refresh: Vec<String>,
+
+    /// Routes of every routed host, by normalized host.
+    #[serde(skip)]
+    routes: HashMap<String, Route>,
+    #[serde(skip)]
+    records: HashMap<String, RecordOptions>,
+    /// Listed records of the routed hosts.
+    #[serde(skip)]
+    existing: HashMap<String, Vec<DnsRecord>>,
+}
+
+#[derive(Debug)]
+struct CachedRecords {
+    listed_at: Instant,
+    records: Vec<DnsRecord>,
 }
 
 pub enum UpdateRoutesError<D: Provider, R: Router> {
     RouterError(R::Error),
     ProviderError(D::Error),
+    /// Some changes failed, while the others were applied.
+    Partial(Vec<FailedChange<D::Error>>),
+    /// The provider kept failing, so reconciles are skipped for the remaining cooldown.
+    CircuitOpen(Duration),
 }
 
 impl<D: Provider, R: Router> Debug for UpdateRoutesError<D, R> {
@@ -109,6 +761,10 @@ impl<D: Provider, R: Router> Debug for UpdateRoutesError<D, R> {
         match self {
             UpdateRoutesError::RouterError(e) => Debug::fmt(e, f),
             UpdateRoutesError::ProviderError(e) => Debug::fmt(e, f),
+            UpdateRoutesError::Partial(failures) => Debug::fmt(failures, f),
+            UpdateRoutesError::CircuitOpen(remaining) => {
+                f.debug_tuple("CircuitOpen").field(remaining).finish()
+            }
         }
     }
 }
@@ -118,6 +774,18 @@ impl<D: Provider, R: Router> Display for UpdateRoutesError<D, R> {
         match self {
             UpdateRoutesError::RouterError(e) => Display::fmt(e, f),
             UpdateRoutesError::ProviderError(e) => Display::fmt(e, f),
+            UpdateRoutesError::Partial(failures) => {
+                write!(f, "{} changes failed", failures.len())?;
+                for failure in failures {
+                    write!(f, "; {}: {}", failure.hosts.join(", "), failure.error)?;
+                }
+                Ok(())
+            }
+            UpdateRoutesError::CircuitOpen(remaining) => write!(
+                f,
+                "circuit breaker is open, skipping reconciles for {:?}",
+                remaining
+            ),
         }
     }
 }
@@ -132,6 +800,23 @@ mod tests {
         router::{MockRouter, Route},
     };
 
+    fn route(host: &str) -> Route {
+        Route {
+            host: host.to_string(),
+            id: host.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn record(host: &str) -> DnsRecord {
+        DnsRecord {
+            host: host.to_string(),
+            id: None,
+            ttl: None,
+            content: "dest".to_string(),
+        }
+    }
+
     #[tokio::test]
     async fn test_update_routes_simple() {
         let mut mock_router = MockRouter::new();
@@ -141,19 +826,23 @@ mod tests {
             Ok(vec![Route {
                 host: "test1.example.com".to_string(),
                 id: "test1".to_string(),
+                ..Default::default()
             }])
         });
 
         mock_provider
             .expect_create_record()
-            .with(mockall::predicate::eq("test1.example.com"))
+            .with(
+                mockall::predicate::eq("test1.example.com"),
+                mockall::predicate::always(),
+            )
             .once()
-            .returning(|_| Ok(()));
+            .returning(|_, _| Ok(()));
 
         mock_provider
             .expect_list_records()
             .once()
-            .returning(|| Ok(vec!["test1.example.com".to_string()]));
+            .returning(|| Ok(vec![]));
 
         let updater = Updater::new(mock_provider, mock_router);
 
@@ -161,7 +850,73 @@ mod tests {
 
         let current_routes = updater.current_routes.lock().await;
         assert_eq!(current_routes.len(), 1);
-        assert!(current_routes.contains("test1.example.com"));
+        assert_eq!(current_routes["test1.example.com"].id, "test1");
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_list_cache() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().times(2).returning(|| {
+            Ok(vec![Route {
+                host: "test1.example.com".to_string(),
+                id: "test1".to_string(),
+                ..Default::default()
+            }])
+        });
+
+        // The second reconcile reuses the records of the first
+        mock_provider
+            .expect_list_records()
+            .once()
+            .returning(|| Ok(vec![record("test1.example.com")]));
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        *updater.list_cache_ttl_mut() = Some(Duration::from_secs(60));
+        updater
+            .current_routes
+            .lock()
+            .await
+            .insert("test1.example.com".to_string(), route("test1.example.com"));
+
+        updater.update_routes().await.unwrap();
+        updater.update_routes().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_list_cache_invalidate() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().times(2).returning(|| {
+            Ok(vec![Route {
+                host: "test1.example.com".to_string(),
+                id: "test1".to_string(),
+                ..Default::default()
+            }])
+        });
+
+        mock_provider
+            .expect_create_record()
+            .with(
+                mockall::predicate::eq("test1.example.com"),
+                mockall::predicate::always(),
+            )
+            .once()
+            .returning(|_, _| Ok(()));
+
+        // Creating a record invalidates the cache, so the zone is listed again
+        mock_provider
+            .expect_list_records()
+            .times(2)
+            .returning(|| Ok(vec![]));
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        *updater.list_cache_ttl_mut() = Some(Duration::from_secs(60));
+
+        updater.update_routes().await.unwrap();
+        updater.update_routes().await.unwrap();
     }
 
     #[tokio::test]
@@ -177,11 +932,11 @@ mod tests {
         mock_provider
             .expect_list_records()
             .once()
-            .returning(|| Ok(vec!["test1.example.com".to_string()]));
+            .returning(|| Ok(vec![record("test1.example.com")]));
 
         mock_provider
             .expect_delete_record()
-            .with(mockall::predicate::eq("test1.example.com"))
+            .with(mockall::predicate::eq(record("test1.example.com")))
             .once()
             .returning(|_| Ok(()));
 
@@ -193,8 +948,320 @@ mod tests {
         assert_eq!(current_routes.len(), 0);
     }
 
+    /// Collects the fields of the events logged with `message`.
+    #[derive(Clone, Default)]
+    struct CaptureLayer {
+        message: &'static str,
+        fields: Arc<std::sync::Mutex<HashMap<String, String>>>,
+    }
+
+    struct FieldVisitor(HashMap<String, String>);
+
+    impl tracing::field::Visit for FieldVisitor {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for CaptureLayer {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut visitor = FieldVisitor(HashMap::new());
+            event.record(&mut visitor);
+            if visitor.0.get("message").map(String::as_str) == Some(self.message) {
+                *self.fields.lock().unwrap() = visitor.0;
+            }
+        }
+    }
+
     #[tokio::test]
-    async fn test_update_routes_exists() {
+    async fn test_update_routes_summary() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(["test1.example.com", "test2.example.com"]
+                .into_iter()
+                .map(|host| Route {
+                    host: host.to_string(),
+                    id: host.to_string(),
+                    ..Default::default()
+                })
+                .collect())
+        });
+
+        mock_provider.expect_list_records().once().returning(|| {
+            Ok(vec![
+                record("test1.example.com"),
+                record("test3.example.com"),
+            ])
+        });
+
+        mock_provider
+            .expect_create_record()
+            .with(
+                mockall::predicate::eq("test2.example.com"),
+                mockall::predicate::always(),
+            )
+            .once()
+            .returning(|_, _| Ok(()));
+
+        mock_provider
+            .expect_delete_record()
+            .with(mockall::predicate::eq(record("test3.example.com")))
+            .once()
+            .returning(|_| Ok(()));
+
+        let updater = Updater::new(mock_provider, mock_router);
+        updater
+            .current_routes
+            .lock()
+            .await
+            .insert("test1.example.com".to_string(), route("test1.example.com"));
+
+        let layer = CaptureLayer {
+            message: "reconcile finished",
+            ..Default::default()
+        };
+        let fields = layer.fields.clone();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+        updater.update_routes().await.unwrap();
+
+        let fields = fields.lock().unwrap();
+        assert_eq!(fields["created"], "1");
+        assert_eq!(fields["updated"], "0");
+        assert_eq!(fields["deleted"], "1");
+        assert_eq!(fields["unchanged"], "1");
+        assert_eq!(fields["failed"], "0");
+        assert!(fields.contains_key("elapsed_ms"));
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_change_events() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        let mut routes = vec![
+            vec![],
+            vec![Route {
+                host: "test1.example.com".to_string(),
+                id: "test1@docker".to_string(),
+                ..Default::default()
+            }],
+        ];
+        mock_router
+            .expect_get_routes()
+            .times(2)
+            .returning(move || Ok(routes.pop().unwrap()));
+
+        let mut records = vec![vec![], vec![record("test1.example.com")]];
+        mock_provider
+            .expect_list_records()
+            .times(2)
+            .returning(move || Ok(records.remove(0)));
+        mock_provider
+            .expect_create_record()
+            .once()
+            .returning(|_, _| Ok(()));
+        mock_provider
+            .expect_delete_record()
+            .once()
+            .returning(|_| Ok(()));
+
+        let updater = Updater::new(mock_provider, mock_router);
+        let layer = CaptureLayer {
+            message: "changed record",
+            ..Default::default()
+        };
+        let fields = layer.fields.clone();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+        updater.update_routes().await.unwrap();
+        {
+            let fields = fields.lock().unwrap();
+            assert_eq!(fields["change"], "\"create\"");
+            assert_eq!(fields["host"], "\"test1.example.com\"");
+            assert_eq!(fields["router"], "\"test1@docker\"");
+        }
+
+        // The route is gone, but its deletion is still attributed to its router
+        updater.update_routes().await.unwrap();
+        let fields = fields.lock().unwrap();
+        assert_eq!(fields["change"], "\"delete\"");
+        assert_eq!(fields["router"], "\"test1@docker\"");
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct WebhookPayload {
+        created: Vec<String>,
+        deleted: Vec<String>,
+    }
+
+    /// Updater creating `new.example.com` and deleting `old.example.com`.
+    fn webhook_updater(server: &httptest::Server) -> Updater<MockProvider, MockRouter> {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(vec![Route {
+                host: "new.example.com".to_string(),
+                id: "new".to_string(),
+                ..Default::default()
+            }])
+        });
+        mock_provider
+            .expect_list_records()
+            .once()
+            .returning(|| Ok(vec![record("old.example.com")]));
+        mock_provider
+            .expect_create_record()
+            .once()
+            .returning(|_, _| Ok(()));
+        mock_provider
+            .expect_delete_record()
+            .once()
+            .returning(|_| Ok(()));
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        *updater.webhook_mut() = Some(
+            Webhook::new(
+                server.url("/hook").to_string().parse().unwrap(),
+                &Default::default(),
+            )
+            .unwrap(),
+        );
+        updater
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_webhook() {
+        use httptest::{matchers::*, responders::*, Expectation, Server};
+
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/hook"),
+                request::body(json_decoded(eq(WebhookPayload {
+                    created: vec!["new.example.com".to_string()],
+                    deleted: vec!["old.example.com".to_string()],
+                }))),
+            ])
+            .respond_with(status_code(204)),
+        );
+
+        webhook_updater(&server).update_routes().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_webhook_failure() {
+        use httptest::{matchers::*, responders::*, Expectation, Server};
+
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/hook"))
+                .respond_with(status_code(500)),
+        );
+
+        // The records were changed, so the reconcile still succeeds
+        webhook_updater(&server).update_routes().await.unwrap();
+    }
+
+    /// Updater whose reconcile lists no routes, failing if `list_error` is set.
+    fn heartbeat_updater(
+        server: &httptest::Server,
+        list_error: bool,
+    ) -> Updater<MockProvider, MockRouter> {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router
+            .expect_get_routes()
+            .once()
+            .returning(|| Ok(vec![]));
+        mock_provider
+            .expect_list_records()
+            .once()
+            .returning(move || {
+                if list_error {
+                    Err(crate::dns::tests::MockProviderError)
+                } else {
+                    Ok(vec![])
+                }
+            });
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        *updater.heartbeat_mut() = Some(
+            Heartbeat::new(
+                server.url("/ping").to_string().parse().unwrap(),
+                &Default::default(),
+            )
+            .unwrap(),
+        );
+        updater
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_heartbeat() {
+        use httptest::{matchers::*, responders::*, Expectation, Server};
+
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/ping"))
+                .times(1)
+                .respond_with(status_code(200)),
+        );
+
+        heartbeat_updater(&server, false)
+            .update_routes()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_heartbeat_failed_reconcile() {
+        use httptest::{matchers::*, responders::*, Expectation, Server};
+
+        // Failed reconciles skip the ping, so the dead man's switch alarms
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/ping"))
+                .times(0)
+                .respond_with(status_code(200)),
+        );
+
+        let mut updater = heartbeat_updater(&server, true);
+        updater.retry_mut().max_attempts = 1;
+        assert!(updater.update_routes().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_heartbeat_failure() {
+        use httptest::{matchers::*, responders::*, Expectation, Server};
+
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/ping"))
+                .respond_with(status_code(500)),
+        );
+
+        // Undelivered pings are only logged
+        heartbeat_updater(&server, false)
+            .update_routes()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_ttl() {
         let mut mock_router = MockRouter::new();
         let mut mock_provider = MockProvider::new();
 
@@ -203,24 +1270,456 @@ mod tests {
                 Route {
                     host: "test1.example.com".to_string(),
                     id: "test1".to_string(),
+                    ttl: Some(60),
+                    ..Default::default()
                 },
                 Route {
                     host: "test2.example.com".to_string(),
                     id: "test2".to_string(),
+                    ..Default::default()
                 },
             ])
         });
 
         mock_provider
             .expect_create_record()
-            .with(mockall::predicate::eq("test2.example.com"))
+            .with(
+                mockall::predicate::eq("test1.example.com"),
+                mockall::predicate::eq(RecordOptions {
+                    ttl: Some(60),
+                    router: Some("test1".to_string()),
+                    ..Default::default()
+                }),
+            )
             .once()
-            .returning(|_| Ok(()));
+            .returning(|_, _| Ok(()));
+        mock_provider
+            .expect_create_record()
+            .with(
+                mockall::predicate::eq("test2.example.com"),
+                mockall::predicate::eq(RecordOptions {
+                    router: Some("test2".to_string()),
+                    ..Default::default()
+                }),
+            )
+            .once()
+            .returning(|_, _| Ok(()));
 
-        mock_provider.expect_list_records().once().returning(|| {
+        mock_provider
+            .expect_list_records()
+            .once()
+            .returning(|| Ok(vec![]));
+
+        let updater = Updater::new(mock_provider, mock_router);
+
+        updater.update_routes().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_plan() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(vec![
+                Route {
+                    host: "test2.example.com".to_string(),
+                    id: "test2".to_string(),
+                    ..Default::default()
+                },
+                Route {
+                    host: "test1.example.com".to_string(),
+                    id: "test1".to_string(),
+                    ..Default::default()
+                },
+            ])
+        });
+        mock_provider
+            .expect_list_records()
+            .once()
+            .returning(|| Ok(vec![record("test2.example.com"), record("old.example.com")]));
+
+        // Nothing is applied, so the provider expects no creates nor deletes
+        let updater = Updater::new(mock_provider, mock_router);
+        let plan = updater.plan().await.unwrap();
+
+        assert_eq!(plan.create, vec!["test1.example.com"]);
+        assert_eq!(plan.delete, vec![record("old.example.com")]);
+        assert!(plan.update.is_empty());
+        assert_eq!(plan.refresh, vec!["test2.example.com"]);
+        assert!(updater.current_routes.lock().await.is_empty());
+
+        assert_eq!(
+            serde_json::to_value(&plan).unwrap(),
+            serde_json::json!({
+                "create": ["test1.example.com"],
+                "delete": [{
+                    "host": "old.example.com",
+                    "id": null,
+                    "ttl": null,
+                    "content": "dest",
+                }],
+                "update": [],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_cleanup_on_exit() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(vec![
+                Route {
+                    host: "test1.example.com".to_string(),
+                    id: "test1".to_string(),
+                    ..Default::default()
+                },
+                Route {
+                    host: "test2.example.com".to_string(),
+                    id: "test2".to_string(),
+                    ..Default::default()
+                },
+            ])
+        });
+        // The records already exist, so nothing is created
+        mock_provider.expect_create_record().never();
+
+        // Listed once for the reconcile, and once for the cleanup
+        mock_provider.expect_list_records().times(2).returning(|| {
             Ok(vec![
+                record("test1.example.com"),
+                record("test2.example.com"),
+            ])
+        });
+        for host in ["test1.example.com", "test2.example.com"] {
+            mock_provider
+                .expect_delete_record()
+                .with(mockall::predicate::eq(record(host)))
+                .once()
+                .returning(|_| Ok(()));
+        }
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        *updater.cleanup_on_exit_mut() = true;
+
+        updater
+            .run_until(Duration::from_secs(60), async {})
+            .await
+            .unwrap();
+        assert!(updater.current_routes.lock().await.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_initial_delay() {
+        let mock_router = MockRouter::new();
+        let mock_provider = MockProvider::new();
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        *updater.initial_delay_mut() = Duration::from_secs(30);
+
+        // No reconcile happens before the delay elapses
+        updater
+            .run_until(
+                Duration::from_secs(60),
+                time::sleep(Duration::from_secs(29)),
+            )
+            .await
+            .unwrap();
+
+        updater
+            .router
+            .expect_get_routes()
+            .once()
+            .returning(|| Ok(vec![]));
+        updater
+            .provider
+            .expect_list_records()
+            .once()
+            .returning(|| Ok(vec![]));
+        updater
+            .run_until(
+                Duration::from_secs(60),
+                time::sleep(Duration::from_secs(31)),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker() {
+        let mut mock_router = MockRouter::new();
+        mock_router.expect_get_routes().returning(|| Ok(vec![]));
+        let mut mock_provider = MockProvider::new();
+        // Not called while the breaker is open
+        mock_provider
+            .expect_list_records()
+            .times(2)
+            .returning(|| Err(crate::dns::tests::MockProviderError));
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        updater.retry_mut().max_attempts = 1;
+        *updater.circuit_breaker_mut() = Some(CircuitBreaker::new(2, Duration::from_secs(60)));
+        updater.health().record_success();
+
+        for _ in 0..2 {
+            let err = updater.update_routes().await.unwrap_err();
+            assert!(matches!(err, UpdateRoutesError::ProviderError(_)));
+        }
+        assert!(!updater.health().is_ready());
+        let err = updater.update_routes().await.unwrap_err();
+        assert!(
+            matches!(err, UpdateRoutesError::CircuitOpen(remaining) if remaining == Duration::from_secs(60))
+        );
+
+        // Half-open after the cooldown, the provider is tried again & closes the breaker
+        time::advance(Duration::from_secs(60)).await;
+        updater.provider.checkpoint();
+        updater
+            .provider
+            .expect_list_records()
+            .once()
+            .returning(|| Ok(vec![]));
+        updater.update_routes().await.unwrap();
+        assert!(!updater.circuit_breaker().unwrap().is_open());
+        assert!(updater.health().is_ready());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_partial() {
+        let mut mock_router = MockRouter::new();
+        mock_router
+            .expect_get_routes()
+            .returning(|| Ok(vec![route("test1.example.com"), route("test2.example.com")]));
+        let mut mock_provider = MockProvider::new();
+        mock_provider
+            .expect_list_records()
+            .times(2)
+            .returning(|| Ok(vec![]));
+        // test1 keeps failing while test2 succeeds
+        mock_provider
+            .expect_create_record()
+            .with(
+                mockall::predicate::eq("test1.example.com"),
+                mockall::predicate::always(),
+            )
+            .times(2)
+            .returning(|_, _| Err(crate::dns::tests::MockProviderError));
+        mock_provider
+            .expect_create_record()
+            .once()
+            .returning(|_, _| Ok(()));
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        updater.retry_mut().max_attempts = 1;
+        *updater.circuit_breaker_mut() = Some(CircuitBreaker::new(2, Duration::from_secs(60)));
+
+        for _ in 0..2 {
+            let err = updater.update_routes().await.unwrap_err();
+            assert!(matches!(err, UpdateRoutesError::Partial(_)));
+        }
+        assert!(updater.circuit_breaker().unwrap().is_open());
+        let err = updater.update_routes().await.unwrap_err();
+        assert!(matches!(err, UpdateRoutesError::CircuitOpen(_)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_triggered() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        // Once on start, and once for the coalesced triggers, well before the next interval
+        mock_router
+            .expect_get_routes()
+            .times(2)
+            .returning(|| Ok(vec![]));
+        mock_provider
+            .expect_list_records()
+            .times(2)
+            .returning(|| Ok(vec![]));
+
+        let updater = Updater::new(mock_provider, mock_router);
+        let (trigger, receiver) = mpsc::channel(2);
+        let shutdown = async {
+            time::sleep(Duration::from_secs(10)).await;
+            trigger.send(()).await.unwrap();
+            trigger.send(()).await.unwrap();
+            time::sleep(Duration::from_secs(10)).await;
+        };
+        updater
+            .run_triggered(Duration::from_secs(60), shutdown, receiver)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_state() {
+        let path = std::env::temp_dir().join("traefik-dns-test-updater-state.json");
+        State {
+            routes: vec![
                 "test1.example.com".to_string(),
                 "test2.example.com".to_string(),
+                "test3.example.com".to_string(),
+            ],
+        }
+        .save(&path)
+        .unwrap();
+
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(["test1", "test2", "test3"]
+                .into_iter()
+                .map(|id| Route {
+                    host: format!("{}.example.com", id),
+                    id: id.to_string(),
+                    ..Default::default()
+                })
+                .collect())
+        });
+        // test3 lost its record since the state was saved
+        mock_provider.expect_list_records().times(2).returning(|| {
+            Ok(vec![
+                record("test1.example.com"),
+                record("test2.example.com"),
+            ])
+        });
+        mock_provider
+            .expect_create_record()
+            .with(
+                mockall::predicate::eq("test3.example.com"),
+                mockall::predicate::always(),
+            )
+            .once()
+            .returning(|_, _| Ok(()));
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        *updater.state_file_mut() = Some(path.clone());
+
+        updater.restore_state().await.unwrap();
+        assert_eq!(updater.current_routes.lock().await.len(), 2);
+
+        // Only the untracked host is created, and the state is saved again
+        updater.update_routes().await.unwrap();
+        assert_eq!(State::load(&path).unwrap().routes.len(), 3);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_state_corrupt() {
+        let path = std::env::temp_dir().join("traefik-dns-test-updater-state-corrupt.json");
+        std::fs::write(&path, "not json").unwrap();
+
+        let mut updater = Updater::new(MockProvider::new(), MockRouter::new());
+        *updater.state_file_mut() = Some(path.clone());
+
+        // Nothing is trusted, so nothing is listed either
+        updater.restore_state().await.unwrap();
+        assert!(updater.current_routes.lock().await.is_empty());
+
+        // A missing file is the same as an empty state
+        std::fs::remove_file(&path).unwrap();
+        updater.restore_state().await.unwrap();
+        assert!(updater.current_routes.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_max_delete_ratio() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        // The router lost every route
+        mock_router
+            .expect_get_routes()
+            .times(2)
+            .returning(|| Ok(vec![]));
+        mock_provider.expect_list_records().times(2).returning(|| {
+            Ok(vec![
+                record("test1.example.com"),
+                record("test2.example.com"),
+            ])
+        });
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        *updater.max_delete_ratio_mut() = Some(0.5);
+
+        // Deleting every record exceeds the threshold, so nothing is deleted
+        updater.update_routes().await.unwrap();
+
+        // Disabling the threshold lets deletions through
+        updater
+            .provider
+            .expect_delete_record()
+            .times(2)
+            .returning(|_| Ok(()));
+        *updater.max_delete_ratio_mut() = Some(1.0);
+        updater.update_routes().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_no_prune() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(vec![Route {
+                host: "test1.example.com".to_string(),
+                id: "test1".to_string(),
+                ..Default::default()
+            }])
+        });
+        mock_provider.expect_list_records().once().returning(|| {
+            Ok(vec![
+                record("extra1.example.com"),
+                record("extra2.example.com"),
+            ])
+        });
+        mock_provider
+            .expect_create_record()
+            .with(
+                mockall::predicate::eq("test1.example.com"),
+                mockall::predicate::always(),
+            )
+            .once()
+            .returning(|_, _| Ok(()));
+        mock_provider.expect_delete_record().never();
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        *updater.prune_mut() = false;
+
+        updater.update_routes().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_exists() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(vec![
+                Route {
+                    host: "test1.example.com".to_string(),
+                    id: "test1".to_string(),
+                    ..Default::default()
+                },
+                Route {
+                    host: "test2.example.com".to_string(),
+                    id: "test2".to_string(),
+                    ..Default::default()
+                },
+            ])
+        });
+
+        // test2 is tracked without creating its existing, up to date record again
+        mock_provider.expect_create_record().never();
+
+        mock_provider.expect_list_records().once().returning(|| {
+            Ok(vec![
+                record("test1.example.com"),
+                record("test2.example.com"),
             ])
         });
 
@@ -229,14 +1728,341 @@ mod tests {
         // Set updater current_routes to test1.example.com
         {
             let mut current_routes = updater.current_routes.lock().await;
-            current_routes.insert("test1.example.com".to_string());
+            current_routes.insert("test1.example.com".to_string(), route("test1.example.com"));
         }
 
         updater.update_routes().await.unwrap();
 
         let current_routes = updater.current_routes.lock().await;
         assert_eq!(current_routes.len(), 2);
-        assert!(current_routes.contains("test1.example.com"));
-        assert!(current_routes.contains("test2.example.com"));
+        assert!(current_routes.contains_key("test1.example.com"));
+        assert!(current_routes.contains_key("test2.example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_single_list() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router
+            .expect_get_routes()
+            .once()
+            .returning(|| Ok(vec![]));
+
+        // Records are listed exactly once, even with multiple deletions
+        mock_provider.expect_list_records().once().returning(|| {
+            Ok(vec![
+                DnsRecord {
+                    id: Some("id1".to_string()),
+                    ..record("test1.example.com")
+                },
+                DnsRecord {
+                    id: Some("id2".to_string()),
+                    ..record("test2.example.com")
+                },
+            ])
+        });
+
+        mock_provider
+            .expect_delete_record()
+            .withf(|r| r.id.is_some())
+            .times(2)
+            .returning(|_| Ok(()));
+
+        let updater = Updater::new(mock_provider, mock_router);
+
+        updater.update_routes().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_once() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(vec![Route {
+                host: "test1.example.com".to_string(),
+                id: "test1".to_string(),
+                ..Default::default()
+            }])
+        });
+
+        mock_provider
+            .expect_list_records()
+            .once()
+            .returning(|| Ok(vec![]));
+
+        mock_provider
+            .expect_create_record()
+            .once()
+            .returning(|_, _| Ok(()));
+
+        let updater = Updater::new(mock_provider, mock_router);
+
+        updater.run_once().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_once_provider_error() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router
+            .expect_get_routes()
+            .once()
+            .returning(|| Ok(vec![]));
+
+        mock_provider
+            .expect_list_records()
+            .times(3)
+            .returning(|| Err(crate::dns::tests::MockProviderError));
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        updater.retry_mut().base_delay = Duration::from_millis(1);
+
+        let err = updater.run_once().await.unwrap_err();
+        assert!(matches!(err, UpdateRoutesError::ProviderError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_retry() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(vec![Route {
+                host: "test1.example.com".to_string(),
+                id: "test1".to_string(),
+                ..Default::default()
+            }])
+        });
+
+        // Listing fails once, then succeeds
+        let mut list_calls = 0;
+        mock_provider
+            .expect_list_records()
+            .times(2)
+            .returning(move || {
+                list_calls += 1;
+                if list_calls == 1 {
+                    Err(crate::dns::tests::MockProviderError)
+                } else {
+                    Ok(vec![])
+                }
+            });
+
+        // Creation fails once, then succeeds
+        let mut create_calls = 0;
+        mock_provider
+            .expect_create_record()
+            .with(
+                mockall::predicate::eq("test1.example.com"),
+                mockall::predicate::always(),
+            )
+            .times(2)
+            .returning(move |_, _| {
+                create_calls += 1;
+                if create_calls == 1 {
+                    Err(crate::dns::tests::MockProviderError)
+                } else {
+                    Ok(())
+                }
+            });
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        updater.retry_mut().base_delay = Duration::from_millis(1);
+
+        updater.update_routes().await.unwrap();
+
+        let current_routes = updater.current_routes.lock().await;
+        assert!(current_routes.contains_key("test1.example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_partial() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(["test1", "test2", "test3"]
+                .into_iter()
+                .map(|id| Route {
+                    host: format!("{}.example.com", id),
+                    id: id.to_string(),
+                    ..Default::default()
+                })
+                .collect())
+        });
+
+        mock_provider
+            .expect_list_records()
+            .once()
+            .returning(|| Ok(vec![record("old.example.com")]));
+
+        // test2 always fails, the others still get created
+        mock_provider
+            .expect_create_record()
+            .with(
+                mockall::predicate::eq("test2.example.com"),
+                mockall::predicate::always(),
+            )
+            .times(3)
+            .returning(|_, _| Err(crate::dns::tests::MockProviderError));
+        mock_provider
+            .expect_create_record()
+            .times(2)
+            .returning(|_, _| Ok(()));
+
+        // Deletions still happen
+        mock_provider
+            .expect_delete_record()
+            .with(mockall::predicate::eq(record("old.example.com")))
+            .once()
+            .returning(|_| Ok(()));
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        updater.retry_mut().base_delay = Duration::from_millis(1);
+
+        let err = updater.update_routes().await.unwrap_err();
+        match err {
+            UpdateRoutesError::Partial(failures) => {
+                assert_eq!(failures.len(), 1);
+                assert_eq!(failures[0].hosts, vec!["test2.example.com"]);
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+
+        let current_routes = updater.current_routes.lock().await;
+        assert_eq!(current_routes.len(), 2);
+        assert!(current_routes.contains_key("test1.example.com"));
+        assert!(current_routes.contains_key("test3.example.com"));
+        assert!(!updater.health().is_ready());
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_filter() {
+        let mut mock_router = MockRouter::new();
+        let mut mock_provider = MockProvider::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok(vec![
+                Route {
+                    host: "test1.example.com".to_string(),
+                    id: "test1".to_string(),
+                    ..Default::default()
+                },
+                Route {
+                    host: "test1.example.org".to_string(),
+                    id: "test2".to_string(),
+                    ..Default::default()
+                },
+            ])
+        });
+
+        // Out of scope records are not deleted
+        mock_provider.expect_list_records().once().returning(|| {
+            Ok(vec![
+                record("old.example.org"),
+                record("excluded.example.com"),
+            ])
+        });
+
+        // Out of scope routes are not created
+        mock_provider
+            .expect_create_record()
+            .with(
+                mockall::predicate::eq("test1.example.com"),
+                mockall::predicate::always(),
+            )
+            .once()
+            .returning(|_, _| Ok(()));
+
+        let mut updater = Updater::new(mock_provider, mock_router);
+        *updater.filter_mut() = HostFilter {
+            include_suffixes: vec!["example.com".to_string()],
+            exclude_hosts: vec!["excluded.example.com".to_string()],
+            exclude_regex: None,
+        };
+
+        updater.update_routes().await.unwrap();
+
+        let current_routes = updater.current_routes.lock().await;
+        assert_eq!(current_routes.len(), 1);
+        assert!(current_routes.contains_key("test1.example.com"));
+        assert_eq!(updater.health().skipped_routes(SKIPPED_FILTERED), 1);
+    }
+
+    /// Provider counting how many creates & deletes are in flight at once.
+    #[derive(Default)]
+    struct CountingProvider {
+        dest: String,
+        in_flight: std::sync::atomic::AtomicUsize,
+        max_in_flight: std::sync::atomic::AtomicUsize,
+    }
+
+    impl CountingProvider {
+        async fn track(&self) -> Result<(), crate::dns::tests::MockProviderError> {
+            use std::sync::atomic::Ordering;
+
+            let n = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(n, Ordering::SeqCst);
+            time::sleep(Duration::from_millis(5)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Provider for CountingProvider {
+        type Error = crate::dns::tests::MockProviderError;
+
+        fn destination(&self) -> &str {
+            &self.dest
+        }
+        fn destination_mut(&mut self) -> &mut String {
+            &mut self.dest
+        }
+
+        async fn list_records(&self) -> Result<Vec<DnsRecord>, Self::Error> {
+            Ok((0..20)
+                .map(|i| record(&format!("old{}.example.com", i)))
+                .collect())
+        }
+        async fn create_record(
+            &self,
+            _host: &str,
+            _options: &RecordOptions,
+        ) -> Result<(), Self::Error> {
+            self.track().await
+        }
+        async fn delete_record(&self, _record: &DnsRecord) -> Result<(), Self::Error> {
+            self.track().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_routes_max_concurrency() {
+        let mut mock_router = MockRouter::new();
+
+        mock_router.expect_get_routes().once().returning(|| {
+            Ok((0..20)
+                .map(|i| Route {
+                    host: format!("test{}.example.com", i),
+                    id: format!("test{}", i),
+                    ..Default::default()
+                })
+                .collect())
+        });
+
+        let mut updater = Updater::new(CountingProvider::default(), mock_router);
+        *updater.max_concurrency_mut() = 3;
+
+        updater.update_routes().await.unwrap();
+
+        let max = updater
+            .provider
+            .max_in_flight
+            .load(std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(max, 3);
     }
 }
@@ -0,0 +1,60 @@
+use serde::Serialize;
+
+/// Build information of the running binary, served on `/version`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Commit the binary was built from, if `GIT_SHA` was set at build time.
+    pub git_sha: Option<&'static str>,
+    /// Enabled provider features.
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    /// Returns the build information of this binary.
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: option_env!("GIT_SHA").filter(|sha| !sha.is_empty()),
+            features: features(),
+        }
+    }
+}
+
+fn features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "alidns") {
+        features.push("alidns");
+    }
+    if cfg!(feature = "aws") {
+        features.push("aws");
+    }
+    if cfg!(feature = "bunny") {
+        features.push("bunny");
+    }
+    if cfg!(feature = "cf") {
+        features.push("cf");
+    }
+    if cfg!(feature = "duckdns") {
+        features.push("duckdns");
+    }
+    if cfg!(feature = "dynu") {
+        features.push("dynu");
+    }
+    if cfg!(feature = "he") {
+        features.push("he");
+    }
+    if cfg!(feature = "ibmcloud") {
+        features.push("ibmcloud");
+    }
+    if cfg!(feature = "namecheap") {
+        features.push("namecheap");
+    }
+    if cfg!(feature = "oci") {
+        features.push("oci");
+    }
+    if cfg!(feature = "zonefile") {
+        features.push("zonefile");
+    }
+    features
+}
@@ -0,0 +1,60 @@
+use std::time::{Duration, SystemTime};
+
+use reqwest::{Client, Url};
+use serde::Serialize;
+
+use crate::http::HttpOptions;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Notifies an HTTP endpoint of the records changed by a reconcile.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    client: Client,
+    url: Url,
+}
+
+/// JSON body POSTed to the webhook.
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    created: &'a [String],
+    deleted: &'a [String],
+    /// Seconds since the Unix epoch.
+    timestamp: u64,
+}
+
+impl Webhook {
+    /// Creates a client of `url`, configured by `options`.
+    pub fn new(url: Url, options: &HttpOptions) -> Result<Self, reqwest::Error> {
+        let client = options.builder()?.timeout(DEFAULT_TIMEOUT).build()?;
+        Ok(Self { client, url })
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// POSTs the hosts whose records were created & deleted.
+    pub async fn notify(
+        &self,
+        created: &[String],
+        deleted: &[String],
+    ) -> Result<(), reqwest::Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let payload = Payload {
+            created,
+            deleted,
+            timestamp,
+        };
+
+        self.client
+            .post(self.url.clone())
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
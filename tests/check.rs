@@ -0,0 +1,56 @@
+use std::process::{Command, Output};
+
+fn check(name: &str, config: &str) -> Output {
+    let path = std::env::temp_dir().join(format!("traefik-dns-test-check-{}.toml", name));
+    std::fs::write(&path, config).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_traefik-dns"))
+        .arg("--config")
+        .arg(&path)
+        .arg("check")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    output
+}
+
+#[cfg(feature = "cf")]
+#[test]
+fn test_check_valid() {
+    let output = check(
+        "valid",
+        r#"
+        traefik_url = "http://traefik:8080"
+        update_interval = "1m"
+
+        [[providers]]
+        type = "Cloudflare"
+        zone_id = "zone"
+        destination = "example.com"
+        token = "token"
+        "#,
+    );
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("1 Traefik instance(s), 1 DNS zone(s)"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_check_invalid() {
+    let output = check(
+        "invalid",
+        r#"
+        traefik_url = "http://traefik:8080"
+        update_interval = "1m"
+        "#,
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("No provider configured"), "{}", stderr);
+}